@@ -0,0 +1,240 @@
+use crate::logging;
+use crate::protocol::{
+    now_millis, CharacterBundle, ClientMessage, Cosmetics, Equipment, Face, Hair, Outfit,
+};
+use crate::ui;
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::info;
+
+pub const EXPORT_DIR: &str = "exports";
+pub const CHARACTER_EXTENSION: &str = "lamc";
+const LOOK_USAGE: &str = "Usage: /look <rrggbb> [hair number] [sprite number]";
+const NOTICE_DURATION: Duration = Duration::from_secs(8);
+
+// Listed by `/help`
+pub const COMMANDS: [&str; 8] = [
+    "name",
+    "look",
+    "party",
+    "consent",
+    "export-character",
+    "import-character",
+    "export-my-data",
+    "delete-my-account",
+];
+
+// Turns a typed `/command` into a request for the server. Returns None for
+// ordinary chat.
+//
+//   /export-my-data            save everything the server has on you
+//   /delete-my-account         get a confirmation code
+//   /delete-my-account <code>  delete the account and its mail
+//   /look <rrggbb> [hair] [sprite]  change body color, hair and sprite if given
+//   /name <name>               change the name shown above you, /nick too
+//   /export-character          save a signed copy of your look
+//   /import-character <path>   take a look exported from a linked server
+//   /consent on|off            agree to being recorded in private rooms
+//   /party [name]              join a party for party chat, leave without a name
+pub fn parse_command(text: &str) -> Option<Result<ClientMessage, String>> {
+    let text = text.trim().strip_prefix('/')?;
+    let (command, rest) = text.split_once(' ').unwrap_or((text, ""));
+    let rest = rest.trim();
+    Some(match command {
+        "export-my-data" => Ok(ClientMessage::RequestDataExport),
+        "delete-my-account" if rest.is_empty() => Ok(ClientMessage::RequestAccountDeletion),
+        "delete-my-account" => Ok(ClientMessage::ConfirmAccountDeletion {
+            code: rest.to_string(),
+        }),
+        "name" | "nick" if !rest.is_empty() => Ok(ClientMessage::SetName {
+            name: rest.to_string(),
+        }),
+        "name" | "nick" => Err(format!("Usage: /{} <name>", command)),
+        "look" => parse_look(rest).map(|cosmetics| ClientMessage::SetCosmetics { cosmetics }),
+        "export-character" => Ok(ClientMessage::RequestCharacterExport),
+        "import-character" => load_character(Path::new(rest))
+            .map(|bundle| ClientMessage::ImportCharacter { bundle })
+            .map_err(|e| format!("Failed to read {}: {}", rest, e)),
+        "party" => Ok(ClientMessage::SetParty {
+            party: (!rest.is_empty()).then(|| rest.to_string()),
+        }),
+        "consent" => match rest {
+            "on" => Ok(ClientMessage::RecordingConsent { consent: true }),
+            "off" => Ok(ClientMessage::RecordingConsent { consent: false }),
+            _ => Err("Usage: /consent on|off".to_string()),
+        },
+        other => Err(format!("Unknown command /{}", other)),
+    })
+}
+
+// Without a hair number the hair is rerolled, without a sprite number the
+// character is a stick figure
+fn parse_look(args: &str) -> Result<Cosmetics, String> {
+    let mut args = args.split_whitespace();
+    let color = args
+        .next()
+        .map(|color| color.trim_start_matches('#'))
+        .filter(|color| color.len() == 6)
+        .and_then(|color| u32::from_str_radix(color, 16).ok())
+        .ok_or(LOOK_USAGE)?;
+    let hair_seed = match args.next() {
+        Some(hair) => hair.parse().map_err(|_| LOOK_USAGE)?,
+        None => ::rand::random(),
+    };
+    let sprite = match args.next() {
+        Some(sprite) => Some(sprite.parse().map_err(|_| LOOK_USAGE)?),
+        None => None,
+    };
+    Ok(Cosmetics {
+        body_color: [(color >> 16) as u8, (color >> 8) as u8, color as u8],
+        hair_seed,
+        sprite,
+        hair: Hair::default(),
+        face: Face::default(),
+        outfit: Outfit::default(),
+        equipment: Equipment::default(),
+    })
+}
+
+fn load_character(path: &Path) -> io::Result<CharacterBundle> {
+    bincode::deserialize(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn save_character(bundle: &CharacterBundle) -> io::Result<PathBuf> {
+    // Names can hold anything, keep the file name safe
+    let name: String = bundle
+        .name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let path = Path::new(EXPORT_DIR).join(format!(
+        "character-{}-{}.{}",
+        name, bundle.exported_at_ms, CHARACTER_EXTENSION
+    ));
+    fs::create_dir_all(EXPORT_DIR)?;
+    fs::write(&path, bincode::serialize(bundle).map_err(io::Error::other)?)?;
+    Ok(path)
+}
+
+fn save_export(json: &str) -> io::Result<PathBuf> {
+    let path = Path::new(EXPORT_DIR).join(format!("data-export-{}.json", now_millis()));
+    fs::create_dir_all(EXPORT_DIR)?;
+    fs::write(&path, json)?;
+    Ok(path)
+}
+
+// Command line opened with the `open_commands` key, `/` unless rebound, plus
+// notices about exports and deletion
+pub struct AccountPanel {
+    command_line: Option<String>,
+    fresh: bool, // Keys queued before the command line opened are still pending
+    notice: Option<(String, Instant)>,
+}
+
+impl Default for AccountPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccountPanel {
+    pub fn new() -> Self {
+        Self {
+            command_line: None,
+            fresh: false,
+            notice: None,
+        }
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.command_line.is_some()
+    }
+
+    pub fn show_notice(&mut self, text: String) {
+        info!(target: logging::AUTH, "{}", text);
+        self.notice = Some((text, Instant::now()));
+    }
+
+    pub fn handle_message(&mut self, message: &ClientMessage) {
+        match message {
+            ClientMessage::DataExport { json } => match save_export(json) {
+                Ok(path) => self.show_notice(format!("Saved your data to {}", path.display())),
+                Err(e) => self.show_notice(format!("Failed to save your data: {}", e)),
+            },
+            ClientMessage::DeletionCode { code } => self.show_notice(format!(
+                "Type /delete-my-account {} to delete your account for good",
+                code
+            )),
+            ClientMessage::CharacterExport { bundle } => match save_character(bundle) {
+                Ok(path) => self.show_notice(format!(
+                    "Saved your character to {}, import it with /import-character",
+                    path.display()
+                )),
+                Err(e) => self.show_notice(format!("Failed to save your character: {}", e)),
+            },
+            ClientMessage::ServerNotice { text } => self.show_notice(text.clone()),
+            _ => {}
+        }
+    }
+
+    // Returns a line once the player submits it
+    pub fn update(&mut self, open_key: KeyCode) -> Option<String> {
+        let Some(line) = &mut self.command_line else {
+            if is_key_pressed(open_key) {
+                self.command_line = Some("/".to_string());
+                self.fresh = true;
+            }
+            return None;
+        };
+
+        while let Some(c) = get_char_pressed() {
+            if !self.fresh && !c.is_control() {
+                line.push(c);
+            }
+        }
+        self.fresh = false;
+        if is_key_pressed(KeyCode::Backspace) {
+            line.pop();
+        }
+        if is_key_pressed(KeyCode::Escape) || line.is_empty() {
+            self.command_line = None;
+        } else if is_key_pressed(KeyCode::Enter) {
+            return self.command_line.take();
+        }
+        None
+    }
+
+    pub fn draw(&self) {
+        if let Some((text, shown_at)) = &self.notice {
+            if shown_at.elapsed() < NOTICE_DURATION {
+                let width = ui::text_width(text, 20.0);
+                let x = (ui::width() - width) / 2.0;
+                let y = ui::height() - 60.0;
+                draw_rectangle(
+                    x - 8.0,
+                    y - 20.0,
+                    width + 16.0,
+                    28.0,
+                    Color::new(0.0, 0.0, 0.0, 0.7),
+                );
+                ui::draw_text(text, x, y, 20.0, WHITE);
+            }
+        }
+
+        if let Some(line) = &self.command_line {
+            let y = ui::height() - 12.0;
+            draw_rectangle(
+                0.0,
+                y - 20.0,
+                ui::width(),
+                28.0,
+                Color::new(1.0, 1.0, 1.0, 0.9),
+            );
+            ui::draw_text(&format!("{}_", line), 8.0, y, 20.0, BLACK);
+        }
+    }
+}
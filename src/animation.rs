@@ -0,0 +1,441 @@
+use crate::components::Animation;
+use crate::protocol::{Emote, Facing, Stance};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, PoisonError, RwLock};
+
+// Clip definitions under the assets directory, see `AnimationSet`
+pub const ANIMATIONS_FILE: &str = "animations.json";
+// Names of the clips the game plays
+pub const RUN: &str = "run";
+pub const SNEAK: &str = "sneak";
+pub const IDLE: &str = "idle";
+pub const WAVE: &str = "wave";
+pub const DANCE: &str = "dance";
+pub const SIT: &str = "sit";
+// Frame durations are written for this pose interval, `lam.toml`'s
+// `pose_interval_ms` speeds the walk cycles up or slows them down from there
+const BASE_POSE_INTERVAL: f32 = 0.1;
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Pose {
+    pub left_arm: (f32, f32),
+    pub right_arm: (f32, f32),
+    pub left_leg: (f32, f32),
+    pub right_leg: (f32, f32),
+}
+
+pub const RUN_POSES: [Pose; 5] = [
+    // Pose 1: Right leg forward, left arm forward
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (15.0, 60.0),
+    },
+    // Pose 2: Both legs mid-motion, arms slightly bent
+    Pose {
+        left_arm: (-15.0, 30.0),
+        right_arm: (15.0, 30.0),
+        left_leg: (-5.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+    // Pose 3: Left leg forward, right arm forward
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (15.0, 60.0),
+        right_leg: (-10.0, 60.0),
+    },
+    // Pose 4: Both legs mid-motion opposite to Pose 2
+    Pose {
+        left_arm: (-15.0, 30.0),
+        right_arm: (15.0, 30.0),
+        left_leg: (10.0, 60.0),
+        right_leg: (-5.0, 60.0),
+    },
+    // Pose 5: Neutral pose
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+];
+
+// Crouched with a short stride, arms out front
+pub const SNEAK_POSES: [Pose; RUN_POSES.len()] = [
+    Pose {
+        left_arm: (-12.0, 32.0),
+        right_arm: (22.0, 28.0),
+        left_leg: (-8.0, 56.0),
+        right_leg: (10.0, 56.0),
+    },
+    Pose {
+        left_arm: (-14.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (-4.0, 56.0),
+        right_leg: (6.0, 56.0),
+    },
+    Pose {
+        left_arm: (-22.0, 28.0),
+        right_arm: (12.0, 32.0),
+        left_leg: (10.0, 56.0),
+        right_leg: (-8.0, 56.0),
+    },
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (14.0, 30.0),
+        left_leg: (6.0, 56.0),
+        right_leg: (-4.0, 56.0),
+    },
+    Pose {
+        left_arm: (-16.0, 30.0),
+        right_arm: (16.0, 30.0),
+        left_leg: (-8.0, 56.0),
+        right_leg: (8.0, 56.0),
+    },
+];
+// Pixels the body is lowered by while sneaking
+pub const SNEAK_CROUCH: f32 = 4.0;
+// Pixels the body bobs up and down by on a run
+pub const BOBBING: f32 = 5.0;
+// A jump's arc, the body rises and lands back in this long
+pub const JUMP_DURATION: f32 = 0.5;
+pub const JUMP_HEIGHT: f32 = 30.0;
+
+impl Pose {
+    // Poses are written facing right
+    pub fn facing(self, facing: Facing) -> Pose {
+        let side = facing.sign();
+        let flip = |(x, y): (f32, f32)| (x * side, y);
+        Pose {
+            left_arm: flip(self.left_arm),
+            right_arm: flip(self.right_arm),
+            left_leg: flip(self.left_leg),
+            right_leg: flip(self.right_leg),
+        }
+    }
+}
+
+pub fn lerp_pose(start: &Pose, end: &Pose, t: f32) -> Pose {
+    Pose {
+        left_arm: (
+            start.left_arm.0 + (end.left_arm.0 - start.left_arm.0) * t,
+            start.left_arm.1 + (end.left_arm.1 - start.left_arm.1) * t,
+        ),
+        right_arm: (
+            start.right_arm.0 + (end.right_arm.0 - start.right_arm.0) * t,
+            start.right_arm.1 + (end.right_arm.1 - start.right_arm.1) * t,
+        ),
+        left_leg: (
+            start.left_leg.0 + (end.left_leg.0 - start.left_leg.0) * t,
+            start.left_leg.1 + (end.left_leg.1 - start.left_leg.1) * t,
+        ),
+        right_leg: (
+            start.right_leg.0 + (end.right_leg.0 - start.right_leg.0) * t,
+            start.right_leg.1 + (end.right_leg.1 - start.right_leg.1) * t,
+        ),
+    }
+}
+
+// Idle pose definition
+pub const IDLE_POSE: Pose = Pose {
+    left_arm: (-20.0, 30.0),
+    right_arm: (20.0, 30.0),
+    left_leg: (-10.0, 60.0),
+    right_leg: (10.0, 60.0),
+};
+
+// Right hand waved back and forth over the head
+pub const WAVE_POSES: [Pose; 2] = [
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, -15.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (32.0, -5.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+];
+
+// Arms up and down in turn, stepping side to side
+pub const DANCE_POSES: [Pose; 4] = [
+    Pose {
+        left_arm: (-22.0, 0.0),
+        right_arm: (20.0, 35.0),
+        left_leg: (-14.0, 60.0),
+        right_leg: (8.0, 58.0),
+    },
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+    Pose {
+        left_arm: (-20.0, 35.0),
+        right_arm: (22.0, 0.0),
+        left_leg: (-8.0, 58.0),
+        right_leg: (14.0, 60.0),
+    },
+    Pose {
+        left_arm: (-20.0, 30.0),
+        right_arm: (20.0, 30.0),
+        left_leg: (-10.0, 60.0),
+        right_leg: (10.0, 60.0),
+    },
+];
+
+// Sat on the ground with the legs out, see `Clip::drop`
+pub const SIT_POSES: [Pose; 1] = [Pose {
+    left_arm: (-18.0, 38.0),
+    right_arm: (18.0, 38.0),
+    left_leg: (-22.0, 42.0),
+    right_leg: (22.0, 42.0),
+}];
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Foot {
+    Left,
+    Right,
+}
+
+// One pose of a clip, blending into the next over its duration
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct Frame {
+    pub pose: Pose,
+    pub duration_ms: u32,
+    #[serde(default)]
+    pub step: Option<Foot>, // Lands as the pose starts, kicking up dust
+}
+
+impl Frame {
+    // Seconds
+    pub fn duration(&self) -> f32 {
+        self.duration_ms as f32 / 1000.0
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Clip {
+    pub frames: Vec<Frame>,
+    #[serde(default, rename = "loop")]
+    pub looping: bool, // Starts over after the last frame
+    #[serde(default)]
+    pub hold: bool, // Stays on the last frame until the player moves, rather than ending
+    #[serde(default)]
+    pub drop: f32, // Pixels the body is lowered by
+}
+
+impl Clip {
+    fn repeated(poses: &[Pose], duration_ms: u32, times: usize) -> Self {
+        let frames = poses.iter().map(|&pose| Frame {
+            pose,
+            duration_ms,
+            step: None,
+        });
+        Self {
+            frames: frames.cycle().take(poses.len() * times).collect(),
+            looping: false,
+            hold: false,
+            drop: 0.0,
+        }
+    }
+
+    // A looped walk cycle, the right foot lands on its first pose and the
+    // left on its third
+    fn walk(poses: &[Pose], duration_ms: u32) -> Self {
+        let mut clip = Self {
+            looping: true,
+            ..Self::repeated(poses, duration_ms, 1)
+        };
+        clip.frames[0].step = Some(Foot::Right);
+        clip.frames[2].step = Some(Foot::Left);
+        clip
+    }
+
+    // Seconds from the first frame to the end of the last
+    pub fn duration(&self) -> f32 {
+        self.frames.iter().map(Frame::duration).sum()
+    }
+
+    pub fn is_finished(&self, elapsed: f32) -> bool {
+        !self.looping && !self.hold && elapsed >= self.duration()
+    }
+
+    // The pose `elapsed` seconds in
+    pub fn sample(&self, elapsed: f32) -> Pose {
+        let Some(last) = self.frames.last() else {
+            return IDLE_POSE;
+        };
+        let duration = self.duration();
+        let mut time = if self.looping && duration > 0.0 {
+            elapsed % duration
+        } else {
+            elapsed
+        };
+        for (index, frame) in self.frames.iter().enumerate() {
+            if time < frame.duration() {
+                let next = match self.frames.get(index + 1) {
+                    Some(next) => next,
+                    None if self.looping => &self.frames[0],
+                    None => frame,
+                };
+                return lerp_pose(&frame.pose, &next.pose, time / frame.duration());
+            }
+            time -= frame.duration();
+        }
+        last.pose
+    }
+}
+
+// Named clips, read from `ANIMATIONS_FILE` so poses and timings can be
+// tweaked without rebuilding. The file is a JSON object of clips by name:
+//
+//     {"wave": {"frames": [{"pose": {"left_arm": [-20, 30], ...},
+//                           "duration_ms": 250, "step": "Left"}, ...],
+//               "loop": false, "hold": false, "drop": 0}}
+//
+// Clips it leaves out, or gives no frames, keep their built-in definitions.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(transparent)]
+pub struct AnimationSet {
+    clips: HashMap<String, Clip>,
+}
+
+impl Default for AnimationSet {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl AnimationSet {
+    // The clips made from the poses above
+    pub fn builtin() -> Self {
+        let clips = [
+            (RUN, Clip::walk(&RUN_POSES, 100)),
+            (SNEAK, Clip::walk(&SNEAK_POSES, 180)),
+            (
+                IDLE,
+                Clip {
+                    hold: true,
+                    ..Clip::repeated(&[IDLE_POSE], 1000, 1)
+                },
+            ),
+            (WAVE, Clip::repeated(&WAVE_POSES, 250, 4)),
+            (DANCE, Clip::repeated(&DANCE_POSES, 200, 5)),
+            (
+                SIT,
+                Clip {
+                    hold: true,
+                    drop: 20.0,
+                    ..Clip::repeated(&SIT_POSES, 1000, 1)
+                },
+            ),
+        ];
+        Self {
+            clips: clips
+                .into_iter()
+                .map(|(name, clip)| (name.to_string(), clip))
+                .collect(),
+        }
+    }
+
+    // Every name the game plays has a built-in clip, so this always finds one
+    pub fn clip(&self, name: &str) -> &Clip {
+        &self.clips[name]
+    }
+
+    // The built-in clips with the usable ones from `self` in their place
+    fn over_builtin(self) -> Self {
+        let mut set = Self::builtin();
+        set.clips.extend(
+            self.clips
+                .into_iter()
+                .filter(|(_, clip)| !clip.frames.is_empty()),
+        );
+        set
+    }
+}
+
+// The clips in use. Characters everywhere read them each frame, so they live
+// here rather than being passed down to every system.
+static CLIPS: LazyLock<RwLock<Arc<AnimationSet>>> =
+    LazyLock::new(|| RwLock::new(Arc::new(AnimationSet::builtin())));
+
+pub fn clips() -> Arc<AnimationSet> {
+    Arc::clone(&CLIPS.read().unwrap_or_else(PoisonError::into_inner))
+}
+
+// Replaces the clips in use, from then on every character plays them
+pub fn install(set: AnimationSet) {
+    *CLIPS.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(set.over_builtin());
+}
+
+// The walk cycle for a stance
+pub fn walk_clip(stance: Stance) -> &'static str {
+    match stance {
+        Stance::Sneaking => SNEAK,
+        _ => RUN,
+    }
+}
+
+pub fn emote_clip(emote: Emote) -> &'static str {
+    match emote {
+        Emote::Wave => WAVE,
+        Emote::Dance => DANCE,
+        Emote::Sit => SIT,
+    }
+}
+
+// How many times faster than a run a stance goes through the walk cycle,
+// and how high it bobs, in step with `WALK_SPEED` and `SPRINT_SPEED`
+pub fn gait(stance: Stance) -> (f32, f32) {
+    match stance {
+        Stance::Walking => (0.65, 0.5),
+        Stance::Sprinting => (1.5, 1.6),
+        _ => (1.0, 1.0),
+    }
+}
+
+// Seconds the walk cycle stays on `index` before moving to the next pose
+pub fn walk_interval(animation: &Animation, clip: &Clip, index: usize) -> f32 {
+    let speed = animation.pose_update_interval.as_secs_f32() / BASE_POSE_INTERVAL;
+    let (rate, _) = gait(animation.stance);
+    clip.frames[index % clip.frames.len()].duration() * speed / rate
+}
+
+// Interpolated pose for the current point of the walk cycle, or of the
+// emote being played while standing still
+pub fn current_pose(animation: &Animation, is_moving: bool) -> Pose {
+    let clips = clips();
+    if is_moving {
+        let frames = &clips.clip(walk_clip(animation.stance)).frames;
+        let start_pose = &frames[animation.current_pose_index % frames.len()].pose;
+        let end_pose = &frames[animation.next_pose_index % frames.len()].pose;
+        lerp_pose(start_pose, end_pose, animation.pose_interp_factor)
+    } else if let Some(emote) = animation.emote {
+        clips
+            .clip(emote_clip(emote))
+            .sample(animation.emote_elapsed)
+    } else {
+        clips.clip(IDLE).sample(animation.idle_elapsed)
+    }
+}
+
+// Like `current_pose`, but the walk cycle holds each pose rather than
+// blending into the next, for characters drawn simplified
+pub fn key_pose(animation: &Animation, is_moving: bool) -> Pose {
+    if is_moving {
+        let clips = clips();
+        let frames = &clips.clip(walk_clip(animation.stance)).frames;
+        frames[animation.current_pose_index % frames.len()].pose
+    } else {
+        current_pose(animation, false)
+    }
+}
@@ -0,0 +1,60 @@
+use crate::palette;
+use crate::protocol::Severity;
+use crate::ui;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+const BANNER_HEIGHT: f32 = 36.0;
+// The last part of its time fading out
+const FADE: Duration = Duration::from_secs(1);
+
+// How long a banner stays up, longer the more it matters
+fn shown_for(severity: Severity) -> Duration {
+    match severity {
+        Severity::Info => Duration::from_secs(8),
+        Severity::Warning => Duration::from_secs(15),
+        Severity::Critical => Duration::from_secs(30),
+    }
+}
+
+// The last `ServerAnnouncement`, across the top of the screen until its
+// time is up. A newer one replaces it, it's in the chat log either way.
+#[derive(Default)]
+pub struct AnnouncementBanner {
+    current: Option<(String, Severity, Instant)>,
+}
+
+impl AnnouncementBanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn show(&mut self, text: String, severity: Severity) {
+        self.current = Some((text, severity, Instant::now()));
+    }
+
+    pub fn draw(&self) {
+        let Some((text, severity, shown_at)) = &self.current else {
+            return;
+        };
+        let left = shown_for(*severity).saturating_sub(shown_at.elapsed());
+        if left.is_zero() {
+            return;
+        }
+        let alpha = (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0);
+        let color = palette::current().severity(*severity);
+        let color = Color { a: alpha, ..color };
+
+        draw_rectangle(
+            0.0,
+            0.0,
+            ui::width(),
+            BANNER_HEIGHT,
+            Color::new(0.0, 0.0, 0.0, 0.75 * alpha),
+        );
+        draw_rectangle(0.0, BANNER_HEIGHT - 3.0, ui::width(), 3.0, color);
+        let width = ui::text_width(text, 22.0);
+        let x = ((ui::width() - width) / 2.0).max(8.0);
+        ui::draw_text(text, x, BANNER_HEIGHT - 12.0, 22.0, color);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::animation::{self, AnimationSet};
+use crate::error;
+use crate::logging;
+use macroquad::audio::{load_sound, Sound};
+use macroquad::experimental::coroutines::start_coroutine;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+pub const ASSETS_DIR: &str = "assets";
+// How often watched files' modification times are checked
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Texture,
+    Font,
+    Sound,
+    Animations,
+}
+
+enum Asset {
+    Texture(Texture2D),
+    Font(Font),
+    Sound(Sound),
+    Animations(AnimationSet),
+}
+
+// A load that finished, handed back by its coroutine
+struct Loaded {
+    name: String,
+    asset: Result<Asset, String>,
+}
+
+// A requested asset and where it comes from
+struct Entry {
+    kind: AssetKind,
+    path: PathBuf,
+    modified: Option<SystemTime>, // When the loaded copy was read
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// Textures, fonts and sounds by name. Files load in the background through
+// macroquad's loaders; until one is done its getter returns None, so callers
+// draw without it. Animation clips aren't kept here, they replace the ones
+// in `animation::clips` once loaded. With `watch` on, a changed file is loaded again and
+// replaces the old copy once it is ready.
+pub struct Assets {
+    dir: PathBuf,
+    entries: HashMap<String, Entry>,
+    textures: HashMap<String, Texture2D>,
+    fonts: HashMap<String, Font>,
+    sounds: HashMap<String, Sound>,
+    finished: Arc<Mutex<Vec<Loaded>>>, // Filled by the loading coroutines
+    loading: usize,
+    watching: bool,
+    last_check: Instant,
+}
+
+impl Default for Assets {
+    fn default() -> Self {
+        Self::new(Path::new(ASSETS_DIR))
+    }
+}
+
+impl Assets {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            entries: HashMap::new(),
+            textures: HashMap::new(),
+            fonts: HashMap::new(),
+            sounds: HashMap::new(),
+            finished: Arc::new(Mutex::new(Vec::new())),
+            loading: 0,
+            watching: false,
+            last_check: Instant::now(),
+        }
+    }
+
+    // Reload files as they change, meant for debug builds
+    pub fn watch(&mut self) {
+        info!(target: logging::ASSETS, "Watching {} for changes", self.dir.display());
+        self.watching = true;
+    }
+
+    pub fn load_texture(&mut self, name: &str, file: &str) {
+        self.request(name, file, AssetKind::Texture);
+    }
+
+    pub fn load_font(&mut self, name: &str, file: &str) {
+        self.request(name, file, AssetKind::Font);
+    }
+
+    pub fn load_sound(&mut self, name: &str, file: &str) {
+        self.request(name, file, AssetKind::Sound);
+    }
+
+    pub fn load_animations(&mut self, file: &str) {
+        self.request(file, file, AssetKind::Animations);
+    }
+
+    pub fn texture(&self, name: &str) -> Option<&Texture2D> {
+        self.textures.get(name)
+    }
+
+    // A texture named after its file, loading it the first time it's asked for
+    pub fn texture_file(&mut self, file: &str) -> Option<&Texture2D> {
+        if !self.entries.contains_key(file) {
+            self.load_texture(file, file);
+        }
+        self.textures.get(file)
+    }
+
+    pub fn font(&self, name: &str) -> Option<&Font> {
+        self.fonts.get(name)
+    }
+
+    pub fn sound(&self, name: &str) -> Option<&Sound> {
+        self.sounds.get(name)
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loading > 0
+    }
+
+    // `file` is relative to the assets directory
+    fn request(&mut self, name: &str, file: &str, kind: AssetKind) {
+        let path = self.dir.join(file);
+        let entry = Entry {
+            kind,
+            modified: modified_time(&path),
+            path,
+        };
+        self.start_load(name, kind, &entry.path);
+        self.entries.insert(name.to_string(), entry);
+    }
+
+    fn start_load(&mut self, name: &str, kind: AssetKind, path: &Path) {
+        self.loading += 1;
+        let finished = Arc::clone(&self.finished);
+        let name = name.to_string();
+        let path = path.to_string_lossy().into_owned();
+        start_coroutine(async move {
+            let asset = match kind {
+                AssetKind::Texture => load_texture(&path)
+                    .await
+                    .map(Asset::Texture)
+                    .map_err(|e| e.to_string()),
+                AssetKind::Font => load_ttf_font(&path)
+                    .await
+                    .map(Asset::Font)
+                    .map_err(|e| e.to_string()),
+                AssetKind::Sound => load_sound(&path)
+                    .await
+                    .map(Asset::Sound)
+                    .map_err(|e| e.to_string()),
+                AssetKind::Animations => load_string(&path)
+                    .await
+                    .map_err(|e| e.to_string())
+                    .and_then(|text| serde_json::from_str(&text).map_err(|e| e.to_string()))
+                    .map(Asset::Animations),
+            }
+            .map_err(|e| format!("{}: {}", path, e));
+            error::lock(&finished).push(Loaded { name, asset });
+        });
+    }
+
+    // Takes in finished loads and starts reloads of changed files. Call once a frame.
+    pub fn update(&mut self) {
+        let finished: Vec<Loaded> = error::lock(&self.finished).drain(..).collect();
+        for loaded in finished {
+            self.loading -= 1;
+            match loaded.asset {
+                Ok(Asset::Texture(texture)) => {
+                    self.textures.insert(loaded.name, texture);
+                }
+                Ok(Asset::Font(font)) => {
+                    self.fonts.insert(loaded.name, font);
+                }
+                Ok(Asset::Sound(sound)) => {
+                    self.sounds.insert(loaded.name, sound);
+                }
+                Ok(Asset::Animations(set)) => animation::install(set),
+                // A broken reload keeps the copy that was already loaded
+                Err(e) => warn!(target: logging::ASSETS, "Failed to load {}: {}", loaded.name, e),
+            }
+        }
+
+        if self.watching && self.last_check.elapsed() >= WATCH_INTERVAL {
+            self.last_check = Instant::now();
+            self.reload_changed();
+        }
+    }
+
+    fn reload_changed(&mut self) {
+        let mut changed = Vec::new();
+        for (name, entry) in &mut self.entries {
+            let modified = modified_time(&entry.path);
+            if modified.is_some() && modified != entry.modified {
+                entry.modified = modified;
+                changed.push((name.clone(), entry.kind, entry.path.clone()));
+            }
+        }
+        for (name, kind, path) in changed {
+            info!(target: logging::ASSETS, "Reloading {}", name);
+            self.start_load(&name, kind, &path);
+        }
+    }
+}
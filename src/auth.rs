@@ -0,0 +1,247 @@
+use crate::logging;
+use crate::protocol::{now_millis, ClientMessage, SessionTicket};
+use crate::ui;
+use directories::ProjectDirs;
+use macroquad::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// Identifies this install to the server so protected names only ask for the
+// recovery code on new devices
+const DEVICE_ID_FILE: &str = "device.id";
+// The last session ticket, so the next start resumes instead of logging in
+const TICKET_FILE: &str = "session.ticket";
+const REJECTED_DURATION: Duration = Duration::from_secs(5);
+const PROMPT_WIDTH: f32 = 460.0;
+
+// Both files are kept in the platform data directory, like the chat logs
+fn data_path(file: &str) -> io::Result<PathBuf> {
+    ProjectDirs::from("", "", "lam")
+        .map(|dirs| dirs.data_dir().join(file))
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))
+}
+
+fn create_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::create_dir_all(dir),
+        None => Ok(()),
+    }
+}
+
+// Reads this install's device id, creating one the first time. One left in
+// the working directory by an older version moves to the data directory.
+pub fn load_device_id() -> String {
+    let path = data_path(DEVICE_ID_FILE);
+    if let Some(id) = path
+        .as_ref()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+    {
+        let id = id.trim();
+        if !id.is_empty() {
+            return id.to_string();
+        }
+    }
+    let id = fs::read_to_string(DEVICE_ID_FILE)
+        .ok()
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .unwrap_or_else(random_device_id);
+    let saved = path.and_then(|path| {
+        create_parent(&path)?;
+        fs::write(path, &id)
+    });
+    if let Err(e) = saved {
+        warn!(target: logging::AUTH, "Failed to save device id: {}", e);
+    }
+    id
+}
+
+pub fn random_device_id() -> String {
+    format!("{:016x}", ::rand::random::<u64>())
+}
+
+pub fn load_ticket() -> Option<SessionTicket> {
+    let file = File::open(data_path(TICKET_FILE).ok()?).ok()?;
+    bincode::deserialize_from(BufReader::new(file)).ok()
+}
+
+fn save_ticket(ticket: &SessionTicket) -> io::Result<()> {
+    let path = data_path(TICKET_FILE)?;
+    create_parent(&path)?;
+    let writer = BufWriter::new(File::create(path)?);
+    bincode::serialize_into(writer, ticket).map_err(io::Error::other)
+}
+
+enum Prompt {
+    None,
+    EnterCode {
+        name: String,
+        code: String,
+        fresh: bool, // Keys queued before the prompt opened are still pending
+    },
+    ShowCode {
+        code: String,
+    },
+    Rejected {
+        reason: String,
+        shown_at: Instant,
+    },
+}
+
+// Client side of name protection: asks for the recovery code when the server
+// wants it and shows a freshly registered one
+pub struct AuthPrompt {
+    prompt: Prompt,
+    // Answers the first challenge without asking, for headless clients
+    pub recovery_code: Option<String>,
+    pub ticket: Option<SessionTicket>,
+    pub save_tickets: bool,                // Keep the latest ticket on disk
+    fallback_login: Option<ClientMessage>, // Sent if the server turns the ticket down
+}
+
+impl Default for AuthPrompt {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AuthPrompt {
+    pub fn new() -> Self {
+        Self {
+            prompt: Prompt::None,
+            recovery_code: None,
+            ticket: None,
+            save_tickets: false,
+            fallback_login: None,
+        }
+    }
+
+    // Resumes the session from the ticket if it is still good for `name`,
+    // otherwise goes straight to `login`
+    pub fn login_message(&mut self, name: &str, login: ClientMessage) -> ClientMessage {
+        match &self.ticket {
+            Some(ticket) if ticket.name == name && ticket.expires_at_ms > now_millis() => {
+                self.fallback_login = Some(login);
+                ClientMessage::ResumeSession {
+                    ticket: ticket.clone(),
+                }
+            }
+            _ => login,
+        }
+    }
+
+    pub fn is_typing(&self) -> bool {
+        matches!(self.prompt, Prompt::EnterCode { .. })
+    }
+
+    pub fn handle_message(&mut self, message: &ClientMessage, outbox: &mut Vec<ClientMessage>) {
+        match message {
+            ClientMessage::RecoveryCodeRequired { name } => {
+                if let Some(code) = self.recovery_code.take() {
+                    outbox.push(ClientMessage::SubmitRecoveryCode { code });
+                    return;
+                }
+                info!(target: logging::AUTH, "{} is protected, enter its recovery code", name);
+                self.prompt = Prompt::EnterCode {
+                    name: name.clone(),
+                    code: String::new(),
+                    fresh: true,
+                };
+            }
+            ClientMessage::RecoveryCode { code } => {
+                info!(target: logging::AUTH, "Recovery code: {}", code);
+                self.prompt = Prompt::ShowCode { code: code.clone() };
+            }
+            ClientMessage::SessionTicket { ticket } => {
+                self.fallback_login = None;
+                if self.save_tickets {
+                    if let Err(e) = save_ticket(ticket) {
+                        warn!(target: logging::AUTH, "Failed to save session ticket: {}", e);
+                    }
+                }
+                self.ticket = Some(ticket.clone());
+            }
+            ClientMessage::LoginRejected { reason } => {
+                if let Some(login) = self.fallback_login.take() {
+                    info!(target: logging::AUTH, "Couldn't resume session: {}", reason);
+                    self.ticket = None;
+                    outbox.push(login);
+                    return;
+                }
+                warn!(target: logging::AUTH, "Login rejected: {}", reason);
+                self.prompt = Prompt::Rejected {
+                    reason: reason.clone(),
+                    shown_at: Instant::now(),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    pub fn update(&mut self, outbox: &mut Vec<ClientMessage>) {
+        match &mut self.prompt {
+            Prompt::None => {}
+            Prompt::EnterCode { code, fresh, .. } => {
+                while let Some(c) = get_char_pressed() {
+                    if !*fresh && !c.is_control() {
+                        code.push(c);
+                    }
+                }
+                *fresh = false;
+                if is_key_pressed(KeyCode::Backspace) {
+                    code.pop();
+                }
+                if is_key_pressed(KeyCode::Enter) && !code.trim().is_empty() {
+                    outbox.push(ClientMessage::SubmitRecoveryCode {
+                        code: code.trim().to_string(),
+                    });
+                    self.prompt = Prompt::None;
+                } else if is_key_pressed(KeyCode::Escape) {
+                    // Keep playing without the name, mail stays locked
+                    self.prompt = Prompt::None;
+                }
+            }
+            Prompt::ShowCode { .. } => {
+                if is_key_pressed(KeyCode::Enter) {
+                    self.prompt = Prompt::None;
+                }
+            }
+            Prompt::Rejected { shown_at, .. } => {
+                if shown_at.elapsed() >= REJECTED_DURATION {
+                    self.prompt = Prompt::None;
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self) {
+        let lines = match &self.prompt {
+            Prompt::None => return,
+            Prompt::EnterCode { name, code, .. } => vec![
+                format!("{} is protected on this server.", name),
+                format!("Recovery code: {}_", code),
+                "Enter submit, Esc play without it".to_string(),
+            ],
+            Prompt::ShowCode { code } => vec![
+                "Your name is protected. Write this code down,".to_string(),
+                "you need it to log in from another device:".to_string(),
+                code.clone(),
+                "Enter to continue".to_string(),
+            ],
+            Prompt::Rejected { reason, .. } => vec![format!("Login failed: {}", reason)],
+        };
+
+        let x = (ui::width() - PROMPT_WIDTH) / 2.0;
+        let y = ui::height() / 2.0 - 60.0;
+        let height = 24.0 + lines.len() as f32 * 26.0;
+        draw_rectangle(x, y, PROMPT_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, PROMPT_WIDTH, height, 2.0, DARKGRAY);
+        for (i, line) in lines.iter().enumerate() {
+            ui::draw_text(line, x + 12.0, y + 30.0 + i as f32 * 26.0, 20.0, BLACK);
+        }
+    }
+}
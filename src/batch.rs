@@ -0,0 +1,473 @@
+use crate::ui;
+use macroquad::models::{draw_mesh, Mesh, Vertex};
+use macroquad::prelude::*;
+use std::mem;
+use std::sync::Arc;
+
+// Segments in a batched circle, the same as macroquad's own `draw_circle`
+const CIRCLE_SIDES: usize = 20;
+// Mesh indices are u16, a batch is drawn early before it outgrows them
+const MAX_BATCH_VERTICES: usize = u16::MAX as usize;
+
+// Drawn bottom to top. Everything in a layer keeps the order it was submitted in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layer {
+    Shadows, // On the ground under every character
+    Characters,
+    Bubbles, // Chat over every character
+    Critters,
+    Particles,
+}
+
+// What has to be bound to draw a primitive. Shapes share one, so runs of
+// them become a single mesh; text needs the font atlas. Sprites sort with the
+// shapes so characters still overlap back to front, each one splits the batch.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Material {
+    Shapes,
+    Text,
+}
+
+enum Primitive {
+    Line {
+        from: Vec2,
+        to: Vec2,
+        thickness: f32,
+        color: Color,
+    },
+    Circle {
+        center: Vec2,
+        radius: f32,
+        color: Color,
+    },
+    // Fades from `color` in the middle to clear at the edge
+    SoftEllipse {
+        center: Vec2,
+        radii: Vec2,
+        color: Color,
+    },
+    CircleLines {
+        center: Vec2,
+        radius: f32,
+        thickness: f32,
+        color: Color,
+    },
+    Rect {
+        rect: Rect,
+        color: Color,
+    },
+    Triangle {
+        corners: [Vec2; 3],
+        color: Color,
+    },
+    Text {
+        text: String,
+        position: Vec2,
+        size: f32,
+        color: Color,
+    },
+    Sprite {
+        texture: Texture2D,
+        source: Rect, // Part of the texture, in pixels
+        dest: Rect,
+        color: Color,
+    },
+    Mesh {
+        mesh: Arc<ShapeMesh>,
+        origin: Vec2,
+        sway: f32,
+        color: Color,
+    },
+}
+
+// Shapes built once and submitted every frame in one piece, e.g. a
+// character's hair. Points are relative to where it is drawn, each with a
+// weight for how far `DrawList::mesh`'s sway moves it sideways.
+#[derive(Clone, Debug, Default)]
+pub struct ShapeMesh {
+    points: Vec<Vec2>,
+    weights: Vec<f32>,
+    indices: Vec<u16>,
+}
+
+impl ShapeMesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Lines past what one batch holds are left out
+    pub fn line(&mut self, from: Vec2, to: Vec2, thickness: f32) {
+        let Some(direction) = (to - from).try_normalize() else {
+            return;
+        };
+        if self.points.len() + 4 > MAX_BATCH_VERTICES {
+            return;
+        }
+        let offset = direction.perp() * thickness / 2.0;
+        let first = self.points.len() as u16;
+        self.points
+            .extend([from + offset, to + offset, to - offset, from - offset]);
+        self.weights.extend([0.0; 4]);
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    // Sets every point's sway weight from where it is
+    pub fn set_weights(&mut self, weight: impl Fn(Vec2) -> f32) {
+        self.weights = self.points.iter().map(|&point| weight(point)).collect();
+    }
+}
+
+impl Primitive {
+    fn material(&self) -> Material {
+        match self {
+            Primitive::Text { .. } => Material::Text,
+            _ => Material::Shapes,
+        }
+    }
+}
+
+struct Command {
+    layer: Layer,
+    primitive: Primitive,
+}
+
+// How the last flush went, for the debug overlay
+#[derive(Default, Clone, Copy, Debug)]
+pub struct BatchStats {
+    pub primitives: usize,
+    pub batches: usize, // Draw calls issued
+}
+
+// Primitives submitted during a frame, drawn together by `flush`. Sorting by
+// layer and material lets neighbouring shapes share one mesh instead of a
+// draw call each. Buffers are kept between frames.
+#[derive(Default)]
+pub struct DrawList {
+    commands: Vec<Command>,
+    vertices: Vec<Vertex>,
+    indices: Vec<u16>,
+    pub stats: BatchStats,
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, layer: Layer, primitive: Primitive) {
+        self.commands.push(Command { layer, primitive });
+    }
+
+    pub fn line(&mut self, layer: Layer, from: Vec2, to: Vec2, thickness: f32, color: Color) {
+        let primitive = Primitive::Line {
+            from,
+            to,
+            thickness,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn circle(&mut self, layer: Layer, x: f32, y: f32, radius: f32, color: Color) {
+        let primitive = Primitive::Circle {
+            center: vec2(x, y),
+            radius,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn soft_ellipse(&mut self, layer: Layer, center: Vec2, radii: Vec2, color: Color) {
+        let primitive = Primitive::SoftEllipse {
+            center,
+            radii,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn circle_lines(
+        &mut self,
+        layer: Layer,
+        x: f32,
+        y: f32,
+        radius: f32,
+        thickness: f32,
+        color: Color,
+    ) {
+        let primitive = Primitive::CircleLines {
+            center: vec2(x, y),
+            radius,
+            thickness,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn rect(&mut self, layer: Layer, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        let primitive = Primitive::Rect {
+            rect: Rect::new(x, y, w, h),
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn triangle(&mut self, layer: Layer, a: Vec2, b: Vec2, c: Vec2, color: Color) {
+        let primitive = Primitive::Triangle {
+            corners: [a, b, c],
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn text(&mut self, layer: Layer, text: &str, x: f32, y: f32, size: f32, color: Color) {
+        let primitive = Primitive::Text {
+            text: text.to_string(),
+            position: vec2(x, y),
+            size,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    // `mesh` with its origin at `origin`, every point moved `sway` times its
+    // weight to the right
+    pub fn mesh(
+        &mut self,
+        layer: Layer,
+        mesh: &Arc<ShapeMesh>,
+        origin: Vec2,
+        sway: f32,
+        color: Color,
+    ) {
+        let primitive = Primitive::Mesh {
+            mesh: Arc::clone(mesh),
+            origin,
+            sway,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    pub fn sprite(
+        &mut self,
+        layer: Layer,
+        texture: &Texture2D,
+        source: Rect,
+        dest: Rect,
+        color: Color,
+    ) {
+        let primitive = Primitive::Sprite {
+            texture: texture.clone(),
+            source,
+            dest,
+            color,
+        };
+        self.push(layer, primitive);
+    }
+
+    // Draws everything submitted since the last flush and empties the list
+    pub fn flush(&mut self) {
+        let mut commands = mem::take(&mut self.commands);
+        // Stable, so only text moves, to the end of its layer
+        commands.sort_by_key(|command| (command.layer, command.primitive.material()));
+
+        let mut batches = 0;
+        for command in &commands {
+            match &command.primitive {
+                Primitive::Text {
+                    text,
+                    position,
+                    size,
+                    color,
+                } => {
+                    batches += self.draw_batch();
+                    ui::draw_text(text, position.x, position.y, *size, *color);
+                    batches += 1;
+                }
+                Primitive::Sprite {
+                    texture,
+                    source,
+                    dest,
+                    color,
+                } => {
+                    batches += self.draw_batch();
+                    draw_texture_ex(
+                        texture,
+                        dest.x,
+                        dest.y,
+                        *color,
+                        DrawTextureParams {
+                            source: Some(*source),
+                            dest_size: Some(dest.size()),
+                            ..Default::default()
+                        },
+                    );
+                    batches += 1;
+                }
+                shape => self.add_shape(shape, &mut batches),
+            }
+        }
+        batches += self.draw_batch();
+
+        self.stats = BatchStats {
+            primitives: commands.len(),
+            batches,
+        };
+        commands.clear();
+        self.commands = commands;
+    }
+
+    // Draws the pending mesh, returning how many draw calls that took
+    fn draw_batch(&mut self) -> usize {
+        if self.indices.is_empty() {
+            return 0;
+        }
+        let mesh = Mesh {
+            vertices: mem::take(&mut self.vertices),
+            indices: mem::take(&mut self.indices),
+            texture: None,
+        };
+        draw_mesh(&mesh);
+        self.vertices = mesh.vertices;
+        self.indices = mesh.indices;
+        self.vertices.clear();
+        self.indices.clear();
+        1
+    }
+
+    fn add_shape(&mut self, shape: &Primitive, batches: &mut usize) {
+        let needed = match shape {
+            Primitive::Line { .. } | Primitive::Rect { .. } => 4,
+            Primitive::Triangle { .. } => 3,
+            Primitive::Circle { .. } | Primitive::SoftEllipse { .. } => CIRCLE_SIDES + 1,
+            Primitive::CircleLines { .. } => CIRCLE_SIDES * 2,
+            Primitive::Mesh { mesh, .. } => mesh.points.len(),
+            Primitive::Text { .. } | Primitive::Sprite { .. } => {
+                unreachable!("text and sprites are drawn on their own")
+            }
+        };
+        if self.vertices.len() + needed > MAX_BATCH_VERTICES {
+            *batches += self.draw_batch();
+        }
+
+        match *shape {
+            Primitive::Line {
+                from,
+                to,
+                thickness,
+                color,
+            } => {
+                let Some(direction) = (to - from).try_normalize() else {
+                    return;
+                };
+                let offset = direction.perp() * thickness / 2.0;
+                self.quad(
+                    [from + offset, to + offset, to - offset, from - offset],
+                    color,
+                );
+            }
+            Primitive::Rect { rect, color } => {
+                let Rect { x, y, w, h } = rect;
+                self.quad(
+                    [
+                        vec2(x, y),
+                        vec2(x + w, y),
+                        vec2(x + w, y + h),
+                        vec2(x, y + h),
+                    ],
+                    color,
+                );
+            }
+            Primitive::Triangle { corners, color } => {
+                let first = self.vertices.len() as u16;
+                for corner in corners {
+                    self.vertex(corner, color);
+                }
+                self.indices.extend([first, first + 1, first + 2]);
+            }
+            Primitive::Circle {
+                center,
+                radius,
+                color,
+            } => {
+                let first = self.vertices.len() as u16;
+                self.vertex(center, color);
+                for side in 0..CIRCLE_SIDES {
+                    self.vertex(center + rim(side) * radius, color);
+                    let next = (side + 1) % CIRCLE_SIDES;
+                    self.indices
+                        .extend([first, first + 1 + side as u16, first + 1 + next as u16]);
+                }
+            }
+            Primitive::SoftEllipse {
+                center,
+                radii,
+                color,
+            } => {
+                let first = self.vertices.len() as u16;
+                let clear = Color { a: 0.0, ..color };
+                self.vertex(center, color);
+                for side in 0..CIRCLE_SIDES {
+                    self.vertex(center + rim(side) * radii, clear);
+                    let next = (side + 1) % CIRCLE_SIDES;
+                    self.indices
+                        .extend([first, first + 1 + side as u16, first + 1 + next as u16]);
+                }
+            }
+            Primitive::CircleLines {
+                center,
+                radius,
+                thickness,
+                color,
+            } => {
+                // A ring of quads between the inner and outer edge
+                let first = self.vertices.len() as u16;
+                for side in 0..CIRCLE_SIDES {
+                    self.vertex(center + rim(side) * (radius - thickness / 2.0), color);
+                    self.vertex(center + rim(side) * (radius + thickness / 2.0), color);
+                    let next = (side + 1) % CIRCLE_SIDES;
+                    let (inner, outer) = (first + side as u16 * 2, first + side as u16 * 2 + 1);
+                    let (next_inner, next_outer) =
+                        (first + next as u16 * 2, first + next as u16 * 2 + 1);
+                    self.indices
+                        .extend([inner, outer, next_outer, inner, next_outer, next_inner]);
+                }
+            }
+            Primitive::Mesh {
+                ref mesh,
+                origin,
+                sway,
+                color,
+            } => {
+                let first = self.vertices.len() as u16;
+                for (&point, &weight) in mesh.points.iter().zip(&mesh.weights) {
+                    self.vertex(origin + point + vec2(sway * weight, 0.0), color);
+                }
+                self.indices
+                    .extend(mesh.indices.iter().map(|&index| first + index));
+            }
+            Primitive::Text { .. } | Primitive::Sprite { .. } => {}
+        }
+    }
+
+    fn vertex(&mut self, position: Vec2, color: Color) {
+        self.vertices
+            .push(Vertex::new(position.x, position.y, 0.0, 0.0, 0.0, color));
+    }
+
+    fn quad(&mut self, corners: [Vec2; 4], color: Color) {
+        let first = self.vertices.len() as u16;
+        for corner in corners {
+            self.vertex(corner, color);
+        }
+        self.indices
+            .extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+}
+
+// Unit vector to a corner of a batched circle
+fn rim(side: usize) -> Vec2 {
+    Vec2::from_angle(side as f32 / CIRCLE_SIDES as f32 * std::f32::consts::TAU)
+}
@@ -0,0 +1,121 @@
+// Prints the wire layout of every protocol message, traced from the serde
+// types themselves, so third-party servers can stay compatible with the client.
+//
+// Usage: cargo run --bin dump-protocol -- [--format markdown|json]
+
+use lam_test::compat::trace_registry;
+use lam_test::protocol::PROTOCOL_VERSION;
+use serde_reflection::{ContainerFormat, Format, Named, Registry, VariantFormat};
+
+const ENCODING: &str = "bincode 1.x default options: little-endian fixed-width integers, \
+    u32 enum variant index, u64 length prefix for strings and sequences, f32 as IEEE 754. \
+    Each message is sent as one FramedTcp frame (varint length prefix).";
+
+fn describe(format: &Format) -> String {
+    match format {
+        Format::Variable(_) => "?".to_string(),
+        Format::TypeName(name) => name.clone(),
+        Format::Unit => "()".to_string(),
+        Format::Bool => "bool".to_string(),
+        Format::I8 => "i8".to_string(),
+        Format::I16 => "i16".to_string(),
+        Format::I32 => "i32".to_string(),
+        Format::I64 => "i64".to_string(),
+        Format::I128 => "i128".to_string(),
+        Format::U8 => "u8".to_string(),
+        Format::U16 => "u16".to_string(),
+        Format::U32 => "u32".to_string(),
+        Format::U64 => "u64".to_string(),
+        Format::U128 => "u128".to_string(),
+        Format::F32 => "f32".to_string(),
+        Format::F64 => "f64".to_string(),
+        Format::Char => "char".to_string(),
+        Format::Str => "string".to_string(),
+        Format::Bytes => "bytes".to_string(),
+        Format::Option(inner) => format!("option<{}>", describe(inner)),
+        Format::Seq(inner) => format!("seq<{}>", describe(inner)),
+        Format::Map { key, value } => format!("map<{}, {}>", describe(key), describe(value)),
+        Format::Tuple(items) => format!(
+            "({})",
+            items.iter().map(describe).collect::<Vec<_>>().join(", ")
+        ),
+        Format::TupleArray { content, size } => format!("[{}; {}]", describe(content), size),
+    }
+}
+
+fn describe_fields(fields: &[Named<Format>]) -> String {
+    fields
+        .iter()
+        .map(|field| format!("`{}`: {}", field.name, describe(&field.value)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn markdown(registry: &Registry) -> String {
+    let mut out = format!("# Protocol v{}\n\n{}\n", PROTOCOL_VERSION, ENCODING);
+
+    for (name, container) in registry {
+        out.push_str(&format!("\n## {}\n\n", name));
+        match container {
+            ContainerFormat::Enum(variants) => {
+                out.push_str("| Index | Variant | Fields |\n|---|---|---|\n");
+                for (index, variant) in variants {
+                    let fields = match &variant.value {
+                        VariantFormat::Variable(_) | VariantFormat::Unit => String::new(),
+                        VariantFormat::NewType(inner) => describe(inner),
+                        VariantFormat::Tuple(items) => {
+                            items.iter().map(describe).collect::<Vec<_>>().join(", ")
+                        }
+                        VariantFormat::Struct(fields) => describe_fields(fields),
+                    };
+                    out.push_str(&format!("| {} | {} | {} |\n", index, variant.name, fields));
+                }
+            }
+            ContainerFormat::Struct(fields) => {
+                out.push_str(&format!("Struct: {}\n", describe_fields(fields)));
+            }
+            ContainerFormat::UnitStruct => out.push_str("Unit struct\n"),
+            ContainerFormat::NewTypeStruct(inner) => {
+                out.push_str(&format!("Newtype of {}\n", describe(inner)));
+            }
+            ContainerFormat::TupleStruct(items) => out.push_str(&format!(
+                "Tuple struct: ({})\n",
+                items.iter().map(describe).collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+    out
+}
+
+fn json(registry: &Registry) -> String {
+    let document = serde_json::json!({
+        "protocol_version": PROTOCOL_VERSION,
+        "encoding": ENCODING,
+        "types": registry,
+    });
+    serde_json::to_string_pretty(&document).expect("Failed to encode registry")
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut format = "markdown".to_string();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().unwrap_or_default(),
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let registry = trace_registry();
+    match format.as_str() {
+        "markdown" | "md" => println!("{}", markdown(&registry)),
+        "json" => println!("{}", json(&registry)),
+        other => {
+            eprintln!("Unknown format {}, expected markdown or json", other);
+            std::process::exit(2);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use lam_test::loadtest::{self, Scenario};
+use lam_test::logging;
+use lam_test::net;
+use lam_test::shutdown;
+use std::path::PathBuf;
+use tracing::info;
+
+const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:3042";
+
+// Usage: lam-loadtest <scenario file> [--server <addr>]
+//                     [--log-level <filter>] [--log-file <file>]
+//
+// See `Scenario` for the file format and loadtests/ for examples.
+fn main() {
+    let mut scenario_path = None;
+    let mut addr = DEFAULT_SERVER_ADDR.to_string();
+    let mut log_filter = logging::DEFAULT_LEVEL.to_string();
+    let mut log_file = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--server" {
+            addr = args.next().expect("--server takes an address");
+        } else if arg == "--log-level" {
+            log_filter = args.next().expect("--log-level takes a filter");
+        } else if arg == "--log-file" {
+            log_file = Some(PathBuf::from(args.next().expect("--log-file takes a file")));
+        } else {
+            scenario_path = Some(PathBuf::from(arg));
+        }
+    }
+    let Some(scenario_path) = scenario_path else {
+        eprintln!("Usage: lam-loadtest <scenario file> [--server <addr>]");
+        std::process::exit(2);
+    };
+
+    logging::init(&log_filter, log_file.as_deref()).expect("Failed to start logging");
+    let scenario = Scenario::from_file(&scenario_path).expect("Failed to load scenario");
+    let rt = net::runtime().expect("Failed to start runtime");
+    shutdown::handle_ctrl_c(&rt);
+    let report = loadtest::run(&scenario, &addr, &rt).expect("Failed to start bots");
+    info!(
+        target: logging::NET,
+        "Done: {} bots spawned, {} churned, {} at peak",
+        report.spawned,
+        report.churned,
+        report.peak
+    );
+}
@@ -0,0 +1,100 @@
+use lam_test::link::SnapshotPolicy;
+use lam_test::logging;
+use lam_test::map::GameMap;
+use lam_test::protocol::{Season, Severity};
+use lam_test::server::{self, ServerHandle, ServerOptions};
+use lam_test::storage::StorageKind;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Address the server listens on when none is given on the command line
+const DEFAULT_LISTEN_ADDR: &str = "0.0.0.0:3042";
+
+// Usage: lam-server [listen addr] [--season spring|summer|autumn|winter]
+//                   [--scripts <dir>] [--ticket-secret <file>]
+//                   [--identity-secret <file>]
+//                   [--snapshot-policy <json file>]
+//                   [--log-level <filter>] [--log-file <file>]
+//                   [--storage memory|files:<dir>|sqlite:<file>]
+//                   [--private] [--map <json file>]
+//
+// Commands typed while it runs:
+//   map <json file>   moves everyone to that map without reconnecting
+//   map default       back to the built-in map
+//   announce [info|warning|critical] <text>
+//                     a banner for everyone connected
+fn main() {
+    let mut addr = DEFAULT_LISTEN_ADDR.to_string();
+    let mut options = ServerOptions::default();
+    let mut log_filter = logging::DEFAULT_LEVEL.to_string();
+    let mut log_file = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--season" {
+            let name = args.next().unwrap_or_default();
+            options.season_override = Some(Season::parse(&name).expect("Unknown season"));
+        } else if arg == "--scripts" {
+            options.scripts_dir = Some(PathBuf::from(
+                args.next().expect("--scripts takes a directory"),
+            ));
+        } else if arg == "--ticket-secret" {
+            options.ticket_secret_file = Some(PathBuf::from(
+                args.next().expect("--ticket-secret takes a file"),
+            ));
+        } else if arg == "--identity-secret" {
+            options.identity_secret_file = Some(PathBuf::from(
+                args.next().expect("--identity-secret takes a file"),
+            ));
+        } else if arg == "--snapshot-policy" {
+            let path = PathBuf::from(args.next().expect("--snapshot-policy takes a file"));
+            options.snapshot_policy =
+                SnapshotPolicy::from_file(&path).expect("Failed to load snapshot policy");
+        } else if arg == "--storage" {
+            let kind = args.next().unwrap_or_default();
+            options.storage = StorageKind::parse(&kind).expect("Unknown storage");
+        } else if arg == "--private" {
+            options.private_room = true;
+        } else if arg == "--map" {
+            options.map_file = Some(PathBuf::from(args.next().expect("--map takes a file")));
+        } else if arg == "--log-level" {
+            log_filter = args.next().expect("--log-level takes a filter");
+        } else if arg == "--log-file" {
+            log_file = Some(PathBuf::from(args.next().expect("--log-file takes a file")));
+        } else {
+            addr = arg;
+        }
+    }
+
+    logging::init(&log_filter, log_file.as_deref()).expect("Failed to start logging");
+    let handle = server::spawn(&addr, options).expect("Failed to start server");
+    // Without a terminal stdin ends straight away and the server just runs
+    for line in io::stdin().lines() {
+        let Ok(line) = line else { break };
+        run_command(&handle, line.trim());
+    }
+    handle.join().expect("Server thread panicked");
+}
+
+fn run_command(handle: &ServerHandle, command: &str) {
+    let mut words = command.splitn(2, ' ');
+    match (words.next(), words.next().map(str::trim)) {
+        (Some(""), None) => {}
+        (Some("map"), Some("default")) => handle.load_map(GameMap::default()),
+        (Some("map"), Some(path)) => match GameMap::from_file(Path::new(path)) {
+            Ok(map) => handle.load_map(map),
+            Err(e) => eprintln!("Failed to load {}: {}", path, e),
+        },
+        // `announce warning Back in 5 minutes`, the severity can be left out
+        (Some("announce"), Some(text)) if !text.is_empty() => {
+            let (severity, text) = text
+                .split_once(' ')
+                .and_then(|(first, rest)| Some((Severity::from_name(first)?, rest)))
+                .unwrap_or((Severity::Info, text));
+            handle.announce(text.to_string(), severity);
+        }
+        _ => eprintln!(
+            "Commands: map <json file>, map default, announce [info|warning|critical] <text>"
+        ),
+    }
+}
@@ -0,0 +1,103 @@
+// Checks this build's messages against every recorded older protocol version,
+// both ways, and lists the variants that would break. Run `record` before a
+// release that bumps PROTOCOL_VERSION and commit the new fixture.
+//
+// Usage: cargo run --bin protocol-compat -- [check|record] [--dir protocol]
+
+use lam_test::compat::{check, Fixture, Verdict, FIXTURE_DIR};
+use lam_test::protocol::PROTOCOL_VERSION;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+fn describe(verdict: &Verdict) -> String {
+    match verdict {
+        Verdict::Compatible => "ok".to_string(),
+        Verdict::Unknown => "unknown variant".to_string(),
+        Verdict::Undecodable(e) => format!("undecodable ({})", e),
+        Verdict::Misread(why) => format!("misread ({})", why),
+        Verdict::LayoutChanged => "layout changed".to_string(),
+    }
+}
+
+fn run_check(dir: &Path) -> bool {
+    let current = Fixture::record();
+    let older = match Fixture::load_older(dir) {
+        Ok(older) => older,
+        Err(e) => {
+            eprintln!("Failed to read fixtures in {}: {}", dir.display(), e);
+            exit(2);
+        }
+    };
+    if older.is_empty() {
+        println!(
+            "No fixtures older than v{} in {}",
+            PROTOCOL_VERSION,
+            dir.display()
+        );
+        return true;
+    }
+
+    let mut compatible = true;
+    for old in &older {
+        let results = check(&current, old);
+        let breaks: Vec<_> = results
+            .iter()
+            .filter(|result| result.old_to_current.is_break() || result.current_to_old.is_break())
+            .collect();
+        println!(
+            "\n## v{} <-> v{}: {} of {} variants break\n",
+            old.protocol_version,
+            PROTOCOL_VERSION,
+            breaks.len(),
+            results.len()
+        );
+        if breaks.is_empty() {
+            continue;
+        }
+        compatible = false;
+        println!(
+            "| Variant | v{0} -> v{1} | v{1} -> v{0} |\n|---|---|---|",
+            old.protocol_version, PROTOCOL_VERSION
+        );
+        for result in breaks {
+            println!(
+                "| {} | {} | {} |",
+                result.variant,
+                describe(&result.old_to_current),
+                describe(&result.current_to_old)
+            );
+        }
+    }
+    compatible
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut command = "check".to_string();
+    let mut dir = PathBuf::from(FIXTURE_DIR);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "check" | "record" => command = arg,
+            "--dir" => dir = PathBuf::from(args.next().unwrap_or_default()),
+            _ => {
+                eprintln!("Unknown argument: {}", arg);
+                exit(2);
+            }
+        }
+    }
+
+    if command == "record" {
+        match Fixture::record().save(&dir) {
+            Ok(path) => println!("Recorded v{} to {}", PROTOCOL_VERSION, path.display()),
+            Err(e) => {
+                eprintln!("Failed to record fixture: {}", e);
+                exit(2);
+            }
+        }
+        return;
+    }
+
+    if !run_check(&dir) {
+        exit(1);
+    }
+}
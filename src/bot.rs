@@ -0,0 +1,175 @@
+use crate::error;
+use crate::game::Game;
+use crate::headless;
+use crate::input::{InputFrame, InputSource, ScriptedInput};
+use crate::logging;
+use crate::net::{send_message, Connection};
+use crate::protocol::ClientMessage;
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use tokio::runtime::Runtime;
+use tracing::info;
+
+// Seconds between a bot's chat messages
+const CHAT_INTERVAL: std::ops::Range<f32> = 5.0..15.0;
+
+const CHAT_LINES: [&str; 5] = [
+    "Hello, world!",
+    "Come over here.",
+    "Okay.",
+    "Anyone racing?",
+    "Nice weather today.",
+];
+
+// How a bot gets around
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Movement {
+    Wander, // Like `ScriptedInput::wander`
+    Still,
+    Circle { radius: f32 }, // Around the middle of the world, one lap every 10 seconds
+}
+
+// What a bot does, the same for every bot in a run
+#[derive(Clone, Debug, PartialEq)]
+pub struct BotBehavior {
+    pub movement: Movement,
+    pub chat_interval: Option<std::ops::Range<f32>>, // Seconds between chat lines, None is silent
+}
+
+impl Default for BotBehavior {
+    fn default() -> Self {
+        Self {
+            movement: Movement::Wander,
+            chat_interval: Some(CHAT_INTERVAL),
+        }
+    }
+}
+
+struct BotInput {
+    behavior: BotBehavior,
+    wander: ScriptedInput,
+    rng: StdRng,
+    next_chat: f32,
+    time: f32,
+}
+
+impl BotInput {
+    fn new(behavior: BotBehavior, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let next_chat = behavior
+            .chat_interval
+            .clone()
+            .map_or(0.0, |interval| rng.gen_range(interval));
+        Self {
+            behavior,
+            wander: ScriptedInput::wander_with_seed(seed),
+            rng,
+            next_chat,
+            time: 0.0,
+        }
+    }
+}
+
+impl InputSource for BotInput {
+    fn poll(&mut self, dt: f32) -> InputFrame {
+        self.time += dt;
+        let mut frame = match self.behavior.movement {
+            Movement::Wander => self.wander.poll(dt),
+            Movement::Still => InputFrame::default(),
+            Movement::Circle { radius } => {
+                let angle = self.time / 10.0 * std::f32::consts::TAU;
+                InputFrame {
+                    move_target: Some((
+                        WORLD_WIDTH / 2.0 + angle.cos() * radius,
+                        WORLD_HEIGHT / 2.0 + angle.sin() * radius,
+                    )),
+                    ..Default::default()
+                }
+            }
+        };
+
+        if let Some(interval) = &self.behavior.chat_interval {
+            self.next_chat -= dt;
+            if self.next_chat <= 0.0 {
+                self.next_chat = self.rng.gen_range(interval.clone());
+                frame.say = Some(CHAT_LINES[self.rng.gen_range(0..CHAT_LINES.len())].to_string());
+            }
+        }
+        frame
+    }
+}
+
+// A simulated player running on its own thread until stopped
+pub struct Bot {
+    pub index: usize,
+    running: Arc<AtomicBool>,
+    handle: JoinHandle<()>,
+}
+
+impl Bot {
+    // Connects bot number `index` to `addr`. The same `seed` makes it move
+    // and chat the same way every run.
+    pub fn spawn(
+        index: usize,
+        addr: &str,
+        rt: &Runtime,
+        behavior: BotBehavior,
+        seed: u64,
+    ) -> error::Result<Bot> {
+        let game = Arc::new(Mutex::new(Game::with_seed(seed)));
+        {
+            let mut game = error::lock(&game);
+            game.headless = true;
+            game.player_name = format!("bot-{}", index);
+        }
+        let network = Some(Connection::connect(addr, Arc::clone(&game), rt)?);
+        let running = Arc::new(AtomicBool::new(true));
+        let keep_going = Arc::clone(&running);
+
+        let handle = thread::Builder::new()
+            .name(format!("bot-{}", index))
+            .spawn(move || {
+                let mut input = BotInput::new(behavior, seed);
+                headless::run_while(&game, &network, &mut input, || {
+                    keep_going.load(Ordering::Relaxed)
+                });
+                // Leave properly, so the server sees churn rather than timeouts
+                send_message(&network, &mut error::lock(&game), &ClientMessage::Goodbye);
+                if let Some(connection) = network {
+                    connection.close();
+                }
+            })?;
+        Ok(Bot {
+            index,
+            running,
+            handle,
+        })
+    }
+
+    // Disconnects the bot and waits for its thread to finish
+    pub fn stop(self) {
+        self.running.store(false, Ordering::Relaxed);
+        let _ = self.handle.join();
+    }
+}
+
+// Connects `count` simulated players to `addr`, each on its own thread, for
+// load testing the server and the many-players rendering path
+pub fn spawn_bots(count: usize, addr: &str, rt: &Runtime) -> error::Result<Vec<Bot>> {
+    let mut bots = Vec::with_capacity(count);
+    for index in 0..count {
+        bots.push(Bot::spawn(
+            index,
+            addr,
+            rt,
+            BotBehavior::default(),
+            ::rand::random(),
+        )?);
+    }
+    info!(target: logging::NET, "Started {} bots", count);
+    Ok(bots)
+}
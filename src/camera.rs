@@ -0,0 +1,97 @@
+use crate::ui;
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use macroquad::prelude::*;
+
+// Share of the window, across and down, the player can move around in before
+// the camera starts following
+const DEAD_ZONE: Vec2 = Vec2::new(0.3, 0.25);
+// How fast the camera closes in on where it should be, higher is snappier
+const FOLLOW_RATE: f32 = 6.0;
+
+// Which part of the world the window shows. Follows the local player with a
+// dead zone, so small moves don't drag the view around, and eases after it
+// rather than snapping. The view never shows past the world's edges; a world
+// smaller than the window sits in the middle of it.
+pub struct Camera {
+    pub center: Vec2,
+    snap: bool, // Jump straight to the next focus instead of easing
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            center: Vec2::new(WORLD_WIDTH / 2.0, WORLD_HEIGHT / 2.0),
+            snap: true,
+        }
+    }
+
+    // The next update jumps to the focus, e.g. after a respawn
+    pub fn snap(&mut self) {
+        self.snap = true;
+    }
+
+    // Moves toward `focus`, kept within `bounds`. Call once a frame.
+    pub fn update(&mut self, focus: Vec2, bounds: Rect, dt: f32) {
+        let screen = screen_size();
+        if self.snap {
+            self.center = focus;
+            self.snap = false;
+        } else {
+            // Only the part of the offset outside the dead zone counts
+            let slack = screen * DEAD_ZONE / 2.0;
+            let offset = focus - self.center;
+            let goal = self.center + offset - offset.clamp(-slack, slack);
+            let t = 1.0 - (-FOLLOW_RATE * dt).exp();
+            self.center += (goal - self.center) * t;
+        }
+        self.center = Vec2::new(
+            clamp_axis(self.center.x, bounds.x, bounds.w, screen.x),
+            clamp_axis(self.center.y, bounds.y, bounds.h, screen.y),
+        );
+    }
+
+    // The visible part of the world
+    pub fn view(&self) -> Rect {
+        let screen = screen_size();
+        let top_left = self.center.round() - screen / 2.0;
+        Rect::new(top_left.x, top_left.y, screen.x, screen.y)
+    }
+
+    // For `set_camera`, whole pixels so lines and text stay crisp
+    pub fn camera2d(&self) -> Camera2D {
+        let screen = screen_size();
+        Camera2D {
+            target: self.center.round(),
+            zoom: Vec2::new(2.0 / screen.x, 2.0 / screen.y),
+            ..Default::default()
+        }
+    }
+
+    // Where a point in the window is in the world, e.g. a mouse click. The
+    // point is in window pixels, the view in UI pixels.
+    pub fn screen_to_world(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let view = self.view();
+        let scale = ui::scale();
+        (view.x + x / scale, view.y + y / scale)
+    }
+}
+
+// The world is drawn at the UI scale, see `ui::scale`
+fn screen_size() -> Vec2 {
+    Vec2::new(ui::width(), ui::height())
+}
+
+// Keeps a view of `view` pixels centered on `center` within `min..min + size`
+fn clamp_axis(center: f32, min: f32, size: f32, view: f32) -> f32 {
+    if size <= view {
+        min + size / 2.0
+    } else {
+        center.clamp(min + view / 2.0, min + size - view / 2.0)
+    }
+}
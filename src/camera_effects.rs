@@ -0,0 +1,111 @@
+use crate::ui;
+use macroquad::prelude::*;
+
+// How far the view moves at full trauma, in pixels and degrees
+const MAX_SHAKE_OFFSET: f32 = 12.0;
+const MAX_SHAKE_ANGLE: f32 = 1.5;
+const SHAKE_DECAY: f32 = 1.5; // Trauma lost a second
+const SHAKE_FREQUENCY: f32 = 25.0;
+// How fast a zoom punch eases back out, higher is snappier
+const PUNCH_RATE: f32 = 10.0;
+const MAX_PUNCH: f32 = 0.5;
+
+// Something that should jolt the view. Whatever caused it pushes it onto
+// `Game::camera_events`, the next update applies it.
+#[derive(Clone, Copy, Debug)]
+pub enum CameraEvent {
+    Shake { trauma: f32 },                 // 0 to 1, adds to a shake in progress
+    Flash { color: Color, duration: f32 }, // Seconds to fade out over
+    ZoomPunch { amount: f32 },             // Share zoomed in by, e.g. 0.05
+}
+
+struct Flash {
+    color: Color,
+    duration: f32,
+    elapsed: f32,
+}
+
+// Screen shake, flashes and zoom punches layered over the `Camera`. Shake
+// follows trauma squared, so small knocks barely register and big ones
+// throw the view around.
+pub struct CameraEffects {
+    trauma: f32,
+    punch: f32,
+    flash: Option<Flash>,
+    time: f32,
+}
+
+impl Default for CameraEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CameraEffects {
+    pub fn new() -> Self {
+        Self {
+            trauma: 0.0,
+            punch: 0.0,
+            flash: None,
+            time: 0.0,
+        }
+    }
+
+    pub fn trigger(&mut self, event: CameraEvent) {
+        match event {
+            CameraEvent::Shake { trauma } => {
+                self.trauma = (self.trauma + trauma.max(0.0)).min(1.0);
+            }
+            CameraEvent::Flash { color, duration } => {
+                self.flash = Some(Flash {
+                    color,
+                    duration: duration.max(f32::EPSILON),
+                    elapsed: 0.0,
+                });
+            }
+            CameraEvent::ZoomPunch { amount } => {
+                self.punch = (self.punch + amount).clamp(-MAX_PUNCH, MAX_PUNCH);
+            }
+        }
+    }
+
+    // Eases everything back to rest. Call once a frame.
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+        self.punch *= (-PUNCH_RATE * dt).exp();
+        if let Some(flash) = &mut self.flash {
+            flash.elapsed += dt;
+            if flash.elapsed >= flash.duration {
+                self.flash = None;
+            }
+        }
+    }
+
+    // `camera` shaken and zoomed, for `set_camera`
+    pub fn apply(&self, camera: Camera2D) -> Camera2D {
+        let shake = self.trauma * self.trauma;
+        // Out of step waves on each axis, smoother than noise
+        let t = self.time * SHAKE_FREQUENCY;
+        let offset = Vec2::new((t * 1.3).sin(), (t * 1.7 + 1.0).sin()) * MAX_SHAKE_OFFSET * shake;
+        Camera2D {
+            target: camera.target + offset.round(),
+            zoom: camera.zoom * (1.0 + self.punch),
+            rotation: camera.rotation + (t * 0.9 + 2.0).sin() * MAX_SHAKE_ANGLE * shake,
+            ..camera
+        }
+    }
+
+    // Over the world and under the UI, with the UI camera set
+    pub fn draw_flash(&self) {
+        let Some(flash) = &self.flash else {
+            return;
+        };
+        let fade = 1.0 - flash.elapsed / flash.duration;
+        let color = Color {
+            a: flash.color.a * fade,
+            ..flash.color
+        };
+        draw_rectangle(0.0, 0.0, ui::width(), ui::height(), color);
+    }
+}
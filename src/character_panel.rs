@@ -0,0 +1,285 @@
+use crate::player::BODY_COLORS;
+use crate::protocol::{
+    ClientMessage, Cosmetics, EyeStyle, HairStyle, Hat, MouthStyle, Weapon, MAX_HAIR_LENGTH,
+    MAX_HAIR_STRANDS, OUTFIT_COLORS,
+};
+use crate::ui;
+use macroquad::prelude::*;
+
+const PANEL_WIDTH: f32 = 340.0;
+const ROW_HEIGHT: f32 = 26.0;
+const MIN_HAIR_LENGTH: u8 = 8;
+const LENGTH_STEP: u8 = 4;
+const STRANDS_STEP: u16 = 25;
+// Hair colors to pick from, by name
+const HAIR_COLORS: [(&str, [u8; 3]); 8] = [
+    ("brown", [127, 106, 79]),
+    ("black", [30, 25, 20]),
+    ("blonde", [230, 200, 110]),
+    ("ginger", [200, 90, 40]),
+    ("gray", [150, 150, 150]),
+    ("white", [240, 240, 235]),
+    ("blue", [60, 110, 220]),
+    ("pink", [235, 120, 180]),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    BodyColor,
+    Eyes,
+    Mouth,
+    Shirt,
+    Pants,
+    Hat,
+    Weapon,
+    Cape,
+    HairColor,
+    HairLength,
+    HairStrands,
+    HairStyle,
+    Reroll,
+}
+
+const ROWS: [Row; 13] = [
+    Row::BodyColor,
+    Row::Eyes,
+    Row::Mouth,
+    Row::Shirt,
+    Row::Pants,
+    Row::Hat,
+    Row::Weapon,
+    Row::Cape,
+    Row::HairColor,
+    Row::HairLength,
+    Row::HairStrands,
+    Row::HairStyle,
+    Row::Reroll,
+];
+
+// The character screen, opened with the `open_character` key. Edits show on
+// the local player straight away and go to the server when it closes, which
+// shows them to everyone else.
+pub struct CharacterPanel {
+    editing: Option<Editing>,
+    selected: usize,
+}
+
+struct Editing {
+    before: Cosmetics,
+    look: Cosmetics,
+}
+
+impl Default for CharacterPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharacterPanel {
+    pub fn new() -> Self {
+        Self {
+            editing: None,
+            selected: 0,
+        }
+    }
+
+    // While open the arrow keys belong to the panel
+    pub fn is_open(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    // `current` is the local player's look. Returns the edited look to show
+    // on the local player when it changed.
+    pub fn update(
+        &mut self,
+        open_key: KeyCode,
+        current: Cosmetics,
+        outbox: &mut Vec<ClientMessage>,
+    ) -> Option<Cosmetics> {
+        let Some(editing) = &mut self.editing else {
+            if is_key_pressed(open_key) {
+                self.editing = Some(Editing {
+                    before: current,
+                    look: current,
+                });
+            }
+            return None;
+        };
+        if is_key_pressed(open_key) || is_key_pressed(KeyCode::Escape) {
+            if editing.look != editing.before {
+                outbox.push(ClientMessage::SetCosmetics {
+                    cosmetics: editing.look,
+                });
+            }
+            self.editing = None;
+            return None;
+        }
+
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) && self.selected + 1 < ROWS.len() {
+            self.selected += 1;
+        }
+        let step = match (
+            is_key_pressed(KeyCode::Left),
+            is_key_pressed(KeyCode::Right),
+        ) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => return None,
+        };
+
+        let look = &mut editing.look;
+        let hair = &mut look.hair;
+        match ROWS[self.selected] {
+            Row::BodyColor => {
+                let index = BODY_COLORS
+                    .iter()
+                    .position(|&(_, color)| color == look.body_color)
+                    .unwrap_or(0) as i32;
+                let index = (index + step).rem_euclid(BODY_COLORS.len() as i32);
+                look.body_color = BODY_COLORS[index as usize].1;
+            }
+            Row::Eyes => look.face.eyes = cycle(&EyeStyle::ALL, look.face.eyes, step),
+            Row::Mouth => look.face.mouth = cycle(&MouthStyle::ALL, look.face.mouth, step),
+            Row::Shirt => look.outfit.shirt = cycle_outfit(look.outfit.shirt, step),
+            Row::Pants => look.outfit.pants = cycle_outfit(look.outfit.pants, step),
+            Row::Hat => look.equipment.hat = cycle_optional(&Hat::ALL, look.equipment.hat, step),
+            Row::Weapon => {
+                look.equipment.weapon = cycle_optional(&Weapon::ALL, look.equipment.weapon, step)
+            }
+            Row::Cape => look.equipment.cape = cycle_outfit(look.equipment.cape, step),
+            Row::HairColor => {
+                let index = HAIR_COLORS
+                    .iter()
+                    .position(|&(_, color)| color == hair.color)
+                    .unwrap_or(0) as i32;
+                let index = (index + step).rem_euclid(HAIR_COLORS.len() as i32);
+                hair.color = HAIR_COLORS[index as usize].1;
+            }
+            Row::HairLength => {
+                let length = hair.length as i32 + step * LENGTH_STEP as i32;
+                hair.length = length.clamp(MIN_HAIR_LENGTH as i32, MAX_HAIR_LENGTH as i32) as u8;
+            }
+            Row::HairStrands => {
+                let strands = hair.strands as i32 + step * STRANDS_STEP as i32;
+                hair.strands = strands.clamp(0, MAX_HAIR_STRANDS as i32) as u16;
+            }
+            Row::HairStyle => hair.style = cycle(&HairStyle::ALL, hair.style, step),
+            Row::Reroll => look.hair_seed = ::rand::random(),
+        }
+        Some(editing.look)
+    }
+
+    pub fn draw(&self) {
+        let Some(editing) = &self.editing else {
+            return;
+        };
+        let look = &editing.look;
+        let hair = &look.hair;
+
+        let x = 20.0;
+        let y = 80.0;
+        let height = 60.0 + ROWS.len() as f32 * ROW_HEIGHT + 30.0;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, PANEL_WIDTH, height, 2.0, DARKGRAY);
+        ui::draw_text("Character", x + 12.0, y + 26.0, 24.0, BLACK);
+
+        for (index, row) in ROWS.iter().enumerate() {
+            let row_y = y + 56.0 + index as f32 * ROW_HEIGHT;
+            if index == self.selected {
+                draw_rectangle(
+                    x + 4.0,
+                    row_y - 18.0,
+                    PANEL_WIDTH - 8.0,
+                    ROW_HEIGHT,
+                    LIGHTGRAY,
+                );
+            }
+            let (label, value) = match row {
+                Row::BodyColor => (
+                    "Body color",
+                    BODY_COLORS
+                        .iter()
+                        .find(|&&(_, color)| color == look.body_color)
+                        .map_or("custom", |&(name, _)| name)
+                        .to_string(),
+                ),
+                Row::Eyes => ("Eyes", look.face.eyes.name().to_string()),
+                Row::Mouth => ("Mouth", look.face.mouth.name().to_string()),
+                Row::Shirt => ("Shirt", outfit_name(look.outfit.shirt).to_string()),
+                Row::Pants => ("Pants", outfit_name(look.outfit.pants).to_string()),
+                Row::Hat => (
+                    "Hat",
+                    look.equipment.hat.map_or("none", Hat::name).to_string(),
+                ),
+                Row::Weapon => (
+                    "Weapon",
+                    look.equipment
+                        .weapon
+                        .map_or("none", Weapon::name)
+                        .to_string(),
+                ),
+                Row::Cape => ("Cape", outfit_name(look.equipment.cape).to_string()),
+                Row::HairColor => (
+                    "Hair color",
+                    HAIR_COLORS
+                        .iter()
+                        .find(|&&(_, color)| color == hair.color)
+                        .map_or("custom", |&(name, _)| name)
+                        .to_string(),
+                ),
+                Row::HairLength => ("Hair length", hair.length.to_string()),
+                Row::HairStrands => ("Hair density", hair.strands.to_string()),
+                Row::HairStyle => ("Hair style", hair.style.name().to_string()),
+                Row::Reroll => ("Hair shape", "reroll".to_string()),
+            };
+            ui::draw_text(label, x + 12.0, row_y, 20.0, BLACK);
+            ui::draw_text(
+                &format!("< {} >", value),
+                x + PANEL_WIDTH / 2.0,
+                row_y,
+                20.0,
+                BLACK,
+            );
+        }
+
+        ui::draw_text(
+            "Up/Down select, Left/Right change, Esc save",
+            x + 12.0,
+            y + height - 12.0,
+            16.0,
+            GRAY,
+        );
+    }
+}
+
+// The option `step` places on from `current`, wrapping around
+fn cycle<T: Copy + PartialEq>(options: &[T], current: T, step: i32) -> T {
+    let index = options
+        .iter()
+        .position(|&option| option == current)
+        .unwrap_or(0) as i32;
+    options[(index + step).rem_euclid(options.len() as i32) as usize]
+}
+
+// None, then each of `options`
+fn cycle_optional<T: Copy + PartialEq>(options: &[T], current: Option<T>, step: i32) -> Option<T> {
+    let options: Vec<Option<T>> = std::iter::once(None)
+        .chain(options.iter().copied().map(Some))
+        .collect();
+    cycle(&options, current, step)
+}
+
+// None, then each of `OUTFIT_COLORS`
+fn cycle_outfit(current: Option<u8>, step: i32) -> Option<u8> {
+    let indices: Vec<u8> = (0..OUTFIT_COLORS.len() as u8).collect();
+    cycle_optional(&indices, current, step)
+}
+
+fn outfit_name(index: Option<u8>) -> &'static str {
+    index
+        .and_then(|index| OUTFIT_COLORS.get(index as usize))
+        .map_or("none", |&(name, _)| name)
+}
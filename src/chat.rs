@@ -0,0 +1,313 @@
+use crate::batch::DrawList;
+use crate::emoji;
+use crate::palette;
+use crate::protocol::{now_millis, ChatChannel, Severity};
+use crate::text_field::TextField;
+use crate::ui;
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+// Longer lines don't fit in a chat bubble
+pub const MAX_LENGTH: usize = 120;
+// Lines the log keeps, and shows while the chat line is closed
+const LOG_SIZE: usize = 50;
+const LOG_SHOWN: usize = 6;
+const LOG_SHOWN_OPEN: usize = 12;
+// Lines fade out of the closed log after this long
+const LOG_LIFETIME: Duration = Duration::from_secs(20);
+
+// The chat line along the bottom of the screen, opened with the `open_chat`
+// key. What's typed there is said with `Game::say`, so `/commands` work in
+// it too. Tab switches channel, which stays picked for the next message.
+pub struct ChatInput {
+    line: Option<TextField>,
+    fresh: bool, // Keys queued before the line opened are still pending
+    pub channel: ChatChannel,
+}
+
+impl Default for ChatInput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatInput {
+    pub fn new() -> Self {
+        Self {
+            line: None,
+            fresh: false,
+            channel: ChatChannel::Global,
+        }
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.line.is_some()
+    }
+
+    // Opens the chat line with `text` already typed, e.g. `/w Ada `
+    pub fn open_with(&mut self, text: String) {
+        self.line = Some(TextField::with_text(MAX_LENGTH, &text));
+        self.fresh = true;
+    }
+
+    // `can_open` is false while something else has the keyboard. Returns a
+    // line once the player sends it, Esc throws it away.
+    pub fn update(&mut self, open_key: KeyCode, can_open: bool) -> Option<String> {
+        let Some(line) = &mut self.line else {
+            if can_open && is_key_pressed(open_key) {
+                self.open_with(String::new());
+            }
+            return None;
+        };
+
+        if self.fresh {
+            while get_char_pressed().is_some() {}
+            self.fresh = false;
+        }
+        line.update();
+        if is_key_pressed(KeyCode::Tab) {
+            let index = ChatChannel::ALL
+                .iter()
+                .position(|&channel| channel == self.channel)
+                .unwrap_or(0);
+            self.channel = ChatChannel::ALL[(index + 1) % ChatChannel::ALL.len()];
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.line = None;
+        } else if is_key_pressed(KeyCode::Enter) {
+            // Enter on an empty line just closes it
+            return self
+                .line
+                .take()
+                .map(TextField::into_text)
+                .filter(|line| !line.trim().is_empty());
+        }
+        None
+    }
+
+    pub fn draw(&self) {
+        let Some(line) = &self.line else {
+            return;
+        };
+        let y = ui::height() - 12.0;
+        draw_rectangle(
+            0.0,
+            y - 20.0,
+            ui::width(),
+            28.0,
+            Color::new(0.0, 0.0, 0.0, 0.6),
+        );
+        let channel = format!("[{}]", self.channel.name());
+        let color = palette::current().channel(self.channel);
+        let width = ui::draw_text(&channel, 8.0, y, 20.0, color).width;
+        line.draw(16.0 + width, y, 20.0, WHITE);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LogKind {
+    Said(ChatChannel),
+    WhisperFrom,
+    WhisperTo,
+    System, // Command output
+    Announcement(Severity),
+}
+
+struct LogLine {
+    id: Option<usize>, // Network id of whoever said it, or was whispered to
+    message: String,
+    kind: LogKind,
+    sent_at_ms: u64,  // By the server's clock, what the log is in order of
+    said_at: Instant, // When it arrived, for fading out
+}
+
+// What's been said lately, over the chat line, in the order the server
+// relayed it so jitter can't swap a question and its answer. Names are
+// looked up when drawn, so a rename shows on lines from before it, and can
+// be clicked.
+pub struct ChatLog {
+    lines: VecDeque<LogLine>,
+    icons: DrawList,           // Shortcode icons, text is drawn straight away
+    names: Vec<(Rect, usize)>, // Where each name was last drawn, in UI pixels
+    // The server's clock less ours, from its last ping. It includes the
+    // trip here, so our own lines land where others would see them.
+    clock_offset_ms: i64,
+}
+
+impl Default for ChatLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChatLog {
+    pub fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            icons: DrawList::new(),
+            names: Vec::new(),
+            clock_offset_ms: 0,
+        }
+    }
+
+    // `server_ms` is the server's clock as a message left it
+    pub fn sync_clock(&mut self, server_ms: u64) {
+        self.clock_offset_ms = server_ms as i64 - now_millis() as i64;
+    }
+
+    // Now, by the server's clock as best we know it
+    fn server_now(&self) -> u64 {
+        now_millis().saturating_add_signed(self.clock_offset_ms)
+    }
+
+    // After every line the server sent no later, the newest go last
+    fn push_line(
+        &mut self,
+        id: Option<usize>,
+        message: String,
+        kind: LogKind,
+        sent_at_ms: Option<u64>,
+    ) {
+        if self.lines.len() == LOG_SIZE {
+            self.lines.pop_front();
+        }
+        let sent_at_ms = sent_at_ms.unwrap_or_else(|| self.server_now());
+        let index = self
+            .lines
+            .iter()
+            .rposition(|line| line.sent_at_ms <= sent_at_ms)
+            .map_or(0, |index| index + 1);
+        self.lines.insert(
+            index,
+            LogLine {
+                id,
+                message,
+                kind,
+                sent_at_ms,
+                said_at: Instant::now(),
+            },
+        );
+    }
+
+    // `sent_at_ms` is None for the local player's own, which is said now
+    pub fn push(
+        &mut self,
+        id: usize,
+        message: String,
+        channel: ChatChannel,
+        sent_at_ms: Option<u64>,
+    ) {
+        self.push_line(Some(id), message, LogKind::Said(channel), sent_at_ms);
+    }
+
+    // A whisper from `id` at `sent_at_ms`, or to them now if that's None
+    pub fn push_whisper(&mut self, id: usize, message: String, sent_at_ms: Option<u64>) {
+        let kind = match sent_at_ms {
+            Some(_) => LogKind::WhisperFrom,
+            None => LogKind::WhisperTo,
+        };
+        self.push_line(Some(id), message, kind, sent_at_ms);
+    }
+
+    // A server announcement, also shown in a banner
+    pub fn push_announcement(&mut self, text: &str, severity: Severity) {
+        let message = format!("[{}] {}", severity.name(), text);
+        self.push_line(None, message, LogKind::Announcement(severity), None);
+    }
+
+    // Command output, shown only here and never sent. Each line of `text`
+    // is a line of its own.
+    pub fn push_system(&mut self, text: &str) {
+        for message in text.lines() {
+            self.push_line(None, message.to_string(), LogKind::System, None);
+        }
+    }
+
+    // Drops everything `id` said, e.g. once they're muted
+    pub fn remove_from(&mut self, id: usize) {
+        self.lines.retain(|line| line.id != Some(id));
+    }
+
+    // The network id of the name drawn under `point`, in UI pixels
+    pub fn name_at(&self, point: Vec2) -> Option<usize> {
+        self.names
+            .iter()
+            .find(|(rect, _)| rect.contains(point))
+            .map(|&(_, id)| id)
+    }
+
+    // Recent lines only, unless the chat line is `open`, each after the
+    // time the server sent it
+    pub fn draw(&mut self, open: bool, name: impl Fn(usize) -> String) {
+        let shown = if open { LOG_SHOWN_OPEN } else { LOG_SHOWN };
+        let mut y = ui::height() - 44.0;
+        self.names.clear();
+        // Arrival order isn't log order, so a late line can be older
+        let recent = self
+            .lines
+            .iter()
+            .rev()
+            .filter(|line| open || line.said_at.elapsed() < LOG_LIFETIME)
+            .take(shown);
+        for line in recent {
+            let age = line.said_at.elapsed();
+            // The last quarter of its life fading out
+            let fade = if open {
+                1.0
+            } else {
+                (4.0 * (1.0 - age.as_secs_f32() / LOG_LIFETIME.as_secs_f32())).min(1.0)
+            };
+            // Before the name and after it
+            let (before, after) = match line.kind {
+                LogKind::Said(_) => ("", ": "),
+                LogKind::WhisperFrom => ("", " whispers: "),
+                LogKind::WhisperTo => ("To ", ": "),
+                LogKind::System | LogKind::Announcement(_) => ("", ""),
+            };
+            let time = format!("{} ", clock(line.sent_at_ms));
+            let time_width = ui::text_width(&time, 18.0);
+            let text = match line.id {
+                Some(id) => {
+                    let name = name(id);
+                    let x = 8.0 + time_width + emoji::measure_message(before, 18.0);
+                    let width = emoji::measure_message(&name, 18.0);
+                    self.names.push((Rect::new(x, y - 16.0, width, 22.0), id));
+                    format!("{}{}{}{}", before, name, after, line.message)
+                }
+                None => line.message.clone(),
+            };
+            let width = time_width + emoji::measure_message(&text, 18.0);
+            draw_rectangle(
+                4.0,
+                y - 16.0,
+                width + 8.0,
+                22.0,
+                Color::new(0.0, 0.0, 0.0, 0.4 * fade),
+            );
+            let palette = palette::current();
+            let color = match line.kind {
+                LogKind::Said(channel) => palette.channel(channel),
+                LogKind::WhisperFrom | LogKind::WhisperTo => palette.whisper,
+                LogKind::System => palette.system_chat,
+                LogKind::Announcement(severity) => palette.severity(severity),
+            };
+            let color = Color { a: fade, ..color };
+            let dim = Color {
+                a: 0.6 * fade,
+                ..palette.system_chat
+            };
+            ui::draw_text(&time, 8.0, y, 18.0, dim);
+            let at = (8.0 + time_width, y);
+            emoji::draw_ui_message(&mut self.icons, &text, at, 18.0, color);
+            y -= 22.0;
+        }
+        self.icons.flush();
+    }
+}
+
+// `HH:MM` in UTC, like the saved chat
+fn clock(unix_ms: u64) -> String {
+    let minutes = unix_ms / 60_000 % (24 * 60);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,113 @@
+use crate::logging;
+use crate::protocol::now_millis;
+use crate::season::civil_date;
+use directories::ProjectDirs;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+const CHAT_DIR: &str = "chat";
+// Older files are deleted as new ones start
+const KEPT_FILES: usize = 20;
+// A long session carries on in a new file past this
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+// e.g. `~/.local/share/lam/chat` on Linux, None if there is no home directory
+pub fn chat_dir() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "lam").map(|dirs| dirs.data_dir().join(CHAT_DIR))
+}
+
+// Everything said during a session, written a line at a time as it's said
+// so nothing is lost when the window closes. The file is only made once
+// someone talks. A file that can't be written is logged once and given up
+// on for the session.
+#[derive(Default)]
+pub struct ChatHistory {
+    file: Option<File>,
+    written: u64, // Bytes in the current file
+    failed: bool,
+}
+
+impl ChatHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // e.g. `2026-10-15 18:04:09 [local] Ada: hi`, the time in UTC. `label`
+    // is the channel, or who a whisper was between.
+    pub fn write(&mut self, name: &str, label: &str, message: &str) {
+        if self.failed {
+            return;
+        }
+        let line = format!(
+            "{} [{}] {}: {}\n",
+            timestamp(now_millis()),
+            label,
+            name,
+            message
+        );
+        if let Err(e) = self.write_line(&line) {
+            warn!(target: logging::GAME, "Failed to save chat, not saving any more this session: {}", e);
+            self.file = None;
+            self.failed = true;
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.file.is_none() || self.written >= MAX_FILE_BYTES {
+            self.file = Some(start_file()?);
+            self.written = 0;
+        }
+        if let Some(file) = &mut self.file {
+            file.write_all(line.as_bytes())?;
+            self.written += line.len() as u64;
+        }
+        Ok(())
+    }
+}
+
+// A new file named for the time it starts, after deleting the oldest so
+// at most `KEPT_FILES` are left
+fn start_file() -> io::Result<File> {
+    let dir =
+        chat_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data directory"))?;
+    fs::create_dir_all(&dir)?;
+
+    let mut old: Vec<PathBuf> = fs::read_dir(&dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("chat-") && name.ends_with(".txt"))
+        })
+        .collect();
+    // The names hold the time, so they sort oldest first
+    old.sort();
+    let excess = (old.len() + 1).saturating_sub(KEPT_FILES);
+    for path in &old[..excess] {
+        if let Err(e) = fs::remove_file(path) {
+            warn!(target: logging::GAME, "Failed to delete old chat {}: {}", path.display(), e);
+        }
+    }
+
+    let path = dir.join(format!("chat-{}.txt", now_millis()));
+    let file = OpenOptions::new().create(true).append(true).open(&path)?;
+    info!(target: logging::GAME, "Saving chat to {}", path.display());
+    Ok(file)
+}
+
+// `YYYY-MM-DD HH:MM:SS` in UTC
+fn timestamp(unix_ms: u64) -> String {
+    let (year, month, day) = civil_date(unix_ms);
+    let seconds = unix_ms / 1000 % 86_400;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
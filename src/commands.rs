@@ -0,0 +1,371 @@
+use crate::account;
+use crate::components::{Hidden, LocalPlayer, NetworkId, Position, Velocity};
+use crate::game::Game;
+use crate::protocol::ClientMessage;
+
+// Shown in the chat log, one line per line. Err is shown the same way, it
+// only reads as a mistake.
+pub type CommandResult = Result<Option<String>, String>;
+pub type Handler = fn(&mut Game, &str) -> CommandResult;
+
+pub struct Command {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    handler: Handler,
+}
+
+// Chat commands the client runs itself, `/help` lists them. Anything
+// embedding the game can register more; plugins add theirs through
+// `ClientPlugin::run_command` instead.
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        let mut registry = Self {
+            commands: Vec::new(),
+        };
+        registry.register("help", "/help [command]", "list the commands", help);
+        registry.register("who", "/who", "list who's online", who);
+        registry.register("ping", "/ping", "show the time to the server", ping);
+        registry.register(
+            "whisper",
+            "/whisper <name> <message>",
+            "say something only they see",
+            whisper,
+        );
+        registry.register("w", "/w <name> <message>", "short for /whisper", whisper);
+        registry.register("mute", "/mute [name]", "hide a player's chat", mute);
+        registry.register("unmute", "/unmute <name>", "show their chat again", unmute);
+        registry.register("friend", "/friend <name>", "add a friend", friend);
+        registry.register("unfriend", "/unfriend <name>", "remove a friend", unfriend);
+        registry.register(
+            "friends",
+            "/friends",
+            "list your friends and who's online",
+            friends,
+        );
+        registry.register(
+            "invite",
+            "/invite <name>",
+            "invite a player to your party",
+            invite,
+        );
+        registry.register(
+            "accept",
+            "/accept",
+            "join the party you were last invited to",
+            accept,
+        );
+        registry.register(
+            "decline",
+            "/decline",
+            "turn down the last party invitation",
+            decline,
+        );
+        registry.register(
+            "plugin",
+            "/plugin [name]",
+            "turn a plugin on or off",
+            plugin,
+        );
+        registry.register(
+            "volume",
+            "/volume [0-100]",
+            "set or show the volume",
+            volume,
+        );
+        if cfg!(debug_assertions) {
+            registry.register("tp", "/tp <x> <y>", "teleport, debug builds only", teleport);
+        }
+        registry
+    }
+
+    // Replaces a command with the same name
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        help: &'static str,
+        handler: Handler,
+    ) {
+        self.commands.retain(|command| command.name != name);
+        self.commands.push(Command {
+            name,
+            usage,
+            help,
+            handler,
+        });
+    }
+
+    pub fn get(&self, name: &str) -> Option<Handler> {
+        self.find(name).map(|command| command.handler)
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|command| command.name == name)
+    }
+
+    pub fn list(&self) -> &[Command] {
+        &self.commands
+    }
+}
+
+// `/name args` as ("name", "args"), None if `text` isn't a command
+pub fn split(text: &str) -> Option<(&str, &str)> {
+    let text = text.trim().strip_prefix('/')?;
+    let (name, args) = text.split_once(' ').unwrap_or((text, ""));
+    Some((name, args.trim()))
+}
+
+fn help(game: &mut Game, args: &str) -> CommandResult {
+    if !args.is_empty() {
+        let name = args.trim_start_matches('/');
+        return match game.commands.find(name) {
+            Some(command) => Ok(Some(format!("{} - {}", command.usage, command.help))),
+            None => Err(format!("No help for /{}", name)),
+        };
+    }
+    let mut lines: Vec<String> = game
+        .commands
+        .list()
+        .iter()
+        .map(|command| format!("{} - {}", command.usage, command.help))
+        .collect();
+    let plugin_commands = game.plugins.commands();
+    if !plugin_commands.is_empty() {
+        let names: Vec<String> = plugin_commands
+            .iter()
+            .map(|name| format!("/{}", name))
+            .collect();
+        lines.push(format!("Plugins: {}", names.join(", ")));
+    }
+    let names: Vec<String> = account::COMMANDS
+        .iter()
+        .map(|name| format!("/{}", name))
+        .collect();
+    lines.push(format!("Account: {}", names.join(", ")));
+    Ok(Some(lines.join("\n")))
+}
+
+fn who(game: &mut Game, _args: &str) -> CommandResult {
+    let mut names: Vec<String> = game
+        .world
+        .query::<&NetworkId>()
+        .without::<&Hidden>()
+        .iter()
+        .map(|id| game.name_of(id.0))
+        .collect();
+    names.sort_by_key(|name| name.to_lowercase());
+    Ok(Some(format!(
+        "{} online: {}",
+        names.len(),
+        names.join(", ")
+    )))
+}
+
+fn ping(game: &mut Game, _args: &str) -> CommandResult {
+    match game.latency.last_one_way_ms {
+        Some(ms) => Ok(Some(format!("Ping: {} ms one way", ms))),
+        None => Err("The server hasn't pinged yet".to_string()),
+    }
+}
+
+// Names can have spaces, so the longest name of someone here that `args`
+// starts with is who it's to
+fn whisper(game: &mut Game, args: &str) -> CommandResult {
+    let usage = || "Usage: /whisper <name> <message>".to_string();
+    let to = game
+        .remote_players
+        .keys()
+        .map(|&id| (id, game.name_of(id)))
+        .filter(|(_, name)| {
+            args.get(..name.len())
+                .is_some_and(|start| start.eq_ignore_ascii_case(name))
+                && args[name.len()..].starts_with(' ')
+        })
+        .max_by_key(|(_, name)| name.len());
+    let Some((id, name)) = to else {
+        let name = args.split_whitespace().next().ok_or_else(usage)?;
+        return Err(format!("Nobody called {} is here", name));
+    };
+    let message = args[name.len()..].trim();
+    if message.is_empty() {
+        return Err(usage());
+    }
+    game.whisper(id, message);
+    Ok(None)
+}
+
+// `/mute <name>` hides a player's chat, online or not, `/mute` lists who is
+fn mute(game: &mut Game, name: &str) -> CommandResult {
+    if name.is_empty() {
+        if game.settings.muted.is_empty() {
+            return Ok(Some("Nobody is muted".to_string()));
+        }
+        return Ok(Some(format!("Muted: {}", game.settings.muted.join(", "))));
+    }
+    match game.player_named(name) {
+        Some(id) => game.set_muted(id, true),
+        None if name.eq_ignore_ascii_case(&game.name_of(game.local_id())) => {
+            return Err("You can't mute yourself".to_string());
+        }
+        None => {
+            game.settings.set_muted(name, true);
+            game.save_settings();
+        }
+    }
+    Ok(Some(format!("Muted {}", name)))
+}
+
+fn unmute(game: &mut Game, name: &str) -> CommandResult {
+    if name.is_empty() {
+        return Err("Usage: /unmute <name>".to_string());
+    }
+    match game.player_named(name) {
+        Some(id) => game.set_muted(id, false),
+        None if game.settings.is_muted(name) => {
+            game.settings.set_muted(name, false);
+            game.save_settings();
+        }
+        None => return Err(format!("{} isn't muted", name)),
+    }
+    Ok(Some(format!("Unmuted {}", name)))
+}
+
+fn friend(game: &mut Game, name: &str) -> CommandResult {
+    if name.is_empty() {
+        return Err("Usage: /friend <name>".to_string());
+    }
+    if game.settings.is_friend(name) {
+        return Err(format!("{} is already a friend", name));
+    }
+    game.settings.set_friend(name, true);
+    game.save_settings();
+    Ok(Some(format!("Added {} as a friend", name)))
+}
+
+fn unfriend(game: &mut Game, name: &str) -> CommandResult {
+    if !game.settings.is_friend(name) {
+        return Err(format!("{} isn't a friend", name));
+    }
+    game.settings.set_friend(name, false);
+    game.save_settings();
+    Ok(Some(format!("Removed {} from your friends", name)))
+}
+
+fn friends(game: &mut Game, _args: &str) -> CommandResult {
+    if game.settings.friends.is_empty() {
+        return Ok(Some(
+            "No friends yet, add one with /friend <name>".to_string(),
+        ));
+    }
+    let names: Vec<String> = game
+        .settings
+        .friends
+        .iter()
+        .map(|name| match game.player_named(name) {
+            Some(_) => format!("{} (online)", name),
+            None => name.clone(),
+        })
+        .collect();
+    Ok(Some(format!("Friends: {}", names.join(", "))))
+}
+
+// The server answers with a notice
+fn invite(game: &mut Game, name: &str) -> CommandResult {
+    if name.is_empty() {
+        return Err("Usage: /invite <name>".to_string());
+    }
+    let id = game
+        .player_named(name)
+        .ok_or_else(|| format!("Nobody called {} is here", name))?;
+    game.outbox.push(ClientMessage::PartyInvite { id });
+    Ok(None)
+}
+
+// Inviting whoever invited us is how the server hears we accept
+fn accept(game: &mut Game, _args: &str) -> CommandResult {
+    let id = game
+        .party
+        .take_invite()
+        .ok_or_else(|| "Nobody has invited you to a party".to_string())?;
+    game.outbox.push(ClientMessage::PartyInvite { id });
+    Ok(None)
+}
+
+fn decline(game: &mut Game, _args: &str) -> CommandResult {
+    match game.party.take_invite() {
+        Some(id) => Ok(Some(format!("Declined {}'s invitation", game.name_of(id)))),
+        None => Err("Nobody has invited you to a party".to_string()),
+    }
+}
+
+// `/plugin <name>` flips a plugin on or off, `/plugin` lists them
+fn plugin(game: &mut Game, name: &str) -> CommandResult {
+    if name.is_empty() {
+        let names: Vec<String> = game
+            .plugins
+            .list()
+            .iter()
+            .map(|(name, enabled)| format!("{}{}", name, if *enabled { " (on)" } else { "" }))
+            .collect();
+        return Ok(Some(format!("Plugins: {}", names.join(", "))));
+    }
+    match game.plugins.toggle(name) {
+        Some(true) => Ok(Some(format!("Enabled {}", name))),
+        Some(false) => Ok(Some(format!("Disabled {}", name))),
+        None => Err(format!("No plugin named {}", name)),
+    }
+}
+
+// `/volume <0-100>` sets the volume, `/volume` shows it
+fn volume(game: &mut Game, volume: &str) -> CommandResult {
+    if !volume.is_empty() {
+        match volume.parse::<f32>() {
+            Ok(percent) if (0.0..=100.0).contains(&percent) => {
+                game.settings.volume = percent / 100.0;
+                game.save_settings();
+            }
+            _ => return Err("Volume takes a number from 0 to 100".to_string()),
+        }
+    }
+    Ok(Some(format!("Volume: {:.0}", game.settings.volume * 100.0)))
+}
+
+// Moves the local player, kept inside the world. The server takes the new
+// position like any other move.
+fn teleport(game: &mut Game, args: &str) -> CommandResult {
+    let coordinates: Vec<f32> = args
+        .split_whitespace()
+        .map(str::parse)
+        .collect::<Result<_, _>>()
+        .map_err(|_| "Usage: /tp <x> <y>".to_string())?;
+    let [x, y] = coordinates[..] else {
+        return Err("Usage: /tp <x> <y>".to_string());
+    };
+    let bounds = game.world_bounds();
+    let (x, y) = (
+        x.clamp(bounds.x, bounds.right()),
+        y.clamp(bounds.y, bounds.bottom()),
+    );
+    if let Ok((position, velocity, local)) =
+        game.world
+            .query_one_mut::<(&mut Position, &mut Velocity, &mut LocalPlayer)>(game.local_player)
+    {
+        position.x = x;
+        position.y = y;
+        velocity.target = None;
+        local.position_changed = true;
+    }
+    game.camera.snap();
+    Ok(Some(format!("Teleported to {:.0}, {:.0}", x, y)))
+}
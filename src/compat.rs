@@ -0,0 +1,360 @@
+use crate::protocol::{
+    ChatChannel, ClientMessage, Emote, Expression, EyeStyle, Facing, HairStyle, Hat, MouthStyle,
+    ObstacleKind, Precipitation, Season, SeasonalEvent, Severity, Stance, Weapon, PROTOCOL_VERSION,
+};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use serde_reflection::{
+    ContainerFormat, Format, Registry, Samples, Tracer, TracerConfig, Value, VariantFormat,
+};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+// Recorded messages of released versions, one `v<N>.json` each
+pub const FIXTURE_DIR: &str = "protocol";
+
+// Traces the layout of every message from the serde types themselves
+pub fn trace_registry() -> Registry {
+    let mut tracer = Tracer::new(TracerConfig::default());
+    // Enums nested inside messages have to be traced on their own first
+    tracer
+        .trace_simple_type::<ObstacleKind>()
+        .expect("Failed to trace ObstacleKind");
+    tracer
+        .trace_simple_type::<Season>()
+        .expect("Failed to trace Season");
+    tracer
+        .trace_simple_type::<SeasonalEvent>()
+        .expect("Failed to trace SeasonalEvent");
+    tracer
+        .trace_simple_type::<Stance>()
+        .expect("Failed to trace Stance");
+    tracer
+        .trace_simple_type::<Facing>()
+        .expect("Failed to trace Facing");
+    tracer
+        .trace_simple_type::<Emote>()
+        .expect("Failed to trace Emote");
+    tracer
+        .trace_simple_type::<ChatChannel>()
+        .expect("Failed to trace ChatChannel");
+    tracer
+        .trace_simple_type::<Expression>()
+        .expect("Failed to trace Expression");
+    tracer
+        .trace_simple_type::<Precipitation>()
+        .expect("Failed to trace Precipitation");
+    tracer
+        .trace_simple_type::<HairStyle>()
+        .expect("Failed to trace HairStyle");
+    tracer
+        .trace_simple_type::<EyeStyle>()
+        .expect("Failed to trace EyeStyle");
+    tracer
+        .trace_simple_type::<MouthStyle>()
+        .expect("Failed to trace MouthStyle");
+    tracer
+        .trace_simple_type::<Hat>()
+        .expect("Failed to trace Hat");
+    tracer
+        .trace_simple_type::<Weapon>()
+        .expect("Failed to trace Weapon");
+    tracer
+        .trace_simple_type::<Severity>()
+        .expect("Failed to trace Severity");
+    tracer
+        .trace_type::<ClientMessage>(&Samples::new())
+        .expect("Failed to trace ClientMessage");
+    tracer.registry().expect("Incomplete protocol registry")
+}
+
+// Made-up field values that differ by type and by position, so a retyped or
+// reordered field changes the recorded bytes. The tracer's own samples are
+// all zeros and would hide both.
+struct Sampler<'a> {
+    registry: &'a Registry,
+    fields: u64, // Values handed out so far in this message
+}
+
+impl Sampler<'_> {
+    fn value(&mut self, format: &Format) -> Value {
+        self.fields += 1;
+        let n = self.fields;
+        match format {
+            Format::TypeName(name) => self.container(name),
+            Format::Variable(_) | Format::Unit => Value::Unit,
+            Format::Bool => Value::Bool(n % 2 == 1),
+            Format::I8 => Value::I8(-(n as i8)),
+            Format::I16 => Value::I16(-0x1600 - n as i16),
+            Format::I32 => Value::I32(-0x3200_0000 - n as i32),
+            Format::I64 => Value::I64(-0x6400_0000_0000 - n as i64),
+            Format::I128 => Value::I128(-(0x128 << 64) - n as i128),
+            Format::U8 => Value::U8(0x80 + n as u8),
+            Format::U16 => Value::U16(0x1600 + n as u16),
+            Format::U32 => Value::U32(0x3200_0000 + n as u32),
+            Format::U64 => Value::U64(0x6400_0000_0000 + n),
+            Format::U128 => Value::U128((0x128 << 64) + n as u128),
+            Format::F32 => Value::F32(n as f32 + 0.25),
+            Format::F64 => Value::F64(n as f64 + 0.5),
+            Format::Char => Value::Char(char::from(b'a' + (n % 26) as u8)),
+            Format::Str => Value::Str(format!("str{}", n)),
+            Format::Bytes => Value::Bytes(vec![n as u8; 3]),
+            Format::Option(format) => Value::Option(Some(Box::new(self.value(format)))),
+            Format::Seq(format) => Value::Seq(vec![self.value(format)]),
+            Format::Map { key, value } => Value::Seq(vec![self.value(key), self.value(value)]),
+            Format::Tuple(formats) => self.values(formats),
+            Format::TupleArray { content, size } => {
+                Value::Seq((0..*size).map(|_| self.value(content)).collect())
+            }
+        }
+    }
+
+    fn values(&mut self, formats: &[Format]) -> Value {
+        Value::Seq(formats.iter().map(|format| self.value(format)).collect())
+    }
+
+    fn container(&mut self, name: &str) -> Value {
+        match self.registry.get(name) {
+            Some(ContainerFormat::NewTypeStruct(format)) => self.value(format),
+            Some(ContainerFormat::TupleStruct(formats)) => self.values(formats),
+            Some(ContainerFormat::Struct(fields)) => Value::Seq(
+                fields
+                    .iter()
+                    .map(|field| self.value(&field.value))
+                    .collect(),
+            ),
+            // The second variant, so an enum doesn't look like its zero
+            // default, and appending variants doesn't change which is picked
+            Some(ContainerFormat::Enum(variants)) => {
+                match variants.iter().nth(1).or(variants.iter().next()) {
+                    Some((index, variant)) => {
+                        Value::Variant(*index, Box::new(self.variant(&variant.value)))
+                    }
+                    None => Value::Unit,
+                }
+            }
+            Some(ContainerFormat::UnitStruct) | None => Value::Unit,
+        }
+    }
+
+    fn variant(&mut self, format: &VariantFormat) -> Value {
+        match format {
+            VariantFormat::Variable(_) | VariantFormat::Unit => Value::Unit,
+            VariantFormat::NewType(format) => self.value(format),
+            VariantFormat::Tuple(formats) => self.values(formats),
+            VariantFormat::Struct(fields) => Value::Seq(
+                fields
+                    .iter()
+                    .map(|field| self.value(&field.value))
+                    .collect(),
+            ),
+        }
+    }
+
+    // One message of every variant, each counting its fields from the start
+    // so a change to one variant leaves the others' samples alone
+    fn messages(registry: &Registry) -> Vec<ClientMessage> {
+        let Some(ContainerFormat::Enum(variants)) = registry.get("ClientMessage") else {
+            return Vec::new();
+        };
+        variants
+            .iter()
+            .map(|(index, variant)| {
+                let mut sampler = Sampler {
+                    registry,
+                    fields: 0,
+                };
+                let value = Value::Variant(*index, Box::new(sampler.variant(&variant.value)));
+                ClientMessage::deserialize(value.into_deserializer())
+                    .unwrap_or_else(|e| panic!("Failed to build a {} sample: {}", variant.name, e))
+            })
+            .collect()
+    }
+}
+
+// Names of the message variants by wire index
+fn variant_names(registry: &Registry) -> Vec<String> {
+    match registry.get("ClientMessage") {
+        Some(ContainerFormat::Enum(variants)) => variants
+            .values()
+            .map(|variant| variant.name.clone())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// bincode starts every message with its variant index as a u32
+fn variant_index(bytes: &[u8]) -> Option<usize> {
+    let index: [u8; 4] = bytes.get(..4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(index) as usize)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedMessage {
+    pub variant: String,
+    pub hex: String, // The message as it went over the wire
+}
+
+impl RecordedMessage {
+    fn bytes(&self) -> Option<Vec<u8>> {
+        if !self.hex.len().is_multiple_of(2) {
+            return None;
+        }
+        (0..self.hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(self.hex.get(i..i + 2)?, 16).ok())
+            .collect()
+    }
+}
+
+// One sample of every message a version sends, recorded before its release so
+// later versions can be checked against it without an old build around
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Fixture {
+    pub protocol_version: u32,
+    pub messages: Vec<RecordedMessage>,
+}
+
+impl Fixture {
+    // Samples this build's messages
+    pub fn record() -> Self {
+        let registry = trace_registry();
+        let names = variant_names(&registry);
+        let mut messages: Vec<RecordedMessage> = Sampler::messages(&registry)
+            .iter()
+            .map(|message| {
+                let bytes = bincode::serialize(message).expect("Failed to encode sample");
+                let variant = variant_index(&bytes)
+                    .and_then(|index| names.get(index).cloned())
+                    .unwrap_or_default();
+                let hex = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                RecordedMessage { variant, hex }
+            })
+            .collect();
+        messages.sort_by_key(|message| names.iter().position(|name| *name == message.variant));
+        Self {
+            protocol_version: PROTOCOL_VERSION,
+            messages,
+        }
+    }
+
+    pub fn path(dir: &Path, version: u32) -> PathBuf {
+        dir.join(format!("v{}.json", version))
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        serde_json::from_str(&text).map_err(io::Error::other)
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+        fs::create_dir_all(dir)?;
+        let path = Self::path(dir, self.protocol_version);
+        let text = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&path, text + "\n")?;
+        Ok(path)
+    }
+
+    // Every recorded version in `dir` older than this build, oldest first
+    pub fn load_older(dir: &Path) -> io::Result<Vec<Self>> {
+        let mut fixtures = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "json")
+            {
+                let fixture = Self::load(&path)?;
+                if fixture.protocol_version < PROTOCOL_VERSION {
+                    fixtures.push(fixture);
+                }
+            }
+        }
+        fixtures.sort_by_key(|fixture| fixture.protocol_version);
+        Ok(fixtures)
+    }
+
+    fn find(&self, variant: &str) -> Option<&RecordedMessage> {
+        self.messages
+            .iter()
+            .find(|message| message.variant == variant)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Compatible,
+    Unknown,             // The receiver has no such variant and drops the connection
+    Undecodable(String), // Known variant, but the fields don't parse
+    Misread(String),     // Parses, but not as what was sent
+    LayoutChanged,       // Same variant, different bytes on the wire
+}
+
+impl Verdict {
+    pub fn is_break(&self) -> bool {
+        *self != Verdict::Compatible
+    }
+}
+
+// How one variant fares between this build and an older version, in both directions
+#[derive(Debug, Clone)]
+pub struct VariantResult {
+    pub variant: String,
+    pub old_to_current: Verdict, // An old peer's message read by this build
+    pub current_to_old: Verdict, // This build's message read by an old peer
+}
+
+// Old messages are decoded for real. The old build isn't around to decode
+// ours, so those count as compatible only if the bytes are what it recorded.
+pub fn check(current: &Fixture, old: &Fixture) -> Vec<VariantResult> {
+    let names = variant_names(&trace_registry());
+    let mut results: Vec<VariantResult> = Vec::new();
+
+    for message in &old.messages {
+        let old_to_current = read_old(message, &names);
+        let current_to_old = match current.find(&message.variant) {
+            Some(sample) if sample.hex == message.hex => Verdict::Compatible,
+            Some(_) => Verdict::LayoutChanged,
+            // Removed since, this build never sends it
+            None => Verdict::Compatible,
+        };
+        results.push(VariantResult {
+            variant: message.variant.clone(),
+            old_to_current,
+            current_to_old,
+        });
+    }
+
+    for message in &current.messages {
+        if old.find(&message.variant).is_none() {
+            results.push(VariantResult {
+                variant: message.variant.clone(),
+                old_to_current: Verdict::Compatible, // An old peer never sends it
+                current_to_old: Verdict::Unknown,
+            });
+        }
+    }
+    results
+}
+
+fn read_old(message: &RecordedMessage, names: &[String]) -> Verdict {
+    let Some(bytes) = message.bytes() else {
+        return Verdict::Undecodable("fixture isn't valid hex".to_string());
+    };
+    let Some(name) = variant_index(&bytes).and_then(|index| names.get(index)) else {
+        return Verdict::Unknown;
+    };
+    let decoded = match bincode::deserialize::<ClientMessage>(&bytes) {
+        Ok(decoded) => decoded,
+        Err(e) => return Verdict::Undecodable(e.to_string()),
+    };
+    if *name != message.variant {
+        return Verdict::Misread(format!("read as {}", name));
+    }
+    // bincode ignores trailing bytes, so a removed field would still parse
+    match bincode::serialize(&decoded) {
+        Ok(reencoded) if reencoded == bytes => Verdict::Compatible,
+        _ => Verdict::Misread("fields shifted or dropped".to_string()),
+    }
+}
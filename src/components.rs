@@ -0,0 +1,235 @@
+use crate::animation::Foot;
+use crate::batch::ShapeMesh;
+use crate::player::{Snapshot, SNEAK_SPEED, SPRINT_SPEED, WALK_SPEED};
+use crate::protocol::{ChatChannel, Emote, Equipment, Expression, Face, Facing, Stance};
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// World-space position of a character's head center
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Position {
+    // Keeps a character of `appearance`'s size inside `bounds`
+    pub fn clamp_to(&mut self, appearance: &Appearance, bounds: &Rect) {
+        self.x = self.x.clamp(bounds.x, bounds.right() - appearance.width);
+        self.y = self.y.clamp(bounds.y, bounds.bottom() - appearance.height);
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Velocity {
+    pub speed: f32,
+    pub target: Option<(f32, f32)>, // Walks toward this point when set
+    pub is_moving: bool,            // Tracks if the entity is currently moving
+}
+
+#[derive(Clone, Debug)]
+pub struct Appearance {
+    pub width: f32,
+    pub height: f32,
+    pub body_color: Color,
+    pub hair: Arc<ShapeMesh>, // Rebuilt only when the look changes
+    pub hair_color: Color,
+    pub face: Face,
+    pub shirt: Option<Color>, // None is bare, drawn in `body_color`
+    pub pants: Option<Color>,
+    pub equipment: Equipment,
+    pub sprite: Option<String>, // Spritesheet file, drawn instead of the stick figure
+}
+
+// Walk cycle and bobbing state
+#[derive(Clone, Debug)]
+pub struct Animation {
+    pub current_pose_index: usize,
+    pub next_pose_index: usize,
+    pub pose_elapsed: f32, // Seconds since the current pose started
+    pub pose_update_interval: Duration,
+    pub pose_interp_factor: f32,
+    pub bobbing_time: f32,    // Time accumulator for bobbing
+    pub bobbing_offset: f32,  // Current y-offset for bobbing
+    pub emote: Option<Emote>, // Playing while standing still
+    pub emote_elapsed: f32,   // Seconds since the emote started
+    pub idle_elapsed: f32,    // Seconds since the character stopped moving
+    pub stance: Stance,
+    pub facing: Facing,
+    pub jump_elapsed: Option<f32>, // Seconds into the current jump
+    pub step: Option<Foot>,        // Landed this update, see `Frame::step`
+    pub hair_sway: f32,            // Pixels the wind blows the hair tips to the right
+}
+
+impl Default for Animation {
+    fn default() -> Self {
+        Self {
+            current_pose_index: 0,
+            next_pose_index: 1,
+            pose_elapsed: 0.0,
+            pose_update_interval: Duration::from_millis(100), // 10 poses per second
+            pose_interp_factor: 0.0,
+            bobbing_time: 0.0,
+            bobbing_offset: 0.0,
+            emote: None,
+            emote_elapsed: 0.0,
+            idle_elapsed: 0.0,
+            stance: Stance::default(),
+            facing: Facing::default(),
+            jump_elapsed: None,
+            step: None,
+            hair_sway: 0.0,
+        }
+    }
+}
+
+impl Animation {
+    // Restarts the walk cycle from the first pose
+    pub fn reset(&mut self) {
+        self.current_pose_index = 0;
+        self.next_pose_index = 1;
+        self.pose_interp_factor = 0.0;
+        self.pose_elapsed = 0.0;
+    }
+
+    // Plays `emote` from the start, until it ends or the character moves
+    pub fn play(&mut self, emote: Emote) {
+        self.emote = Some(emote);
+        self.emote_elapsed = 0.0;
+    }
+
+    pub fn jump(&mut self) {
+        self.jump_elapsed = Some(0.0);
+    }
+
+    // Follows a remote player's stance, starting the animations it implies
+    pub fn set_stance(&mut self, stance: Stance) {
+        if stance == Stance::Jumping && self.stance != Stance::Jumping {
+            self.jump();
+        }
+        if stance == Stance::Sitting && self.emote != Some(Emote::Sit) {
+            self.play(Emote::Sit);
+        }
+        self.stance = stance;
+    }
+}
+
+// Older messages kept up over a player's newest one
+const MAX_STACKED: usize = 2;
+
+// A message still up after a newer one replaced it, see `ChatBubble::stack`
+#[derive(Clone, Debug)]
+pub struct StackedBubble {
+    pub message: String,
+    pub shown_at: Instant,
+    pub channel: ChatChannel,
+    pub alpha: f32, // As `ChatBubble::alpha`
+}
+
+#[derive(Clone, Debug)]
+pub struct ChatBubble {
+    pub message: Option<String>,
+    pub shown_at: Instant,
+    pub sent: bool, // Whether the local player's message reached the server yet
+    pub expression: Option<Expression>, // Shown on the face as long as the message
+    pub channel: ChatChannel,
+    // How much of the bubble shows, 0 out of range of the local player and
+    // falling towards the end of its lifetime. Set by `chat_fade_system`.
+    pub alpha: f32,
+    pub stacked: Vec<StackedBubble>, // Above the newest message, oldest first
+}
+
+impl Default for ChatBubble {
+    fn default() -> Self {
+        Self {
+            message: None,
+            shown_at: Instant::now(),
+            sent: true,
+            expression: None,
+            channel: ChatChannel::Global,
+            alpha: 1.0,
+            stacked: Vec::new(),
+        }
+    }
+}
+
+impl ChatBubble {
+    pub fn show(&mut self, message: String, expression: Option<Expression>) {
+        self.stack();
+        self.message = Some(message);
+        self.expression = expression;
+        self.shown_at = Instant::now();
+    }
+
+    // Moves the message showing onto the stack to make room for a new one.
+    // It keeps its own time left, the oldest goes once the stack is full.
+    pub fn stack(&mut self) {
+        let Some(message) = self.message.take() else {
+            return;
+        };
+        if self.stacked.len() == MAX_STACKED {
+            self.stacked.remove(0);
+        }
+        self.stacked.push(StackedBubble {
+            message,
+            shown_at: self.shown_at,
+            channel: self.channel,
+            alpha: self.alpha,
+        });
+    }
+
+    // How many bubbles can be seen, the newest and the stacked ones
+    pub fn visible(&self) -> usize {
+        let newest = usize::from(self.message.is_some() && self.alpha > 0.0);
+        newest
+            + self
+                .stacked
+                .iter()
+                .filter(|older| older.alpha > 0.0)
+                .count()
+    }
+}
+
+// Server-assigned player id. 0 until the server assigns one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetworkId(pub usize);
+
+// Remote position timeline, oldest first
+#[derive(Clone, Debug, Default)]
+pub struct SnapshotBuffer {
+    pub snapshots: VecDeque<Snapshot>,
+    // How old the latest snapshot was when it arrived, by the server's stamp
+    // against this machine's clock, so off by however far those disagree
+    pub latency_ms: Option<u64>,
+}
+
+// Marks the entity controlled by this client
+#[derive(Clone, Debug, Default)]
+pub struct LocalPlayer {
+    pub position_changed: bool, // Needs to be sent to the server
+    pub sneaking: bool,         // Moves at `SNEAK_SPEED` of the usual speed
+    pub walking: bool,          // At `WALK_SPEED`
+    pub sprinting: bool,        // At `SPRINT_SPEED`
+}
+
+impl LocalPlayer {
+    // Share of `Velocity::speed` the player moves at. Sneaking wins over
+    // walking, and walking over sprinting, when more than one is held.
+    pub fn speed_scale(&self) -> f32 {
+        if self.sneaking {
+            SNEAK_SPEED
+        } else if self.walking {
+            WALK_SPEED
+        } else if self.sprinting {
+            SPRINT_SPEED
+        } else {
+            1.0
+        }
+    }
+}
+
+// Entities with this marker are not rendered
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Hidden;
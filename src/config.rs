@@ -0,0 +1,125 @@
+use crate::logging;
+use crate::player::PLAYER_SPEED;
+use crate::systems::{BUBBLE_RADIUS, MESSAGE_LIFETIME};
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{info, warn};
+
+pub const CONFIG_PATH: &str = "lam.toml";
+// How often the file's modification time is checked
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Feel tunables read from `lam.toml`. Missing keys keep their defaults, so an
+// empty or absent file plays like the built-in values.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Config {
+    pub window_width: i32, // Only read at startup, the settings panel overrides it
+    pub window_height: i32,
+    pub send_interval_ms: u64, // Minimum gap between position updates
+    pub message_send_interval_ms: u64,
+    pub player_speed: f32, // Pixels per second
+    pub pose_interval_ms: u64,
+    pub message_lifetime_secs: f32,
+    pub bubble_radius: f32, // Bubbles further than this from the local player are hidden
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window_width: 800,
+            window_height: 600,
+            send_interval_ms: 16, // ~60 updates per second
+            message_send_interval_ms: 1000,
+            player_speed: PLAYER_SPEED,
+            pose_interval_ms: 100, // 10 poses per second
+            message_lifetime_secs: MESSAGE_LIFETIME.as_secs_f32(),
+            bubble_radius: BUBBLE_RADIUS,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // The config at `path`, or the defaults if there is no usable file
+    pub fn load_or_default(path: &Path) -> Self {
+        match Self::load(path) {
+            Ok(config) => config,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!(target: logging::CONFIG, "Failed to load {}, using defaults: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn send_interval(&self) -> Duration {
+        Duration::from_millis(self.send_interval_ms)
+    }
+
+    pub fn message_send_interval(&self) -> Duration {
+        Duration::from_millis(self.message_send_interval_ms)
+    }
+
+    pub fn pose_interval(&self) -> Duration {
+        Duration::from_millis(self.pose_interval_ms.max(1))
+    }
+
+    pub fn message_lifetime(&self) -> Duration {
+        Duration::from_secs_f32(self.message_lifetime_secs.max(0.0))
+    }
+}
+
+// Notices when the config file is saved so changes apply without a restart
+pub struct ConfigWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+impl ConfigWatcher {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            modified: modified_time(path),
+            last_check: Instant::now(),
+        }
+    }
+
+    // Returns the new config once the file has changed and parses. A broken
+    // edit is reported and skipped, keeping the last good values.
+    pub fn poll(&mut self) -> Option<Config> {
+        if self.last_check.elapsed() < WATCH_INTERVAL {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = modified_time(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        modified?; // Deleted, keep what we have
+        match Config::load(&self.path) {
+            Ok(config) => {
+                info!(target: logging::CONFIG, "Reloaded {}", self.path.display());
+                Some(config)
+            }
+            Err(e) => {
+                warn!(target: logging::CONFIG, "Failed to reload {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+}
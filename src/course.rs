@@ -0,0 +1,142 @@
+pub struct Checkpoint {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+// A named checkpoint sequence. Checkpoint 0 is the start/finish pad that
+// triggers the race, the rest must be touched in order.
+pub struct Course {
+    pub name: &'static str,
+    pub checkpoints: &'static [Checkpoint],
+}
+
+const PERIMETER: [Checkpoint; 4] = [
+    Checkpoint {
+        x: 100.0,
+        y: 100.0,
+        radius: 40.0,
+    },
+    Checkpoint {
+        x: 700.0,
+        y: 100.0,
+        radius: 40.0,
+    },
+    Checkpoint {
+        x: 700.0,
+        y: 500.0,
+        radius: 40.0,
+    },
+    Checkpoint {
+        x: 100.0,
+        y: 500.0,
+        radius: 40.0,
+    },
+];
+
+// Courses laid out on the current map. The server times laps of them too,
+// from the positions players send.
+pub const MAP_COURSES: [Course; 1] = [Course {
+    name: "perimeter",
+    checkpoints: &PERIMETER,
+}];
+
+// Index into `MAP_COURSES` of the course called `name`
+pub fn find(name: &str) -> Option<usize> {
+    MAP_COURSES.iter().position(|course| course.name == name)
+}
+
+pub enum LapEvent {
+    Started,
+    Checkpoint,
+    Finished { lap_ms: u64, splits: Vec<u64> },
+}
+
+// Tracks progress around a course on a millisecond clock, so it can be driven
+// both live and from recorded paths
+pub struct LapTimer {
+    next_checkpoint: usize,
+    lap_started_ms: Option<u64>,
+    splits: Vec<u64>, // ms from the start line at each checkpoint of this lap
+    pub last_lap_ms: Option<u64>,
+    pub best_lap_ms: Option<u64>,
+    pub best_splits: Vec<u64>,
+}
+
+impl Default for LapTimer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LapTimer {
+    pub fn new() -> Self {
+        Self {
+            next_checkpoint: 0,
+            lap_started_ms: None,
+            splits: Vec::new(),
+            last_lap_ms: None,
+            best_lap_ms: None,
+            best_splits: Vec::new(),
+        }
+    }
+
+    pub fn update(
+        &mut self,
+        checkpoints: &[Checkpoint],
+        now_ms: u64,
+        x: f32,
+        y: f32,
+    ) -> Option<LapEvent> {
+        let checkpoint = checkpoints.get(self.next_checkpoint)?;
+        if (x - checkpoint.x).hypot(y - checkpoint.y) > checkpoint.radius {
+            return None;
+        }
+
+        let reached = self.next_checkpoint;
+        self.next_checkpoint = (self.next_checkpoint + 1) % checkpoints.len();
+
+        if reached != 0 {
+            if let Some(started) = self.lap_started_ms {
+                self.splits.push(now_ms - started);
+            }
+            return Some(LapEvent::Checkpoint);
+        }
+
+        // Crossing the start line finishes the running lap and starts the next
+        let event = match self.lap_started_ms {
+            Some(started) => {
+                let lap_ms = now_ms - started;
+                let splits = std::mem::take(&mut self.splits);
+                self.last_lap_ms = Some(lap_ms);
+                if self.best_lap_ms.is_none_or(|best| lap_ms < best) {
+                    self.best_lap_ms = Some(lap_ms);
+                    self.best_splits = splits.clone();
+                }
+                LapEvent::Finished { lap_ms, splits }
+            }
+            None => LapEvent::Started,
+        };
+        self.lap_started_ms = Some(now_ms);
+        Some(event)
+    }
+
+    // Drops the running lap, e.g. when the player was moved off the course
+    pub fn abandon(&mut self) {
+        self.next_checkpoint = 0;
+        self.lap_started_ms = None;
+        self.splits.clear();
+    }
+
+    pub fn current_lap_ms(&self, now_ms: u64) -> Option<u64> {
+        self.lap_started_ms.map(|started| now_ms - started)
+    }
+
+    pub fn splits(&self) -> &[u64] {
+        &self.splits
+    }
+
+    pub fn next_checkpoint(&self) -> usize {
+        self.next_checkpoint
+    }
+}
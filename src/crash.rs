@@ -0,0 +1,180 @@
+use crate::logging;
+use std::backtrace::Backtrace;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::panic::{self, PanicHookInfo};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+pub const CRASH_DIR: &str = "crashes";
+// How many network events a report keeps
+const NET_EVENT_HISTORY: usize = 50;
+// Long messages are cut down so one obstacle snapshot doesn't fill the report
+const MAX_EVENT_LEN: usize = 160;
+
+// What the crash report knows about the session, filled in as it goes
+struct CrashContext {
+    server_addr: Option<String>,
+    connected: bool,
+    gpu: Option<String>,
+    headless: bool,
+    // From every connection in the process, bots included
+    net_events: VecDeque<String>,
+}
+
+static CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext {
+    server_addr: None,
+    connected: false,
+    gpu: None,
+    headless: false,
+    net_events: VecDeque::new(),
+});
+
+fn with_context(f: impl FnOnce(&mut CrashContext)) {
+    // A panic while the lock was held must not stop the report being written
+    let mut context = CONTEXT.lock().unwrap_or_else(|e| e.into_inner());
+    f(&mut context);
+}
+
+pub fn set_server_addr(addr: &str) {
+    with_context(|context| context.server_addr = Some(addr.to_string()));
+}
+
+pub fn set_gpu(gpu: String) {
+    with_context(|context| context.gpu = Some(gpu));
+}
+
+pub fn set_headless(headless: bool) {
+    with_context(|context| context.headless = headless);
+}
+
+pub fn set_connected(connected: bool) {
+    with_context(|context| context.connected = connected);
+    net_event(if connected {
+        "connected"
+    } else {
+        "disconnected"
+    });
+}
+
+// Remembers something that happened on the network, dropping the oldest
+pub fn net_event(event: &str) {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let mut line = format!("{} {}", millis, event);
+    if line.len() > MAX_EVENT_LEN {
+        let mut end = MAX_EVENT_LEN;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        line.truncate(end);
+        line.push_str("...");
+    }
+    with_context(|context| {
+        if context.net_events.len() == NET_EVENT_HISTORY {
+            context.net_events.pop_front();
+        }
+        context.net_events.push_back(line);
+    });
+}
+
+fn report(info: &PanicHookInfo, backtrace: &Backtrace) -> String {
+    let mut text = String::new();
+    let thread = std::thread::current();
+    let _ = writeln!(text, "lam {} crashed", env!("CARGO_PKG_VERSION"));
+    let _ = writeln!(
+        text,
+        "{} (thread {})",
+        info,
+        thread.name().unwrap_or("unnamed")
+    );
+    let _ = writeln!(
+        text,
+        "os: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+    with_context(|context| {
+        let _ = writeln!(text, "gpu: {}", context.gpu.as_deref().unwrap_or("unknown"));
+        let _ = writeln!(
+            text,
+            "server: {} ({})",
+            context.server_addr.as_deref().unwrap_or("none"),
+            if context.connected {
+                "connected"
+            } else {
+                "not connected"
+            }
+        );
+        let _ = writeln!(text, "\nlast {} network events:", context.net_events.len());
+        for event in &context.net_events {
+            let _ = writeln!(text, "  {}", event);
+        }
+    });
+    let _ = writeln!(text, "\nbacktrace:\n{}", backtrace);
+    text
+}
+
+fn write_report(text: &str) -> io::Result<PathBuf> {
+    fs::create_dir_all(CRASH_DIR)?;
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let path = PathBuf::from(CRASH_DIR).join(format!("crash-{}.txt", secs));
+    fs::write(&path, text)?;
+    Ok(path)
+}
+
+fn show_dialog(path: Option<&PathBuf>) {
+    let description = match path {
+        Some(path) => format!(
+            "Something went wrong and the game has to close.\n\n\
+             A crash report was saved to {}. Please include it when reporting the problem.",
+            path.display()
+        ),
+        None => "Something went wrong and the game has to close.".to_string(),
+    };
+    rfd::MessageDialog::new()
+        .set_level(rfd::MessageLevel::Error)
+        .set_title("lam crashed")
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show();
+}
+
+// Replaces the default panic output with a crash report in `CRASH_DIR`, then
+// tells the player and exits. Without a console (`windows_subsystem`) the
+// game would otherwise just vanish. Any panic ends the process, even one on
+// the network task, since the game can't be trusted to keep going after it.
+pub fn install_panic_hook() {
+    panic::set_hook(Box::new(|info| {
+        let backtrace = Backtrace::force_capture();
+        let text = report(info, &backtrace);
+        error!(target: logging::GAME, "{}", info);
+
+        let path = match write_report(&text) {
+            Ok(path) => {
+                eprintln!("Crash report saved to {}", path.display());
+                Some(path)
+            }
+            Err(e) => {
+                eprintln!("Failed to write crash report: {}\n{}", e, text);
+                None
+            }
+        };
+
+        let mut headless = false;
+        with_context(|context| headless = context.headless);
+        if !headless {
+            show_dialog(path.as_ref());
+        }
+        std::process::exit(101);
+    }));
+}
@@ -0,0 +1,20 @@
+// A whole day and night, in ms of server time
+pub const DAY_LENGTH_MS: u64 = 10 * 60 * 1000;
+// Servers start their first day mid morning
+pub const START_TIME_MS: u64 = DAY_LENGTH_MS * 3 / 10;
+
+// How dark it is `time_ms` into a day of `day_length_ms`: 0 from a while
+// after sunrise until a while before sunset, 1 around midnight. The day
+// starts at midnight, noon is halfway.
+pub fn darkness(time_ms: u64, day_length_ms: u64) -> f32 {
+    let phase = (time_ms % day_length_ms.max(1)) as f32 / day_length_ms.max(1) as f32;
+    // 1 at noon, -1 at midnight
+    let sun = -(phase * std::f32::consts::TAU).cos();
+    ((0.2 - sun) / 0.7).clamp(0.0, 1.0)
+}
+
+// e.g. "18:30", for showing the time of day
+pub fn clock(time_ms: u64, day_length_ms: u64) -> String {
+    let minutes = (time_ms % day_length_ms.max(1)) * 24 * 60 / day_length_ms.max(1);
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
@@ -0,0 +1,101 @@
+use crate::components::{NetworkId, Position, SnapshotBuffer};
+use crate::obstacle_view::Obstacles;
+use crate::ui;
+use hecs::World;
+use macroquad::prelude::*;
+
+// Errors above this many pixels are drawn in red
+const ERROR_WARN_PX: f32 = 20.0;
+const OUTLINE_COLOR: Color = MAGENTA;
+
+fn error_color(error: f32) -> Color {
+    if error > ERROR_WARN_PX {
+        RED
+    } else {
+        DARKGREEN
+    }
+}
+
+// Draws where the server last said things were as outlines over where the
+// client draws them, with the gap in pixels. The local player is never
+// echoed back by the server, so only remote players and obstacles show up.
+// Drawn in world space, returns the summary for `draw_summary`.
+pub fn draw_snapshot_diff(world: &World, obstacles: &Obstacles) -> String {
+    let mut errors = Vec::new();
+
+    for (id, position, buffer) in world
+        .query::<(&NetworkId, &Position, &SnapshotBuffer)>()
+        .iter()
+    {
+        let Some(latest) = buffer.snapshots.back() else {
+            continue;
+        };
+        let error = (latest.x - position.x).hypot(latest.y - position.y);
+        errors.push(error);
+
+        // Head and body outline at the authoritative position
+        draw_circle_lines(latest.x, latest.y, 15.0, 2.0, OUTLINE_COLOR);
+        draw_rectangle_lines(
+            latest.x - 15.0,
+            latest.y + 15.0,
+            30.0,
+            45.0,
+            2.0,
+            OUTLINE_COLOR,
+        );
+        draw_line(
+            position.x,
+            position.y,
+            latest.x,
+            latest.y,
+            1.0,
+            OUTLINE_COLOR,
+        );
+        ui::draw_text(
+            &format!("#{} {:.1}px", id.0, error),
+            latest.x + 18.0,
+            latest.y - 18.0,
+            16.0,
+            error_color(error),
+        );
+    }
+
+    for (id, drawn, latest) in obstacles.drawn_and_latest() {
+        let error = (latest.x - drawn.x).hypot(latest.y - drawn.y);
+        errors.push(error);
+        draw_rectangle_lines(latest.x, latest.y, latest.w, latest.h, 2.0, OUTLINE_COLOR);
+        ui::draw_text(
+            &format!("obstacle {} {:.1}px", id, error),
+            latest.x,
+            latest.y - 4.0,
+            16.0,
+            error_color(error),
+        );
+    }
+
+    if errors.is_empty() {
+        "Snapshot diff (F7): no snapshots yet".to_string()
+    } else {
+        let max = errors.iter().cloned().fold(0.0, f32::max);
+        let mean = errors.iter().sum::<f32>() / errors.len() as f32;
+        format!(
+            "Snapshot diff (F7): {} tracked, mean {:.1}px, max {:.1}px",
+            errors.len(),
+            mean,
+            max
+        )
+    }
+}
+
+// The totals from `draw_snapshot_diff`, in the window's corner
+pub fn draw_summary(summary: &str) {
+    let width = ui::text_width(summary, 18.0);
+    draw_rectangle(
+        ui::width() - width - 18.0,
+        6.0,
+        width + 12.0,
+        24.0,
+        Color::new(1.0, 1.0, 1.0, 0.85),
+    );
+    ui::draw_text(summary, ui::width() - width - 12.0, 24.0, 18.0, BLACK);
+}
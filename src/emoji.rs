@@ -0,0 +1,238 @@
+use crate::batch::{DrawList, Layer};
+use crate::protocol::Emote;
+use crate::ui;
+use macroquad::prelude::*;
+
+const SKIN: Color = Color::new(1.0, 0.8, 0.3, 1.0);
+const GOLD: Color = Color::new(0.95, 0.78, 0.2, 1.0);
+const WOOD: Color = Color::new(0.55, 0.35, 0.2, 1.0);
+
+// A `:shortcode:` in chat, drawn as a small icon in bubbles and the chat log
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Emoji {
+    Smile,
+    Sad,
+    Heart,
+    Star,
+    Wave,
+    Dance,
+    Sit,
+}
+
+impl Emoji {
+    pub const ALL: [Emoji; 7] = [
+        Emoji::Smile,
+        Emoji::Sad,
+        Emoji::Heart,
+        Emoji::Star,
+        Emoji::Wave,
+        Emoji::Dance,
+        Emoji::Sit,
+    ];
+
+    // Typed between colons, e.g. `:wave:`
+    pub fn shortcode(self) -> &'static str {
+        match self {
+            Emoji::Smile => "smile",
+            Emoji::Sad => "sad",
+            Emoji::Heart => "heart",
+            Emoji::Star => "star",
+            Emoji::Wave => "wave",
+            Emoji::Dance => "dance",
+            Emoji::Sit => "sit",
+        }
+    }
+
+    // The emote a character plays when saying it
+    pub fn emote(self) -> Option<Emote> {
+        match self {
+            Emoji::Wave => Some(Emote::Wave),
+            Emoji::Dance => Some(Emote::Dance),
+            Emoji::Sit => Some(Emote::Sit),
+            _ => None,
+        }
+    }
+}
+
+// Part of a chat message, plain text or an icon
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Piece<'a> {
+    Text(&'a str),
+    Emoji(Emoji),
+}
+
+// `message` split around its shortcodes. Colons that don't make a known
+// shortcode stay as text.
+pub fn pieces(message: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut text_start = 0;
+    let mut search = 0;
+    while let Some(open) = message[search..].find(':').map(|i| search + i) {
+        let Some(close) = message[open + 1..].find(':').map(|i| open + 1 + i) else {
+            break;
+        };
+        let name = &message[open + 1..close];
+        match Emoji::ALL.into_iter().find(|e| e.shortcode() == name) {
+            Some(emoji) => {
+                if text_start < open {
+                    pieces.push(Piece::Text(&message[text_start..open]));
+                }
+                pieces.push(Piece::Emoji(emoji));
+                text_start = close + 1;
+                search = close + 1;
+            }
+            // The closing colon may open the next shortcode
+            None => search = close,
+        }
+    }
+    if text_start < message.len() {
+        pieces.push(Piece::Text(&message[text_start..]));
+    }
+    pieces
+}
+
+// The first emote `message` asks for
+pub fn emote(message: &str) -> Option<Emote> {
+    pieces(message).into_iter().find_map(|piece| match piece {
+        Piece::Emoji(emoji) => emoji.emote(),
+        Piece::Text(_) => None,
+    })
+}
+
+// Submits `message` with its shortcodes as icons, the baseline at `y`
+pub fn draw_message(
+    list: &mut DrawList,
+    layer: Layer,
+    message: &str,
+    (x, y): (f32, f32),
+    size: f32,
+    color: Color,
+) {
+    let mut x = x;
+    for piece in pieces(message) {
+        match piece {
+            Piece::Text(text) => {
+                list.text(layer, text, x, y, size, color);
+                x += ui::text_width(text, size);
+            }
+            Piece::Emoji(emoji) => {
+                draw_icon(
+                    list,
+                    layer,
+                    emoji,
+                    vec2(x + size / 2.0, y - size * 0.35),
+                    size,
+                    color.a,
+                );
+                x += size;
+            }
+        }
+    }
+}
+
+// Like `draw_message`, but the text goes straight to the screen with
+// `ui::draw_text` and only the icons into `list`, for the UI
+pub fn draw_ui_message(
+    list: &mut DrawList,
+    message: &str,
+    (x, y): (f32, f32),
+    size: f32,
+    color: Color,
+) {
+    let mut x = x;
+    for piece in pieces(message) {
+        match piece {
+            Piece::Text(text) => x += ui::draw_text(text, x, y, size, color).width,
+            Piece::Emoji(emoji) => {
+                let center = vec2(x + size / 2.0, y - size * 0.35);
+                draw_icon(list, Layer::Bubbles, emoji, center, size, color.a);
+                x += size;
+            }
+        }
+    }
+}
+
+// Width `message` takes up at font `size`, icons included
+pub fn measure_message(message: &str, size: f32) -> f32 {
+    pieces(message)
+        .into_iter()
+        .map(|piece| match piece {
+            Piece::Text(text) => ui::text_width(text, size),
+            Piece::Emoji(_) => size,
+        })
+        .sum()
+}
+
+// An icon `size` across centered on `center`, in its own colors faded by `alpha`
+fn draw_icon(list: &mut DrawList, layer: Layer, emoji: Emoji, center: Vec2, size: f32, alpha: f32) {
+    let fade = |color: Color| Color::new(color.r, color.g, color.b, color.a * alpha);
+    let r = size * 0.4;
+    let at = |x: f32, y: f32| center + vec2(x, y) * r;
+    let thin = (size / 12.0).max(1.0);
+    match emoji {
+        Emoji::Smile | Emoji::Sad => {
+            list.circle(layer, center.x, center.y, r, fade(SKIN));
+            for side in [-1.0, 1.0] {
+                let eye = at(side * 0.35, -0.25);
+                list.circle(layer, eye.x, eye.y, thin, fade(BLACK));
+            }
+            // Corners up for a smile, down for sad
+            let (corners, middle) = if emoji == Emoji::Smile {
+                (0.2, 0.5)
+            } else {
+                (0.55, 0.3)
+            };
+            list.line(
+                layer,
+                at(-0.45, corners),
+                at(0.0, middle),
+                thin,
+                fade(BLACK),
+            );
+            list.line(layer, at(0.0, middle), at(0.45, corners), thin, fade(BLACK));
+        }
+        Emoji::Heart => {
+            let red = fade(RED);
+            for side in [-1.0, 1.0] {
+                let lobe = at(side * 0.45, -0.3);
+                list.circle(layer, lobe.x, lobe.y, r * 0.5, red);
+            }
+            list.triangle(layer, at(-0.93, -0.15), at(0.93, -0.15), at(0.0, 0.95), red);
+        }
+        Emoji::Star => {
+            let gold = fade(GOLD);
+            list.triangle(layer, at(0.0, -1.0), at(0.87, 0.5), at(-0.87, 0.5), gold);
+            list.triangle(layer, at(0.0, 1.0), at(-0.87, -0.5), at(0.87, -0.5), gold);
+        }
+        Emoji::Wave => {
+            // An open hand with lines for the motion
+            let skin = fade(SKIN);
+            list.circle(layer, center.x, center.y + r * 0.3, r * 0.55, skin);
+            for finger in [-0.45, -0.15, 0.15, 0.45] {
+                list.line(
+                    layer,
+                    at(finger * 0.8, 0.0),
+                    at(finger, -0.85),
+                    thin * 2.0,
+                    skin,
+                );
+            }
+            list.line(layer, at(0.9, -0.6), at(1.0, 0.0), thin, fade(GRAY));
+        }
+        Emoji::Dance => {
+            // A music note
+            let ink = fade(BLACK);
+            let head = at(-0.3, 0.6);
+            list.circle(layer, head.x, head.y, r * 0.35, ink);
+            list.line(layer, at(0.0, 0.6), at(0.0, -0.9), thin, ink);
+            list.line(layer, at(0.0, -0.9), at(0.6, -0.5), thin * 1.5, ink);
+        }
+        Emoji::Sit => {
+            // A chair from the side
+            let wood = fade(WOOD);
+            list.line(layer, at(-0.5, -0.9), at(-0.5, 0.9), thin * 1.5, wood);
+            list.line(layer, at(-0.5, 0.1), at(0.5, 0.1), thin * 1.5, wood);
+            list.line(layer, at(0.5, 0.1), at(0.5, 0.9), thin * 1.5, wood);
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use crate::animation::Pose;
+use crate::batch::{DrawList, Layer};
+use crate::player::outfit_color;
+use crate::protocol::{Equipment, Facing, Hat, Weapon};
+use macroquad::prelude::*;
+
+// Where items attach, relative to the center of the head. Hands come from
+// the pose.
+const HEAD_TOP: f32 = -20.0;
+const SHOULDER: Vec2 = Vec2::new(0.0, 20.0);
+const CAPE_TOP: f32 = 16.0;
+const CAPE_BOTTOM: f32 = 56.0;
+const GOLD: Color = Color::new(0.95, 0.78, 0.2, 1.0);
+const STEEL: Color = Color::new(0.75, 0.78, 0.82, 1.0);
+const WOOD: Color = Color::new(0.45, 0.3, 0.15, 1.0);
+
+// Submits the items that hang behind the body, before the character
+pub fn draw_behind(list: &mut DrawList, origin: Vec2, equipment: &Equipment, alpha: f32) {
+    let fade = |color: Color| Color::new(color.r, color.g, color.b, color.a * alpha);
+    let at = |x: f32, y: f32| origin + vec2(x, y);
+
+    if let Some(color) = equipment.cape.and_then(outfit_color).map(fade) {
+        let layer = Layer::Characters;
+        let (top_left, top_right) = (at(-8.0, CAPE_TOP), at(8.0, CAPE_TOP));
+        let (bottom_left, bottom_right) = (at(-16.0, CAPE_BOTTOM), at(16.0, CAPE_BOTTOM));
+        list.triangle(layer, top_left, top_right, bottom_right, color);
+        list.triangle(layer, top_left, bottom_right, bottom_left, color);
+    }
+}
+
+// Submits the items worn over the body, after the character. `pose` is
+// already turned to `facing`.
+pub fn draw_in_front(
+    list: &mut DrawList,
+    origin: Vec2,
+    pose: &Pose,
+    facing: Facing,
+    equipment: &Equipment,
+    alpha: f32,
+) {
+    let fade = |color: Color| Color::new(color.r, color.g, color.b, color.a * alpha);
+    let side = facing.sign();
+    let at = |x: f32, y: f32| origin + vec2(x * side, y);
+    let layer = Layer::Characters;
+
+    match equipment.hat {
+        Some(Hat::TopHat) => {
+            list.rect(
+                layer,
+                origin.x - 16.0,
+                origin.y + HEAD_TOP - 2.0,
+                32.0,
+                4.0,
+                fade(BLACK),
+            );
+            list.rect(
+                layer,
+                origin.x - 10.0,
+                origin.y + HEAD_TOP - 22.0,
+                20.0,
+                20.0,
+                fade(BLACK),
+            );
+        }
+        Some(Hat::Cap) => {
+            list.rect(
+                layer,
+                origin.x - 16.0,
+                origin.y + HEAD_TOP - 4.0,
+                32.0,
+                8.0,
+                fade(BLUE),
+            );
+            list.line(
+                layer,
+                at(8.0, HEAD_TOP + 4.0),
+                at(26.0, HEAD_TOP + 4.0),
+                3.0,
+                fade(BLUE),
+            );
+        }
+        Some(Hat::Crown) => {
+            let base = origin.y + HEAD_TOP - 2.0;
+            list.rect(layer, origin.x - 14.0, base - 4.0, 28.0, 6.0, fade(GOLD));
+            for x in [-14.0, -5.0, 4.0] {
+                let (left, right) = (at(x, HEAD_TOP - 6.0), at(x + 10.0, HEAD_TOP - 6.0));
+                list.triangle(layer, left, right, at(x + 5.0, HEAD_TOP - 16.0), fade(GOLD));
+            }
+        }
+        None => {}
+    }
+
+    let hand = at(pose.right_arm.0, pose.right_arm.1);
+    // Held pointing away from the shoulder
+    let Some(direction) = (hand - (origin + SHOULDER)).try_normalize() else {
+        return;
+    };
+    match equipment.weapon {
+        Some(Weapon::Sword) => {
+            let blade = direction.rotate(Vec2::from_angle(-1.2 * side));
+            let guard = blade.perp() * 6.0;
+            list.line(layer, hand, hand + blade * 30.0, 3.0, fade(STEEL));
+            list.line(layer, hand - guard, hand + guard, 3.0, fade(WOOD));
+        }
+        Some(Weapon::Staff) => {
+            let top = hand - vec2(0.0, 35.0);
+            list.line(layer, hand + vec2(0.0, 15.0), top, 3.0, fade(WOOD));
+            list.circle(layer, top.x, top.y, 4.0, fade(SKYBLUE));
+        }
+        None => {}
+    }
+}
@@ -0,0 +1,37 @@
+use crate::logging;
+use std::io;
+use std::sync::{Mutex, MutexGuard};
+use thiserror::Error;
+use tracing::warn;
+
+// What can go wrong outside of startup. Shown to the player as the reason on
+// the disconnected screen, so messages read as sentences.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Couldn't reach {addr}: {source}")]
+    Connect { addr: String, source: io::Error },
+    #[error("The server didn't accept the connection")]
+    Refused,
+    // Usually a server on another protocol version
+    #[error("Couldn't read a message from the server: {0}")]
+    Decode(bincode::Error),
+    #[error("Couldn't encode a message: {0}")]
+    Encode(bincode::Error),
+    #[error("Couldn't start the network runtime: {0}")]
+    Runtime(io::Error),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Locks `mutex` even if a thread panicked while holding it. The game state
+// may be half updated, but the next frame or snapshot puts it right, which
+// beats taking the whole client down with the thread that panicked.
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| {
+        warn!(target: logging::GAME, "Recovering a lock poisoned by a panic");
+        mutex.clear_poison();
+        e.into_inner()
+    })
+}
@@ -0,0 +1,71 @@
+use crate::batch::{DrawList, Layer};
+use crate::components::Position;
+use crate::ui;
+use macroquad::prelude::*;
+
+const LIFETIME: f32 = 1.5; // Seconds
+const RISE_SPEED: f32 = 30.0; // Pixels a second
+const FONT_SIZE: f32 = 18.0;
+// Above the head of a character at a position
+const HEAD_OFFSET: f32 = -40.0;
+
+struct FloatingText {
+    text: String,
+    color: Color,
+    at: Vec2, // Where it started, in the world
+    age: f32,
+}
+
+// Short-lived text in the world that rises and fades out, e.g. damage
+// numbers, "+1 coin" or someone leaving
+pub struct FloatingTexts {
+    texts: Vec<FloatingText>,
+}
+
+impl Default for FloatingTexts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FloatingTexts {
+    pub fn new() -> Self {
+        Self { texts: Vec::new() }
+    }
+
+    // Text centered on `at` in the world
+    pub fn spawn(&mut self, text: impl Into<String>, color: Color, at: Vec2) {
+        self.texts.push(FloatingText {
+            text: text.into(),
+            color,
+            at,
+            age: 0.0,
+        });
+    }
+
+    // Text over the head of a character at `position`
+    pub fn above(&mut self, position: &Position, text: impl Into<String>, color: Color) {
+        self.spawn(text, color, vec2(position.x, position.y + HEAD_OFFSET));
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        for text in &mut self.texts {
+            text.age += dt;
+        }
+        self.texts.retain(|text| text.age < LIFETIME);
+    }
+
+    // Over the characters, fading out over the second half of their life
+    pub fn draw(&self, list: &mut DrawList) {
+        for text in &self.texts {
+            let fade = (2.0 * (1.0 - text.age / LIFETIME)).min(1.0);
+            let color = Color {
+                a: text.color.a * fade,
+                ..text.color
+            };
+            let width = ui::text_width(&text.text, FONT_SIZE);
+            let at = text.at - vec2(width / 2.0, text.age * RISE_SPEED);
+            list.text(Layer::Bubbles, &text.text, at.x, at.y, FONT_SIZE, color);
+        }
+    }
+}
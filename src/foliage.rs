@@ -0,0 +1,125 @@
+use crate::quality::GraphicsQuality;
+use crate::spatial::SpatialHash;
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use macroquad::prelude::*;
+
+// Blades per patch at `GraphicsQuality::High`
+const BLADES_PER_PATCH: usize = 60;
+const PATCH_RADIUS: f32 = 45.0;
+// How close a player's feet must be to push a blade over
+const PUSH_RADIUS: f32 = 28.0;
+const MAX_BEND: f32 = 1.1; // Radians
+const SPRING: f32 = 18.0;
+const DAMPING: f32 = 6.0;
+// Characters' feet are this far below their position
+const FEET_OFFSET: f32 = 60.0;
+
+// Seed for the patch layout, so every client sees the same grass
+const LAYOUT_SEED: u64 = 0x6c616d;
+
+struct Blade {
+    base: Vec2,
+    height: f32,
+    bend: f32, // Current lean, negative is left
+    bend_velocity: f32,
+    sway_phase: f32,
+}
+
+struct Patch {
+    center: Vec2,
+    blades: Vec<Blade>,
+}
+
+// Purely decorative grass that parts around players walking through it
+pub struct Foliage {
+    patches: Vec<Patch>,
+    time: f32,
+}
+
+impl Foliage {
+    pub fn new(patch_centers: &[(f32, f32)], quality: GraphicsQuality) -> Self {
+        let mut rng = StdRng::seed_from_u64(LAYOUT_SEED);
+        let blade_count = (BLADES_PER_PATCH as f32 * quality.density_scale()) as usize;
+
+        let patches = patch_centers
+            .iter()
+            .map(|&(x, y)| Patch {
+                center: Vec2::new(x, y),
+                blades: (0..blade_count)
+                    .map(|_| {
+                        let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                        let distance = PATCH_RADIUS * rng.gen_range(0.0f32..1.0).sqrt();
+                        Blade {
+                            base: Vec2::new(
+                                x + angle.cos() * distance,
+                                y + angle.sin() * distance * 0.5,
+                            ),
+                            height: rng.gen_range(8.0..16.0),
+                            bend: 0.0,
+                            bend_velocity: 0.0,
+                            sway_phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        Self { patches, time: 0.0 }
+    }
+
+    pub fn update(&mut self, dt: f32, spatial: &SpatialHash) {
+        self.time += dt;
+
+        for patch in &mut self.patches {
+            // Positions are heads; shift the query so it covers feet in the patch
+            let query_center = patch.center - Vec2::new(0.0, FEET_OFFSET);
+            let feet: Vec<Vec2> = spatial
+                .query_radius(query_center, PATCH_RADIUS + PUSH_RADIUS)
+                .into_iter()
+                .map(|(_, position)| position + Vec2::new(0.0, FEET_OFFSET))
+                .collect();
+
+            for blade in &mut patch.blades {
+                // Lean away from the nearest foot, harder the closer it is
+                let mut target = 0.0;
+                for foot in &feet {
+                    let offset = blade.base - *foot;
+                    let distance = offset.length();
+                    if distance < PUSH_RADIUS {
+                        let strength = 1.0 - distance / PUSH_RADIUS;
+                        let lean = offset.x.signum() * strength * MAX_BEND;
+                        if lean.abs() > f32::abs(target) {
+                            target = lean;
+                        }
+                    }
+                }
+
+                // Damped spring toward the target lean
+                let accel = (target - blade.bend) * SPRING - blade.bend_velocity * DAMPING;
+                blade.bend_velocity += accel * dt;
+                blade.bend += blade.bend_velocity * dt;
+            }
+        }
+    }
+
+    pub fn draw(&self, color: Color) {
+        for patch in &self.patches {
+            for blade in &patch.blades {
+                let sway = (self.time * 1.5 + blade.sway_phase).sin() * 0.08;
+                let angle = blade.bend + sway;
+                let tip = blade.base + Vec2::new(angle.sin(), -angle.cos()) * blade.height;
+                draw_line(blade.base.x, blade.base.y, tip.x, tip.y, 1.5, color);
+            }
+        }
+    }
+}
+
+// Grass patch centers in the default world
+pub const DEFAULT_PATCHES: [(f32, f32); 5] = [
+    (160.0, 420.0),
+    (400.0, 520.0),
+    (620.0, 380.0),
+    (300.0, 220.0),
+    (560.0, 180.0),
+];
@@ -0,0 +1,1557 @@
+use crate::account::{self, AccountPanel};
+use crate::announcement::AnnouncementBanner;
+use crate::assets::Assets;
+use crate::auth::{self, AuthPrompt};
+use crate::batch::{DrawList, Layer};
+use crate::camera::Camera;
+use crate::camera_effects::{CameraEffects, CameraEvent};
+use crate::character_panel::CharacterPanel;
+use crate::chat::{ChatInput, ChatLog};
+use crate::chat_history::ChatHistory;
+use crate::commands::{self, CommandRegistry};
+use crate::components::{
+    Animation, Appearance, ChatBubble, Hidden, LocalPlayer, NetworkId, Position, SnapshotBuffer,
+    Velocity,
+};
+use crate::config::Config;
+use crate::desync;
+use crate::emoji;
+use crate::floating_text::FloatingTexts;
+use crate::foliage::{Foliage, DEFAULT_PATCHES};
+use crate::help;
+use crate::input::{Focus, InputFrame};
+use crate::latency::LatencyProbe;
+use crate::lighting::Lighting;
+use crate::logging;
+use crate::mail::MailPanel;
+use crate::map::DEFAULT_MAP;
+use crate::minigame::Minigame;
+use crate::minimap::Minimap;
+use crate::obstacle_view::Obstacles;
+use crate::palette;
+use crate::particles::CharacterEffects;
+use crate::party::Party;
+use crate::pathfinding::{self, Route};
+use crate::player::{apply_cosmetics, spawn_local_player, spawn_remote_player, BODY_COLORS};
+use crate::player_list::PlayerListPanel;
+use crate::player_menu::{PlayerAction, PlayerMenu};
+use crate::plugin::PluginRegistry;
+use crate::post_process::PostProcess;
+use crate::profiler::FrameProfiler;
+use crate::protocol::{
+    now_millis, ChatChannel, ClientMessage, Cosmetics, Emote, Equipment, Expression, Face, Facing,
+    Hair, Outfit, Season, SeasonalEvent, Stance,
+};
+use crate::quality::GraphicsQuality;
+use crate::render::{self, draw_name_tag, render_system};
+use crate::replay::{self, ReplayRecorder};
+use crate::season::current_season;
+use crate::seasonal::SeasonalEffects;
+use crate::settings::{Action, Settings, WindowMode};
+use crate::settings_panel::SettingsPanel;
+use crate::spatial::SpatialHash;
+use crate::systems;
+use crate::tilemap::Tilemap;
+use crate::ui;
+use crate::weather::WeatherEffects;
+use crate::wildlife::Wildlife;
+use crate::word_filter;
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use hecs::{Component, Entity, World};
+use macroquad::prelude::*;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+// Cell size of the per-frame spatial hash, roughly one character across
+const SPATIAL_CELL_SIZE: f32 = 64.0;
+
+// Sideways movement under this, in input or pixels to the target, doesn't
+// turn the local player around
+const FACING_DEADBAND: f32 = 0.5;
+
+// Players further away than this aren't close enough to be caught on camera
+const RECORDING_INDICATOR_RADIUS: f32 = 300.0;
+
+// Step used by `Game::step`. The same seed and inputs stepped at this rate
+// always end in the same state; drawing-only systems are the exception.
+pub const FIXED_DT: f32 = 1.0 / 60.0;
+
+pub struct Game {
+    pub seed: u64, // Everything random in the simulation derives from this
+    pub world: World,
+    pub local_player: Entity,
+    pub remote_players: HashMap<usize, Entity>, // Network id -> entity
+    pub cosmetics: HashMap<usize, Cosmetics>,   // Looks picked by players, by network id
+    pub names: HashMap<usize, String>,          // Shown above players, by network id
+    pub last_send_time: Instant,
+    pub send_interval: Duration,
+    pub message_send_interval: Duration,
+    pub last_message_time: Instant,
+    // Said by the local player and not sent yet, oldest first. One goes out
+    // every `message_send_interval`.
+    pub unsent_messages: VecDeque<(String, Option<Expression>, ChatChannel)>,
+    pub config: Config,
+    pub settings: Settings, // Saved between runs by windowed clients
+    pub position_tick: u64,
+    pub show_debug_overlay: bool,
+    pub show_help: bool,          // Every control and its key, see `help::draw`
+    pub show_snapshot_diff: bool, // Server positions outlined over drawn ones
+    pub latency: LatencyProbe,
+    pub profiler: FrameProfiler,
+    pub camera: Camera,
+    pub camera_effects: CameraEffects,
+    pub camera_events: Vec<CameraEvent>, // Shakes, flashes and punches queued by game systems
+    pub draw_list: DrawList,             // Characters, critters and particles, batched
+    pub post_process: PostProcess,
+    pub floating_text: FloatingTexts, // Notices, numbers and such rising over the world
+    pub recorder: Option<ReplayRecorder>,
+    recording_requested: bool, // Waiting for the server to allow recording
+    pub recording_players: HashSet<usize>, // Network ids of players recording or streaming
+    pub hovered_player: Option<usize>, // Network id of the player under the mouse
+    // Network id of the player last clicked on, for whispers, trades and
+    // menus about them. Cleared when they leave.
+    pub selected_player: Option<usize>,
+    pub player_menu: PlayerMenu, // Opened by right clicking another player
+    // Network ids muted this session, names are kept in the settings so
+    // they stay muted
+    pub muted_players: HashSet<usize>,
+    pub party: Party,                 // Who we're grouped with, from the server
+    pub player_list: PlayerListPanel, // Everyone connected, held open
+    pub spectating: bool,             // Watching a replay, the local player is hidden
+    pub minigames: Vec<Box<dyn Minigame>>,
+    pub plugins: PluginRegistry,
+    pub outbox: Vec<ClientMessage>, // Messages queued by game systems for the server
+    pub obstacles: Obstacles,
+    pub route: Route, // Where a click to move is walking around obstacles
+    pub spatial: SpatialHash,
+    pub graphics_quality: GraphicsQuality,
+    pub foliage: Foliage,
+    pub wildlife: Wildlife,
+    pub lighting: Lighting,
+    pub effects: CharacterEffects, // Dust, chat pops, join and leave puffs
+    pub tilemap: Option<Tilemap>,  // Ground for the current map, if it has one
+    pub headless: bool,            // No window, skips everything that only affects drawing
+    pub seasonal: SeasonalEffects,
+    pub weather: WeatherEffects, // Whatever the server last sent
+    pub season_locked: bool,     // Set from the command line, ignores the server
+    pub player_name: String,     // Sent on login, other players mail this name
+    pub device_id: String,
+    pub protect_name: bool, // Ask for a recovery code if the name is new
+    pub auth: AuthPrompt,
+    pub mailbox: MailPanel,
+    pub settings_panel: SettingsPanel,
+    pub character_panel: CharacterPanel,
+    pub minimap: Minimap,
+    pub account: AccountPanel,
+    pub chat: ChatInput,
+    pub chat_log: ChatLog,
+    pub chat_history: ChatHistory, // The chat log on disk, if `Settings::save_chat`
+    pub commands: CommandRegistry, // `/help`, `/who` and the like, run here and never sent
+    pub announcement: AnnouncementBanner,
+    // The server switched to this map and its obstacles haven't arrived yet
+    pub loading_map: Option<String>,
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Game {
+    pub fn new() -> Self {
+        Self::with_seed(::rand::random())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        let mut world = World::new();
+        // Start at center
+        let local_player = spawn_local_player(&mut world, 400.0, 300.0, seed);
+        // Until the server tells us otherwise
+        let (season, event) = current_season();
+        let seasonal = SeasonalEffects::new(season, event);
+        Self {
+            seed,
+            world,
+            local_player,
+            remote_players: HashMap::new(),
+            cosmetics: HashMap::new(),
+            names: HashMap::new(),
+            last_send_time: Instant::now(),
+            send_interval: Config::default().send_interval(),
+            message_send_interval: Config::default().message_send_interval(),
+            last_message_time: Instant::now(),
+            unsent_messages: VecDeque::new(),
+            config: Config::default(),
+            settings: Settings::default(),
+            position_tick: 0,
+            show_debug_overlay: false,
+            show_help: false,
+            show_snapshot_diff: false,
+            latency: LatencyProbe::new(),
+            profiler: FrameProfiler::new(),
+            camera: Camera::new(),
+            camera_effects: CameraEffects::new(),
+            camera_events: Vec::new(),
+            draw_list: DrawList::new(),
+            post_process: PostProcess::new(),
+            floating_text: FloatingTexts::new(),
+            recorder: None,
+            recording_requested: false,
+            recording_players: HashSet::new(),
+            hovered_player: None,
+            selected_player: None,
+            player_menu: PlayerMenu::new(),
+            muted_players: HashSet::new(),
+            party: Party::new(),
+            player_list: PlayerListPanel::new(),
+            spectating: false,
+            minigames: Vec::new(),
+            plugins: PluginRegistry::new(),
+            outbox: Vec::new(),
+            obstacles: Obstacles::new(),
+            route: Route::new(),
+            spatial: SpatialHash::new(SPATIAL_CELL_SIZE),
+            graphics_quality: GraphicsQuality::High,
+            foliage: Foliage::new(&DEFAULT_PATCHES, GraphicsQuality::High),
+            wildlife: Wildlife::new(
+                GraphicsQuality::High.density_scale(),
+                seasonal.theme.butterflies,
+            ),
+            lighting: Lighting::new(),
+            effects: CharacterEffects::new(),
+            tilemap: Tilemap::for_map(DEFAULT_MAP),
+            headless: false,
+            seasonal,
+            weather: WeatherEffects::new(),
+            season_locked: false,
+            player_name: format!("player-{}", ::rand::random::<u16>()),
+            device_id: auth::random_device_id(),
+            protect_name: false,
+            auth: AuthPrompt::new(),
+            mailbox: MailPanel::new(),
+            settings_panel: SettingsPanel::new(),
+            character_panel: CharacterPanel::new(),
+            minimap: Minimap::new(),
+            account: AccountPanel::new(),
+            chat: ChatInput::new(),
+            chat_log: ChatLog::new(),
+            chat_history: ChatHistory::new(),
+            commands: CommandRegistry::new(),
+            announcement: AnnouncementBanner::new(),
+            loading_map: None,
+        }
+    }
+
+    // A component of the local player. Every component spawned with the local
+    // player is always present, so this only panics on programmer error.
+    pub fn local<T: Component>(&self) -> hecs::Ref<'_, T> {
+        self.world
+            .get::<&T>(self.local_player)
+            .expect("Local player is missing a component")
+    }
+
+    pub fn local_mut<T: Component>(&self) -> hecs::RefMut<'_, T> {
+        self.world
+            .get::<&mut T>(self.local_player)
+            .expect("Local player is missing a component")
+    }
+
+    pub fn local_id(&self) -> usize {
+        self.local::<NetworkId>().0
+    }
+
+    // The local player's look, the one they spawned with until they pick one
+    pub fn local_cosmetics(&self) -> Cosmetics {
+        self.cosmetics
+            .get(&self.local_id())
+            .copied()
+            .unwrap_or(Cosmetics {
+                body_color: BODY_COLORS[0].1,
+                hair_seed: self.seed,
+                sprite: None,
+                hair: Hair::default(),
+                face: Face::default(),
+                outfit: Outfit::default(),
+                equipment: Equipment::default(),
+            })
+    }
+
+    // Drops everything learned from the last server, ready to connect again
+    pub fn forget_server(&mut self) {
+        for (_, entity) in self.remote_players.drain() {
+            let _ = self.world.despawn(entity);
+        }
+        self.cosmetics.clear();
+        self.names.clear();
+        self.recording_players.clear();
+        self.recording_requested = false;
+        self.local_mut::<NetworkId>().0 = 0;
+        // The next server only mentions its map if it isn't the default
+        self.tilemap = Tilemap::for_map(DEFAULT_MAP);
+    }
+
+    // Where players can walk: the tilemap if there is one, otherwise the
+    // default world size
+    pub fn world_bounds(&self) -> Rect {
+        self.tilemap
+            .as_ref()
+            .map(Tilemap::bounds)
+            .unwrap_or(Rect::new(0.0, 0.0, WORLD_WIDTH, WORLD_HEIGHT))
+    }
+
+    // Nothing from the old map carries over. Other players reappear as their
+    // respawned positions come in, obstacles with the next snapshot.
+    fn change_map(&mut self, name: String, spawn_x: f32, spawn_y: f32) {
+        info!(target: logging::GAME, "Map: {}", name);
+        for (_, entity) in self.remote_players.drain() {
+            let _ = self.world.despawn(entity);
+        }
+        self.obstacles = Obstacles::new();
+        if let Ok((position, velocity, local)) =
+            self.world
+                .query_one_mut::<(&mut Position, &mut Velocity, &mut LocalPlayer)>(
+                    self.local_player,
+                )
+        {
+            position.x = spawn_x;
+            position.y = spawn_y;
+            velocity.target = None;
+            local.position_changed = true;
+        }
+        self.tilemap = Tilemap::for_map(&name);
+        self.camera.snap();
+        self.loading_map = Some(name);
+    }
+
+    // Hides the local player while a replay is being watched
+    pub fn set_spectating(&mut self) {
+        self.spectating = true;
+        let _ = self.world.insert_one(self.local_player, Hidden);
+    }
+
+    pub fn set_graphics_quality(&mut self, quality: GraphicsQuality) {
+        info!(target: logging::RENDER, "Graphics quality: {:?}", quality);
+        self.graphics_quality = quality;
+        self.foliage = Foliage::new(&DEFAULT_PATCHES, quality);
+        self.wildlife = Wildlife::new(quality.density_scale(), self.seasonal.theme.butterflies);
+        self.effects.set_density(quality.density_scale());
+    }
+
+    pub fn set_season(&mut self, season: Season, event: Option<SeasonalEvent>) {
+        if self.seasonal.season == season && self.seasonal.event == event {
+            return;
+        }
+        info!(target: logging::GAME, "Season: {:?} {:?}", season, event);
+        self.seasonal = SeasonalEffects::new(season, event);
+        self.wildlife = Wildlife::new(
+            self.graphics_quality.density_scale(),
+            self.seasonal.theme.butterflies,
+        );
+    }
+
+    // Applies tunables from `lam.toml` to the running game and every character
+    pub fn apply_config(&mut self, config: Config) {
+        self.send_interval = config.send_interval();
+        self.message_send_interval = config.message_send_interval();
+        let characters: Vec<Entity> = self
+            .world
+            .query::<(Entity, &Velocity)>()
+            .iter()
+            .map(|(entity, _)| entity)
+            .collect();
+        for entity in characters {
+            apply_config_to_character(&mut self.world, entity, &config);
+        }
+        self.config = config;
+    }
+
+    // Shows `message` above the local player and queues it for the server.
+    // Registered and plugin commands run here and answer in the chat log,
+    // `/happy hi` says "hi" with a smile, other `/commands` go to the server.
+    pub fn say(&mut self, message: &str) {
+        if let Some((name, args)) = commands::split(message) {
+            let result = match self.commands.get(name) {
+                Some(handler) => Some(handler(self, args)),
+                None => self.plugins.run_command(name, args),
+            };
+            if let Some(result) = result {
+                match result {
+                    Ok(Some(output)) | Err(output) => self.chat_log.push_system(&output),
+                    Ok(None) => {}
+                }
+                return;
+            }
+        }
+        if let Some((expression, text)) = split_expression(message) {
+            self.show_local_message(text, Some(expression));
+            return;
+        }
+        if let Some(command) = account::parse_command(message) {
+            match command {
+                // Everything else is picked on the character screen
+                Ok(ClientMessage::SetCosmetics { cosmetics }) => {
+                    let cosmetics = Cosmetics {
+                        body_color: cosmetics.body_color,
+                        hair_seed: cosmetics.hair_seed,
+                        sprite: cosmetics.sprite,
+                        ..self.local_cosmetics()
+                    };
+                    self.outbox.push(ClientMessage::SetCosmetics { cosmetics });
+                }
+                Ok(command) => self.outbox.push(command),
+                Err(e) => self.chat_log.push_system(&e),
+            }
+            return;
+        }
+        self.show_local_message(message, Expression::detect(message));
+    }
+
+    // Plays `emote` on the local player and shows everyone else
+    fn play_emote(&mut self, emote: Emote) {
+        self.local_mut::<Animation>().play(emote);
+        let id = self.local_id();
+        self.outbox.push(ClientMessage::Emote { id, emote });
+    }
+
+    // `:wave:` and the like play their emote as well, unless turned off
+    fn show_local_message(&mut self, message: &str, expression: Option<Expression>) {
+        if let Some(emote) = emoji::emote(message).filter(|_| self.settings.chat_emotes) {
+            self.play_emote(emote);
+        }
+        let channel = self.chat.channel;
+        self.log_chat(self.local_id(), message, channel, None);
+        self.unsent_messages
+            .push_back((message.to_string(), expression, channel));
+        let mut bubble = self.local_mut::<ChatBubble>();
+        bubble.show(message.to_string(), expression);
+        bubble.channel = channel;
+        bubble.sent = false;
+    }
+
+    // Adds a line to the chat log, and to the file unless it's a bot or a
+    // replay talking. `sent_at_ms` is None for the local player's own.
+    fn log_chat(
+        &mut self,
+        id: usize,
+        message: &str,
+        channel: ChatChannel,
+        sent_at_ms: Option<u64>,
+    ) {
+        if self.settings.save_chat && !self.headless && !self.spectating {
+            let name = self.name_of(id);
+            self.chat_history.write(&name, channel.name(), message);
+        }
+        self.chat_log
+            .push(id, message.to_string(), channel, sent_at_ms);
+    }
+
+    // Sends `message` to `id` alone
+    pub fn whisper(&mut self, id: usize, message: &str) {
+        self.outbox.push(ClientMessage::Whisper {
+            id,
+            message: message.to_string(),
+            sent_at_ms: 0,
+        });
+        self.log_whisper(id, message, None);
+    }
+
+    // Like `log_chat`, for a whisper from `id` sent at `sent_at_ms`, or to
+    // them if that's None
+    fn log_whisper(&mut self, id: usize, message: &str, sent_at_ms: Option<u64>) {
+        if self.settings.save_chat && !self.headless && !self.spectating {
+            let (name, label) = if sent_at_ms.is_none() {
+                let label = format!("whisper to {}", self.name_of(id));
+                (self.name_of(self.local_id()), label)
+            } else {
+                (self.name_of(id), "whisper".to_string())
+            };
+            self.chat_history.write(&name, &label, message);
+        }
+        self.chat_log
+            .push_whisper(id, message.to_string(), sent_at_ms);
+    }
+
+    // Applies a message from the server (or a replay) to the world
+    pub fn handle_message(&mut self, message: ClientMessage) {
+        if let Some(recorder) = &mut self.recorder {
+            recorder.record(&message);
+        }
+
+        for minigame in &mut self.minigames {
+            minigame.handle_message(&message);
+        }
+        self.plugins.on_message(&message);
+        self.auth.handle_message(&message, &mut self.outbox);
+        self.mailbox.handle_message(&message);
+        self.account.handle_message(&message);
+
+        match message {
+            ClientMessage::PlayerPosition {
+                id,
+                x,
+                y,
+                tick,
+                timestamp_ms,
+                stance,
+                facing,
+            } => {
+                if id == self.local_id() {
+                    return;
+                }
+                let world = &mut self.world;
+                let config = &self.config;
+                let cosmetics = &self.cosmetics;
+                let name = self.names.get(&id);
+                let floating_text = &mut self.floating_text;
+                let effects = (!self.headless).then_some(&mut self.effects);
+                let entity = *self.remote_players.entry(id).or_insert_with(|| {
+                    if let Some(effects) = effects {
+                        effects.puff(&Position { x, y });
+                    }
+                    if let Some(name) = name {
+                        let notice = format!("{} joined", name);
+                        floating_text.above(&Position { x, y }, notice, palette::current().notice);
+                    }
+                    let entity = spawn_remote_player(world, id, x, y);
+                    apply_config_to_character(world, entity, config);
+                    if let Some(cosmetics) = cosmetics.get(&id) {
+                        apply_cosmetics_to_character(world, entity, cosmetics);
+                    }
+                    entity
+                });
+                if let Ok((buffer, velocity, animation)) =
+                    self.world
+                        .query_one_mut::<(&mut SnapshotBuffer, &mut Velocity, &mut Animation)>(
+                            entity,
+                        )
+                {
+                    if buffer.push(tick, timestamp_ms, x, y) {
+                        velocity.target = Some((x, y));
+                        animation.set_stance(stance);
+                        animation.facing = facing;
+                    }
+                }
+            }
+            ClientMessage::AssignPlayerId { id } => {
+                info!(target: logging::NET, "Assigned player id: {}", id);
+                self.local_mut::<NetworkId>().0 = id;
+                let login = ClientMessage::Login {
+                    name: self.player_name.clone(),
+                    device: self.device_id.clone(),
+                    protect: self.protect_name,
+                };
+                let message = self.auth.login_message(&self.player_name, login);
+                self.outbox.push(message);
+            }
+            ClientMessage::OtherPlayerDisconnected { id } => {
+                if let Some(entity) = self.remote_players.remove(&id) {
+                    if let Ok(position) = self.world.get::<&Position>(entity) {
+                        if !self.headless {
+                            self.effects.puff(&position);
+                        }
+                        if let Some(name) = self.names.get(&id) {
+                            let notice = format!("{} left", name);
+                            self.floating_text
+                                .above(&position, notice, palette::current().notice);
+                        }
+                    }
+                    let _ = self.world.despawn(entity);
+                }
+                self.cosmetics.remove(&id);
+                self.names.remove(&id);
+                self.recording_players.remove(&id);
+                self.muted_players.remove(&id);
+                self.player_menu.close_for(id);
+                self.party.invites.retain(|&other| other != id);
+            }
+            ClientMessage::RecordingStatus { id, recording } if id == self.local_id() => {
+                let requested = std::mem::take(&mut self.recording_requested);
+                if recording && requested {
+                    self.start_recording();
+                } else if !recording && self.recorder.is_some() {
+                    self.stop_recording();
+                }
+            }
+            ClientMessage::RecordingStatus { id, recording } => {
+                if recording {
+                    self.recording_players.insert(id);
+                } else {
+                    self.recording_players.remove(&id);
+                }
+            }
+            // Remote players may not have been seen yet, they pick it up on spawn
+            ClientMessage::PlayerName { id, name } if id == self.local_id() => {
+                if self.settings.display_name.as_ref() != Some(&name) {
+                    self.settings.display_name = Some(name.clone());
+                    self.save_settings();
+                }
+                self.names.insert(id, name);
+            }
+            ClientMessage::PlayerName { id, name } => {
+                // Seen before their name came in, so they had no join notice
+                let entity = self.remote_players.get(&id).copied();
+                if let Some(entity) = entity.filter(|_| !self.names.contains_key(&id)) {
+                    if let Ok(position) = self.world.get::<&Position>(entity) {
+                        let notice = format!("{} joined", name);
+                        self.floating_text
+                            .above(&position, notice, palette::current().notice);
+                    }
+                }
+                self.names.insert(id, name);
+            }
+            ClientMessage::PlayerCosmetics { id, cosmetics } => {
+                self.cosmetics.insert(id, cosmetics);
+                let entity = if id == self.local_id() {
+                    Some(self.local_player)
+                } else {
+                    self.remote_players.get(&id).copied()
+                };
+                if let Some(entity) = entity {
+                    apply_cosmetics_to_character(&mut self.world, entity, &cosmetics);
+                }
+            }
+            ClientMessage::UpdateMessage {
+                id,
+                message,
+                expression,
+                channel,
+                sent_at_ms,
+            } => {
+                if id != self.local_id() && !self.is_muted(id) {
+                    let message = if self.settings.word_filter {
+                        word_filter::mask(&message, &self.settings.filtered_words)
+                    } else {
+                        message
+                    };
+                    self.log_chat(id, &message, channel, Some(sent_at_ms));
+                    if let Some(&entity) = self.remote_players.get(&id) {
+                        if let Ok(bubble) = self.world.query_one_mut::<&mut ChatBubble>(entity) {
+                            let expression = expression.or_else(|| Expression::detect(&message));
+                            bubble.show(message, expression);
+                            bubble.channel = channel;
+                        }
+                    }
+                }
+            }
+            ClientMessage::Emote { id, emote } => {
+                if id != self.local_id() {
+                    if let Some(&entity) = self.remote_players.get(&id) {
+                        if let Ok(animation) = self.world.query_one_mut::<&mut Animation>(entity) {
+                            animation.play(emote);
+                        }
+                    }
+                }
+            }
+            ClientMessage::Ping { sent_at_ms } => {
+                self.latency
+                    .server_latency(now_millis().saturating_sub(sent_at_ms));
+                self.chat_log.sync_clock(sent_at_ms);
+                self.outbox.push(ClientMessage::Pong { sent_at_ms });
+            }
+            ClientMessage::PartyInvite { id } => {
+                self.party.invited(id);
+                let notice = format!(
+                    "{} invited you to their party, /accept to join",
+                    self.name_of(id)
+                );
+                self.chat_log.push_system(&notice);
+            }
+            ClientMessage::PartyUpdate { party, members } => {
+                // Party chat has nowhere to go once out of the party
+                if party.is_none() && self.chat.channel == ChatChannel::Party {
+                    self.chat.channel = ChatChannel::Global;
+                }
+                self.party.update(party, members);
+            }
+            ClientMessage::PlayerList { players } => self.player_list.set(players),
+            ClientMessage::ServerAnnouncement { text, severity } => {
+                self.chat_log.push_announcement(&text, severity);
+                self.announcement.show(text, severity);
+            }
+            ClientMessage::Whisper {
+                id,
+                message,
+                sent_at_ms,
+            } => {
+                if !self.is_muted(id) {
+                    let message = if self.settings.word_filter {
+                        word_filter::mask(&message, &self.settings.filtered_words)
+                    } else {
+                        message
+                    };
+                    self.log_whisper(id, &message, Some(sent_at_ms));
+                }
+            }
+            ClientMessage::ObstacleSnapshot { tick, obstacles } => {
+                self.obstacles.apply_snapshot(tick, obstacles);
+                self.loading_map = None;
+            }
+            ClientMessage::MapChange {
+                name,
+                spawn_x,
+                spawn_y,
+            } => self.change_map(name, spawn_x, spawn_y),
+            ClientMessage::TimeOfDay {
+                time_ms,
+                day_length_ms,
+            } => self.lighting.sync(time_ms, day_length_ms),
+            ClientMessage::SetWeather { weather } => self.weather.set(weather),
+            // Logged in, the server may not know the name picked last time
+            ClientMessage::SessionTicket { .. } => {
+                if let Some(name) = self.settings.display_name.clone() {
+                    self.outbox.push(ClientMessage::SetName { name });
+                }
+            }
+            ClientMessage::SeasonInfo { season, event } => {
+                if !self.season_locked {
+                    self.set_season(season, event);
+                }
+            }
+            // Handled by the minigames and panels, or only ever sent to the server
+            ClientMessage::Leaderboard { .. }
+            | ClientMessage::RaceFinished { .. }
+            | ClientMessage::Mailbox { .. }
+            | ClientMessage::RecoveryCodeRequired { .. }
+            | ClientMessage::RecoveryCode { .. }
+            | ClientMessage::LoginRejected { .. }
+            | ClientMessage::Login { .. }
+            | ClientMessage::ResumeSession { .. }
+            | ClientMessage::Pong { .. }
+            | ClientMessage::Goodbye
+            | ClientMessage::SubmitRecoveryCode { .. }
+            | ClientMessage::SendMail { .. }
+            | ClientMessage::MarkMailRead { .. }
+            | ClientMessage::DeleteMail { .. }
+            | ClientMessage::DataExport { .. }
+            | ClientMessage::DeletionCode { .. }
+            | ClientMessage::ServerNotice { .. }
+            | ClientMessage::RequestDataExport
+            | ClientMessage::RequestAccountDeletion
+            | ClientMessage::ConfirmAccountDeletion { .. }
+            | ClientMessage::SetCosmetics { .. }
+            | ClientMessage::SetName { .. }
+            | ClientMessage::SetParty { .. }
+            | ClientMessage::RequestPlayerList
+            | ClientMessage::RequestCharacterExport
+            | ClientMessage::CharacterExport { .. }
+            | ClientMessage::ImportCharacter { .. }
+            | ClientMessage::SetBackground { .. }
+            | ClientMessage::RequestSnapshot
+            | ClientMessage::SetRecording { .. }
+            | ClientMessage::RecordingConsent { .. } => {}
+        }
+    }
+
+    pub fn toggle_fullscreen(&mut self) {
+        self.settings.window_mode = self.settings.window_mode.toggled();
+        self.apply_window_settings();
+        self.save_settings();
+    }
+
+    // Puts the window in the mode and size the settings ask for. The camera
+    // and everything on screen follow the new size on the next frame.
+    pub fn apply_window_settings(&self) {
+        let fullscreen = self.settings.window_mode == WindowMode::Fullscreen;
+        set_fullscreen(fullscreen);
+        if let Some((width, height)) = self.settings.resolution.filter(|_| !fullscreen) {
+            request_new_screen_size(width as f32, height as f32);
+        }
+    }
+
+    // Headless clients never touch the player's settings
+    pub fn save_settings(&self) {
+        if self.headless {
+            return;
+        }
+        if let Err(e) = self.settings.save() {
+            error!(target: logging::CONFIG, "Failed to save settings: {}", e);
+        }
+    }
+
+    // Starts a recording, or stops the current one and writes it to disk.
+    // Online, the server is asked first so other players see it.
+    pub fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            if self.local_id() != 0 {
+                self.outbox
+                    .push(ClientMessage::SetRecording { recording: false });
+            }
+        } else if self.local_id() == 0 {
+            self.start_recording();
+        } else if !self.recording_requested {
+            self.recording_requested = true;
+            self.outbox
+                .push(ClientMessage::SetRecording { recording: true });
+        }
+    }
+
+    fn start_recording(&mut self) {
+        info!(target: logging::GAME, "Recording replay");
+        self.recorder = Some(ReplayRecorder::new(self.local_id()));
+    }
+
+    fn stop_recording(&mut self) {
+        let Some(recorder) = self.recorder.take() else {
+            return;
+        };
+        let path = replay::new_replay_path();
+        match recorder.finish().export(&path) {
+            Ok(()) => info!(target: logging::GAME, "Saved replay to {}", path.display()),
+            Err(e) => error!(target: logging::GAME, "Failed to save replay: {}", e),
+        }
+    }
+
+    // Advances the simulation by one fixed tick
+    pub fn step(&mut self, input: &InputFrame) {
+        self.update(FIXED_DT, input);
+    }
+
+    pub fn update(&mut self, dt: f32, input: &InputFrame) {
+        // Keys typed into the login prompt, mailbox, chat, command line or
+        // quick chat table aren't game input
+        if !self.headless {
+            let entering_code = self.auth.is_typing();
+            let chatting = self.chat.is_typing();
+            // Chat only opens if nothing else had the keyboard coming into
+            // this frame, Enter may have just closed it
+            let busy = entering_code
+                || self.account.is_typing()
+                || self.mailbox.is_typing()
+                || self.settings_panel.is_open()
+                || self.character_panel.is_open();
+            let editing_settings = self.settings_panel.is_typing();
+            self.auth.update(&mut self.outbox);
+            if !entering_code && !chatting && !editing_settings && !self.account.is_typing() {
+                let key = self.settings.keys.key(Action::OpenMailbox).0;
+                self.mailbox.update(key, &mut self.outbox);
+            }
+            if !entering_code && !chatting && !editing_settings && !self.mailbox.is_typing() {
+                let key = self.settings.keys.key(Action::OpenCommands).0;
+                if let Some(line) = self.account.update(key) {
+                    self.say(&line);
+                }
+            }
+            if !busy || chatting {
+                let key = self.settings.keys.key(Action::OpenChat).0;
+                if let Some(line) = self.chat.update(key, !busy) {
+                    self.say(&line);
+                }
+            }
+            if input.open_chat && !busy && !self.chat.is_typing() {
+                self.chat.open_with(String::new());
+            }
+        }
+        let typing = self.auth.is_typing()
+            || self.mailbox.is_typing()
+            || self.account.is_typing()
+            || self.chat.is_typing();
+        let editing_character = self.character_panel.is_open();
+        if !self.headless
+            && !typing
+            && !editing_character
+            && self.settings_panel.update(&mut self.settings)
+        {
+            self.apply_window_settings();
+            self.save_settings();
+        }
+        if !self.headless && !typing && !self.settings_panel.is_open() {
+            let key = self.settings.keys.key(Action::OpenCharacter).0;
+            let current = self.local_cosmetics();
+            if let Some(preview) = self.character_panel.update(key, current, &mut self.outbox) {
+                apply_cosmetics_to_character(&mut self.world, self.local_player, &preview);
+            }
+        }
+        // Something may have taken the keyboard since the frame was polled,
+        // like Enter opening the chat
+        let idle = InputFrame::default();
+        let input = if self.focus() == Focus::Ui {
+            &idle
+        } else {
+            input
+        };
+        // A right click on another player opens their menu rather than
+        // walking there
+        let without_clicks;
+        let input = if self.update_selection(input) {
+            without_clicks = InputFrame {
+                move_target: None,
+                select: false,
+                context: false,
+                ..input.clone()
+            };
+            &without_clicks
+        } else {
+            input
+        };
+        self.handle_input(input, dt);
+        let list_held = input.show_player_list && !self.headless;
+        self.player_list.update(list_held, &mut self.outbox);
+        systems::movement_system(&mut self.world, dt);
+        if !self.spectating {
+            let bounds = self.world_bounds();
+            if let Ok((position, velocity, appearance, local)) =
+                self.world
+                    .query_one_mut::<(&mut Position, &mut Velocity, &Appearance, &mut LocalPlayer)>(
+                        self.local_player,
+                    )
+            {
+                self.obstacles
+                    .resolve_collision(position, velocity, appearance, local, &bounds);
+                self.route.follow(vec2(position.x, position.y), velocity);
+            }
+        }
+        self.spatial.rebuild(&self.world);
+        for event in self.camera_events.drain(..) {
+            self.camera_effects.trigger(event);
+        }
+        // Purely visual systems need a window
+        if !self.headless {
+            let focus = {
+                let position = self.local::<Position>();
+                Vec2::new(position.x, position.y)
+            };
+            self.camera.update(focus, self.world_bounds(), dt);
+            self.camera_effects.update(dt);
+            self.foliage.update(dt, &self.spatial);
+            self.wildlife.update(dt, &self.spatial, &self.camera.view());
+            self.seasonal.update(dt);
+            self.weather.update(&mut self.world, dt);
+            systems::animation_system(&mut self.world, dt);
+            let listener = {
+                let position = self.local::<Position>();
+                vec2(position.x, position.y)
+            };
+            // A replay's local player is hidden, everyone's bubbles show
+            let radius = if self.spectating {
+                f32::INFINITY
+            } else {
+                self.config.bubble_radius
+            };
+            let lifetime = self.config.message_lifetime();
+            systems::chat_fade_system(&mut self.world, listener, radius, lifetime);
+            self.effects.update(&self.world, dt);
+        }
+        // Notices are queued headless too, so they have to expire
+        self.floating_text.update(dt);
+        let expired = systems::chat_expiry_system(&mut self.world, self.config.message_lifetime());
+        if !self.headless {
+            for bubble in expired {
+                self.effects.pop(bubble);
+            }
+        }
+        for minigame in &mut self.minigames {
+            minigame.update(dt, &self.world, self.local_player, &mut self.outbox);
+        }
+        self.plugins.update(dt, &self.world, self.local_player);
+        self.latency.applied();
+    }
+
+    // Something is taking text, so keys aren't game input
+    pub fn is_typing(&self) -> bool {
+        self.auth.is_typing()
+            || self.mailbox.is_typing()
+            || self.account.is_typing()
+            || self.chat.is_typing()
+            || self.settings_panel.is_typing()
+    }
+
+    // Whether keys go to a text field or panel or to the player. Open
+    // panels take arrows and Enter for themselves.
+    pub fn focus(&self) -> Focus {
+        if self.is_typing() || self.settings_panel.is_open() || self.character_panel.is_open() {
+            Focus::Ui
+        } else {
+            Focus::Gameplay
+        }
+    }
+
+    // Picks out the player under the pointer, and selects them on a click.
+    // Clicking on nobody clears the selection, right clicking on somebody
+    // or clicking their name in the chat log opens their menu. True if the
+    // menu took this frame's clicks.
+    fn update_selection(&mut self, input: &InputFrame) -> bool {
+        if self
+            .selected_player
+            .is_some_and(|id| !self.remote_players.contains_key(&id))
+        {
+            self.selected_player = None;
+        }
+        if !self.headless && self.player_menu.is_open() {
+            if let Some((id, action)) = self.player_menu.update() {
+                self.run_player_action(id, action);
+            }
+            return true;
+        }
+        // Read straight from the mouse, the log is clickable while typing
+        let clicked_name = (!self.headless
+            && !self.settings_panel.is_open()
+            && !self.character_panel.is_open()
+            && is_mouse_button_pressed(MouseButton::Left))
+        .then(|| self.chat_log.name_at(ui::mouse()))
+        .flatten()
+        .filter(|&id| id != self.local_id());
+        if let Some(id) = clicked_name {
+            let name = self.name_of(id);
+            let actions = self.player_actions(id);
+            self.player_menu.open(id, name, actions);
+            return true;
+        }
+        self.hovered_player = input.pointer.and_then(|(x, y)| self.player_at(vec2(x, y)));
+        if input.select {
+            self.selected_player = self.hovered_player;
+        }
+        match self
+            .hovered_player
+            .filter(|_| input.context && !self.headless)
+        {
+            Some(id) => {
+                let name = self.name_of(id);
+                let actions = self.player_actions(id);
+                self.player_menu.open(id, name, actions);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // What the menu offers for `id`
+    fn player_actions(&self, id: usize) -> Vec<PlayerAction> {
+        let mut actions = vec![PlayerAction::Whisper];
+        actions.push(if self.is_muted(id) {
+            PlayerAction::Unmute
+        } else {
+            PlayerAction::Mute
+        });
+        if !self.party.contains(id) {
+            actions.push(PlayerAction::Invite);
+        }
+        actions.push(if self.settings.is_friend(&self.name_of(id)) {
+            PlayerAction::RemoveFriend
+        } else {
+            PlayerAction::AddFriend
+        });
+        actions.push(PlayerAction::Locate);
+        actions
+    }
+
+    fn run_player_action(&mut self, id: usize, action: PlayerAction) {
+        match action {
+            PlayerAction::Mute | PlayerAction::Unmute => {
+                let muted = action == PlayerAction::Mute;
+                self.set_muted(id, muted);
+                let verb = if muted { "Muted" } else { "Unmuted" };
+                let notice = format!("{} {}", verb, self.name_of(id));
+                self.chat_log.push_system(&notice);
+            }
+            // The server answers with a notice
+            PlayerAction::Invite => self.outbox.push(ClientMessage::PartyInvite { id }),
+            PlayerAction::Whisper => {
+                let text = format!("/w {} ", self.name_of(id));
+                self.chat.open_with(text);
+            }
+            PlayerAction::AddFriend | PlayerAction::RemoveFriend => {
+                let friend = action == PlayerAction::AddFriend;
+                let name = self.name_of(id);
+                self.settings.set_friend(&name, friend);
+                self.save_settings();
+                let notice = if friend {
+                    format!("Added {} as a friend", name)
+                } else {
+                    format!("Removed {} from your friends", name)
+                };
+                self.chat_log.push_system(&notice);
+            }
+            PlayerAction::Locate => self.minimap.locate(id),
+        }
+    }
+
+    // Whether chat from `id` is hidden, by network id or display name
+    pub fn is_muted(&self, id: usize) -> bool {
+        self.muted_players.contains(&id)
+            || self
+                .names
+                .get(&id)
+                .is_some_and(|name| self.settings.is_muted(name))
+    }
+
+    // Hides or shows chat from `id` from now on. Muting also clears what they
+    // already said from the log and their bubble.
+    pub fn set_muted(&mut self, id: usize, muted: bool) {
+        if muted {
+            self.muted_players.insert(id);
+            self.chat_log.remove_from(id);
+            if let Some(&entity) = self.remote_players.get(&id) {
+                if let Ok(bubble) = self.world.query_one_mut::<&mut ChatBubble>(entity) {
+                    bubble.message = None;
+                    bubble.expression = None;
+                    bubble.stacked.clear();
+                }
+            }
+        } else {
+            self.muted_players.remove(&id);
+        }
+        if let Some(name) = self.names.get(&id) {
+            self.settings.set_muted(name, muted);
+            self.save_settings();
+        }
+    }
+
+    // The other player going by `name`, ignoring case
+    pub fn player_named(&self, name: &str) -> Option<usize> {
+        let local_id = self.local_id();
+        self.names
+            .iter()
+            .find(|&(&id, other)| id != local_id && other.eq_ignore_ascii_case(name))
+            .map(|(&id, _)| id)
+    }
+
+    // The other player drawn at `point` in the world, the one in front when
+    // they overlap
+    pub fn player_at(&self, point: Vec2) -> Option<usize> {
+        let mut query = self
+            .world
+            .query::<(&Position, &NetworkId)>()
+            .without::<&Hidden>()
+            .without::<&LocalPlayer>();
+        query
+            .iter()
+            .filter(|(position, _)| render::character_bounds(position).contains(point))
+            .max_by(|(a, _), (b, _)| a.y.total_cmp(&b.y))
+            .map(|(_, id)| id.0)
+    }
+
+    fn handle_input(&mut self, input: &InputFrame, dt: f32) {
+        if input.toggle_debug_overlay {
+            self.show_debug_overlay = !self.show_debug_overlay;
+        }
+
+        // Movement latency, then frame time, then neither
+        if input.cycle_timing_overlay {
+            if self.latency.enabled {
+                self.latency.toggle();
+                self.profiler.toggle();
+            } else if self.profiler.enabled {
+                self.profiler.toggle();
+            } else {
+                self.latency.toggle();
+            }
+        }
+
+        if input.toggle_recording && !self.spectating {
+            self.toggle_recording();
+        }
+
+        if input.toggle_snapshot_diff {
+            self.show_snapshot_diff = !self.show_snapshot_diff;
+        }
+
+        if input.cycle_graphics_quality {
+            self.set_graphics_quality(self.graphics_quality.next());
+        }
+
+        if input.toggle_fullscreen && !self.headless {
+            self.toggle_fullscreen();
+        }
+
+        if input.toggle_help {
+            self.show_help = !self.show_help;
+        }
+
+        if input.toggle_minimap && !self.headless {
+            self.settings.show_minimap = !self.settings.show_minimap;
+            self.save_settings();
+        }
+
+        if input.reset_animation {
+            self.local_mut::<Animation>().reset();
+        }
+
+        if let Some(message) = &input.say {
+            self.say(message);
+        }
+
+        if let Some(emote) = input.emote {
+            self.play_emote(emote);
+        }
+
+        let bounds = self.world_bounds();
+        let Ok((position, velocity, appearance, animation, local)) =
+            self.world.query_one_mut::<(
+                &mut Position,
+                &mut Velocity,
+                &Appearance,
+                &mut Animation,
+                &mut LocalPlayer,
+            )>(self.local_player)
+        else {
+            return;
+        };
+
+        local.sneaking = input.sneak;
+        local.walking = input.walk;
+        local.sprinting = input.sprint;
+        let speed = velocity.speed * local.speed_scale();
+
+        // Determine if the player is moving via WASD
+        let mut is_moving = false;
+        if input.direction != Vec2::ZERO {
+            let direction = input.direction.normalize();
+            position.x += direction.x * speed * dt;
+            position.y += direction.y * speed * dt;
+            local.position_changed = true;
+            is_moving = true;
+
+            // Clamp to the map
+            position.clamp_to(appearance, &bounds);
+        }
+
+        if let Some((x, y)) = input.move_target {
+            let mut target = Position { x, y };
+            target.clamp_to(appearance, &bounds);
+            // Around obstacles when there's a way, straight there when there isn't
+            let walkable = Rect::new(
+                bounds.x,
+                bounds.y,
+                bounds.w - appearance.width,
+                bounds.h - appearance.height,
+            );
+            let start = vec2(position.x, position.y);
+            let goal = vec2(target.x, target.y);
+            // Holding the button asks for about the same place every frame,
+            // it's only searched again once the goal moves to another square
+            // or behind an obstacle
+            let obstacles = self.obstacles.blocking();
+            let retargeted = self.route.ends_near(goal, walkable)
+                && self.route.retarget(start, goal, &obstacles, velocity);
+            if !retargeted {
+                let waypoints = pathfinding::find_path(start, goal, walkable, &obstacles)
+                    .unwrap_or_else(|| vec![goal]);
+                self.route.set(waypoints, velocity);
+            }
+            is_moving = true;
+        }
+
+        // Determine if the player is moving based on input or target position
+        velocity.is_moving = is_moving || velocity.target.is_some();
+
+        if input.jump && animation.jump_elapsed.is_none() && animation.emote != Some(Emote::Sit) {
+            animation.jump();
+        }
+        // Others see the change with the next position update
+        let stance = if animation.jump_elapsed.is_some() {
+            Stance::Jumping
+        } else if animation.emote == Some(Emote::Sit) && !velocity.is_moving {
+            Stance::Sitting
+        } else if local.sneaking {
+            Stance::Sneaking
+        } else if local.walking {
+            Stance::Walking
+        } else if local.sprinting {
+            Stance::Sprinting
+        } else {
+            Stance::Standing
+        };
+        if stance != animation.stance {
+            animation.stance = stance;
+            local.position_changed = true;
+        }
+
+        // Straight up or down keeps the way it was facing
+        let heading = if input.direction.x != 0.0 {
+            input.direction.x
+        } else {
+            velocity.target.map_or(0.0, |(x, _)| x - position.x)
+        };
+        let facing = if heading < -FACING_DEADBAND {
+            Facing::Left
+        } else if heading > FACING_DEADBAND {
+            Facing::Right
+        } else {
+            animation.facing
+        };
+        if facing != animation.facing {
+            animation.facing = facing;
+            local.position_changed = true;
+        }
+    }
+
+    pub fn draw(&mut self, assets: &mut Assets) {
+        // Everything up to the UI is in world coordinates, into a texture
+        // when a filter is picked
+        let mut camera = self.camera_effects.apply(self.camera.camera2d());
+        let effect = self.settings.post_effect;
+        let filtered = self.post_process.begin(effect, &mut camera);
+        set_camera(&camera);
+        if filtered {
+            clear_background(self.weather.tint(self.seasonal.theme.background));
+        }
+        if let Some(tilemap) = &self.tilemap {
+            tilemap.draw(assets, &self.camera.view());
+        }
+        // Minigame props (checkpoints, ghosts) sit underneath everyone
+        for minigame in &self.minigames {
+            minigame.draw_world();
+        }
+        self.obstacles.draw();
+        if self.settings.show_path && !self.spectating {
+            let position = self.local::<Position>();
+            self.route.draw(vec2(position.x, position.y));
+        }
+        self.foliage.draw(self.seasonal.theme.grass);
+
+        render_system(
+            &self.world,
+            &self.effects,
+            &self.seasonal,
+            &self.camera.view(),
+            &mut self.draw_list,
+            assets,
+        );
+        self.draw_selection();
+        self.draw_name_tags();
+        self.floating_text.draw(&mut self.draw_list);
+        self.draw_recording_indicators();
+        // Critters fly over everyone
+        self.wildlife.draw(&mut self.draw_list, &self.camera.view());
+        self.seasonal.draw_overlay(&mut self.draw_list);
+        self.draw_list.flush();
+        self.lighting
+            .draw(&self.camera.view(), &self.world, self.settings.night_glow);
+        let snapshot_diff = self
+            .show_snapshot_diff
+            .then(|| desync::draw_snapshot_diff(&self.world, &self.obstacles));
+        if filtered {
+            self.post_process.finish(effect);
+        }
+        ui::set_ui_camera();
+        self.weather.draw();
+        self.camera_effects.draw_flash();
+
+        if self.recorder.is_some() {
+            draw_circle(ui::width() - 20.0, 20.0, 8.0, palette::current().alert);
+        }
+
+        for minigame in &self.minigames {
+            minigame.draw_ui();
+        }
+        if self.settings.show_minimap || self.minimap.is_locating() {
+            self.minimap.draw(
+                &self.world,
+                self.world_bounds(),
+                self.camera.view(),
+                self.settings.minimap_size,
+                &self.party.members,
+            );
+        }
+        self.plugins.draw_overlay(&self.world, self.local_player);
+        self.mailbox.draw();
+        let (local_id, names, player_name) = (self.local_id(), &self.names, &self.player_name);
+        self.chat_log.draw(self.chat.is_typing(), |id| {
+            name_of(names, id, local_id, player_name)
+        });
+        self.chat.draw();
+        self.party
+            .draw(local_id, |id| name_of(names, id, local_id, player_name));
+        self.player_list.draw(local_id);
+        self.player_menu.draw();
+        self.announcement.draw();
+        self.settings_panel.draw(&self.settings);
+        self.character_panel.draw();
+        self.account.draw();
+        self.auth.draw();
+        if self.show_help {
+            help::draw(&self.settings);
+        }
+
+        if self.show_debug_overlay {
+            self.draw_debug_overlay();
+        }
+        if let Some(summary) = snapshot_diff {
+            desync::draw_summary(&summary);
+        }
+        self.latency.draw();
+        self.profiler.draw();
+    }
+
+    // Names over everyone we know the name of, always over ourselves
+    fn draw_name_tags(&mut self) {
+        let mut query = self
+            .world
+            .query::<(Entity, &Position, &Animation, &NetworkId, &ChatBubble)>()
+            .without::<&Hidden>();
+        for (entity, position, animation, id, bubble) in query.iter() {
+            let local = entity == self.local_player;
+            let name = match self.names.get(&id.0) {
+                Some(name) => name,
+                None if local => &self.player_name,
+                None => continue,
+            };
+            let bubbles = bubble.visible();
+            draw_name_tag(
+                &mut self.draw_list,
+                position,
+                animation,
+                name,
+                local,
+                bubbles,
+            );
+        }
+    }
+
+    // The name shown for a player, e.g. in the chat log
+    pub fn name_of(&self, id: usize) -> String {
+        name_of(&self.names, id, self.local_id(), &self.player_name)
+    }
+
+    // Outlines the player under the mouse, and the one selected more boldly
+    fn draw_selection(&mut self) {
+        let palette = palette::current();
+        let outlines = [
+            (self.hovered_player, palette.hovered, 1.0),
+            (self.selected_player, palette.selected, 2.0),
+        ];
+        for (id, color, thickness) in outlines {
+            let Some(&entity) = id.and_then(|id| self.remote_players.get(&id)) else {
+                continue;
+            };
+            if let Ok(position) = self.world.get::<&Position>(entity) {
+                render::draw_outline(&mut self.draw_list, &position, color, thickness);
+            }
+        }
+    }
+
+    // A red dot beside players near us who are recording or streaming
+    fn draw_recording_indicators(&mut self) {
+        let local = *self.local::<Position>();
+        for id in &self.recording_players {
+            let Some(&entity) = self.remote_players.get(id) else {
+                continue;
+            };
+            let Ok(position) = self.world.get::<&Position>(entity) else {
+                continue;
+            };
+            let distance = (position.x - local.x).hypot(position.y - local.y);
+            if distance > RECORDING_INDICATOR_RADIUS {
+                continue;
+            }
+            // Clear of the name tag over their head
+            let (x, y) = (position.x + 28.0, position.y - 8.0);
+            let color = palette::current().alert;
+            self.draw_list.circle(Layer::Bubbles, x, y, 5.0, color);
+            self.draw_list
+                .text(Layer::Bubbles, "REC", x + 8.0, y + 5.0, 16.0, color);
+        }
+    }
+
+    fn draw_debug_overlay(&self) {
+        let mut y = 20.0;
+        ui::draw_text(
+            &format!(
+                "id: {}  FPS: {}  time: {}",
+                self.local_id(),
+                get_fps(),
+                self.lighting.clock()
+            ),
+            10.0,
+            y,
+            20.0,
+            BLACK,
+        );
+        y += 20.0;
+        let stats = self.draw_list.stats;
+        ui::draw_text(
+            &format!(
+                "batched: {} primitives in {} draw calls",
+                stats.primitives, stats.batches
+            ),
+            10.0,
+            y,
+            20.0,
+            BLACK,
+        );
+
+        let mut query = self.world.query::<(&NetworkId, &SnapshotBuffer)>();
+        let mut remotes: Vec<_> = query.iter().collect();
+        remotes.sort_by_key(|(id, _)| id.0);
+
+        for (id, buffer) in remotes {
+            y += 20.0;
+            let latency = match buffer.latency_ms {
+                Some(ms) => format!("{} ms", ms),
+                None => "-".to_string(),
+            };
+            let tick = buffer.snapshots.back().map_or(0, |s| s.tick);
+            // Mark where the latest snapshot placed this player
+            if let Some(snapshot) = buffer.snapshots.back() {
+                draw_circle_lines(snapshot.x, snapshot.y, 22.0, 1.0, GRAY);
+            }
+            let status = if buffer.is_stale() { " (stale)" } else { "" };
+            ui::draw_text(
+                &format!(
+                    "player {}: tick {} latency {}{}",
+                    id.0, tick, latency, status
+                ),
+                10.0,
+                y,
+                20.0,
+                if buffer.is_stale() { RED } else { BLACK },
+            );
+        }
+    }
+
+    // Builds the next position update for the local player
+    pub fn position_message(&mut self) -> ClientMessage {
+        self.position_tick += 1;
+        let position = *self.local::<Position>();
+        ClientMessage::PlayerPosition {
+            id: self.local_id(),
+            x: position.x,
+            y: position.y,
+            tick: self.position_tick,
+            timestamp_ms: now_millis(),
+            stance: self.local::<Animation>().stance,
+            facing: self.local::<Animation>().facing,
+        }
+    }
+}
+
+fn apply_config_to_character(world: &mut World, entity: Entity, config: &Config) {
+    if let Ok((velocity, animation)) =
+        world.query_one_mut::<(&mut Velocity, &mut Animation)>(entity)
+    {
+        velocity.speed = config.player_speed;
+        animation.pose_update_interval = config.pose_interval();
+    }
+}
+
+fn apply_cosmetics_to_character(world: &mut World, entity: Entity, cosmetics: &Cosmetics) {
+    if let Ok(appearance) = world.query_one_mut::<&mut Appearance>(entity) {
+        apply_cosmetics(appearance, cosmetics);
+    }
+}
+
+// `/happy hi` is "hi" said with `Expression::Happy`, likewise for the other
+// expressions by name
+fn split_expression(message: &str) -> Option<(Expression, &str)> {
+    let rest = message.trim().strip_prefix('/')?;
+    Expression::ALL.into_iter().find_map(|expression| {
+        let text = rest
+            .strip_prefix(expression.name())?
+            .strip_prefix(' ')?
+            .trim();
+        (!text.is_empty()).then_some((expression, text))
+    })
+}
+
+// A player's display name, the local player's login name until the server
+// sends one
+fn name_of(names: &HashMap<usize, String>, id: usize, local_id: usize, login: &str) -> String {
+    match names.get(&id) {
+        Some(name) => name.clone(),
+        None if id == local_id => login.to_string(),
+        None => format!("Player {}", id),
+    }
+}
@@ -0,0 +1,141 @@
+use crate::batch::DrawList;
+use crate::components::{Animation, Appearance, Position};
+use crate::course::{Checkpoint, LapEvent, LapTimer};
+use crate::player;
+use crate::protocol::{ClientMessage, Hair};
+use crate::render::{draw_character, draw_shadow};
+use crate::replay::Replay;
+use crate::systems::animate;
+use macroquad::prelude::*;
+use std::time::Instant;
+
+const GHOST_ALPHA: f32 = 0.35;
+
+// A previous run of the local player, played back alongside the live world
+pub struct Ghost {
+    path: Vec<(u64, f32, f32)>, // (ms since lap start, x, y)
+    position: Position,
+    appearance: Appearance,
+    animation: Animation,
+    is_moving: bool,
+    started: Instant,
+    pub lap_time_ms: Option<u64>,
+}
+
+impl Ghost {
+    // Extracts the local player's path from a replay. If the path contains a
+    // full lap of `course` only that lap is kept, so the ghost starts at the line.
+    pub fn from_replay(replay: &Replay, course: &[Checkpoint]) -> Option<Ghost> {
+        let path: Vec<(u64, f32, f32)> = replay
+            .events
+            .iter()
+            .filter_map(|event| match event.message {
+                ClientMessage::PlayerPosition { id, x, y, .. }
+                    if id == replay.header.local_player_id =>
+                {
+                    Some((event.time_ms, x, y))
+                }
+                _ => None,
+            })
+            .collect();
+        let &(_, start_x, start_y) = path.first()?;
+
+        let mut timer = LapTimer::new();
+        let mut lap_start = None;
+        let mut lap_end = None;
+        for (i, &(time_ms, x, y)) in path.iter().enumerate() {
+            match timer.update(course, time_ms, x, y) {
+                Some(LapEvent::Started) => lap_start = Some(i),
+                Some(LapEvent::Finished { .. }) => {
+                    lap_end = Some(i);
+                    break;
+                }
+                Some(LapEvent::Checkpoint) | None => {}
+            }
+        }
+
+        let (path, lap_time_ms) = match (lap_start, lap_end) {
+            (Some(start), Some(end)) => {
+                let start_ms = path[start].0;
+                let lap: Vec<_> = path[start..=end]
+                    .iter()
+                    .map(|&(time_ms, x, y)| (time_ms - start_ms, x, y))
+                    .collect();
+                (lap, Some(path[end].0 - start_ms))
+            }
+            _ => {
+                let start_ms = path[0].0;
+                let run = path
+                    .iter()
+                    .map(|&(time_ms, x, y)| (time_ms - start_ms, x, y))
+                    .collect();
+                (run, None)
+            }
+        };
+
+        Some(Ghost {
+            path,
+            position: Position {
+                x: start_x,
+                y: start_y,
+            },
+            appearance: player::appearance(
+                BLACK,
+                replay.header.local_player_id as u64,
+                &Hair::default(),
+            ),
+            animation: Animation::default(),
+            is_moving: false,
+            started: Instant::now(),
+            lap_time_ms,
+        })
+    }
+
+    pub fn restart(&mut self) {
+        self.started = Instant::now();
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+
+        // Interpolate between the two path points around `elapsed`
+        let next = self
+            .path
+            .iter()
+            .position(|&(time_ms, _, _)| time_ms > elapsed)
+            .unwrap_or(self.path.len() - 1);
+        let (x, y) = if next == 0 {
+            (self.path[0].1, self.path[0].2)
+        } else {
+            let (t0, x0, y0) = self.path[next - 1];
+            let (t1, x1, y1) = self.path[next];
+            let t = if t1 > t0 {
+                ((elapsed - t0) as f32 / (t1 - t0) as f32).min(1.0)
+            } else {
+                1.0
+            };
+            (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t)
+        };
+
+        // Drive the walk cycle the same way remote players do
+        self.is_moving = (x, y) != (self.position.x, self.position.y);
+        self.position = Position { x, y };
+        animate(&mut self.animation, self.is_moving, dt);
+    }
+
+    // Drawn with the race props under everyone, so on its own list
+    pub fn draw(&self) {
+        let mut list = DrawList::new();
+        draw_shadow(&mut list, &self.position, &self.animation, GHOST_ALPHA);
+        draw_character(
+            &mut list,
+            &self.position,
+            &self.appearance,
+            &self.animation,
+            self.is_moving,
+            None,
+            GHOST_ALPHA,
+        );
+        list.flush();
+    }
+}
@@ -0,0 +1,47 @@
+use crate::error;
+use crate::game::{Game, FIXED_DT};
+use crate::input::InputSource;
+use crate::net::{sync_network, Connection};
+use crate::shutdown;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+// Simulation rate without a window to pace frames
+const TICK: Duration = Duration::from_millis(1000 / 60);
+
+// Runs `game` without drawing anything until `duration` has passed or Ctrl+C
+// is pressed.
+// The simulation always advances by `FIXED_DT`, so a slow machine runs slower
+// rather than differently.
+pub fn run(
+    game: &Mutex<Game>,
+    network: &Option<Connection>,
+    input: &mut dyn InputSource,
+    duration: Option<Duration>,
+) {
+    let started = Instant::now();
+    run_while(game, network, input, || {
+        !shutdown::quit_requested() && duration.is_none_or(|duration| started.elapsed() < duration)
+    });
+}
+
+// Like `run`, checking `keep_going` before every tick
+pub fn run_while(
+    game: &Mutex<Game>,
+    network: &Option<Connection>,
+    input: &mut dyn InputSource,
+    keep_going: impl Fn() -> bool,
+) {
+    while keep_going() {
+        let frame_start = Instant::now();
+
+        let frame = input.poll(FIXED_DT);
+        error::lock(game).step(&frame);
+        sync_network(game, network);
+
+        if let Some(remaining) = TICK.checked_sub(frame_start.elapsed()) {
+            sleep(remaining);
+        }
+    }
+}
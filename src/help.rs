@@ -0,0 +1,62 @@
+use crate::settings::{Action, Settings};
+use crate::ui;
+use macroquad::prelude::*;
+
+const COLUMN_WIDTH: f32 = 320.0;
+const ROW_HEIGHT: f32 = 20.0;
+// Rows in a column before the next one starts
+const COLUMN_ROWS: usize = 22;
+// Characters of a quick chat phrase that fit beside its key
+const PHRASE_SHOWN: usize = 16;
+
+// Every control as it's bound right now, shown with the `help` key. Built
+// from the settings each time, so rebinding a key or editing quick chat
+// shows here straight away.
+pub fn draw(settings: &Settings) {
+    let mut rows: Vec<(String, String)> = Action::ALL
+        .iter()
+        .map(|&action| {
+            let key = settings.keys.key(action).name().to_string();
+            (key, action.label().to_string())
+        })
+        .collect();
+    for quick in &settings.quick_chat {
+        if let Some(key) = quick.key {
+            let phrase = if quick.phrase.chars().count() > PHRASE_SHOWN {
+                let start: String = quick.phrase.chars().take(PHRASE_SHOWN).collect();
+                format!("{}...", start)
+            } else {
+                quick.phrase.clone()
+            };
+            rows.push((key.name().to_string(), format!("Say \"{}\"", phrase)));
+        }
+    }
+    let walk = if settings.hold_to_move {
+        "Walk to the mouse (held)"
+    } else {
+        "Walk there"
+    };
+    rows.push(("Right click".to_string(), walk.to_string()));
+    rows.push(("Right click".to_string(), "A player's menu".to_string()));
+    rows.push(("Left click".to_string(), "Select a player".to_string()));
+
+    let columns = rows.len().div_ceil(COLUMN_ROWS);
+    let width = columns as f32 * COLUMN_WIDTH + 20.0;
+    let height = 60.0 + rows.len().min(COLUMN_ROWS) as f32 * ROW_HEIGHT;
+    let x = ((ui::width() - width) / 2.0).max(0.0);
+    let y = ((ui::height() - height) / 2.0).max(0.0);
+    draw_rectangle(x, y, width, height, Color::new(1.0, 1.0, 1.0, 0.95));
+    draw_rectangle_lines(x, y, width, height, 2.0, DARKGRAY);
+    let title = format!(
+        "Controls ({} to close)",
+        settings.keys.key(Action::Help).name()
+    );
+    ui::draw_text(&title, x + 12.0, y + 26.0, 24.0, BLACK);
+
+    for (index, (key, what)) in rows.iter().enumerate() {
+        let column_x = x + 12.0 + (index / COLUMN_ROWS) as f32 * COLUMN_WIDTH;
+        let row_y = y + 52.0 + (index % COLUMN_ROWS) as f32 * ROW_HEIGHT;
+        ui::draw_text(key, column_x, row_y, 18.0, DARKGRAY);
+        ui::draw_text(what, column_x + 120.0, row_y, 18.0, BLACK);
+    }
+}
@@ -0,0 +1,272 @@
+use crate::protocol::Emote;
+use crate::settings::{Action, KeyBindings, QuickChat};
+use crate::touch::TouchControls;
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use macroquad::prelude::*;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Everything the player asked for during one frame
+#[derive(Clone, Debug, Default)]
+pub struct InputFrame {
+    pub direction: Vec2, // WASD-style movement, not normalized
+    pub move_target: Option<(f32, f32)>,
+    pub pointer: Option<(f32, f32)>, // Where the mouse is, if there is one
+    pub select: bool,                // Clicked on whatever is under the pointer
+    pub context: bool,               // Right clicked, for a menu about whatever is under it
+    pub say: Option<String>,
+    pub toggle_debug_overlay: bool,
+    pub cycle_timing_overlay: bool, // Latency, then frame time, then off
+    pub toggle_recording: bool,
+    pub cycle_graphics_quality: bool,
+    pub toggle_snapshot_diff: bool,
+    pub reset_animation: bool,
+    pub toggle_fullscreen: bool,
+    pub toggle_minimap: bool,
+    pub toggle_help: bool,
+    pub emote: Option<Emote>,
+    pub sneak: bool,  // Held down this frame
+    pub walk: bool,   // Held down this frame
+    pub sprint: bool, // Held down this frame
+    pub jump: bool,
+    pub show_player_list: bool, // Held down this frame
+    pub open_chat: bool,        // From a touch button, the chat key opens it itself
+}
+
+// Where keys go this frame. A text field or panel with the keyboard gets
+// every key itself and gameplay sees none, so typing a `w` into the chat
+// doesn't walk and a space doesn't fire quick chat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Focus {
+    #[default]
+    Gameplay,
+    Ui,
+}
+
+pub trait InputSource {
+    fn poll(&mut self, dt: f32) -> InputFrame;
+}
+
+// Reads the keyboard and mouse through macroquad
+pub struct KeyboardInput {
+    pub keys: KeyBindings,
+    pub quick_chat: Vec<QuickChat>, // Kept in step with the settings, see `Settings::quick_chat`
+    pub touch: Option<TouchControls>, // Takes over from the mouse, see `Settings::touch_controls`
+    pub hold_to_move: bool,         // See `Settings::hold_to_move`
+    pub focus: Focus,               // Set from `Game::focus` before each poll
+}
+
+impl KeyboardInput {
+    pub fn new(keys: KeyBindings, quick_chat: Vec<QuickChat>) -> Self {
+        Self {
+            keys,
+            quick_chat,
+            touch: None,
+            hold_to_move: false,
+            focus: Focus::Gameplay,
+        }
+    }
+}
+
+impl InputSource for KeyboardInput {
+    fn poll(&mut self, _dt: f32) -> InputFrame {
+        // The pointer still shows what's under it
+        if self.focus == Focus::Ui {
+            return InputFrame {
+                pointer: Some(mouse_position()),
+                ..Default::default()
+            };
+        }
+        let keys = &self.keys;
+        let mut frame = InputFrame {
+            toggle_debug_overlay: keys.pressed(Action::DebugOverlay),
+            cycle_timing_overlay: keys.pressed(Action::TimingOverlay),
+            toggle_recording: keys.pressed(Action::ToggleRecording),
+            cycle_graphics_quality: keys.pressed(Action::GraphicsQuality),
+            toggle_snapshot_diff: keys.pressed(Action::SnapshotDiff),
+            reset_animation: keys.pressed(Action::ResetAnimation),
+            toggle_fullscreen: keys.pressed(Action::ToggleFullscreen),
+            toggle_minimap: keys.pressed(Action::ToggleMinimap),
+            toggle_help: keys.pressed(Action::Help),
+            sneak: keys.down(Action::Sneak),
+            walk: keys.down(Action::Walk),
+            sprint: keys.down(Action::Sprint),
+            jump: keys.pressed(Action::Jump),
+            show_player_list: keys.down(Action::PlayerList),
+            ..Default::default()
+        };
+
+        if keys.down(Action::Up) {
+            frame.direction.y -= 1.0;
+        }
+        if keys.down(Action::Down) {
+            frame.direction.y += 1.0;
+        }
+        if keys.down(Action::Left) {
+            frame.direction.x -= 1.0;
+        }
+        if keys.down(Action::Right) {
+            frame.direction.x += 1.0;
+        }
+
+        frame.say = self
+            .quick_chat
+            .iter()
+            .find(|quick| quick.key.is_some_and(|key| is_key_pressed(key.0)))
+            .map(|quick| quick.phrase.clone());
+
+        if keys.pressed(Action::EmoteWave) {
+            frame.emote = Some(Emote::Wave);
+        }
+        if keys.pressed(Action::EmoteDance) {
+            frame.emote = Some(Emote::Dance);
+        }
+        if keys.pressed(Action::EmoteSit) {
+            frame.emote = Some(Emote::Sit);
+        }
+
+        if let Some(touch) = &mut self.touch {
+            touch.poll(&mut frame, &self.quick_chat);
+            return frame;
+        }
+        // Held, the target follows the mouse every frame
+        let moving = if self.hold_to_move {
+            is_mouse_button_down(MouseButton::Right)
+        } else {
+            is_mouse_button_pressed(MouseButton::Right)
+        };
+        if moving {
+            frame.move_target = Some(mouse_position());
+        }
+        frame.pointer = Some(mouse_position());
+        frame.select = is_mouse_button_pressed(MouseButton::Left);
+        frame.context = is_mouse_button_pressed(MouseButton::Right);
+
+        frame
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ScriptCommand {
+    MoveTo(f32, f32),
+    Wait(f32),
+    Say(String),
+}
+
+// Plays back a list of commands, looping forever. Without a script it
+// wanders to a random point every few seconds.
+//
+// Script format, one command per line, `#` starts a comment:
+//   move <x> <y>   walk towards a point
+//   wait <secs>    do nothing for a while
+//   say <text>     send a chat message
+pub struct ScriptedInput {
+    commands: Vec<ScriptCommand>,
+    next_command: usize,
+    waiting: f32,
+    rng: StdRng, // Picks wander targets
+}
+
+fn parse_command(line: &str) -> Result<ScriptCommand, String> {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let numbers = || -> Result<Vec<f32>, String> {
+        rest.split_whitespace()
+            .map(|n| n.parse().map_err(|_| format!("bad number {:?}", n)))
+            .collect()
+    };
+    match command {
+        "move" => match numbers()?.as_slice() {
+            [x, y] => Ok(ScriptCommand::MoveTo(*x, *y)),
+            _ => Err("move takes <x> <y>".to_string()),
+        },
+        "wait" => match numbers()?.as_slice() {
+            [secs] => Ok(ScriptCommand::Wait(*secs)),
+            _ => Err("wait takes <secs>".to_string()),
+        },
+        "say" if !rest.trim().is_empty() => Ok(ScriptCommand::Say(rest.trim().to_string())),
+        "say" => Err("say takes <text>".to_string()),
+        other => Err(format!("unknown command {:?}", other)),
+    }
+}
+
+impl ScriptedInput {
+    pub fn wander() -> Self {
+        Self::wander_with_seed(::rand::random())
+    }
+
+    // Wanders the same way every time for the same seed
+    pub fn wander_with_seed(seed: u64) -> Self {
+        Self {
+            commands: Vec::new(),
+            next_command: 0,
+            waiting: 0.0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let mut commands = Vec::new();
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let command = parse_command(line).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, e),
+                )
+            })?;
+            commands.push(command);
+        }
+        Ok(Self {
+            commands,
+            next_command: 0,
+            waiting: 0.0,
+            rng: StdRng::seed_from_u64(0),
+        })
+    }
+
+    fn next(&mut self) -> ScriptCommand {
+        if self.commands.is_empty() {
+            let rng = &mut self.rng;
+            if self.next_command.is_multiple_of(2) {
+                self.next_command += 1;
+                return ScriptCommand::MoveTo(
+                    rng.gen_range(0.0..WORLD_WIDTH),
+                    rng.gen_range(0.0..WORLD_HEIGHT),
+                );
+            }
+            self.next_command += 1;
+            return ScriptCommand::Wait(rng.gen_range(2.0..4.0));
+        }
+
+        let command = self.commands[self.next_command].clone();
+        self.next_command = (self.next_command + 1) % self.commands.len();
+        command
+    }
+}
+
+impl InputSource for ScriptedInput {
+    fn poll(&mut self, dt: f32) -> InputFrame {
+        let mut frame = InputFrame::default();
+        self.waiting -= dt;
+
+        // Run commands until one of them makes us wait. A script without
+        // waits still only runs each command once per frame.
+        for _ in 0..self.commands.len().max(2) {
+            if self.waiting > 0.0 {
+                break;
+            }
+            match self.next() {
+                ScriptCommand::MoveTo(x, y) => frame.move_target = Some((x, y)),
+                ScriptCommand::Wait(secs) => self.waiting = secs,
+                ScriptCommand::Say(message) => frame.say = Some(message),
+            }
+        }
+        frame
+    }
+}
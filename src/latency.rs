@@ -0,0 +1,153 @@
+use crate::ui;
+use macroquad::prelude::*;
+use std::time::Instant;
+
+// Weight of the newest sample in the rolling averages
+const SMOOTHING: f32 = 0.1;
+const BAR_SCALE: f32 = 4.0; // Pixels per millisecond
+const PANEL_WIDTH: f32 = 380.0;
+const BAR_X: f32 = 240.0; // Bars start after the labels
+
+// One movement input on its way to the screen and the server
+struct Sample {
+    captured: Instant,
+    applied: Option<Instant>,
+    sent: Option<Instant>,
+}
+
+// Rolling average in milliseconds, None until the first sample
+#[derive(Default, Clone, Copy)]
+struct Average(Option<f32>);
+
+impl Average {
+    fn add(&mut self, ms: f32) {
+        self.0 = Some(match self.0 {
+            Some(average) => average + (ms - average) * SMOOTHING,
+            None => ms,
+        });
+    }
+}
+
+fn ms_between(from: Instant, to: Instant) -> f32 {
+    to.duration_since(from).as_secs_f32() * 1000.0
+}
+
+// Where the local player's own movement spends its time between the key
+// press and the frame that shows it, plus the trip to the server. F4 cycles
+// to the overlay first; nothing is measured while it is off.
+#[derive(Default)]
+pub struct LatencyProbe {
+    pub enabled: bool,
+    sample: Option<Sample>,
+    input_to_apply: Average,
+    apply_to_send: Average,
+    apply_to_present: Average,
+    server_to_client: Average, // One way, from the server's ping timestamps
+    pub last_one_way_ms: Option<u64>, // Kept with the overlay off too, for `/ping`
+}
+
+impl LatencyProbe {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.sample = None;
+    }
+
+    // A frame of input that moves the player was read. Later input waits
+    // until the current sample reaches the screen.
+    pub fn input_captured(&mut self) {
+        if self.enabled && self.sample.is_none() {
+            self.sample = Some(Sample {
+                captured: Instant::now(),
+                applied: None,
+                sent: None,
+            });
+        }
+    }
+
+    // The simulation has applied the captured input
+    pub fn applied(&mut self) {
+        if let Some(sample) = &mut self.sample {
+            if sample.applied.is_none() {
+                let now = Instant::now();
+                self.input_to_apply.add(ms_between(sample.captured, now));
+                sample.applied = Some(now);
+            }
+        }
+    }
+
+    // The moved position was handed to the network
+    pub fn position_sent(&mut self) {
+        if let Some(sample) = &mut self.sample {
+            if let (Some(applied), None) = (sample.applied, sample.sent) {
+                let now = Instant::now();
+                self.apply_to_send.add(ms_between(applied, now));
+                sample.sent = Some(now);
+            }
+        }
+    }
+
+    // The frame showing the applied input was presented, closing the sample
+    pub fn presented(&mut self) {
+        if let Some(applied) = self.sample.as_ref().and_then(|sample| sample.applied) {
+            self.apply_to_present
+                .add(ms_between(applied, Instant::now()));
+            self.sample = None;
+        }
+    }
+
+    pub fn server_latency(&mut self, one_way_ms: u64) {
+        self.last_one_way_ms = Some(one_way_ms);
+        if self.enabled {
+            self.server_to_client.add(one_way_ms as f32);
+        }
+    }
+
+    pub fn draw(&self) {
+        if !self.enabled {
+            return;
+        }
+        let stages = [
+            ("input -> simulation", self.input_to_apply, SKYBLUE),
+            ("simulation -> present", self.apply_to_present, GREEN),
+            ("simulation -> send", self.apply_to_send, ORANGE),
+            ("network (one way)", self.server_to_client, RED),
+        ];
+
+        let x = 10.0;
+        let y = ui::height() - 40.0 - stages.len() as f32 * 22.0;
+        draw_rectangle(
+            x - 4.0,
+            y - 20.0,
+            PANEL_WIDTH,
+            stages.len() as f32 * 22.0 + 44.0,
+            Color::new(1.0, 1.0, 1.0, 0.85),
+        );
+        ui::draw_text("Own movement latency (F4)", x, y, 18.0, BLACK);
+
+        for (i, (label, average, color)) in stages.iter().enumerate() {
+            let row_y = y + 22.0 + i as f32 * 22.0;
+            let text = match average.0 {
+                Some(ms) => format!("{:<22} {:>6.1} ms", label, ms),
+                None => format!("{:<22}      -", label),
+            };
+            ui::draw_text(&text, x, row_y, 16.0, BLACK);
+            if let Some(ms) = average.0 {
+                let width = (ms * BAR_SCALE).min(PANEL_WIDTH - BAR_X - 10.0);
+                draw_rectangle(x + BAR_X, row_y - 10.0, width, 10.0, *color);
+            }
+        }
+
+        let local = self.input_to_apply.0.unwrap_or(0.0) + self.apply_to_present.0.unwrap_or(0.0);
+        ui::draw_text(
+            &format!("input to photon {:.1} ms", local),
+            x,
+            y + 22.0 + stages.len() as f32 * 22.0,
+            16.0,
+            DARKGRAY,
+        );
+    }
+}
@@ -0,0 +1,156 @@
+// Shared by the client and the server, builds without macroquad
+pub mod compat;
+pub mod course;
+pub mod daytime;
+pub mod error;
+pub mod logging;
+pub mod map;
+pub mod obstacle;
+pub mod protocol;
+pub mod season;
+pub mod world;
+
+#[cfg(feature = "client")]
+pub mod account;
+#[cfg(feature = "client")]
+pub mod animation;
+#[cfg(feature = "client")]
+pub mod announcement;
+#[cfg(feature = "client")]
+pub mod assets;
+#[cfg(feature = "client")]
+pub mod auth;
+#[cfg(feature = "client")]
+pub mod batch;
+#[cfg(feature = "client")]
+pub mod camera;
+#[cfg(feature = "client")]
+pub mod camera_effects;
+#[cfg(feature = "client")]
+pub mod character_panel;
+#[cfg(feature = "client")]
+pub mod chat;
+#[cfg(feature = "client")]
+pub mod chat_history;
+#[cfg(feature = "client")]
+pub mod commands;
+#[cfg(feature = "client")]
+pub mod components;
+#[cfg(feature = "client")]
+pub mod config;
+#[cfg(feature = "client")]
+pub mod crash;
+#[cfg(feature = "client")]
+pub mod desync;
+#[cfg(feature = "client")]
+pub mod emoji;
+#[cfg(feature = "client")]
+pub mod equipment;
+#[cfg(feature = "client")]
+pub mod floating_text;
+#[cfg(feature = "client")]
+pub mod foliage;
+#[cfg(feature = "client")]
+pub mod game;
+#[cfg(feature = "client")]
+pub mod ghost;
+#[cfg(feature = "client")]
+pub mod headless;
+#[cfg(feature = "client")]
+pub mod help;
+#[cfg(feature = "client")]
+pub mod input;
+#[cfg(feature = "client")]
+pub mod latency;
+#[cfg(feature = "client")]
+pub mod lifecycle;
+#[cfg(feature = "client")]
+pub mod lighting;
+#[cfg(feature = "client")]
+pub mod mail;
+#[cfg(feature = "client")]
+pub mod metrics;
+#[cfg(feature = "client")]
+pub mod minigame;
+#[cfg(feature = "client")]
+pub mod minimap;
+#[cfg(feature = "client")]
+pub mod net;
+#[cfg(feature = "client")]
+pub mod obstacle_view;
+#[cfg(feature = "client")]
+pub mod palette;
+#[cfg(feature = "client")]
+pub mod particles;
+#[cfg(feature = "client")]
+pub mod party;
+#[cfg(feature = "client")]
+pub mod pathfinding;
+#[cfg(feature = "client")]
+pub mod player;
+#[cfg(feature = "client")]
+pub mod player_list;
+#[cfg(feature = "client")]
+pub mod player_menu;
+#[cfg(feature = "client")]
+pub mod plugin;
+#[cfg(feature = "client")]
+pub mod post_process;
+#[cfg(feature = "client")]
+pub mod profiler;
+#[cfg(feature = "client")]
+pub mod quality;
+#[cfg(feature = "client")]
+pub mod race;
+#[cfg(feature = "client")]
+pub mod render;
+#[cfg(feature = "client")]
+pub mod replay;
+#[cfg(feature = "client")]
+pub mod scene;
+#[cfg(feature = "client")]
+pub mod seasonal;
+#[cfg(feature = "client")]
+pub mod settings;
+#[cfg(feature = "client")]
+pub mod settings_panel;
+#[cfg(feature = "client")]
+pub mod shutdown;
+#[cfg(feature = "client")]
+pub mod spatial;
+#[cfg(feature = "client")]
+pub mod sprite;
+#[cfg(feature = "client")]
+pub mod systems;
+#[cfg(feature = "client")]
+pub mod text_field;
+#[cfg(feature = "client")]
+pub mod tilemap;
+#[cfg(feature = "client")]
+pub mod touch;
+#[cfg(feature = "client")]
+pub mod transition;
+#[cfg(feature = "client")]
+pub mod ui;
+#[cfg(feature = "client")]
+pub mod weather;
+#[cfg(feature = "client")]
+pub mod wildlife;
+#[cfg(feature = "client")]
+pub mod word_filter;
+
+#[cfg(feature = "server")]
+pub mod link;
+#[cfg(feature = "server")]
+pub mod scripting;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "server")]
+pub mod storage;
+#[cfg(feature = "server")]
+pub mod ticket;
+
+#[cfg(feature = "bots")]
+pub mod bot;
+#[cfg(feature = "bots")]
+pub mod loadtest;
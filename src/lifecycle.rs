@@ -0,0 +1,62 @@
+use macroquad::input::utils::{register_input_subscriber, repeat_all_miniquad_input};
+use macroquad::miniquad::EventHandler;
+
+// Only phones send the game to the background; a minimized desktop window
+// keeps playing
+pub const MOBILE: bool = cfg!(any(target_os = "android", target_os = "ios"));
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppEvent {
+    Backgrounded,
+    Foregrounded,
+}
+
+// Collects the events the OS sent since the last poll
+struct Collector<'a> {
+    events: &'a mut Vec<AppEvent>,
+}
+
+impl EventHandler for Collector<'_> {
+    fn update(&mut self) {}
+
+    fn draw(&mut self) {}
+
+    fn window_minimized_event(&mut self) {
+        self.events.push(AppEvent::Backgrounded);
+    }
+
+    fn window_restored_event(&mut self) {
+        self.events.push(AppEvent::Foregrounded);
+    }
+}
+
+// Tells the main loop when the app goes to or comes back from the background
+pub struct Lifecycle {
+    subscriber: usize,
+}
+
+impl Default for Lifecycle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self {
+            subscriber: register_input_subscriber(),
+        }
+    }
+
+    // The latest change since the last poll, if any. Call once a frame.
+    pub fn poll(&mut self) -> Option<AppEvent> {
+        let mut events = Vec::new();
+        repeat_all_miniquad_input(
+            &mut Collector {
+                events: &mut events,
+            },
+            self.subscriber,
+        );
+        events.pop()
+    }
+}
@@ -0,0 +1,82 @@
+use crate::components::{Hidden, Position};
+use crate::daytime::{self, DAY_LENGTH_MS, START_TIME_MS};
+use hecs::World;
+use macroquad::prelude::*;
+use std::time::Instant;
+
+// The color the world fades towards at night, and how far at most
+const NIGHT: Color = Color::new(0.04, 0.06, 0.2, 1.0);
+const MAX_SHADE: f32 = 0.7;
+const GLOW_RADIUS: f32 = 70.0;
+const GLOW_RINGS: usize = 4;
+const GLOW_COLOR: Color = Color::new(1.0, 0.85, 0.5, 1.0);
+// From a character's position to the middle of its body
+const BODY_OFFSET: f32 = 20.0;
+
+// Time of day, following the server's clock once it has sent it. Until then,
+// or without a server, the day runs from the same start as a new server's.
+pub struct Lighting {
+    time_ms: u64, // Into the day when `synced_at` was taken
+    day_length_ms: u64,
+    synced_at: Instant,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Lighting {
+    pub fn new() -> Self {
+        Self {
+            time_ms: START_TIME_MS,
+            day_length_ms: DAY_LENGTH_MS,
+            synced_at: Instant::now(),
+        }
+    }
+
+    pub fn sync(&mut self, time_ms: u64, day_length_ms: u64) {
+        self.time_ms = time_ms;
+        self.day_length_ms = day_length_ms;
+        self.synced_at = Instant::now();
+    }
+
+    pub fn time_ms(&self) -> u64 {
+        self.time_ms + self.synced_at.elapsed().as_millis() as u64
+    }
+
+    pub fn clock(&self) -> String {
+        daytime::clock(self.time_ms(), self.day_length_ms)
+    }
+
+    // 0 by day, 1 in the middle of the night
+    pub fn darkness(&self) -> f32 {
+        daytime::darkness(self.time_ms(), self.day_length_ms)
+    }
+
+    // Shades everything drawn so far inside `view`, background and players
+    // alike, then with `glow` lights a warm circle around every character.
+    // Drawn in world coordinates, before the UI.
+    pub fn draw(&self, view: &Rect, world: &World, glow: bool) {
+        let darkness = self.darkness();
+        if darkness <= 0.0 {
+            return;
+        }
+        let shade = Color::new(NIGHT.r, NIGHT.g, NIGHT.b, darkness * MAX_SHADE);
+        draw_rectangle(view.x, view.y, view.w, view.h, shade);
+        if !glow {
+            return;
+        }
+
+        let color = Color::new(GLOW_COLOR.r, GLOW_COLOR.g, GLOW_COLOR.b, darkness * 0.06);
+        let mut query = world.query::<&Position>().without::<&Hidden>();
+        for position in query.iter() {
+            // Overlapping rings, brightest in the middle
+            for ring in 0..GLOW_RINGS {
+                let radius = GLOW_RADIUS * (GLOW_RINGS - ring) as f32 / GLOW_RINGS as f32;
+                draw_circle(position.x, position.y + BODY_OFFSET, radius, color);
+            }
+        }
+    }
+}
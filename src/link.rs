@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// How the server backs off a client that can't keep up, and when it lets go
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SnapshotPolicy {
+    pub max_rtt_ms: u64,          // Slower round trips count as pressure
+    pub max_pings_in_flight: u32, // Unanswered pings, standing in for the send queue
+    pub max_interval_ticks: u32,  // Slowest obstacle snapshot rate, in server ticks
+    pub reduced_radius: f32,      // Only players this close are relayed while backed off
+    pub prefetch_margin: f32,     // Reaches this much further for players a client is closing on
+    pub recover_after: u32,       // Healthy pings in a row before speeding back up
+}
+
+impl Default for SnapshotPolicy {
+    fn default() -> Self {
+        Self {
+            max_rtt_ms: 250,
+            max_pings_in_flight: 2,
+            max_interval_ticks: 8,
+            reduced_radius: 500.0,
+            prefetch_margin: 250.0,
+            recover_after: 3,
+        }
+    }
+}
+
+impl SnapshotPolicy {
+    // Reads a policy from JSON, missing fields keep their defaults
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        serde_json::from_str(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// The server's view of one client's connection
+pub struct LinkQuality {
+    pub rtt_ms: Option<u64>,
+    pings_in_flight: u32,
+    pub snapshot_interval: u32, // Obstacle snapshots go out every this many ticks
+    ticks_until_snapshot: u32,
+    healthy_streak: u32,
+}
+
+impl Default for LinkQuality {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinkQuality {
+    pub fn new() -> Self {
+        Self {
+            rtt_ms: None,
+            pings_in_flight: 0,
+            snapshot_interval: 1,
+            ticks_until_snapshot: 0,
+            healthy_streak: 0,
+        }
+    }
+
+    pub fn ping_sent(&mut self) {
+        self.pings_in_flight += 1;
+    }
+
+    pub fn pong_received(&mut self, rtt_ms: u64) {
+        self.pings_in_flight = self.pings_in_flight.saturating_sub(1);
+        self.rtt_ms = Some(rtt_ms);
+    }
+
+    // Called once per ping. Halves the snapshot rate under pressure and
+    // doubles it again after a run of healthy pings. Returns whether it changed.
+    pub fn adjust(&mut self, policy: &SnapshotPolicy) -> bool {
+        let under_pressure = self.pings_in_flight > policy.max_pings_in_flight
+            || self.rtt_ms.is_some_and(|rtt| rtt > policy.max_rtt_ms);
+        let interval = if under_pressure {
+            self.healthy_streak = 0;
+            (self.snapshot_interval * 2).min(policy.max_interval_ticks.max(1))
+        } else {
+            self.healthy_streak += 1;
+            if self.healthy_streak < policy.recover_after {
+                return false;
+            }
+            self.healthy_streak = 0;
+            (self.snapshot_interval / 2).max(1)
+        };
+        let changed = interval != self.snapshot_interval;
+        self.snapshot_interval = interval;
+        changed
+    }
+
+    // Counts down one server tick, true when this client is due a snapshot
+    pub fn snapshot_due(&mut self) -> bool {
+        if self.ticks_until_snapshot > 0 {
+            self.ticks_until_snapshot -= 1;
+            return false;
+        }
+        self.ticks_until_snapshot = self.snapshot_interval - 1;
+        true
+    }
+
+    // How far away other players are still relayed, None for everyone
+    pub fn interest_radius(&self, policy: &SnapshotPolicy) -> Option<f32> {
+        (self.snapshot_interval > 1).then_some(policy.reduced_radius)
+    }
+
+    // Whether a player `offset` away is relayed to this client, `closing`
+    // being how the two last moved relative to each other. Backed off,
+    // players it's closing in on are relayed from `prefetch_margin` further
+    // out, so they're already there when they come into range instead of
+    // popping in.
+    pub fn is_relayed(
+        &self,
+        policy: &SnapshotPolicy,
+        offset: (f32, f32),
+        closing: (f32, f32),
+    ) -> bool {
+        let Some(radius) = self.interest_radius(policy) else {
+            return true;
+        };
+        let distance = offset.0.hypot(offset.1);
+        let approaching = offset.0 * closing.0 + offset.1 * closing.1 > 0.0;
+        distance <= radius || (approaching && distance <= radius + policy.prefetch_margin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backed_off() -> LinkQuality {
+        LinkQuality {
+            snapshot_interval: 2,
+            ..LinkQuality::new()
+        }
+    }
+
+    #[test]
+    fn receding_player_outside_radius_is_not_relayed() {
+        let policy = SnapshotPolicy::default();
+        let offset = (policy.reduced_radius + 100.0, 0.0);
+        assert!(!backed_off().is_relayed(&policy, offset, (-1.0, 0.0)));
+        assert!(!backed_off().is_relayed(&policy, offset, (0.0, 0.0)));
+    }
+
+    #[test]
+    fn approaching_player_within_prefetch_margin_is_relayed() {
+        let policy = SnapshotPolicy::default();
+        let inside = (policy.reduced_radius + policy.prefetch_margin - 10.0, 0.0);
+        assert!(backed_off().is_relayed(&policy, inside, (1.0, 0.0)));
+        let beyond = (policy.reduced_radius + policy.prefetch_margin + 10.0, 0.0);
+        assert!(!backed_off().is_relayed(&policy, beyond, (1.0, 0.0)));
+    }
+
+    #[test]
+    fn full_rate_link_relays_everyone() {
+        let policy = SnapshotPolicy::default();
+        let link = LinkQuality::new();
+        let far = (policy.reduced_radius + policy.prefetch_margin + 1000.0, 0.0);
+        assert!(link.is_relayed(&policy, far, (-1.0, 0.0)));
+        assert!(link.is_relayed(&policy, far, (1.0, 0.0)));
+    }
+}
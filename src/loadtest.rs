@@ -0,0 +1,216 @@
+use crate::bot::{Bot, BotBehavior, Movement};
+use crate::error;
+use crate::logging;
+use crate::shutdown;
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tracing::info;
+
+// How often the runner tops up and churns bots
+const SCHEDULE_TICK: Duration = Duration::from_millis(100);
+
+// Bring the bot count up by `bots`, spread evenly over `over_secs`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stage {
+    pub bots: usize,
+    pub over_secs: f32,
+}
+
+// Every `every_secs`, `percent` of the bots leave and new ones take their place
+#[derive(Clone, Debug, PartialEq)]
+pub struct Churn {
+    pub percent: f32,
+    pub every_secs: f32,
+}
+
+// A traffic shape for `lam-loadtest`, so runs against different server builds
+// see the same load.
+//
+// Format, one setting per line, `#` starts a comment:
+//   spawn <n> over <secs>          add n bots over that many seconds, stages run in order
+//   move wander|still|circle <r>   how the bots move
+//   chat <secs>|off                mean gap between a bot's chat lines
+//   churn <percent>% every <secs>  replace some bots now and then
+//   duration <secs>                stop after this long, otherwise run until killed
+//   seed <n>                       the same seed moves, chats and churns the same way
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scenario {
+    pub stages: Vec<Stage>,
+    pub behavior: BotBehavior,
+    pub churn: Option<Churn>,
+    pub duration: Option<Duration>,
+    pub seed: u64,
+}
+
+fn number<T: std::str::FromStr>(text: Option<&str>, what: &str) -> Result<T, String> {
+    let text = text.ok_or_else(|| format!("missing {}", what))?;
+    text.parse().map_err(|_| format!("bad {} {:?}", what, text))
+}
+
+fn parse_setting(scenario: &mut Scenario, line: &str) -> Result<(), String> {
+    let mut words = line.split_whitespace();
+    let setting = words.next().unwrap_or_default();
+    match setting {
+        "spawn" => {
+            let bots = number(words.next(), "bot count")?;
+            if words.next() != Some("over") {
+                return Err("spawn takes <n> over <secs>".to_string());
+            }
+            let over_secs = number(words.next(), "seconds")?;
+            scenario.stages.push(Stage { bots, over_secs });
+        }
+        "move" => {
+            scenario.behavior.movement = match words.next() {
+                Some("wander") => Movement::Wander,
+                Some("still") => Movement::Still,
+                Some("circle") => Movement::Circle {
+                    radius: number(words.next(), "radius")?,
+                },
+                _ => return Err("move takes wander, still or circle <radius>".to_string()),
+            };
+        }
+        "chat" => {
+            scenario.behavior.chat_interval = match words.next() {
+                Some("off") => None,
+                secs => {
+                    let secs: f32 = number(secs, "seconds")?;
+                    if secs <= 0.0 {
+                        return Err("chat takes a positive number of seconds".to_string());
+                    }
+                    // Spread out so bots don't chat in lockstep
+                    Some(secs * 0.5..secs * 1.5)
+                }
+            };
+        }
+        "churn" => {
+            let percent = words.next().and_then(|percent| percent.strip_suffix('%'));
+            let percent: f32 = number(percent, "churn percentage")?;
+            if words.next() != Some("every") {
+                return Err("churn takes <percent>% every <secs>".to_string());
+            }
+            let every_secs: f32 = number(words.next(), "seconds")?;
+            if every_secs <= 0.0 {
+                return Err("churn takes a positive number of seconds".to_string());
+            }
+            scenario.churn = Some(Churn {
+                percent,
+                every_secs,
+            });
+        }
+        "duration" => {
+            let secs: f32 = number(words.next(), "seconds")?;
+            scenario.duration = Some(Duration::from_secs_f32(secs.max(0.0)));
+        }
+        "seed" => scenario.seed = number(words.next(), "seed")?,
+        other => return Err(format!("unknown setting {:?}", other)),
+    }
+    match words.next() {
+        Some(extra) => Err(format!("unexpected {:?}", extra)),
+        None => Ok(()),
+    }
+}
+
+impl Scenario {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scenario = Scenario::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            parse_setting(&mut scenario, line)
+                .map_err(|e| format!("line {}: {}", number + 1, e))?;
+        }
+        Ok(scenario)
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // How many bots should be connected `secs` into the run
+    pub fn target_bots(&self, secs: f32) -> usize {
+        let mut start = 0.0;
+        let mut total = 0;
+        for stage in &self.stages {
+            let progress = if stage.over_secs <= 0.0 {
+                1.0
+            } else {
+                ((secs - start) / stage.over_secs).clamp(0.0, 1.0)
+            };
+            total += (stage.bots as f32 * progress).floor() as usize;
+            if progress < 1.0 {
+                break;
+            }
+            start += stage.over_secs;
+        }
+        total
+    }
+}
+
+// What happened during a run
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LoadReport {
+    pub spawned: usize,
+    pub churned: usize,
+    pub peak: usize,
+}
+
+// Plays `scenario` against the server at `addr`, returning once its duration is
+// up or Ctrl+C is pressed. Every bot leaves properly either way.
+pub fn run(scenario: &Scenario, addr: &str, rt: &Runtime) -> error::Result<LoadReport> {
+    let mut rng = StdRng::seed_from_u64(scenario.seed);
+    let mut bots: Vec<Bot> = Vec::new();
+    let mut report = LoadReport::default();
+    let started = Instant::now();
+    let mut last_churn = Instant::now();
+
+    while !shutdown::quit_requested()
+        && scenario
+            .duration
+            .is_none_or(|duration| started.elapsed() < duration)
+    {
+        if let Some(churn) = &scenario.churn {
+            if last_churn.elapsed().as_secs_f32() >= churn.every_secs {
+                last_churn = Instant::now();
+                let leaving = (bots.len() as f32 * churn.percent / 100.0).round() as usize;
+                for _ in 0..leaving.min(bots.len()) {
+                    let bot = bots.swap_remove(rng.gen_range(0..bots.len()));
+                    bot.stop();
+                }
+                report.churned += leaving;
+                info!(target: logging::NET, "Churned {} bots", leaving);
+            }
+        }
+
+        // Also replaces the bots that just left
+        let target = scenario.target_bots(started.elapsed().as_secs_f32());
+        while bots.len() < target {
+            let index = report.spawned;
+            let seed = scenario.seed.wrapping_add(index as u64);
+            bots.push(Bot::spawn(
+                index,
+                addr,
+                rt,
+                scenario.behavior.clone(),
+                seed,
+            )?);
+            report.spawned += 1;
+        }
+        report.peak = report.peak.max(bots.len());
+
+        sleep(SCHEDULE_TICK);
+    }
+
+    for bot in bots {
+        bot.stop();
+    }
+    Ok(report)
+}
@@ -0,0 +1,42 @@
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use tracing_subscriber::fmt;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::EnvFilter;
+
+pub const DEFAULT_LEVEL: &str = "info";
+
+// Every log line carries one of these targets, so a level can be set per
+// subsystem, e.g. `--log-level info,net=debug`
+pub const NET: &str = "net"; // Connection and message traffic
+pub const GAME: &str = "game"; // Simulation, minigames, replays
+pub const RENDER: &str = "render"; // Graphics quality and drawing
+pub const AUTH: &str = "auth"; // Logins, tickets, accounts
+pub const SERVER: &str = "server";
+pub const SCRIPTS: &str = "scripts";
+pub const CONFIG: &str = "config";
+pub const ASSETS: &str = "assets";
+pub const METRICS: &str = "metrics";
+
+// Sends log lines to stdout, and to `file` as well when given. `filter` is a
+// plain level or per-target directives, see `EnvFilter`.
+pub fn init(filter: &str, file: Option<&Path>) -> io::Result<()> {
+    let filter =
+        EnvFilter::try_new(filter).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let file_layer = match file {
+        Some(path) => Some(
+            fmt::layer()
+                .with_ansi(false)
+                .with_writer(Mutex::new(File::create(path)?)),
+        ),
+        None => None,
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .with(file_layer)
+        .try_init()
+        .map_err(|e| io::Error::new(io::ErrorKind::AlreadyExists, e))
+}
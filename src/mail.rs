@@ -0,0 +1,294 @@
+use crate::protocol::{ClientMessage, Mail};
+use crate::ui;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+// How long the "new mail" notice stays up
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(5);
+const PANEL_WIDTH: f32 = 420.0;
+const PANEL_ROWS: usize = 8;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ComposeField {
+    To,
+    Body,
+}
+
+struct Compose {
+    to: String,
+    body: String,
+    field: ComposeField,
+}
+
+// Mailbox notification and the panel to read, delete and write mail. The
+// `open_mailbox` key opens it.
+pub struct MailPanel {
+    mail: Vec<Mail>,
+    open: bool,
+    selected: usize,
+    reading: Option<u64>, // Id of the mail shown in full
+    compose: Option<Compose>,
+    notification: Option<(String, Instant)>,
+}
+
+impl Default for MailPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MailPanel {
+    pub fn new() -> Self {
+        Self {
+            mail: Vec::new(),
+            open: false,
+            selected: 0,
+            reading: None,
+            compose: None,
+            notification: None,
+        }
+    }
+
+    // Typing into the compose form, so keys shouldn't move the player
+    pub fn is_typing(&self) -> bool {
+        self.compose.is_some()
+    }
+
+    pub fn handle_message(&mut self, message: &ClientMessage) {
+        let ClientMessage::Mailbox { mail } = message else {
+            return;
+        };
+
+        let known: Vec<u64> = self.mail.iter().map(|m| m.id).collect();
+        let new_unread = mail
+            .iter()
+            .filter(|m| !m.read && !known.contains(&m.id))
+            .count();
+        if new_unread > 0 {
+            let unread = mail.iter().filter(|m| !m.read).count();
+            self.notification = Some((
+                format!("You have {} unread mail (M to open)", unread),
+                Instant::now(),
+            ));
+        }
+
+        self.mail = mail.clone();
+        self.selected = self.selected.min(self.mail.len().saturating_sub(1));
+    }
+
+    pub fn update(&mut self, open_key: KeyCode, outbox: &mut Vec<ClientMessage>) {
+        if let Some(compose) = &mut self.compose {
+            if compose.update(outbox) {
+                self.compose = None;
+            }
+            return;
+        }
+
+        if is_key_pressed(open_key) {
+            self.open = !self.open;
+            self.reading = None;
+        }
+        if !self.open {
+            return;
+        }
+
+        if is_key_pressed(KeyCode::N) {
+            // Drop keys queued before the form opened, including the N itself
+            while get_char_pressed().is_some() {}
+            self.compose = Some(Compose {
+                to: String::new(),
+                body: String::new(),
+                field: ComposeField::To,
+            });
+            return;
+        }
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+            self.reading = None;
+        }
+        if is_key_pressed(KeyCode::Down) && self.selected + 1 < self.mail.len() {
+            self.selected += 1;
+            self.reading = None;
+        }
+
+        let Some(mail) = self.mail.get_mut(self.selected) else {
+            return;
+        };
+        if is_key_pressed(KeyCode::Enter) {
+            self.reading = Some(mail.id);
+            if !mail.read {
+                mail.read = true;
+                outbox.push(ClientMessage::MarkMailRead { mail_id: mail.id });
+            }
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            outbox.push(ClientMessage::DeleteMail { mail_id: mail.id });
+            self.mail.remove(self.selected);
+            self.selected = self.selected.min(self.mail.len().saturating_sub(1));
+            self.reading = None;
+        }
+    }
+
+    pub fn draw(&self) {
+        if let Some((text, shown_at)) = &self.notification {
+            if shown_at.elapsed() < NOTIFICATION_DURATION {
+                let width = ui::text_width(text, 20.0);
+                let x = (ui::width() - width) / 2.0;
+                draw_rectangle(
+                    x - 8.0,
+                    12.0,
+                    width + 16.0,
+                    28.0,
+                    Color::new(0.0, 0.0, 0.0, 0.7),
+                );
+                ui::draw_text(text, x, 32.0, 20.0, WHITE);
+            }
+        }
+
+        if !self.open {
+            return;
+        }
+
+        let x = (ui::width() - PANEL_WIDTH) / 2.0;
+        let y = 80.0;
+        let height = 60.0 + PANEL_ROWS as f32 * 22.0 + 80.0;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, PANEL_WIDTH, height, 2.0, DARKGRAY);
+        ui::draw_text("Mailbox", x + 12.0, y + 26.0, 24.0, BLACK);
+
+        if let Some(compose) = &self.compose {
+            compose.draw(x + 12.0, y + 56.0);
+            return;
+        }
+
+        ui::draw_text(
+            "Up/Down select, Enter read, Del delete, N new, M close",
+            x + 12.0,
+            y + height - 12.0,
+            16.0,
+            GRAY,
+        );
+        if self.mail.is_empty() {
+            ui::draw_text("No mail", x + 12.0, y + 56.0, 20.0, GRAY);
+            return;
+        }
+
+        // Scroll so the selected row stays visible
+        let first = self.selected.saturating_sub(PANEL_ROWS - 1);
+        for (row, (index, mail)) in self
+            .mail
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(PANEL_ROWS)
+            .enumerate()
+        {
+            let row_y = y + 56.0 + row as f32 * 22.0;
+            if index == self.selected {
+                draw_rectangle(x + 4.0, row_y - 16.0, PANEL_WIDTH - 8.0, 22.0, LIGHTGRAY);
+            }
+            let marker = if mail.read { " " } else { "*" };
+            let preview: String = mail.body.chars().take(32).collect();
+            ui::draw_text(
+                &format!("{} {}: {}", marker, mail.from, preview),
+                x + 12.0,
+                row_y,
+                20.0,
+                BLACK,
+            );
+        }
+
+        if let Some(mail) = self.mail.iter().find(|m| Some(m.id) == self.reading) {
+            let body_y = y + 56.0 + PANEL_ROWS as f32 * 22.0;
+            ui::draw_text(
+                &format!("From {}", mail.from),
+                x + 12.0,
+                body_y,
+                18.0,
+                DARKGRAY,
+            );
+            // Naive wrap, good enough for short notes
+            let chars: Vec<char> = mail.body.chars().collect();
+            for (i, line) in chars.chunks(48).take(2).enumerate() {
+                let line: String = line.iter().collect();
+                ui::draw_text(
+                    &line,
+                    x + 12.0,
+                    body_y + 20.0 + i as f32 * 18.0,
+                    18.0,
+                    BLACK,
+                );
+            }
+        }
+    }
+}
+
+impl Compose {
+    // Returns true once the form is sent or cancelled
+    fn update(&mut self, outbox: &mut Vec<ClientMessage>) -> bool {
+        if is_key_pressed(KeyCode::Escape) {
+            return true;
+        }
+        if is_key_pressed(KeyCode::Tab) {
+            self.field = match self.field {
+                ComposeField::To => ComposeField::Body,
+                ComposeField::Body => ComposeField::To,
+            };
+        }
+
+        let text = match self.field {
+            ComposeField::To => &mut self.to,
+            ComposeField::Body => &mut self.body,
+        };
+        while let Some(c) = get_char_pressed() {
+            if !c.is_control() {
+                text.push(c);
+            }
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            text.pop();
+        }
+
+        if is_key_pressed(KeyCode::Enter) {
+            match self.field {
+                ComposeField::To => self.field = ComposeField::Body,
+                ComposeField::Body => {
+                    if self.to.trim().is_empty() || self.body.trim().is_empty() {
+                        return false;
+                    }
+                    outbox.push(ClientMessage::SendMail {
+                        to: self.to.trim().to_string(),
+                        body: self.body.trim().to_string(),
+                    });
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn draw(&self, x: f32, y: f32) {
+        let cursor = |field| if self.field == field { "_" } else { "" };
+        ui::draw_text(
+            &format!("To: {}{}", self.to, cursor(ComposeField::To)),
+            x,
+            y,
+            20.0,
+            BLACK,
+        );
+        ui::draw_text(
+            &format!("Message: {}{}", self.body, cursor(ComposeField::Body)),
+            x,
+            y + 26.0,
+            20.0,
+            BLACK,
+        );
+        ui::draw_text(
+            "Tab switch field, Enter send, Esc cancel",
+            x,
+            y + 56.0,
+            16.0,
+            GRAY,
+        );
+    }
+}
@@ -1,675 +1,346 @@
 // #![windows_subsystem = "windows"]
 
-use ::rand::thread_rng;
-use ::rand::Rng;
-use bincode;
+use lam_test::animation::ANIMATIONS_FILE;
+use lam_test::assets::{Assets, ASSETS_DIR};
+#[cfg(feature = "bots")]
+use lam_test::bot;
+use lam_test::config::{Config, ConfigWatcher, CONFIG_PATH};
+use lam_test::course::MAP_COURSES;
+use lam_test::error::{self, Error};
+use lam_test::game::Game;
+use lam_test::ghost::Ghost;
+use lam_test::input::{KeyboardInput, ScriptedInput};
+use lam_test::lifecycle::{self, Lifecycle};
+use lam_test::logging;
+use lam_test::metrics;
+use lam_test::net::{self, Connection};
+use lam_test::protocol::Season;
+use lam_test::race::CheckpointRace;
+use lam_test::replay::{Replay, ReplayPlayback};
+use lam_test::scene::{Scene, Session, MAX_ADDRESS_LENGTH};
+#[cfg(feature = "server")]
+use lam_test::server::{self, ServerOptions};
+use lam_test::settings::{Settings, WindowMode};
+use lam_test::shutdown::{self, shutdown};
+use lam_test::text_field::TextField;
+use lam_test::transition::Transition;
+use lam_test::{auth, crash, headless, palette, ui};
 use macroquad::prelude::*;
-use message_io::network::{NetEvent, Transport};
-use message_io::node::{self, NodeEvent};
-use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread::sleep;
 use std::time::{Duration, Instant};
 use tokio::runtime::Runtime;
+use tracing::{error, info};
 
-#[derive(Serialize, Deserialize, Debug)]
-enum ClientMessage {
-    PlayerPosition { id: usize, x: f32, y: f32 },
-    AssignPlayerId { id: usize },
-    UpdateMessage { id: usize, message: String },
-    OtherPlayerDisconnected { id: usize },
-}
+const DEFAULT_SERVER_ADDR: &str = "40.124.89.57:3042";
+const HOST_LISTEN_ADDR: &str = "0.0.0.0:3042";
+const HOST_CONNECT_ADDR: &str = "127.0.0.1:3042";
 
-#[derive(Clone)]
-struct Player {
-    id: usize,
-    x: f32,
-    y: f32,
-    width: f32,
-    height: f32,
-    speed: f32,
-    target_x: Option<f32>,
-    target_y: Option<f32>,
-    last_message_send_time: Instant,
-    message: Option<String>,
-    message_sent: bool,
-    position_changed: bool,
-    current_pose_index: usize,
-    next_pose_index: usize,
-    last_pose_update_time: Instant,
-    pose_update_interval: Duration,
-    pose_interp_factor: f32,
-    hair_lines: Vec<((f32, f32), (f32, f32))>,
-    is_local: bool,
-    is_moving: bool,     // Tracks if the player is currently moving
-    bobbing_time: f32,   // Time accumulator for bobbing
-    bobbing_offset: f32, // Current y-offset for bobbing
+// Returns the value following `name` on the command line, e.g. `--replay <path>`
+fn arg_value(name: &str) -> Option<String> {
+    let mut args = std::env::args();
+    args.find(|arg| arg == name)?;
+    args.next()
 }
 
-impl Player {
-    fn new_local(x: f32, y: f32, width: f32, height: f32) -> Self {
-        let mut player = Player {
-            id: 0, // Will be set by the server
-            x,
-            y,
-            width,
-            height,
-            speed: 250.0,
-            target_x: None,
-            target_y: None,
-            last_message_send_time: Instant::now(),
-            message: None,
-            message_sent: false,
-            position_changed: false,
-            current_pose_index: 0,
-            next_pose_index: 1,
-            last_pose_update_time: Instant::now(),
-            pose_update_interval: Duration::from_millis(100), // 20 updates per second
-            pose_interp_factor: 0.0,
-            hair_lines: Vec::new(),
-            is_local: true,
-            is_moving: false,
-            bobbing_time: 0.0,
-            bobbing_offset: 0.0,
-        };
-        player.generate_hair();
-        player
-    }
-
-    fn new_other(id: usize, x: f32, y: f32) -> Self {
-        let mut player = Player {
-            id,
-            x,
-            y,
-            width: 30.0, // Default values for other players
-            height: 30.0,
-            speed: 250.0,
-            target_x: Some(x),
-            target_y: Some(y),
-            last_message_send_time: Instant::now(),
-            message: None,
-            message_sent: true, // Other players don't send messages
-            position_changed: false,
-            current_pose_index: 0,
-            next_pose_index: 1,
-            last_pose_update_time: Instant::now(),
-            pose_update_interval: Duration::from_millis(100),
-            pose_interp_factor: 0.0,
-            hair_lines: Vec::new(),
-            is_local: false,
-            is_moving: false,
-            bobbing_time: 0.0,
-            bobbing_offset: 0.0,
-        };
-        player.generate_hair();
-        player
+//window conf
+fn window_conf() -> Conf {
+    let config = Config::load_or_default(Path::new(CONFIG_PATH));
+    let settings = Settings::load();
+    let (window_width, window_height) = settings
+        .resolution
+        .map(|(width, height)| (width as i32, height as i32))
+        .unwrap_or((config.window_width, config.window_height));
+    Conf {
+        window_title: "Smooth Multiplayer Game".to_owned(),
+        window_width,
+        window_height,
+        window_resizable: true,
+        fullscreen: settings.window_mode == WindowMode::Fullscreen,
+        // Full resolution on high-DPI displays, `ui::scale` keeps things
+        // their usual size
+        high_dpi: true,
+        ..Default::default()
     }
+}
 
-    fn generate_hair(&mut self) {
-        let mut hair_lines = Vec::with_capacity(250);
-        let hair_count = 250;
-        let base_hair_length = 20.0;
-
-        let mut rng = thread_rng();
-
-        for _ in 0..hair_count {
-            let angle = rng.gen_range(-180.0_f32.to_radians()..180.0_f32.to_radians());
-            let angle_variation = rng.gen_range(-5.0_f32.to_radians()..5.0_f32.to_radians());
-
-            let start_x = 15.0 * angle.cos();
-            let start_y = -30.0 + rng.gen_range(0.0..10.0);
-
-            let hair_length = base_hair_length + rng.gen_range(-5.0..5.0);
-
-            let end_x = start_x + hair_length * (angle + angle_variation).cos();
-            let end_y =
-                start_y + hair_length * (angle + angle_variation).sin() + rng.gen_range(0.0..5.0);
-
-            hair_lines.push(((start_x, start_y), (end_x, end_y)));
-        }
-
-        self.hair_lines = hair_lines;
+// `--name <name>` picks the login name, `--protect` asks for a recovery code
+// when it is first registered, `--recovery-code <code>` answers the challenge
+// on a new device without prompting. The last session ticket is reused.
+fn apply_login_args(game: &mut Game) {
+    if let Some(name) = arg_value("--name") {
+        game.player_name = name;
     }
+    game.device_id = auth::load_device_id();
+    game.protect_name = std::env::args().any(|arg| arg == "--protect");
+    game.auth.recovery_code = arg_value("--recovery-code");
+    game.auth.ticket = auth::load_ticket();
+    game.auth.save_tickets = true;
+}
 
-    fn update(&mut self, dt: f32) {
-        // Move towards target position at a constant speed
-        if let (Some(target_x), Some(target_y)) = (self.target_x, self.target_y) {
-            let direction = Vec2::new(target_x - self.x, target_y - self.y);
-            let distance = direction.length();
-
-            if distance < self.speed * dt {
-                // Close enough to the target
-                self.x = target_x;
-                self.y = target_y;
-                self.target_x = None;
-                self.target_y = None;
-                self.is_moving = false;
-            } else {
-                let direction = direction.normalize();
-                self.x += direction.x * self.speed * dt;
-                self.y += direction.y * self.speed * dt;
-                self.is_moving = true;
-            }
-            self.position_changed = true;
-        }
-
-        // Clear message after 15 seconds
-        if self.last_message_send_time.elapsed() >= Duration::from_secs(15) {
-            self.message = None;
-            self.last_message_send_time = Instant::now();
-        }
-
-        // Update pose
-        let now = Instant::now();
-        if self.is_moving {
-            if now.duration_since(self.last_pose_update_time) >= self.pose_update_interval {
-                self.current_pose_index = self.next_pose_index;
-                self.next_pose_index = (self.next_pose_index + 1) % RUN_POSES.len();
-                self.pose_interp_factor = 0.0;
-                self.last_pose_update_time = now;
-            } else {
-                self.pose_interp_factor +=
-                    1.0 / (self.pose_update_interval.as_secs_f32() * get_fps() as f32); // Assuming 60 FPS
-                if self.pose_interp_factor > 1.0 {
-                    self.pose_interp_factor = 1.0;
-                }
-            }
-
-            // Update bobbing when moving
-            self.bobbing_time += dt * 1.0; // Adjust speed as needed
-            self.bobbing_offset = (self.bobbing_time * 5.0).sin() * 5.0; // amplitude of 5.0
-        } else {
-            // Reset bobbing when not moving
-            self.bobbing_time = 0.0;
-            self.bobbing_offset = 0.0;
-        }
-    }
+// `--season <name>` pins the season instead of following the server
+fn season_arg() -> Option<Season> {
+    arg_value("--season").map(|name| Season::parse(&name).expect("Unknown season"))
+}
 
-    fn get_current_pose(&self) -> Pose {
-        if self.is_moving {
-            let start_pose = &RUN_POSES[self.current_pose_index];
-            let end_pose = &RUN_POSES[self.next_pose_index];
-            lerp_pose(start_pose, end_pose, self.pose_interp_factor)
-        } else {
-            IDLE_POSE
-        }
+fn add_races(game: &mut Game, ghost_replay: Option<&Replay>) {
+    for course in &MAP_COURSES {
+        let ghost = ghost_replay.and_then(|replay| Ghost::from_replay(replay, course.checkpoints));
+        game.minigames
+            .push(Box::new(CheckpointRace::new(course, ghost)));
     }
+}
 
-    fn draw(&self) {
-        // Apply bobbing offset
-        let y_offset = self.bobbing_offset;
-
-        // Draw hair
-        for line in &self.hair_lines {
-            draw_line(
-                self.x + line.0 .0,            // Start x (translated)
-                self.y + line.0 .1 + y_offset, // Start y (translated with bobbing)
-                self.x + line.1 .0,            // End x (translated)
-                self.y + line.1 .1 + y_offset, // End y (translated with bobbing)
-                1.0,                           // Thickness of hair strands
-                BROWN,                         // Color of hair
-            );
-        }
-
-        // Determine color based on whether it's the local player
-        let body_color = if self.is_local { RED } else { BLACK };
-
-        // Draw head
-        draw_circle(self.x, self.y + y_offset, 20.0, body_color);
-
-        // Draw eyes
-        let eye_color = WHITE;
-        draw_circle(self.x - 7.0, self.y - 5.0 + y_offset, 3.0, eye_color);
-        draw_circle(self.x + 7.0, self.y - 5.0 + y_offset, 3.0, eye_color);
-
-        // Draw mouth
-        let mouth_color = WHITE;
-        draw_line(
-            self.x - 7.0,
-            self.y + 5.0 + y_offset,
-            self.x,
-            self.y + 10.0 + y_offset,
-            2.0,
-            mouth_color,
-        );
-        draw_line(
-            self.x,
-            self.y + 10.0 + y_offset,
-            self.x + 7.0,
-            self.y + 5.0 + y_offset,
-            2.0,
-            mouth_color,
-        );
-
-        // Draw body
-        draw_line(
-            self.x,
-            self.y + 10.0 + y_offset,
-            self.x,
-            self.y + 40.0 + y_offset,
-            2.0,
-            body_color,
-        );
-
-        // Get interpolated pose
-        let pose = self.get_current_pose();
-
-        // Draw arms
-        draw_line(
-            self.x,
-            self.y + 20.0 + y_offset,
-            self.x + pose.left_arm.0,
-            self.y + pose.left_arm.1 + y_offset,
-            2.0,
-            body_color,
-        );
-        draw_line(
-            self.x,
-            self.y + 20.0 + y_offset,
-            self.x + pose.right_arm.0,
-            self.y + pose.right_arm.1 + y_offset,
-            2.0,
-            body_color,
-        );
+// Runs a server inside this process, with gameplay scripts from `--scripts <dir>`.
+// `--private` makes recording need everyone's consent.
+#[cfg(feature = "server")]
+fn host_server() {
+    let options = ServerOptions {
+        season_override: season_arg(),
+        scripts_dir: arg_value("--scripts").map(PathBuf::from),
+        private_room: std::env::args().any(|arg| arg == "--private"),
+        ..Default::default()
+    };
+    server::spawn(HOST_LISTEN_ADDR, options).expect("Failed to start server");
+}
 
-        // Draw legs
-        draw_line(
-            self.x,
-            self.y + 40.0 + y_offset,
-            self.x + pose.left_leg.0,
-            self.y + pose.left_leg.1 + y_offset,
-            2.0,
-            body_color,
-        );
-        draw_line(
-            self.x,
-            self.y + 40.0 + y_offset,
-            self.x + pose.right_leg.0,
-            self.y + pose.right_leg.1 + y_offset,
-            2.0,
-            body_color,
-        );
+#[cfg(not(feature = "server"))]
+fn host_server() {
+    eprintln!("--host needs a build with the server feature");
+    std::process::exit(2);
+}
 
-        // Draw message
-        if let Some(message) = &self.message {
-            // Draw black rectangle centered above player
-            draw_rectangle(
-                self.x - 75.0,
-                self.y - 70.0 + y_offset,
-                150.0,
-                50.0,
-                Color::new(0.0, 0.0, 0.0, 0.8),
-            );
-            draw_text(
-                message,
-                self.x - 50.0,
-                self.y - 35.0 + y_offset,
-                20.0,
-                WHITE,
-            );
-        }
+// `--host` runs a server inside this process and plays on it.
+// `--server <addr>` picks another server, otherwise `last_server` or the
+// default one.
+fn server_addr(last_server: Option<&str>) -> String {
+    let host = std::env::args().any(|arg| arg == "--host");
+    if host {
+        host_server();
+        HOST_CONNECT_ADDR.to_string()
+    } else {
+        arg_value("--server")
+            .or_else(|| last_server.map(str::to_string))
+            .unwrap_or_else(|| DEFAULT_SERVER_ADDR.to_string())
     }
 }
 
-struct Pose {
-    left_arm: (f32, f32),
-    right_arm: (f32, f32),
-    left_leg: (f32, f32),
-    right_leg: (f32, f32),
+// `--bots <n>` simulated players join `addr` alongside the local player
+#[cfg(feature = "bots")]
+fn spawn_bots(addr: &str, rt: &Runtime) {
+    if let Some(count) = arg_value("--bots") {
+        let count = count.parse().expect("--bots takes a number");
+        bot::spawn_bots(count, addr, rt).expect("Failed to start bots");
+    }
 }
 
-const RUN_POSES: [Pose; 5] = [
-    // Pose 1: Right leg forward, left arm forward
-    Pose {
-        left_arm: (-20.0, 30.0),
-        right_arm: (20.0, 30.0),
-        left_leg: (-10.0, 60.0),
-        right_leg: (15.0, 60.0),
-    },
-    // Pose 2: Both legs mid-motion, arms slightly bent
-    Pose {
-        left_arm: (-15.0, 30.0),
-        right_arm: (15.0, 30.0),
-        left_leg: (-5.0, 60.0),
-        right_leg: (10.0, 60.0),
-    },
-    // Pose 3: Left leg forward, right arm forward
-    Pose {
-        left_arm: (-20.0, 30.0),
-        right_arm: (20.0, 30.0),
-        left_leg: (15.0, 60.0),
-        right_leg: (-10.0, 60.0),
-    },
-    // Pose 4: Both legs mid-motion opposite to Pose 2
-    Pose {
-        left_arm: (-15.0, 30.0),
-        right_arm: (15.0, 30.0),
-        left_leg: (10.0, 60.0),
-        right_leg: (-5.0, 60.0),
-    },
-    // Pose 5: Neutral pose
-    Pose {
-        left_arm: (-20.0, 30.0),
-        right_arm: (20.0, 30.0),
-        left_leg: (-10.0, 60.0),
-        right_leg: (10.0, 60.0),
-    },
-];
-
-fn lerp_pose(start: &Pose, end: &Pose, t: f32) -> Pose {
-    Pose {
-        left_arm: (
-            start.left_arm.0 + (end.left_arm.0 - start.left_arm.0) * t,
-            start.left_arm.1 + (end.left_arm.1 - start.left_arm.1) * t,
-        ),
-        right_arm: (
-            start.right_arm.0 + (end.right_arm.0 - start.right_arm.0) * t,
-            start.right_arm.1 + (end.right_arm.1 - start.right_arm.1) * t,
-        ),
-        left_leg: (
-            start.left_leg.0 + (end.left_leg.0 - start.left_leg.0) * t,
-            start.left_leg.1 + (end.left_leg.1 - start.left_leg.1) * t,
-        ),
-        right_leg: (
-            start.right_leg.0 + (end.right_leg.0 - start.right_leg.0) * t,
-            start.right_leg.1 + (end.right_leg.1 - start.right_leg.1) * t,
-        ),
+#[cfg(not(feature = "bots"))]
+fn spawn_bots(_addr: &str, _rt: &Runtime) {
+    if arg_value("--bots").is_some() {
+        tracing::warn!(target: logging::GAME, "This build has no bots, ignoring --bots");
     }
 }
 
-struct Game {
-    local_player: Player,
-    other_players: Vec<Player>,
-    last_send_time: Instant,
-    send_interval: Duration,
-    message_send_interval: Duration,
+// Problems before there is anything to show end the process with a message
+// rather than a panic
+fn exit_with(e: Error) -> ! {
+    error!(target: logging::GAME, "{}", e);
+    std::process::exit(1);
 }
 
-impl Game {
-    fn new() -> Self {
-        Self {
-            local_player: Player::new_local(400.0, 300.0, 30.0, 30.0), // Start at center
-            other_players: Vec::new(),
-            last_send_time: Instant::now(),
-            send_interval: Duration::from_millis(16), // ~60 updates per second
-            message_send_interval: Duration::from_secs(1),
-        }
-    }
+fn connect(game: &Arc<Mutex<Game>>, rt: &Runtime) -> Connection {
+    let addr = server_addr(None);
+    let connection =
+        Connection::connect(&addr, Arc::clone(game), rt).unwrap_or_else(|e| exit_with(e));
+    spawn_bots(&addr, rt);
+    connection
+}
 
-    fn update(&mut self, dt: f32) {
-        self.handle_input(dt);
-        self.local_player.update(dt);
-        for player in &mut self.other_players {
-            player.update(dt);
+// `--headless` runs the client without a window, driven by `--script <path>`
+// or random wandering. `--duration <secs>` stops it after a while, `--seed <n>`
+// makes the run repeatable.
+fn run_headless() {
+    crash::set_headless(true);
+    let rt = net::runtime().unwrap_or_else(|e| exit_with(e));
+    shutdown::handle_ctrl_c(&rt);
+    let seed = arg_value("--seed")
+        .map(|seed| seed.parse().expect("--seed takes a number"))
+        .unwrap_or_else(::rand::random);
+    info!(target: logging::GAME, "Seed: {}", seed);
+    let game = Arc::new(Mutex::new(Game::with_seed(seed)));
+    {
+        let mut game = error::lock(&game);
+        game.headless = true;
+        game.apply_config(Config::load_or_default(Path::new(CONFIG_PATH)));
+        apply_login_args(&mut game);
+        if let Some(season) = season_arg() {
+            game.set_season(season, None);
+            game.season_locked = true;
         }
+        add_races(&mut game, None);
     }
+    let network = Some(connect(&game, &rt));
 
-    fn handle_input(&mut self, dt: f32) {
-        if is_key_pressed(KeyCode::R) {
-            self.local_player.current_pose_index = 0;
-            self.local_player.next_pose_index = 1;
-            self.local_player.pose_interp_factor = 0.0;
-            self.local_player.last_pose_update_time = Instant::now();
-        }
-
-        let mut direction = Vec2::ZERO;
-        if is_key_down(KeyCode::W) {
-            direction.y -= 1.0;
-        }
-        if is_key_down(KeyCode::S) {
-            direction.y += 1.0;
-        }
-        if is_key_down(KeyCode::A) {
-            direction.x -= 1.0;
-        }
-        if is_key_down(KeyCode::D) {
-            direction.x += 1.0;
-        }
-
-        if is_key_pressed(KeyCode::Space) {
-            let message = "Hello, world!".to_string();
-            self.local_player.message = Some(message.clone());
-            self.local_player.message_sent = false;
-        }
-
-        if is_key_pressed(KeyCode::G) {
-            let message = "Come over here.".to_string();
-            self.local_player.message = Some(message.clone());
-            self.local_player.message_sent = false;
-        }
+    let mut input = match arg_value("--script") {
+        Some(path) => ScriptedInput::from_file(Path::new(&path)).expect("Failed to load script"),
+        None => ScriptedInput::wander_with_seed(seed),
+    };
+    let duration = arg_value("--duration")
+        .map(|secs| Duration::from_secs_f32(secs.parse().expect("--duration takes seconds")));
 
-        if is_key_pressed(KeyCode::H) {
-            let message = "Okay.".to_string();
-            self.local_player.message = Some(message.clone());
-            self.local_player.message_sent = false;
-        }
+    headless::run(&game, &network, &mut input, duration);
 
-        // Determine if the player is moving via WASD
-        let mut is_moving = false;
-        if direction != Vec2::ZERO {
-            direction = direction.normalize();
-            self.local_player.x += direction.x * self.local_player.speed * dt;
-            self.local_player.y += direction.y * self.local_player.speed * dt;
-            self.local_player.position_changed = true;
-            is_moving = true;
-
-            // Clamp to screen
-            self.local_player.x = self
-                .local_player
-                .x
-                .clamp(0.0, 800.0 - self.local_player.width);
-            self.local_player.y = self
-                .local_player
-                .y
-                .clamp(0.0, 600.0 - self.local_player.height);
-        }
+    shutdown(&game, network, rt);
+}
 
-        if is_mouse_button_pressed(MouseButton::Right) {
-            // Changed from is_mouse_button_down
-            let mouse_pos = mouse_position();
-            self.local_player.target_x = Some(mouse_pos.0);
-            self.local_player.target_y = Some(mouse_pos.1);
-            is_moving = true;
-        }
+// `--log-level <filter>` sets how much is logged, e.g. `debug` or
+// `info,net=trace`. `--log-file <path>` writes the log to a file as well.
+fn init_logging() {
+    let filter = arg_value("--log-level").unwrap_or_else(|| logging::DEFAULT_LEVEL.to_string());
+    let file = arg_value("--log-file").map(PathBuf::from);
+    logging::init(&filter, file.as_deref()).expect("Failed to start logging");
+}
 
-        // Determine if the player is moving based on input or target position
-        self.local_player.is_moving = is_moving || self.local_player.target_x.is_some();
+// `--metrics <path>` writes performance counters to a JSON file now and then,
+// `--metrics-http <addr>` serves them, e.g. on 127.0.0.1:9100. Off otherwise.
+fn init_metrics() {
+    if let Some(path) = arg_value("--metrics") {
+        metrics::dump_to(Path::new(&path)).expect("Failed to start metrics");
     }
-
-    fn draw(&self) {
-        let mut local_player_drawn = false;
-
-        // Draw other players and insert the local player at the correct position
-        for player in &self.other_players {
-            if !local_player_drawn && self.local_player.y < player.y {
-                self.local_player.draw();
-                local_player_drawn = true;
-            }
-            player.draw();
-        }
-
-        // Draw the local player if it hasn't been drawn yet
-        if !local_player_drawn {
-            self.local_player.draw();
-        }
+    if let Some(addr) = arg_value("--metrics-http") {
+        metrics::serve(&addr).expect("Failed to serve metrics");
     }
 }
 
-// Idle pose definition
-const IDLE_POSE: Pose = Pose {
-    left_arm: (-20.0, 30.0),
-    right_arm: (20.0, 30.0),
-    left_leg: (-10.0, 60.0),
-    right_leg: (10.0, 60.0),
-};
-
-//window conf
-fn window_conf() -> Conf {
-    Conf {
-        window_title: "Smooth Multiplayer Game".to_owned(),
-        window_width: 800,
-        window_height: 600,
-        fullscreen: false,
-        ..Default::default()
+fn main() {
+    init_logging();
+    init_metrics();
+    crash::install_panic_hook();
+    if std::env::args().any(|arg| arg == "--headless") {
+        run_headless();
+    } else {
+        macroquad::Window::from_config(window_conf(), run_windowed());
     }
 }
 
-#[macroquad::main(window_conf)]
-async fn main() {
+async fn run_windowed() {
     // Define the target frame rate and frame duration
     const TARGET_FPS: u32 = 60;
     const TARGET_FRAME_DURATION: Duration = Duration::from_millis(1000 / TARGET_FPS as u64);
+    // Barely wake up while the app is in the background
+    const BACKGROUND_FRAME_DURATION: Duration = Duration::from_millis(250);
 
-    let rt = Runtime::new().unwrap();
+    let rt = net::runtime().unwrap_or_else(|e| exit_with(e));
+    shutdown::handle_ctrl_c(&rt);
 
-    let (handler, listener) = node::split::<()>();
-    let server_addr = "40.124.89.57:3042";
-
-    let (server_endpoint, _) = handler
-        .network()
-        .connect(Transport::FramedTcp, server_addr)
-        .expect("Failed to connect to server");
+    let gl = unsafe { get_internal_gl() }.quad_context.info();
+    crash::set_gpu(format!("{:?} {}", gl.backend, gl.gl_version_string));
 
     let game = Arc::new(Mutex::new(Game::new()));
-    let game_clone = Arc::clone(&game);
-    let handler_clone = handler.clone();
-
-    rt.spawn(async move {
-        listener.for_each(move |event| {
-            if let NodeEvent::Network(net_event) = event {
-                match net_event {
-                    NetEvent::Connected(_endpoint, _success) => {
-                        println!("Connected to server");
-                    }
-                    NetEvent::Accepted(_, _) => unreachable!(),
-                    NetEvent::Message(_endpoint, data) => {
-                        match bincode::deserialize::<ClientMessage>(&data) {
-                            Ok(message) => match message {
-                                ClientMessage::PlayerPosition { id, x, y } => {
-                                    let mut game = game_clone.lock().unwrap();
-                                    if id != game.local_player.id {
-                                        if let Some(player) =
-                                            game.other_players.iter_mut().find(|p| p.id == id)
-                                        {
-                                            player.target_x = Some(x);
-                                            player.target_y = Some(y);
-                                        } else {
-                                            game.other_players.push(Player::new_other(id, x, y));
-                                        }
-                                    }
-                                }
-                                ClientMessage::AssignPlayerId { id } => {
-                                    let mut game = game_clone.lock().unwrap();
-                                    println!("Assigned player id: {}", id);
-                                    game.local_player.id = id;
-                                }
-                                ClientMessage::OtherPlayerDisconnected { id } => {
-                                    let mut game = game_clone.lock().unwrap();
-                                    game.other_players.retain(|p| p.id != id);
-                                }
-                                ClientMessage::UpdateMessage { id, message } => {
-                                    let mut game = game_clone.lock().unwrap();
-                                    if id != game.local_player.id {
-                                        if let Some(player) =
-                                            game.other_players.iter_mut().find(|p| p.id == id)
-                                        {
-                                            player.last_message_send_time = Instant::now();
-                                            player.message = Some(message);
-                                        }
-                                    }
-                                }
-                            },
-                            Err(e) => {
-                                println!("Failed to deserialize message: {:?}", e);
-                            }
-                        }
-                    }
-                    NetEvent::Disconnected(_endpoint) => {
-                        println!("Disconnected from server");
-                    }
-                }
-            }
-        });
-    });
-
-    loop {
-        let frame_start = Instant::now();
+    error::lock(&game).apply_config(Config::load_or_default(Path::new(CONFIG_PATH)));
+    // Edits to lam.toml apply while playing
+    let mut config_watcher = ConfigWatcher::new(Path::new(CONFIG_PATH));
+    {
+        // The saved name is used unless `--name` picks another, which is then saved
+        let mut game = error::lock(&game);
+        game.settings = Settings::load();
+        if let Some(name) = game.settings.name.clone() {
+            game.player_name = name;
+        }
+        apply_login_args(&mut game);
+        game.settings.name = Some(game.player_name.clone());
+    }
+    if let Some(season) = season_arg() {
+        let mut game = error::lock(&game);
+        game.set_season(season, None);
+        game.season_locked = true;
+    }
 
-        let dt = get_frame_time();
+    // `--replay <path>` watches a recorded session instead of connecting
+    let mut playback = None;
+    let mut addr = String::new();
+    if let Some(path) = arg_value("--replay") {
+        let replay = Replay::import(Path::new(&path)).expect("Failed to load replay");
+        info!(
+            target: logging::GAME,
+            "Playing replay of {} players, {} ms",
+            replay.header.participants.len(),
+            replay.header.duration_ms
+        );
+        error::lock(&game).set_spectating();
+        playback = Some(ReplayPlayback::new(replay));
+    } else {
+        // `--ghost <path>` races the local player against a recorded run
+        let ghost_replay = arg_value("--ghost")
+            .map(|path| Replay::import(Path::new(&path)).expect("Failed to load ghost replay"));
 
-        // Update game state
         {
-            let mut game = game.lock().unwrap();
-            game.update(dt);
-        }
+            let mut game = error::lock(&game);
+            add_races(&mut game, ghost_replay.as_ref());
 
-        // Send heartbeat position to server every 1 second
-        {
-            let mut game = game.lock().unwrap();
-            if game.local_player.id != 0 && game.last_send_time.elapsed() >= Duration::from_secs(1)
-            {
-                let message = ClientMessage::PlayerPosition {
-                    id: game.local_player.id,
-                    x: game.local_player.x,
-                    y: game.local_player.y,
-                };
-                let serialized = bincode::serialize(&message).unwrap();
-                handler_clone.network().send(server_endpoint, &serialized);
-                println!("Sent heartbeat to server");
-                game.last_send_time = Instant::now();
+            addr = server_addr(game.settings.last_server.as_deref());
+            // A server hosted in this process isn't worth coming back to
+            if addr != HOST_CONNECT_ADDR {
+                game.settings.last_server = Some(addr.clone());
             }
         }
+        spawn_bots(&addr, &rt);
+    }
 
-        // Send position update if enough time has passed
-        {
-            let mut game = game.lock().unwrap();
-            if game.local_player.id != 0
-                && game.last_send_time.elapsed() >= game.send_interval
-                && game.local_player.position_changed
-            {
-                let message = ClientMessage::PlayerPosition {
-                    id: game.local_player.id,
-                    x: game.local_player.x,
-                    y: game.local_player.y,
-                };
-                let serialized = bincode::serialize(&message).unwrap();
-                handler_clone.network().send(server_endpoint, &serialized);
-                game.last_send_time = Instant::now();
-                game.local_player.position_changed = false;
-            }
+    let input = {
+        let settings = &error::lock(&game).settings;
+        KeyboardInput::new(settings.keys.clone(), settings.quick_chat.clone())
+    };
+    let mut assets = Assets::default();
+    assets.load_animations(ANIMATIONS_FILE);
+    ui::load_fonts(&Path::new(ASSETS_DIR).join(ui::FONTS_DIR));
+    // Edited textures, fonts, sounds and animations show up without a restart
+    // while developing
+    if cfg!(debug_assertions) {
+        assets.watch();
+    }
+    let mut session = Session {
+        rt,
+        game,
+        network: None,
+        playback,
+        address: TextField::with_text(MAX_ADDRESS_LENGTH, &addr),
+        server_addr: addr,
+        input,
+        assets,
+        lifecycle: lifecycle::MOBILE.then(Lifecycle::new),
+        transition: Transition::new(),
+    };
+    let mut scene = Scene::first(&session);
+
+    // Closing the window ends the loop so the shutdown sequence can run
+    prevent_quit();
+
+    loop {
+        if is_quit_requested() || shutdown::quit_requested() {
+            break;
         }
+        let frame_start = Instant::now();
 
-        {
-            let mut game = game.lock().unwrap();
-
-            if game.local_player.last_message_send_time.elapsed() >= game.message_send_interval {
-                if let Some(message) = &game.local_player.message {
-                    if !game.local_player.message_sent {
-                        let message = ClientMessage::UpdateMessage {
-                            id: game.local_player.id,
-                            message: message.clone(),
-                        };
-                        let serialized = bincode::serialize(&message).unwrap();
-                        handler_clone.network().send(server_endpoint, &serialized);
-                        println!("Sent message to server");
-                        game.local_player.message_sent = true;
-                        game.local_player.last_message_send_time = Instant::now();
-                    }
-                }
-            }
+        let dt = get_frame_time();
 
-            // After 15 seconds, clear the message
-            if game.local_player.last_message_send_time.elapsed() >= Duration::from_secs(15) {
-                game.local_player.message = None;
-                game.local_player.last_message_send_time = Instant::now();
-            }
+        if let Some(config) = config_watcher.poll() {
+            error::lock(&session.game).apply_config(config);
         }
+        session.assets.update();
+        // Scenes draw in UI pixels unless they set a camera of their own
+        let (ui_scale, color_theme) = {
+            let game = error::lock(&session.game);
+            (game.settings.ui_scale, game.settings.color_theme)
+        };
+        ui::set_scale(ui_scale);
+        palette::set(color_theme);
+        ui::set_ui_camera();
 
-        // Render
-        clear_background(WHITE);
-        {
-            let game = game.lock().unwrap();
-            game.draw();
+        match scene.frame(&mut session, dt) {
+            Some(next) => scene = next,
+            None => break,
         }
 
         // Display FPS (optional)
@@ -680,10 +351,21 @@ async fn main() {
 
         // Calculate frame duration
         let frame_duration = frame_start.elapsed();
+        {
+            let mut game = error::lock(&session.game);
+            game.latency.presented();
+            game.profiler.end_frame(frame_duration);
+        }
+        metrics::frame(frame_duration);
+
+        let target_frame_duration = match scene {
+            Scene::Background => BACKGROUND_FRAME_DURATION,
+            _ => TARGET_FRAME_DURATION,
+        };
 
         // Calculate remaining time to sleep
-        if frame_duration < TARGET_FRAME_DURATION {
-            let sleep_duration = TARGET_FRAME_DURATION - frame_duration;
+        if frame_duration < target_frame_duration {
+            let sleep_duration = target_frame_duration - frame_duration;
             // Convert Duration to f32 seconds for macroquad's sleep
             let sleep_duration_secs = sleep_duration.as_secs_f32();
             sleep(Duration::from_secs_f32(sleep_duration_secs));
@@ -693,4 +375,9 @@ async fn main() {
             // println!("Frame overrun: {:?}", frame_duration);
         }
     }
+
+    let Session {
+        rt, game, network, ..
+    } = session;
+    shutdown(&game, network, rt);
 }
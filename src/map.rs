@@ -0,0 +1,36 @@
+use crate::obstacle::{default_obstacles, Obstacle};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// Name of the built-in map, the one clients start on
+pub const DEFAULT_MAP: &str = "default";
+
+// A layout the server can switch everyone to while they play. Only the
+// server simulates it; clients hear the name and spawn point in `MapChange`
+// and the obstacles through the usual snapshots.
+#[derive(Serialize, Deserialize)]
+pub struct GameMap {
+    pub name: String,
+    pub spawn: (f32, f32), // Where players start and respawn on a change
+    pub obstacles: Vec<Obstacle>,
+}
+
+impl Default for GameMap {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_MAP.to_string(),
+            spawn: (400.0, 300.0),
+            obstacles: default_obstacles(),
+        }
+    }
+}
+
+impl GameMap {
+    // Reads a map saved as JSON
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        serde_json::from_str(&json).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
@@ -0,0 +1,202 @@
+use crate::error;
+use crate::logging;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// How often `--metrics <file>` is rewritten while playing
+const DUMP_INTERVAL: Duration = Duration::from_secs(10);
+// Upper bounds of the frame time buckets in milliseconds, the last one is open
+const FRAME_BUCKETS_MS: [u64; 7] = [4, 8, 16, 33, 50, 100, 250];
+
+// Counters for diagnosing performance reports from players. Nothing is
+// collected until `enable` is called, so the hooks cost one atomic load
+// otherwise. Process-wide, so bots in the same process are counted too.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static STARTED: OnceLock<Instant> = OnceLock::new();
+static DUMP_PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+static FRAMES: [AtomicU64; FRAME_BUCKETS_MS.len() + 1] =
+    [const { AtomicU64::new(0) }; FRAME_BUCKETS_MS.len() + 1];
+static LOCK_WAITS: AtomicU64 = AtomicU64::new(0);
+static LOCK_WAIT_TOTAL_US: AtomicU64 = AtomicU64::new(0);
+static LOCK_WAIT_MAX_US: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_IN: AtomicU64 = AtomicU64::new(0);
+static MESSAGES_OUT: AtomicU64 = AtomicU64::new(0);
+static BYTES_IN: AtomicU64 = AtomicU64::new(0);
+static BYTES_OUT: AtomicU64 = AtomicU64::new(0);
+static CONNECTS: AtomicU64 = AtomicU64::new(0);
+
+pub fn enable() {
+    STARTED.get_or_init(Instant::now);
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+// How long a frame took to update and present, sleeping excluded
+pub fn frame(duration: Duration) {
+    if !is_enabled() {
+        return;
+    }
+    let ms = duration.as_millis() as u64;
+    let bucket = FRAME_BUCKETS_MS
+        .iter()
+        .position(|&bound| ms < bound)
+        .unwrap_or(FRAME_BUCKETS_MS.len());
+    FRAMES[bucket].fetch_add(1, Ordering::Relaxed);
+}
+
+// `error::lock`, also counting how long it waited for the other thread
+pub fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    if !is_enabled() {
+        return error::lock(mutex);
+    }
+    let started = Instant::now();
+    let guard = error::lock(mutex);
+    let waited = started.elapsed().as_micros() as u64;
+    LOCK_WAITS.fetch_add(1, Ordering::Relaxed);
+    LOCK_WAIT_TOTAL_US.fetch_add(waited, Ordering::Relaxed);
+    LOCK_WAIT_MAX_US.fetch_max(waited, Ordering::Relaxed);
+    guard
+}
+
+pub fn message_in(bytes: usize) {
+    if is_enabled() {
+        MESSAGES_IN.fetch_add(1, Ordering::Relaxed);
+        BYTES_IN.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+pub fn message_out(bytes: usize) {
+    if is_enabled() {
+        MESSAGES_OUT.fetch_add(1, Ordering::Relaxed);
+        BYTES_OUT.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+// A connection to the server was made, every one after the first is a reconnect
+pub fn connected() {
+    if is_enabled() {
+        CONNECTS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FrameBucket {
+    pub below_ms: Option<u64>, // None for the open-ended last bucket
+    pub frames: u64,
+}
+
+// Everything collected so far, as written to the file or served over HTTP
+#[derive(Debug, Clone, Serialize)]
+pub struct MetricsReport {
+    pub uptime_secs: f64,
+    pub frame_ms: Vec<FrameBucket>,
+    pub lock_waits: u64,
+    pub lock_wait_total_ms: f64,
+    pub lock_wait_max_ms: f64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub reconnects: u64,
+}
+
+pub fn report() -> MetricsReport {
+    let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    let frame_ms = FRAMES
+        .iter()
+        .enumerate()
+        .map(|(i, frames)| FrameBucket {
+            below_ms: FRAME_BUCKETS_MS.get(i).copied(),
+            frames: load(frames),
+        })
+        .collect();
+    MetricsReport {
+        uptime_secs: STARTED
+            .get()
+            .map_or(0.0, |started| started.elapsed().as_secs_f64()),
+        frame_ms,
+        lock_waits: load(&LOCK_WAITS),
+        lock_wait_total_ms: load(&LOCK_WAIT_TOTAL_US) as f64 / 1000.0,
+        lock_wait_max_ms: load(&LOCK_WAIT_MAX_US) as f64 / 1000.0,
+        messages_in: load(&MESSAGES_IN),
+        messages_out: load(&MESSAGES_OUT),
+        bytes_in: load(&BYTES_IN),
+        bytes_out: load(&BYTES_OUT),
+        reconnects: load(&CONNECTS).saturating_sub(1),
+    }
+}
+
+fn report_json() -> String {
+    serde_json::to_string_pretty(&report()).expect("Metrics always serialize")
+}
+
+pub fn dump(path: &Path) -> io::Result<()> {
+    fs::write(path, report_json())
+}
+
+// Collects and writes the report to `path` every `DUMP_INTERVAL`, and once
+// more on shutdown through `save`
+pub fn dump_to(path: &Path) -> io::Result<()> {
+    enable();
+    *error::lock(&DUMP_PATH) = Some(path.to_path_buf());
+    let dump_path = path.to_path_buf();
+    thread::Builder::new()
+        .name("metrics-dump".to_string())
+        .spawn(move || loop {
+            thread::sleep(DUMP_INTERVAL);
+            if let Err(e) = dump(&dump_path) {
+                warn!(target: logging::METRICS, "Failed to write {}: {}", dump_path.display(), e);
+            }
+        })?;
+    info!(target: logging::METRICS, "Writing metrics to {}", path.display());
+    Ok(())
+}
+
+// Writes the final report if `dump_to` was set up
+pub fn save() {
+    let path = error::lock(&DUMP_PATH).clone();
+    if let Some(path) = path {
+        if let Err(e) = dump(&path) {
+            warn!(target: logging::METRICS, "Failed to write {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Collects and answers any HTTP request on `addr` with the report as JSON.
+// Meant for localhost; there is no authentication.
+pub fn serve(addr: &str) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    enable();
+    info!(target: logging::METRICS, "Serving metrics on http://{}", listener.local_addr()?);
+    thread::Builder::new()
+        .name("metrics-http".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                // The request itself doesn't matter, every path gets the report
+                let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+                let mut request_line = String::new();
+                let _ = BufReader::new(&stream).read_line(&mut request_line);
+                let body = report_json();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes());
+            }
+        })?;
+    Ok(())
+}
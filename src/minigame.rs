@@ -0,0 +1,23 @@
+use crate::protocol::ClientMessage;
+use hecs::{Entity, World};
+
+// An activity layered on top of the shared world, e.g. a checkpoint race.
+// Minigames are owned by `Game` and driven every frame alongside the players.
+pub trait Minigame: Send {
+    // `outbox` collects messages to send to the server this frame
+    fn update(
+        &mut self,
+        dt: f32,
+        world: &World,
+        local_player: Entity,
+        outbox: &mut Vec<ClientMessage>,
+    );
+
+    fn handle_message(&mut self, _message: &ClientMessage) {}
+
+    // Drawn in world space underneath the players
+    fn draw_world(&self) {}
+
+    // Drawn on top of everything
+    fn draw_ui(&self) {}
+}
@@ -0,0 +1,112 @@
+use crate::components::{Hidden, LocalPlayer, NetworkId, Position};
+use crate::palette;
+use crate::ui;
+use hecs::World;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+const MINIMAP_MARGIN: f32 = 10.0;
+// Widths the settings panel offers, the height follows the map's shape
+pub const MINIMAP_SIZES: [f32; 5] = [120.0, 160.0, 200.0, 260.0, 320.0];
+// Remote players farther than this from the local player aren't shown
+const NEARBY_RANGE: f32 = 1200.0;
+const LOCAL_COLOR: Color = RED;
+const REMOTE_COLOR: Color = BLACK;
+// How long a located player stays pointed out
+const LOCATE_TIME: Duration = Duration::from_secs(5);
+
+// The current map shrunk into the bottom right corner: its bounds, the part
+// the camera shows, the local player, remote players nearby and party
+// members wherever they are. A player being located is ringed wherever
+// they are too, and the map shows while they are even if it's turned off.
+#[derive(Default)]
+pub struct Minimap {
+    locating: Option<(usize, Instant)>, // Network id, and since when
+}
+
+impl Minimap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn locate(&mut self, id: usize) {
+        self.locating = Some((id, Instant::now()));
+    }
+
+    pub fn is_locating(&self) -> bool {
+        self.locating
+            .is_some_and(|(_, since)| since.elapsed() < LOCATE_TIME)
+    }
+
+    // `width` in UI pixels, `bounds` and `view` in world coordinates. `party`
+    // holds the network ids of the local player's party.
+    pub fn draw(&self, world: &World, bounds: Rect, view: Rect, width: f32, party: &[usize]) {
+        let scale = width / bounds.w;
+        let height = bounds.h * scale;
+        let x = ui::width() - width - MINIMAP_MARGIN;
+        let y = ui::height() - height - MINIMAP_MARGIN;
+        let to_map = |point: Vec2| {
+            let point = point.clamp(bounds.point(), bounds.point() + bounds.size());
+            Vec2::new(x, y) + (point - bounds.point()) * scale
+        };
+
+        draw_rectangle(x, y, width, height, Color::new(1.0, 1.0, 1.0, 0.6));
+        draw_rectangle_lines(x, y, width, height, 1.0, DARKGRAY);
+        let top_left = to_map(view.point());
+        let bottom_right = to_map(view.point() + view.size());
+        let shown = bottom_right - top_left;
+        draw_rectangle_lines(top_left.x, top_left.y, shown.x, shown.y, 1.0, GRAY);
+
+        // Hidden while spectating, then every remote player is shown
+        let local = world
+            .query::<&Position>()
+            .with::<&LocalPlayer>()
+            .without::<&Hidden>()
+            .iter()
+            .next()
+            .map(|position| Vec2::new(position.x, position.y));
+
+        let mut remotes = world
+            .query::<(&Position, &NetworkId)>()
+            .without::<&LocalPlayer>()
+            .without::<&Hidden>();
+        let located = self.locating.filter(|_| self.is_locating());
+        let mut members = Vec::new();
+        let mut found = None;
+        for (position, id) in remotes.iter() {
+            let position = Vec2::new(position.x, position.y);
+            if located.is_some_and(|(located, _)| located == id.0) {
+                found = Some(position);
+            }
+            if party.contains(&id.0) {
+                members.push(position);
+            } else if local.is_none_or(|local| position.distance(local) <= NEARBY_RANGE) {
+                let dot = to_map(position);
+                draw_circle(dot.x, dot.y, 2.5, REMOTE_COLOR);
+            }
+        }
+        // Over everyone else, ringed so they stand out
+        for position in members {
+            let dot = to_map(position);
+            draw_circle(dot.x, dot.y, 3.5, palette::current().party_chat);
+            draw_circle_lines(dot.x, dot.y, 4.5, 1.0, DARKGRAY);
+        }
+        if let Some(local) = local {
+            let dot = to_map(local);
+            draw_circle(dot.x, dot.y, 3.0, LOCAL_COLOR);
+        }
+        // A ring closing in on them, once a second
+        if let (Some(position), Some((_, since))) = (found, located) {
+            let dot = to_map(position);
+            let pulse = since.elapsed().as_secs_f32().fract();
+            draw_circle(dot.x, dot.y, 3.0, palette::current().selected);
+            draw_circle_lines(
+                dot.x,
+                dot.y,
+                14.0 - 9.0 * pulse,
+                2.0,
+                palette::current().selected,
+            );
+        }
+    }
+}
@@ -0,0 +1,229 @@
+use crate::components::{ChatBubble, LocalPlayer};
+use crate::crash;
+use crate::error::{self, Error};
+use crate::game::Game;
+use crate::logging;
+use crate::metrics;
+use crate::protocol::ClientMessage;
+use message_io::network::{Endpoint, NetEvent, Transport};
+use message_io::node::{self, NodeEvent, NodeHandler};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tracing::{debug, error, info, trace, trace_span, warn};
+
+// Heartbeat gap while the app is in the background, long enough to let the
+// radio sleep in between
+const BACKGROUND_HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connecting,
+    Connected,
+    Lost, // Never connected, or dropped since
+}
+
+// A live connection to the game server
+pub struct Connection {
+    handler: NodeHandler<()>,
+    endpoint: Endpoint,
+    state: Arc<Mutex<ConnectionState>>, // Updated by the background task
+    failure: Arc<Mutex<Option<Error>>>, // Why the state went to Lost, if known
+}
+
+// Starts the runtime the connections' background tasks run on
+pub fn runtime() -> error::Result<Runtime> {
+    Runtime::new().map_err(Error::Runtime)
+}
+
+impl Connection {
+    // Connects to `addr` and applies every message received to `game` on a
+    // background task
+    pub fn connect(addr: &str, game: Arc<Mutex<Game>>, rt: &Runtime) -> error::Result<Connection> {
+        let (handler, listener) = node::split::<()>();
+        let (endpoint, _) = handler
+            .network()
+            .connect(Transport::FramedTcp, addr)
+            .map_err(|source| Error::Connect {
+                addr: addr.to_string(),
+                source,
+            })?;
+        crash::set_server_addr(addr);
+        let state = Arc::new(Mutex::new(ConnectionState::Connecting));
+        let task_state = Arc::clone(&state);
+        let failure = Arc::new(Mutex::new(None));
+        let task_failure = Arc::clone(&failure);
+        // Marks the connection lost, the scene then disconnects and shows why
+        let fail = {
+            let state = Arc::clone(&state);
+            move |e: Error| {
+                error::lock(&task_failure).get_or_insert(e);
+                *error::lock(&state) = ConnectionState::Lost;
+            }
+        };
+
+        rt.spawn(async move {
+            listener.for_each(move |event| {
+                if let NodeEvent::Network(net_event) = event {
+                    match net_event {
+                        NetEvent::Connected(_endpoint, success) => {
+                            crash::set_connected(success);
+                            if success {
+                                info!(target: logging::NET, "Connected to server");
+                                metrics::connected();
+                                *error::lock(&task_state) = ConnectionState::Connected;
+                            } else {
+                                warn!(target: logging::NET, "Failed to connect to server");
+                                fail(Error::Refused);
+                            }
+                        }
+                        NetEvent::Accepted(_, _) => unreachable!(),
+                        NetEvent::Message(_endpoint, data) => {
+                            metrics::message_in(data.len());
+                            match bincode::deserialize::<ClientMessage>(data) {
+                                Ok(message) => {
+                                    crash::net_event(&format!("recv {:?}", message));
+                                    metrics::lock(&game).handle_message(message)
+                                }
+                                // Most likely a server on another protocol version, which
+                                // playing on would only make worse
+                                Err(e) => {
+                                    error!(target: logging::NET, "Failed to deserialize message: {:?}", e);
+                                    fail(Error::Decode(e));
+                                }
+                            }
+                        }
+                        NetEvent::Disconnected(_endpoint) => {
+                            info!(target: logging::NET, "Disconnected from server");
+                            crash::set_connected(false);
+                            *error::lock(&task_state) = ConnectionState::Lost;
+                        }
+                    }
+                }
+            });
+        });
+
+        Ok(Connection {
+            handler,
+            endpoint,
+            state,
+            failure,
+        })
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        *error::lock(&self.state)
+    }
+
+    // What ended the connection, for the disconnected screen
+    pub fn failure(&self) -> Option<String> {
+        error::lock(&self.failure).as_ref().map(Error::to_string)
+    }
+
+    pub fn send(&self, message: &ClientMessage) -> error::Result<()> {
+        crash::net_event(&format!("send {:?}", message));
+        let serialized = bincode::serialize(message).map_err(Error::Encode)?;
+        metrics::message_out(serialized.len());
+        self.handler.network().send(self.endpoint, &serialized);
+        Ok(())
+    }
+
+    // Stops the listener, which ends the background task once it notices
+    pub fn close(self) {
+        self.handler.network().remove(self.endpoint.resource_id());
+        self.handler.stop();
+    }
+}
+
+// Serializes a message for the server, also capturing it if a replay is being recorded
+pub fn send_message(network: &Option<Connection>, game: &mut Game, message: &ClientMessage) {
+    if let Some(recorder) = &mut game.recorder {
+        recorder.record(message);
+    }
+    if let ClientMessage::PlayerPosition { .. } = message {
+        game.latency.position_sent();
+    }
+    // Dropping one message is better than dropping the game
+    if let Some(connection) = network {
+        if let Err(e) = connection.send(message) {
+            error!(target: logging::NET, "{}", e);
+        }
+    }
+}
+
+// Sends whatever the game has queued up for the server this frame
+pub fn sync_network(game: &Mutex<Game>, network: &Option<Connection>) {
+    let _span = trace_span!(target: logging::NET, "sync").entered();
+
+    // Flush messages queued by game systems
+    {
+        let mut game = metrics::lock(game);
+        for message in std::mem::take(&mut game.outbox) {
+            send_message(network, &mut game, &message);
+        }
+    }
+
+    // Send heartbeat position to server every 1 second
+    {
+        let mut game = metrics::lock(game);
+        if game.local_id() != 0 && game.last_send_time.elapsed() >= Duration::from_secs(1) {
+            let message = game.position_message();
+            send_message(network, &mut game, &message);
+            trace!(target: logging::NET, "Sent heartbeat to server");
+            game.last_send_time = Instant::now();
+        }
+    }
+
+    // Send position update if enough time has passed
+    {
+        let mut game = metrics::lock(game);
+        if game.local_id() != 0
+            && game.last_send_time.elapsed() >= game.send_interval
+            && game.local::<LocalPlayer>().position_changed
+        {
+            let message = game.position_message();
+            send_message(network, &mut game, &message);
+            game.last_send_time = Instant::now();
+            game.local_mut::<LocalPlayer>().position_changed = false;
+        }
+    }
+
+    {
+        let mut game = metrics::lock(game);
+
+        let pending = if game.last_message_time.elapsed() >= game.message_send_interval {
+            game.unsent_messages.pop_front()
+        } else {
+            None
+        };
+        if let Some((message, expression, channel)) = pending {
+            let message = ClientMessage::UpdateMessage {
+                id: game.local_id(),
+                message,
+                expression,
+                channel,
+                sent_at_ms: 0,
+            };
+            send_message(network, &mut game, &message);
+            debug!(target: logging::NET, "Sent message to server");
+            game.last_message_time = Instant::now();
+            // The newest starts fading once it's out
+            if game.unsent_messages.is_empty() {
+                let mut bubble = game.local_mut::<ChatBubble>();
+                bubble.sent = true;
+                bubble.shown_at = Instant::now();
+            }
+        }
+    }
+}
+
+// Sends only the heartbeat, for an app in the background
+pub fn keep_alive(game: &Mutex<Game>, network: &Option<Connection>) {
+    let mut game = metrics::lock(game);
+    if game.local_id() != 0 && game.last_send_time.elapsed() >= BACKGROUND_HEARTBEAT_INTERVAL {
+        let message = game.position_message();
+        send_message(network, &mut game, &message);
+        trace!(target: logging::NET, "Sent background heartbeat to server");
+        game.last_send_time = Instant::now();
+    }
+}
@@ -0,0 +1,113 @@
+use crate::protocol::{ObstacleKind, ObstacleState};
+use serde::{Deserialize, Serialize};
+
+// How an obstacle moves. Positions are a pure function of server uptime so the
+// simulation needs no per-tick state.
+#[derive(Serialize, Deserialize)]
+pub enum ObstacleMotion {
+    // Slides back and forth between two points
+    Oscillate {
+        from: (f32, f32),
+        to: (f32, f32),
+        period_secs: f32,
+    },
+    // Walks a closed loop of waypoints at a constant speed
+    Patrol {
+        waypoints: Vec<(f32, f32)>,
+        speed: f32,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Obstacle {
+    pub id: u32,
+    pub kind: ObstacleKind,
+    pub width: f32,
+    pub height: f32,
+    pub motion: ObstacleMotion,
+}
+
+impl Obstacle {
+    // Top-left corner at `t_secs` of simulation time
+    pub fn position_at(&self, t_secs: f32) -> (f32, f32) {
+        match &self.motion {
+            ObstacleMotion::Oscillate {
+                from,
+                to,
+                period_secs,
+            } => {
+                // 0 -> 1 -> 0 over one period, eased at the ends
+                let phase = (t_secs / period_secs) * std::f32::consts::TAU;
+                let t = 0.5 - 0.5 * phase.cos();
+                (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+            }
+            ObstacleMotion::Patrol { waypoints, speed } => {
+                let legs: Vec<f32> = (0..waypoints.len())
+                    .map(|i| {
+                        let (a, b) = (waypoints[i], waypoints[(i + 1) % waypoints.len()]);
+                        ((b.0 - a.0).powi(2) + (b.1 - a.1).powi(2)).sqrt()
+                    })
+                    .collect();
+                let loop_length: f32 = legs.iter().sum();
+                if loop_length <= 0.0 {
+                    return waypoints.first().copied().unwrap_or((0.0, 0.0));
+                }
+
+                let mut distance = (t_secs * speed) % loop_length;
+                for (i, leg) in legs.iter().enumerate() {
+                    if distance <= *leg {
+                        let (a, b) = (waypoints[i], waypoints[(i + 1) % waypoints.len()]);
+                        let t = distance / leg;
+                        return (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+                    }
+                    distance -= leg;
+                }
+                waypoints[0]
+            }
+        }
+    }
+
+    pub fn state_at(&self, t_secs: f32) -> ObstacleState {
+        let (x, y) = self.position_at(t_secs);
+        ObstacleState {
+            id: self.id,
+            kind: self.kind,
+            x,
+            y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+}
+
+// Obstacles placed in the default world
+pub fn default_obstacles() -> Vec<Obstacle> {
+    vec![
+        Obstacle {
+            id: 1,
+            kind: ObstacleKind::Platform,
+            width: 120.0,
+            height: 30.0,
+            motion: ObstacleMotion::Oscillate {
+                from: (200.0, 280.0),
+                to: (480.0, 280.0),
+                period_secs: 6.0,
+            },
+        },
+        Obstacle {
+            id: 2,
+            kind: ObstacleKind::Hazard,
+            width: 40.0,
+            height: 40.0,
+            motion: ObstacleMotion::Patrol {
+                waypoints: vec![
+                    (250.0, 180.0),
+                    (530.0, 180.0),
+                    (530.0, 400.0),
+                    (250.0, 400.0),
+                ],
+                speed: 90.0,
+            },
+        },
+    ]
+}
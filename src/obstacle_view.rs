@@ -0,0 +1,177 @@
+use crate::components::{Appearance, LocalPlayer, Position, Velocity};
+use crate::protocol::{ObstacleKind, ObstacleState};
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+// Distance a hazard throws the local player
+const HAZARD_KNOCKBACK: f32 = 60.0;
+// What of the local player collides, around their position
+const BODY: Rect = Rect {
+    x: -15.0,
+    y: -20.0,
+    w: 30.0,
+    h: 80.0,
+};
+
+struct RemoteObstacle {
+    state: ObstacleState,
+    previous: (f32, f32), // Position in the snapshot before `state`
+}
+
+impl RemoteObstacle {
+    fn rect_at(&self, t: f32) -> Rect {
+        Rect::new(
+            self.previous.0 + (self.state.x - self.previous.0) * t,
+            self.previous.1 + (self.state.y - self.previous.1) * t,
+            self.state.width,
+            self.state.height,
+        )
+    }
+}
+
+// Client view of the server-simulated obstacles. Rendering runs one snapshot
+// behind and interpolates toward the latest one.
+pub struct Obstacles {
+    obstacles: Vec<RemoteObstacle>,
+    last_tick: u64,
+    received_at: Instant,
+    interval: Duration, // Measured time between snapshots
+}
+
+impl Default for Obstacles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Obstacles {
+    pub fn new() -> Self {
+        Self {
+            obstacles: Vec::new(),
+            last_tick: 0,
+            received_at: Instant::now(),
+            interval: Duration::from_millis(50),
+        }
+    }
+
+    pub fn apply_snapshot(&mut self, tick: u64, states: Vec<ObstacleState>) {
+        if tick <= self.last_tick {
+            return; // Out of order
+        }
+        self.last_tick = tick;
+
+        let now = Instant::now();
+        self.interval = now
+            .duration_since(self.received_at)
+            .clamp(Duration::from_millis(16), Duration::from_millis(200));
+        self.received_at = now;
+
+        let t = self.interp_factor();
+        self.obstacles = states
+            .into_iter()
+            .map(|state| {
+                // Continue from where the obstacle is currently drawn to avoid pops
+                let previous = self
+                    .obstacles
+                    .iter()
+                    .find(|o| o.state.id == state.id)
+                    .map(|o| {
+                        let rect = o.rect_at(t);
+                        (rect.x, rect.y)
+                    })
+                    .unwrap_or((state.x, state.y));
+                RemoteObstacle { state, previous }
+            })
+            .collect();
+    }
+
+    fn interp_factor(&self) -> f32 {
+        (self.received_at.elapsed().as_secs_f32() / self.interval.as_secs_f32()).min(1.0)
+    }
+
+    // Keeps the local player out of solid obstacles and applies hazard knockback
+    pub fn resolve_collision(
+        &self,
+        position: &mut Position,
+        velocity: &mut Velocity,
+        appearance: &Appearance,
+        local: &mut LocalPlayer,
+        bounds: &Rect,
+    ) {
+        let t = self.interp_factor();
+        for obstacle in &self.obstacles {
+            let rect = obstacle.rect_at(t);
+            let body = BODY.offset(vec2(position.x, position.y));
+            let Some(overlap) = body.intersect(rect) else {
+                continue;
+            };
+
+            let away = Vec2::new(
+                body.center().x - rect.center().x,
+                body.center().y - rect.center().y,
+            );
+            match obstacle.state.kind {
+                // Push out along the axis of least penetration
+                ObstacleKind::Platform => {
+                    if overlap.w < overlap.h {
+                        position.x += overlap.w * away.x.signum();
+                    } else {
+                        position.y += overlap.h * away.y.signum();
+                    }
+                }
+                ObstacleKind::Hazard => {
+                    let push = away.try_normalize().unwrap_or(Vec2::Y) * HAZARD_KNOCKBACK;
+                    position.x += push.x;
+                    position.y += push.y;
+                    velocity.target = None;
+                }
+            }
+            position.clamp_to(appearance, bounds);
+            local.position_changed = true;
+        }
+    }
+
+    // Where the local player's position can't be without touching an
+    // obstacle, as of the latest snapshot. Hazards are in the way as much
+    // as platforms.
+    pub fn blocking(&self) -> Vec<Rect> {
+        self.obstacles
+            .iter()
+            .map(|obstacle| {
+                let state = &obstacle.state;
+                Rect::new(
+                    state.x - BODY.x - BODY.w,
+                    state.y - BODY.y - BODY.h,
+                    state.width + BODY.w,
+                    state.height + BODY.h,
+                )
+            })
+            .collect()
+    }
+
+    // Each obstacle's id, where it is drawn, and where the latest snapshot put it
+    pub fn drawn_and_latest(&self) -> Vec<(u32, Rect, Rect)> {
+        let t = self.interp_factor();
+        self.obstacles
+            .iter()
+            .map(|obstacle| {
+                let state = &obstacle.state;
+                let latest = Rect::new(state.x, state.y, state.width, state.height);
+                (state.id, obstacle.rect_at(t), latest)
+            })
+            .collect()
+    }
+
+    pub fn draw(&self) {
+        let t = self.interp_factor();
+        for obstacle in &self.obstacles {
+            let rect = obstacle.rect_at(t);
+            let color = match obstacle.state.kind {
+                ObstacleKind::Platform => DARKGRAY,
+                ObstacleKind::Hazard => ORANGE,
+            };
+            draw_rectangle(rect.x, rect.y, rect.w, rect.h, color);
+            draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 2.0, BLACK);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::protocol::{ChatChannel, Severity};
+use crate::settings::ColorTheme;
+use macroquad::prelude::*;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+// Colors the game draws with that aren't the player's to pick. Body, hair
+// and outfit colors are chosen by each player and stay as they are.
+pub struct Palette {
+    pub face: Color,        // Eyes, mouth and eyebrows
+    pub pupil: Color,       // Inside wide eyes
+    pub shadow: Color,      // Under everyone's feet
+    pub bubble: Color,      // Behind chat messages
+    pub bubble_text: Color, // Global chat
+    pub local_chat: Color,
+    pub party_chat: Color,
+    pub system_chat: Color, // Command output in the chat log
+    pub whisper: Color,
+    pub name: Color,
+    pub name_backing: Color,
+    pub local_name: Color, // The local player's own name stands out
+    pub local_name_backing: Color,
+    pub alert: Color,  // Recording indicators
+    pub notice: Color, // Players joining and leaving
+    pub info: Color,   // Server announcements, critical ones use `alert`
+    pub warning: Color,
+    pub hovered: Color,  // Outline around the player under the mouse
+    pub selected: Color, // And around the one clicked on
+}
+
+pub const STANDARD: Palette = Palette {
+    face: WHITE,
+    pupil: BLACK,
+    shadow: Color::new(0.0, 0.0, 0.0, 0.35),
+    bubble: Color::new(0.0, 0.0, 0.0, 0.8),
+    bubble_text: WHITE,
+    local_chat: Color::new(0.7, 0.9, 1.0, 1.0),
+    party_chat: Color::new(0.6, 1.0, 0.6, 1.0),
+    system_chat: Color::new(0.8, 0.8, 0.8, 1.0),
+    whisper: Color::new(1.0, 0.65, 0.9, 1.0),
+    name: BLACK,
+    name_backing: Color::new(1.0, 1.0, 1.0, 0.5),
+    local_name: Color::new(0.1, 0.3, 0.8, 1.0),
+    local_name_backing: Color::new(1.0, 1.0, 1.0, 0.85),
+    alert: RED,
+    notice: Color::new(0.2, 0.2, 0.2, 1.0),
+    info: Color::new(0.55, 0.8, 1.0, 1.0),
+    warning: Color::new(1.0, 0.75, 0.2, 1.0),
+    hovered: Color::new(1.0, 1.0, 1.0, 0.6),
+    selected: Color::new(1.0, 0.85, 0.2, 1.0),
+};
+
+// Blue and orange in place of anything red
+pub const DEUTERANOPIA: Palette = Palette {
+    local_name: Color::new(0.0, 0.45, 0.7, 1.0),
+    party_chat: Color::new(1.0, 0.75, 0.3, 1.0),
+    alert: Color::new(0.9, 0.6, 0.0, 1.0),
+    warning: Color::new(0.95, 0.9, 0.4, 1.0),
+    ..STANDARD
+};
+
+// Solid backings and bright text, nothing see-through
+pub const HIGH_CONTRAST: Palette = Palette {
+    face: WHITE,
+    pupil: BLACK,
+    shadow: Color::new(0.0, 0.0, 0.0, 0.6),
+    bubble: BLACK,
+    bubble_text: YELLOW,
+    local_chat: Color::new(0.4, 1.0, 1.0, 1.0),
+    party_chat: Color::new(0.4, 1.0, 0.4, 1.0),
+    system_chat: WHITE,
+    whisper: Color::new(1.0, 0.5, 1.0, 1.0),
+    name: WHITE,
+    name_backing: BLACK,
+    local_name: YELLOW,
+    local_name_backing: BLACK,
+    alert: Color::new(1.0, 0.0, 1.0, 1.0),
+    notice: WHITE,
+    info: Color::new(0.4, 1.0, 1.0, 1.0),
+    warning: YELLOW,
+    hovered: WHITE,
+    selected: YELLOW,
+};
+
+impl Palette {
+    // Text said on `channel`
+    pub fn channel(&self, channel: ChatChannel) -> Color {
+        match channel {
+            ChatChannel::Global => self.bubble_text,
+            ChatChannel::Local => self.local_chat,
+            ChatChannel::Party => self.party_chat,
+        }
+    }
+
+    pub fn severity(&self, severity: Severity) -> Color {
+        match severity {
+            Severity::Info => self.info,
+            Severity::Warning => self.warning,
+            Severity::Critical => self.alert,
+        }
+    }
+}
+
+// Like the UI scale, everything drawn reads it
+static THEME: AtomicU8 = AtomicU8::new(0);
+
+pub fn current() -> &'static Palette {
+    match ColorTheme::ALL.get(THEME.load(Ordering::Relaxed) as usize) {
+        Some(ColorTheme::Deuteranopia) => &DEUTERANOPIA,
+        Some(ColorTheme::HighContrast) => &HIGH_CONTRAST,
+        Some(ColorTheme::Standard) | None => &STANDARD,
+    }
+}
+
+// Call once a frame, like `ui::set_scale`
+pub fn set(theme: ColorTheme) {
+    let index = ColorTheme::ALL
+        .iter()
+        .position(|&option| option == theme)
+        .unwrap_or(0);
+    THEME.store(index as u8, Ordering::Relaxed);
+}
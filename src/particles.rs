@@ -0,0 +1,238 @@
+use crate::animation::{current_pose, Foot};
+use crate::batch::{DrawList, Layer};
+use crate::components::{Animation, Hidden, Position};
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use hecs::World;
+use macroquad::prelude::*;
+
+// Characters' feet are this far below their position
+pub const FEET_OFFSET: f32 = 60.0;
+// Middle of a character's body, where join and leave puffs start
+const BODY_OFFSET: f32 = 20.0;
+const PUFF_COUNT: usize = 24;
+const POP_COUNT: usize = 12;
+const STEP_COUNT: usize = 6;
+
+// How an emitter's particles start out and change over their life
+#[derive(Clone, Copy, Debug)]
+pub struct EmitterConfig {
+    pub rate: f32,         // Per second, while emitting continuously
+    pub lifetime: f32,     // Seconds
+    pub speed: (f32, f32), // Range, in pixels a second
+    pub direction: f32,    // Radians, 0 is right and positive is down
+    pub spread: f32,       // Radians either side of `direction`
+    pub gravity: f32,      // Pixels a second squared, positive is down
+    pub drag: f32,         // Share of speed lost a second
+    pub size: (f32, f32),  // Radius at birth, then at death
+    pub color: (Color, Color),
+}
+
+// Kicked up where a walking character's foot lands
+pub const DUST: EmitterConfig = EmitterConfig {
+    rate: 0.0,
+    lifetime: 0.5,
+    speed: (10.0, 30.0),
+    direction: -std::f32::consts::FRAC_PI_2,
+    spread: 1.2,
+    gravity: 40.0,
+    drag: 2.0,
+    size: (2.5, 5.0),
+    color: (
+        Color::new(0.55, 0.48, 0.38, 0.5),
+        Color::new(0.55, 0.48, 0.38, 0.0),
+    ),
+};
+
+// Scattered when a chat bubble goes away
+pub const POP: EmitterConfig = EmitterConfig {
+    rate: 0.0,
+    lifetime: 0.35,
+    speed: (60.0, 120.0),
+    direction: 0.0,
+    spread: std::f32::consts::PI,
+    gravity: 0.0,
+    drag: 4.0,
+    size: (3.0, 1.0),
+    color: (
+        Color::new(0.1, 0.1, 0.1, 0.8),
+        Color::new(0.1, 0.1, 0.1, 0.0),
+    ),
+};
+
+// Where players appear and disappear
+pub const PUFF: EmitterConfig = EmitterConfig {
+    rate: 0.0,
+    lifetime: 0.7,
+    speed: (30.0, 90.0),
+    direction: 0.0,
+    spread: std::f32::consts::PI,
+    gravity: -20.0,
+    drag: 3.0,
+    size: (6.0, 12.0),
+    color: (
+        Color::new(0.9, 0.9, 0.9, 0.8),
+        Color::new(0.9, 0.9, 0.9, 0.0),
+    ),
+};
+
+pub struct Particle {
+    pub position: Vec2,
+    velocity: Vec2,
+    age: f32,
+}
+
+// Particles of one kind. Spawned continuously with `emit`, all at once with
+// `burst`, and gone once they outlive `EmitterConfig::lifetime`.
+pub struct ParticleEmitter {
+    pub config: EmitterConfig,
+    pub density: f32, // Scales how many are spawned, see `GraphicsQuality`
+    particles: Vec<Particle>,
+    owed: f32, // Fractions of a particle carried over between frames
+    rng: StdRng,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig) -> Self {
+        Self {
+            config,
+            density: 1.0,
+            particles: Vec::new(),
+            owed: 0.0,
+            rng: StdRng::from_entropy(),
+        }
+    }
+
+    // Spawns `rate` particles a second at `at`, call every frame it should
+    pub fn emit(&mut self, at: Vec2, dt: f32) {
+        self.owed += self.config.rate * self.density * dt;
+        let count = self.owed as usize;
+        self.owed -= count as f32;
+        self.spawn(at, count);
+    }
+
+    pub fn burst(&mut self, at: Vec2, count: usize) {
+        self.spawn(at, (count as f32 * self.density).ceil() as usize);
+    }
+
+    fn spawn(&mut self, at: Vec2, count: usize) {
+        let config = &self.config;
+        for _ in 0..count {
+            let angle = config.direction + self.rng.gen_range(-config.spread..=config.spread);
+            let speed = self.rng.gen_range(config.speed.0..=config.speed.1);
+            self.particles.push(Particle {
+                position: at,
+                velocity: Vec2::from_angle(angle) * speed,
+                age: 0.0,
+            });
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let config = &self.config;
+        self.particles.retain_mut(|particle| {
+            particle.age += dt;
+            particle.velocity.y += config.gravity * dt;
+            particle.velocity *= (1.0 - config.drag * dt).max(0.0);
+            particle.position += particle.velocity * dt;
+            particle.age < config.lifetime
+        });
+    }
+
+    pub fn particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    // Submits one of this emitter's particles, sized and colored for its age
+    pub fn draw_particle(&self, particle: &Particle, list: &mut DrawList) {
+        let t = (particle.age / self.config.lifetime).min(1.0);
+        let (size, color) = (self.config.size, self.config.color);
+        let radius = size.0 + (size.1 - size.0) * t;
+        let color = Color::new(
+            color.0.r + (color.1.r - color.0.r) * t,
+            color.0.g + (color.1.g - color.0.g) * t,
+            color.0.b + (color.1.b - color.0.b) * t,
+            color.0.a + (color.1.a - color.0.a) * t,
+        );
+        let Vec2 { x, y } = particle.position;
+        list.circle(Layer::Characters, x, y, radius, color);
+    }
+}
+
+// The particles around characters. Drawn by `render_system`, sorted in with
+// the characters by how far down the screen they are.
+pub struct CharacterEffects {
+    pub dust: ParticleEmitter,
+    pub pops: ParticleEmitter,
+    pub puffs: ParticleEmitter,
+}
+
+impl Default for CharacterEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CharacterEffects {
+    pub fn new() -> Self {
+        Self {
+            dust: ParticleEmitter::new(DUST),
+            pops: ParticleEmitter::new(POP),
+            puffs: ParticleEmitter::new(PUFF),
+        }
+    }
+
+    pub fn set_density(&mut self, density: f32) {
+        for emitter in self.emitters_mut() {
+            emitter.density = density;
+        }
+    }
+
+    // Kicks up dust under every foot that landed since the last update and
+    // moves every particle along. Call after `animation_system`.
+    pub fn update(&mut self, world: &World, dt: f32) {
+        let mut query = world
+            .query::<(&Position, &Animation)>()
+            .without::<&Hidden>();
+        for (position, animation) in query.iter() {
+            let Some(foot) = animation.step else {
+                continue;
+            };
+            let pose = current_pose(animation, true);
+            let (x, _) = match foot {
+                Foot::Left => pose.left_leg,
+                Foot::Right => pose.right_leg,
+            };
+            // On the ground, wherever the body is in its bob
+            self.dust.burst(
+                Vec2::new(position.x + x, position.y + FEET_OFFSET),
+                STEP_COUNT,
+            );
+        }
+        for emitter in self.emitters_mut() {
+            emitter.update(dt);
+        }
+    }
+
+    // A chat bubble centered on `at` went away
+    pub fn pop(&mut self, at: Vec2) {
+        self.pops.burst(at, POP_COUNT);
+    }
+
+    // A character at `position` joined or left
+    pub fn puff(&mut self, position: &Position) {
+        self.puffs
+            .burst(Vec2::new(position.x, position.y + BODY_OFFSET), PUFF_COUNT);
+    }
+
+    // Every live particle with the emitter that draws it
+    pub fn particles(&self) -> impl Iterator<Item = (&ParticleEmitter, &Particle)> {
+        [&self.dust, &self.pops, &self.puffs]
+            .into_iter()
+            .flat_map(|emitter| emitter.particles().iter().map(move |p| (emitter, p)))
+    }
+
+    fn emitters_mut(&mut self) -> [&mut ParticleEmitter; 3] {
+        [&mut self.dust, &mut self.pops, &mut self.puffs]
+    }
+}
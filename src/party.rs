@@ -0,0 +1,77 @@
+use crate::palette;
+use crate::ui;
+use macroquad::prelude::*;
+
+const PANEL_WIDTH: f32 = 200.0;
+const ROW_HEIGHT: f32 = 20.0;
+
+// The local player's party as the server last said, and the invitations
+// waiting on an answer. Invite with `/invite <name>` or from a player's
+// menu, answer with `/accept` or `/decline`.
+#[derive(Default)]
+pub struct Party {
+    pub name: Option<String>,
+    pub members: Vec<usize>, // Network ids, the local player's too
+    pub invites: Vec<usize>, // Network ids of whoever invited us, oldest first
+}
+
+impl Party {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn contains(&self, id: usize) -> bool {
+        self.members.contains(&id)
+    }
+
+    pub fn invited(&mut self, id: usize) {
+        if !self.invites.contains(&id) {
+            self.invites.push(id);
+        }
+    }
+
+    pub fn update(&mut self, name: Option<String>, members: Vec<usize>) {
+        self.name = name;
+        self.members = members;
+        let members = &self.members;
+        self.invites.retain(|id| !members.contains(id));
+    }
+
+    // The newest invitation, taken off the list
+    pub fn take_invite(&mut self) -> Option<usize> {
+        self.invites.pop()
+    }
+
+    // Down the left side of the screen, only while in a party or invited
+    pub fn draw(&self, local_id: usize, name: impl Fn(usize) -> String) {
+        if self.name.is_none() && self.invites.is_empty() {
+            return;
+        }
+        let palette = palette::current();
+        let rows = self.members.len() + self.invites.len() + 1;
+        let x = 10.0;
+        let mut y = ui::height() * 0.3;
+        draw_rectangle(
+            x - 4.0,
+            y - 16.0,
+            PANEL_WIDTH,
+            rows as f32 * ROW_HEIGHT + 8.0,
+            Color::new(0.0, 0.0, 0.0, 0.4),
+        );
+        let title = match &self.name {
+            Some(party) => format!("Party: {}", party),
+            None => "No party".to_string(),
+        };
+        ui::draw_text(&title, x, y, 18.0, palette.party_chat);
+        for &id in &self.members {
+            y += ROW_HEIGHT;
+            let you = if id == local_id { " (you)" } else { "" };
+            ui::draw_text(&format!("{}{}", name(id), you), x + 8.0, y, 16.0, WHITE);
+        }
+        for &id in &self.invites {
+            y += ROW_HEIGHT;
+            let text = format!("{} invited you", name(id));
+            ui::draw_text(&text, x + 8.0, y, 16.0, palette.system_chat);
+        }
+    }
+}
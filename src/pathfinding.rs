@@ -0,0 +1,407 @@
+use crate::components::Velocity;
+use crate::particles::FEET_OFFSET;
+use macroquad::prelude::*;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+// Side of a square of the walking grid, in world pixels
+const CELL: f32 = 16.0;
+// Squares looked at before giving up, so a huge map can't stall a frame
+const MAX_SEARCHED: usize = 40_000;
+// Reaching a waypoint puts the player exactly on it, anything further off
+// means something else moved them
+const ARRIVED: f32 = 1.0;
+
+// Where the player can stand, a square at a time. A square is blocked when
+// any part of it is inside an obstacle.
+struct Grid {
+    origin: Vec2,
+    columns: usize,
+    rows: usize,
+    blocked: Vec<bool>, // Row by row
+}
+
+impl Grid {
+    fn new(bounds: Rect, obstacles: &[Rect]) -> Self {
+        let columns = (bounds.w / CELL).ceil().max(1.0) as usize;
+        let rows = (bounds.h / CELL).ceil().max(1.0) as usize;
+        let mut blocked = vec![false; columns * rows];
+        for obstacle in obstacles {
+            let first_column = ((obstacle.x - bounds.x) / CELL).floor().max(0.0) as usize;
+            let first_row = ((obstacle.y - bounds.y) / CELL).floor().max(0.0) as usize;
+            let last_column = ((obstacle.right() - bounds.x) / CELL).ceil().max(0.0) as usize;
+            let last_row = ((obstacle.bottom() - bounds.y) / CELL).ceil().max(0.0) as usize;
+            for row in first_row..last_row.min(rows) {
+                for column in first_column..last_column.min(columns) {
+                    blocked[row * columns + column] = true;
+                }
+            }
+        }
+        Self {
+            origin: bounds.point(),
+            columns,
+            rows,
+            blocked,
+        }
+    }
+
+    // The square `point` is in, points outside count as the nearest edge
+    fn index(&self, point: Vec2) -> usize {
+        let column = ((point.x - self.origin.x) / CELL).clamp(0.0, (self.columns - 1) as f32);
+        let row = ((point.y - self.origin.y) / CELL).clamp(0.0, (self.rows - 1) as f32);
+        row as usize * self.columns + column as usize
+    }
+
+    fn center(&self, index: usize) -> Vec2 {
+        let (column, row) = (index % self.columns, index / self.columns);
+        self.origin + vec2(column as f32 + 0.5, row as f32 + 0.5) * CELL
+    }
+
+    // The open squares next to `index` and the cost of stepping there.
+    // Diagonal steps can't cut the corner of a blocked square.
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = (usize, f32)> + '_ {
+        let (column, row) = ((index % self.columns) as i64, (index / self.columns) as i64);
+        let open = move |dx: i64, dy: i64| {
+            let (x, y) = (column + dx, row + dy);
+            (x >= 0 && y >= 0 && x < self.columns as i64 && y < self.rows as i64)
+                .then(|| y as usize * self.columns + x as usize)
+                .filter(|&index| !self.blocked[index])
+        };
+        [
+            (-1, 0),
+            (1, 0),
+            (0, -1),
+            (0, 1),
+            (-1, -1),
+            (1, -1),
+            (-1, 1),
+            (1, 1),
+        ]
+        .into_iter()
+        .filter_map(move |(dx, dy)| {
+            let to = open(dx, dy)?;
+            if dx != 0 && dy != 0 {
+                open(dx, 0)?;
+                open(0, dy)?;
+                return Some((to, std::f32::consts::SQRT_2));
+            }
+            Some((to, 1.0))
+        })
+    }
+}
+
+// Steps on the way to `index`, cheapest first out of the heap
+struct Open {
+    estimate: f32,
+    index: usize,
+}
+
+impl PartialEq for Open {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+
+impl Eq for Open {}
+
+impl PartialOrd for Open {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Open {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+// Squares between two squares moving in 8 directions
+fn octile(grid: &Grid, from: usize, to: usize) -> f32 {
+    let dx = (from % grid.columns).abs_diff(to % grid.columns) as f32;
+    let dy = (from / grid.columns).abs_diff(to / grid.columns) as f32;
+    dx.max(dy) + (std::f32::consts::SQRT_2 - 1.0) * dx.min(dy)
+}
+
+// Nothing in `obstacles` is on the straight line from `from` to `to`
+fn is_clear(from: Vec2, to: Vec2, obstacles: &[Rect]) -> bool {
+    let steps = (from.distance(to) / (CELL / 4.0)).ceil().max(1.0) as usize;
+    (0..=steps).all(|step| {
+        let point = from.lerp(to, step as f32 / steps as f32);
+        !obstacles.iter().any(|obstacle| obstacle.contains(point))
+    })
+}
+
+// The points to walk through from `start` to `goal`, ending on `goal`, with
+// `obstacles` in the way. Positions outside `bounds` can't be stood on.
+// None when the goal is inside an obstacle or walled off.
+pub fn find_path(start: Vec2, goal: Vec2, bounds: Rect, obstacles: &[Rect]) -> Option<Vec<Vec2>> {
+    if is_clear(start, goal, obstacles) {
+        return Some(vec![goal]);
+    }
+    if obstacles.iter().any(|obstacle| obstacle.contains(goal)) {
+        return None;
+    }
+    let mut grid = Grid::new(bounds, obstacles);
+    let (from, to) = (grid.index(start), grid.index(goal));
+    if from == to {
+        return Some(vec![goal]);
+    }
+    // Squares the ends are in can be part covered, they're still open
+    grid.blocked[from] = false;
+    grid.blocked[to] = false;
+
+    let mut cost = vec![f32::INFINITY; grid.blocked.len()];
+    let mut came_from = vec![usize::MAX; grid.blocked.len()];
+    let mut open = BinaryHeap::new();
+    cost[from] = 0.0;
+    open.push(Open {
+        estimate: octile(&grid, from, to),
+        index: from,
+    });
+    let mut searched = 0;
+    while let Some(Open { index, .. }) = open.pop() {
+        if index == to {
+            break;
+        }
+        searched += 1;
+        if searched > MAX_SEARCHED {
+            return None;
+        }
+        for (next, step) in grid.neighbours(index) {
+            let next_cost = cost[index] + step;
+            if next_cost < cost[next] {
+                cost[next] = next_cost;
+                came_from[next] = index;
+                open.push(Open {
+                    estimate: next_cost + octile(&grid, next, to),
+                    index: next,
+                });
+            }
+        }
+    }
+    if cost[to].is_infinite() {
+        return None;
+    }
+
+    let mut squares = vec![to];
+    while let Some(&last) = squares.last() {
+        if last == from {
+            break;
+        }
+        squares.push(came_from[last]);
+    }
+    squares.reverse();
+    let mut points: Vec<Vec2> = squares.iter().map(|&index| grid.center(index)).collect();
+    points[0] = start;
+    *points.last_mut()? = goal;
+
+    // Cut the corners of the grid: from each point, skip to the furthest
+    // one in a straight line
+    let mut path = Vec::new();
+    let mut at = 0;
+    while at < points.len() - 1 {
+        let next = (at + 1..points.len())
+            .rev()
+            .find(|&next| is_clear(points[at], points[next], obstacles))
+            .unwrap_or(at + 1);
+        path.push(points[next]);
+        at = next;
+    }
+    Some(path)
+}
+
+// A click to move, walked a waypoint at a time through `Velocity::target`.
+// The front waypoint is always the one being walked to.
+#[derive(Default)]
+pub struct Route {
+    waypoints: VecDeque<Vec2>,
+}
+
+impl Route {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&mut self, waypoints: Vec<Vec2>, velocity: &mut Velocity) {
+        self.waypoints = waypoints.into();
+        velocity.target = self.waypoints.front().map(|point| (point.x, point.y));
+    }
+
+    // Still being walked, to the same square of the grid over `bounds` as
+    // `goal`
+    pub fn ends_near(&self, goal: Vec2, bounds: Rect) -> bool {
+        let square = |point: Vec2| ((point - bounds.point()) / CELL).floor();
+        self.waypoints
+            .back()
+            .is_some_and(|&end| square(end) == square(goal))
+    }
+
+    // Moves the end of the route to `goal` without searching again. False,
+    // leaving the route alone, when `obstacles` are in the way of the last
+    // leg, from the waypoint before the end or from `start` if there's none.
+    pub fn retarget(
+        &mut self,
+        start: Vec2,
+        goal: Vec2,
+        obstacles: &[Rect],
+        velocity: &mut Velocity,
+    ) -> bool {
+        let from = match self.waypoints.len() {
+            0 => return false,
+            1 => start,
+            len => self.waypoints[len - 2],
+        };
+        if !is_clear(from, goal, obstacles) {
+            return false;
+        }
+        if let Some(end) = self.waypoints.back_mut() {
+            *end = goal;
+        }
+        if self.waypoints.len() == 1 {
+            velocity.target = Some((goal.x, goal.y));
+        }
+        true
+    }
+
+    // Hands `velocity` the next waypoint once the player reaches one.
+    // Anything else changing the target, like a hazard knocking the player
+    // back or a new click, ends the route.
+    pub fn follow(&mut self, position: Vec2, velocity: &mut Velocity) {
+        let Some(&next) = self.waypoints.front() else {
+            return;
+        };
+        match velocity.target {
+            Some((x, y)) if vec2(x, y) == next => {}
+            None if position.distance(next) <= ARRIVED => {
+                self.waypoints.pop_front();
+                velocity.target = self.waypoints.front().map(|point| (point.x, point.y));
+                velocity.is_moving = velocity.target.is_some();
+            }
+            _ => self.waypoints.clear(),
+        }
+    }
+
+    // The rest of the way from `from`, along the ground in world coordinates
+    pub fn draw(&self, from: Vec2) {
+        let Some(&goal) = self.waypoints.back() else {
+            return;
+        };
+        let color = Color::new(1.0, 1.0, 1.0, 0.6);
+        let feet = vec2(0.0, FEET_OFFSET);
+        let mut last = from + feet;
+        for &point in &self.waypoints {
+            let point = point + feet;
+            draw_line(last.x, last.y, point.x, point.y, 2.0, color);
+            last = point;
+        }
+        let goal = goal + feet;
+        draw_circle_lines(goal.x, goal.y, 6.0, 2.0, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn velocity() -> Velocity {
+        Velocity {
+            speed: 1.0,
+            target: None,
+            is_moving: false,
+        }
+    }
+
+    // Walks `path` from `start`, checking no leg goes through `obstacles`
+    fn is_walkable(start: Vec2, path: &[Vec2], obstacles: &[Rect]) -> bool {
+        let mut from = start;
+        path.iter().all(|&to| {
+            let clear = is_clear(from, to, obstacles);
+            from = to;
+            clear
+        })
+    }
+
+    // A wall down the middle of `bounds` from the bottom, leaving a gap of
+    // two squares at the top
+    fn dividing_wall(bounds: Rect) -> Rect {
+        let middle = bounds.x + bounds.w / 2.0;
+        Rect::new(middle - 8.0, bounds.y + 2.0 * CELL, 16.0, bounds.h)
+    }
+
+    #[test]
+    fn routes_around_a_wall() {
+        let bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let wall = [Rect::new(90.0, 40.0, 20.0, 120.0)];
+        let (start, goal) = (vec2(20.0, 100.0), vec2(180.0, 100.0));
+        let path = find_path(start, goal, bounds, &wall).expect("no path around the wall");
+        assert!(path.len() > 1);
+        assert_eq!(path.last(), Some(&goal));
+        assert!(is_walkable(start, &path, &wall));
+    }
+
+    #[test]
+    fn goal_inside_an_obstacle_has_no_path() {
+        let bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let block = [Rect::new(90.0, 90.0, 40.0, 40.0)];
+        assert_eq!(
+            find_path(vec2(20.0, 20.0), vec2(100.0, 100.0), bounds, &block),
+            None
+        );
+    }
+
+    #[test]
+    fn walled_off_goal_has_no_path() {
+        let bounds = Rect::new(0.0, 0.0, 200.0, 200.0);
+        let walls = [
+            Rect::new(110.0, 110.0, 10.0, 80.0),
+            Rect::new(180.0, 110.0, 10.0, 80.0),
+            Rect::new(110.0, 110.0, 80.0, 10.0),
+            Rect::new(110.0, 180.0, 80.0, 10.0),
+        ];
+        assert_eq!(
+            find_path(vec2(20.0, 20.0), vec2(150.0, 150.0), bounds, &walls),
+            None
+        );
+    }
+
+    #[test]
+    fn gives_up_after_max_searched_squares() {
+        // The same detour, on a map small enough to search and one that isn't
+        let small = Rect::new(0.0, 0.0, 800.0, 800.0);
+        let wall = [dividing_wall(small)];
+        let path = find_path(vec2(300.0, 700.0), vec2(500.0, 700.0), small, &wall);
+        assert!(path.is_some_and(|path| is_walkable(vec2(300.0, 700.0), &path, &wall)));
+
+        let large = Rect::new(0.0, 0.0, 4800.0, 4800.0);
+        assert!((large.w / CELL) * (large.h / CELL) > MAX_SEARCHED as f32);
+        let wall = [dividing_wall(large)];
+        assert_eq!(
+            find_path(vec2(2300.0, 4700.0), vec2(2500.0, 4700.0), large, &wall),
+            None
+        );
+    }
+
+    #[test]
+    fn ends_near_measures_squares_from_the_bounds() {
+        // Squares start at x = 10, so 20 and 25 share one and 25 and 27 don't
+        let bounds = Rect::new(10.0, 0.0, 200.0, 200.0);
+        let mut route = Route::new();
+        route.set(vec![vec2(20.0, 5.0)], &mut velocity());
+        assert!(route.ends_near(vec2(25.0, 5.0), bounds));
+        route.set(vec![vec2(27.0, 5.0)], &mut velocity());
+        assert!(!route.ends_near(vec2(25.0, 5.0), bounds));
+    }
+
+    #[test]
+    fn retarget_refuses_a_goal_behind_an_obstacle() {
+        let wall = [Rect::new(50.0, 0.0, 10.0, 100.0)];
+        let mut velocity = velocity();
+        let mut route = Route::new();
+        route.set(vec![vec2(40.0, 50.0)], &mut velocity);
+        assert!(route.retarget(vec2(0.0, 50.0), vec2(45.0, 50.0), &wall, &mut velocity));
+        assert_eq!(velocity.target, Some((45.0, 50.0)));
+        assert!(!route.retarget(vec2(0.0, 50.0), vec2(70.0, 50.0), &wall, &mut velocity));
+        assert_eq!(velocity.target, Some((45.0, 50.0)));
+    }
+}
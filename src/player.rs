@@ -0,0 +1,253 @@
+use crate::batch::ShapeMesh;
+use crate::components::{
+    Animation, Appearance, ChatBubble, LocalPlayer, NetworkId, Position, SnapshotBuffer, Velocity,
+};
+use crate::protocol::{now_millis, Cosmetics, Equipment, Face, Hair, HairStyle, OUTFIT_COLORS};
+use crate::sprite::sheet_file;
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use hecs::{Entity, World};
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Number of remote snapshots kept per player
+pub const SNAPSHOT_BUFFER_LEN: usize = 32;
+// A remote player with no snapshot for this long is considered stale
+pub const STALE_SNAPSHOT_AGE: Duration = Duration::from_secs(3);
+
+pub const PLAYER_SPEED: f32 = 250.0;
+const HEAD_RADIUS: f32 = 20.0;
+// Share of the usual speed a sneaking player moves at
+pub const SNEAK_SPEED: f32 = 0.4;
+// Shares of the usual speed for walking and sprinting
+pub const WALK_SPEED: f32 = 0.55;
+pub const SPRINT_SPEED: f32 = 1.6;
+
+// Names and RGB of the body colors offered on the character screen. Remote
+// players who haven't picked one get one of these by id, never the first,
+// which is the local player's.
+pub const BODY_COLORS: [(&str, [u8; 3]); 8] = [
+    ("red", [230, 41, 55]),
+    ("black", [20, 20, 20]),
+    ("tan", [210, 160, 110]),
+    ("brown", [120, 80, 50]),
+    ("olive", [110, 120, 50]),
+    ("teal", [30, 130, 130]),
+    ("purple", [110, 60, 150]),
+    ("orange", [230, 120, 30]),
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Snapshot {
+    pub tick: u64,
+    pub timestamp_ms: u64,
+    pub received_at: Instant,
+    pub x: f32,
+    pub y: f32,
+}
+
+impl SnapshotBuffer {
+    pub fn new() -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(SNAPSHOT_BUFFER_LEN),
+            latency_ms: None,
+        }
+    }
+
+    // Places a remote snapshot on the timeline. Returns false if the snapshot
+    // is older than the latest one we have and was dropped.
+    pub fn push(&mut self, tick: u64, timestamp_ms: u64, x: f32, y: f32) -> bool {
+        if let Some(latest) = self.snapshots.back() {
+            if tick < latest.tick || (tick == latest.tick && timestamp_ms < latest.timestamp_ms) {
+                return false;
+            }
+        }
+
+        if self.snapshots.len() == SNAPSHOT_BUFFER_LEN {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot {
+            tick,
+            timestamp_ms,
+            received_at: Instant::now(),
+            x,
+            y,
+        });
+        self.latency_ms = Some(now_millis().saturating_sub(timestamp_ms));
+        true
+    }
+
+    pub fn is_stale(&self) -> bool {
+        match self.snapshots.back() {
+            Some(snapshot) => snapshot.received_at.elapsed() >= STALE_SNAPSHOT_AGE,
+            None => false,
+        }
+    }
+}
+
+// One strand of hair, from the scalp to its tip
+pub type Strand = ((f32, f32), (f32, f32));
+
+// Grows `hair.strands` strands in `hair.style` around the top of the head
+pub fn generate_hair(rng: &mut impl Rng, hair: &Hair) -> Vec<Strand> {
+    let mut hair_lines = Vec::with_capacity(hair.strands as usize);
+    let base_hair_length = hair.length as f32;
+
+    for _ in 0..hair.strands {
+        match hair.style {
+            HairStyle::Messy => {
+                let angle = rng.gen_range(-180.0_f32.to_radians()..180.0_f32.to_radians());
+                let angle_variation = rng.gen_range(-5.0_f32.to_radians()..5.0_f32.to_radians());
+
+                let start_x = 15.0 * angle.cos();
+                let start_y = -30.0 + rng.gen_range(0.0..10.0);
+
+                let hair_length = base_hair_length + rng.gen_range(-5.0..5.0);
+
+                let end_x = start_x + hair_length * (angle + angle_variation).cos();
+                let end_y = start_y
+                    + hair_length * (angle + angle_variation).sin()
+                    + rng.gen_range(0.0..5.0);
+
+                hair_lines.push(((start_x, start_y), (end_x, end_y)));
+            }
+            HairStyle::Spiky => {
+                // Outward from the top half of the head
+                let angle = rng.gen_range(-170.0_f32.to_radians()..-10.0_f32.to_radians());
+                let (start_x, start_y) = (18.0 * angle.cos(), 18.0 * angle.sin());
+                let hair_length = base_hair_length + rng.gen_range(-5.0..5.0);
+                let end_x = start_x + hair_length * angle.cos();
+                let end_y = start_y + hair_length * angle.sin();
+                hair_lines.push(((start_x, start_y), (end_x, end_y)));
+            }
+            HairStyle::Straight => {
+                // From the crown, falling down past the sides of the face
+                let angle = rng.gen_range(-180.0_f32.to_radians()..0.0);
+                let (start_x, start_y) = (20.0 * angle.cos(), 20.0 * angle.sin());
+                let hair_length = base_hair_length + rng.gen_range(-5.0..5.0);
+                let end_x = start_x + angle.cos() * rng.gen_range(0.0..4.0);
+                let end_y = start_y + hair_length;
+                hair_lines.push(((start_x, start_y), (end_x, end_y)));
+            }
+            HairStyle::Curly => {
+                // A short zigzag, one line per kink
+                let angle = rng.gen_range(-180.0_f32.to_radians()..0.0);
+                let mut from = (20.0 * angle.cos(), 20.0 * angle.sin());
+                let hair_length = base_hair_length + rng.gen_range(-5.0..5.0);
+                let kinks = 3;
+                let step = hair_length / kinks as f32;
+                for kink in 0..kinks {
+                    let side = if kink % 2 == 0 { 1.0 } else { -1.0 };
+                    let turn = angle + side * 60.0_f32.to_radians();
+                    let to = (from.0 + step * turn.cos(), from.1 + step * turn.sin());
+                    hair_lines.push((from, to));
+                    from = to;
+                }
+            }
+        }
+    }
+
+    hair_lines
+}
+
+// Hair as one mesh around the head's center, so drawing it doesn't redo the
+// geometry of every strand each frame. Points farther from the scalp sway
+// more in the wind, those twice the head's radius out the full
+// `Animation::hair_sway`.
+pub fn hair_mesh(lines: &[Strand]) -> ShapeMesh {
+    let mut mesh = ShapeMesh::new();
+    for &((x1, y1), (x2, y2)) in lines {
+        mesh.line(vec2(x1, y1), vec2(x2, y2), 1.0);
+    }
+    mesh.set_weights(|point| (point.length() - HEAD_RADIUS).max(0.0) / HEAD_RADIUS);
+    mesh
+}
+
+// The same `hair_seed` and `hair` always grow the same hair
+pub fn appearance(body_color: Color, hair_seed: u64, hair: &Hair) -> Appearance {
+    Appearance {
+        width: 30.0,
+        height: 30.0,
+        body_color,
+        hair: Arc::new(hair_mesh(&generate_hair(
+            &mut StdRng::seed_from_u64(hair_seed),
+            hair,
+        ))),
+        hair_color: rgb(hair.color),
+        face: Face::default(),
+        shirt: None,
+        pants: None,
+        equipment: Equipment::default(),
+        sprite: None,
+    }
+}
+
+// Replaces the default look with one picked by the player
+pub fn apply_cosmetics(appearance: &mut Appearance, cosmetics: &Cosmetics) {
+    *appearance = self::appearance(
+        rgb(cosmetics.body_color),
+        cosmetics.hair_seed,
+        &cosmetics.hair,
+    );
+    appearance.face = cosmetics.face;
+    appearance.shirt = cosmetics.outfit.shirt.and_then(outfit_color);
+    appearance.pants = cosmetics.outfit.pants.and_then(outfit_color);
+    appearance.equipment = cosmetics.equipment;
+    appearance.sprite = cosmetics.sprite.map(sheet_file);
+}
+
+pub fn rgb([r, g, b]: [u8; 3]) -> Color {
+    Color::from_rgba(r, g, b, 255)
+}
+
+pub fn outfit_color(index: u8) -> Option<Color> {
+    OUTFIT_COLORS
+        .get(index as usize)
+        .map(|&(_, color)| rgb(color))
+}
+
+// Until they pick a look remote players differ by id
+fn remote_body_color(id: usize) -> Color {
+    rgb(BODY_COLORS[1 + id % (BODY_COLORS.len() - 1)].1)
+}
+
+// Components shared by every character, local or remote
+fn character(
+    x: f32,
+    y: f32,
+    body_color: Color,
+    hair_seed: u64,
+) -> (Position, Velocity, Appearance, Animation, ChatBubble) {
+    (
+        Position { x, y },
+        Velocity {
+            speed: PLAYER_SPEED,
+            target: None,
+            is_moving: false,
+        },
+        appearance(body_color, hair_seed, &Hair::default()),
+        Animation::default(),
+        ChatBubble::default(),
+    )
+}
+
+pub fn spawn_local_player(world: &mut World, x: f32, y: f32, hair_seed: u64) -> Entity {
+    let entity = world.spawn(character(x, y, rgb(BODY_COLORS[0].1), hair_seed));
+    world
+        .insert(entity, (NetworkId(0), LocalPlayer::default()))
+        .expect("Entity was just spawned");
+    entity
+}
+
+pub fn spawn_remote_player(world: &mut World, id: usize, x: f32, y: f32) -> Entity {
+    // Seeded by id so every client draws a remote player with the same hair
+    let mut components = character(x, y, remote_body_color(id), id as u64);
+    components.1.target = Some((x, y));
+    let entity = world.spawn(components);
+    world
+        .insert(entity, (NetworkId(id), SnapshotBuffer::new()))
+        .expect("Entity was just spawned");
+    entity
+}
@@ -0,0 +1,81 @@
+use crate::palette;
+use crate::protocol::{ClientMessage, PlayerListEntry};
+use crate::ui;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+const PANEL_WIDTH: f32 = 360.0;
+const ROW_HEIGHT: f32 = 22.0;
+// How often the list is asked for again while it's held open
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// Everyone connected, shown while the `player_list` key is held. The server
+// is asked when it opens and every second after, so players out of range
+// and pings show too.
+#[derive(Default)]
+pub struct PlayerListPanel {
+    players: Vec<PlayerListEntry>,
+    requested_at: Option<Instant>, // None while closed
+}
+
+impl PlayerListPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.requested_at.is_some()
+    }
+
+    pub fn update(&mut self, held: bool, outbox: &mut Vec<ClientMessage>) {
+        if !held {
+            self.requested_at = None;
+            return;
+        }
+        if self
+            .requested_at
+            .is_none_or(|at| at.elapsed() >= REFRESH_INTERVAL)
+        {
+            outbox.push(ClientMessage::RequestPlayerList);
+            self.requested_at = Some(Instant::now());
+        }
+    }
+
+    pub fn set(&mut self, players: Vec<PlayerListEntry>) {
+        self.players = players;
+    }
+
+    // The last list the server sent, which may be a moment old when it opens
+    pub fn draw(&self, local_id: usize) {
+        if !self.is_open() {
+            return;
+        }
+        let height = 50.0 + self.players.len() as f32 * ROW_HEIGHT;
+        let x = (ui::width() - PANEL_WIDTH) / 2.0;
+        let y = 60.0;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.9));
+        draw_rectangle_lines(x, y, PANEL_WIDTH, height, 2.0, DARKGRAY);
+        let title = format!("{} online", self.players.len());
+        ui::draw_text(&title, x + 12.0, y + 24.0, 22.0, BLACK);
+
+        for (index, player) in self.players.iter().enumerate() {
+            let row_y = y + 48.0 + index as f32 * ROW_HEIGHT;
+            let name = player
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("Player {}", player.id));
+            let color = if player.id == local_id {
+                palette::current().local_name
+            } else {
+                BLACK
+            };
+            ui::draw_text(&name, x + 12.0, row_y, 18.0, color);
+            let id = format!("#{}", player.id);
+            ui::draw_text(&id, x + PANEL_WIDTH - 150.0, row_y, 18.0, GRAY);
+            let ping = player
+                .ping_ms
+                .map_or("-".to_string(), |ms| format!("{} ms", ms));
+            ui::draw_text(&ping, x + PANEL_WIDTH - 80.0, row_y, 18.0, DARKGRAY);
+        }
+    }
+}
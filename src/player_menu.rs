@@ -0,0 +1,126 @@
+use crate::ui;
+use macroquad::prelude::*;
+
+const WIDTH: f32 = 160.0;
+const ROW_HEIGHT: f32 = 24.0;
+
+// Something to do about another player, from their menu
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlayerAction {
+    Whisper, // Opens the chat line with `/w <name> `
+    Mute,
+    Unmute,
+    Invite, // To the local player's party
+    AddFriend,
+    RemoveFriend,
+    Locate, // Points them out on the minimap
+}
+
+impl PlayerAction {
+    pub fn label(self) -> &'static str {
+        match self {
+            PlayerAction::Whisper => "Whisper",
+            PlayerAction::Mute => "Mute",
+            PlayerAction::Unmute => "Unmute",
+            PlayerAction::Invite => "Invite to party",
+            PlayerAction::AddFriend => "Add friend",
+            PlayerAction::RemoveFriend => "Remove friend",
+            PlayerAction::Locate => "Locate on minimap",
+        }
+    }
+}
+
+struct OpenMenu {
+    id: usize, // Network id of the player it's about
+    name: String,
+    at: Vec2, // Top left, in UI pixels
+    actions: Vec<PlayerAction>,
+}
+
+// The menu right clicking another player, or clicking their name in the
+// chat log, opens at the mouse. Any click
+// closes it, picking the action under the mouse if there is one.
+#[derive(Default)]
+pub struct PlayerMenu {
+    open: Option<OpenMenu>,
+}
+
+impl PlayerMenu {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // While open, clicks belong to the menu
+    pub fn is_open(&self) -> bool {
+        self.open.is_some()
+    }
+
+    pub fn open(&mut self, id: usize, name: String, actions: Vec<PlayerAction>) {
+        let at = ui::mouse();
+        // Kept on screen
+        let height = (actions.len() + 1) as f32 * ROW_HEIGHT;
+        let at = vec2(
+            at.x.min(ui::width() - WIDTH).max(0.0),
+            at.y.min(ui::height() - height).max(0.0),
+        );
+        self.open = Some(OpenMenu {
+            id,
+            name,
+            at,
+            actions,
+        });
+    }
+
+    // Closes the menu if it's about `id`, e.g. when they leave
+    pub fn close_for(&mut self, id: usize) {
+        if self.open.as_ref().is_some_and(|menu| menu.id == id) {
+            self.open = None;
+        }
+    }
+
+    // The player and action picked this frame, if any
+    pub fn update(&mut self) -> Option<(usize, PlayerAction)> {
+        let menu = self.open.as_ref()?;
+        if is_key_pressed(KeyCode::Escape) {
+            self.open = None;
+            return None;
+        }
+        if !is_mouse_button_pressed(MouseButton::Left)
+            && !is_mouse_button_pressed(MouseButton::Right)
+        {
+            return None;
+        }
+        let mouse = ui::mouse() - menu.at;
+        let picked = if is_mouse_button_pressed(MouseButton::Left)
+            && (0.0..WIDTH).contains(&mouse.x)
+            && mouse.y >= ROW_HEIGHT
+        {
+            let row = (mouse.y / ROW_HEIGHT) as usize - 1;
+            menu.actions.get(row).map(|&action| (menu.id, action))
+        } else {
+            None
+        };
+        self.open = None;
+        picked
+    }
+
+    pub fn draw(&self) {
+        let Some(menu) = &self.open else {
+            return;
+        };
+        let (x, y) = (menu.at.x, menu.at.y);
+        let height = (menu.actions.len() + 1) as f32 * ROW_HEIGHT;
+        draw_rectangle(x, y, WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, WIDTH, height, 2.0, DARKGRAY);
+        ui::draw_text(&menu.name, x + 8.0, y + 17.0, 18.0, DARKGRAY);
+
+        let mouse = ui::mouse();
+        for (index, action) in menu.actions.iter().enumerate() {
+            let row_y = y + (index + 1) as f32 * ROW_HEIGHT;
+            if Rect::new(x, row_y, WIDTH, ROW_HEIGHT).contains(mouse) {
+                draw_rectangle(x + 2.0, row_y, WIDTH - 4.0, ROW_HEIGHT, LIGHTGRAY);
+            }
+            ui::draw_text(action.label(), x + 8.0, row_y + 17.0, 18.0, BLACK);
+        }
+    }
+}
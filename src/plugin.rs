@@ -0,0 +1,103 @@
+use crate::commands::CommandResult;
+use crate::protocol::ClientMessage;
+use hecs::{Entity, World};
+
+// An optional client feature, e.g. a stats overlay. Plugins only
+// observe the game; anything that talks to the server belongs in a `Minigame`.
+pub trait ClientPlugin: Send {
+    // Used to toggle the plugin with `/plugin <name>`
+    fn name(&self) -> &str;
+
+    fn update(&mut self, _dt: f32, _world: &World, _local_player: Entity) {}
+
+    fn on_message(&mut self, _message: &ClientMessage) {}
+
+    // Drawn on top of everything, under the menus
+    fn draw_overlay(&self, _world: &World, _local_player: Entity) {}
+
+    // Chat commands the plugin answers, without the slash, listed by `/help`
+    fn commands(&self) -> &[&'static str] {
+        &[]
+    }
+
+    // Runs one of `commands`, its output goes to the chat log
+    fn run_command(&mut self, _name: &str, _args: &str) -> CommandResult {
+        Ok(None)
+    }
+}
+
+struct Entry {
+    plugin: Box<dyn ClientPlugin>,
+    enabled: bool,
+}
+
+// Plugins registered with `Game`. Disabled plugins get no hooks at all.
+#[derive(Default)]
+pub struct PluginRegistry {
+    entries: Vec<Entry>,
+}
+
+impl PluginRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, plugin: Box<dyn ClientPlugin>, enabled: bool) {
+        self.entries.push(Entry { plugin, enabled });
+    }
+
+    // Flips a plugin on or off, returning its new state. None if no plugin has that name.
+    pub fn toggle(&mut self, name: &str) -> Option<bool> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.plugin.name() == name)?;
+        entry.enabled = !entry.enabled;
+        Some(entry.enabled)
+    }
+
+    // Every plugin's name and whether it is on
+    pub fn list(&self) -> Vec<(&str, bool)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.plugin.name(), entry.enabled))
+            .collect()
+    }
+
+    pub fn update(&mut self, dt: f32, world: &World, local_player: Entity) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            entry.plugin.update(dt, world, local_player);
+        }
+    }
+
+    pub fn on_message(&mut self, message: &ClientMessage) {
+        for entry in self.entries.iter_mut().filter(|entry| entry.enabled) {
+            entry.plugin.on_message(message);
+        }
+    }
+
+    // Commands of the plugins that are on
+    pub fn commands(&self) -> Vec<&'static str> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .flat_map(|entry| entry.plugin.commands().iter().copied())
+            .collect()
+    }
+
+    // None if no plugin that's on answers `name`
+    pub fn run_command(&mut self, name: &str, args: &str) -> Option<CommandResult> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .filter(|entry| entry.enabled)
+            .find(|entry| entry.plugin.commands().contains(&name))?;
+        Some(entry.plugin.run_command(name, args))
+    }
+
+    pub fn draw_overlay(&self, world: &World, local_player: Entity) {
+        for entry in self.entries.iter().filter(|entry| entry.enabled) {
+            entry.plugin.draw_overlay(world, local_player);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use crate::logging;
+use crate::settings::PostEffect;
+use macroquad::prelude::*;
+use std::collections::HashMap;
+use tracing::warn;
+
+const VERTEX: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+varying lowp vec2 uv;
+varying lowp vec4 color;
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+// Darker towards the corners
+const VIGNETTE: &str = r#"#version 100
+precision lowp float;
+varying vec2 uv;
+varying vec4 color;
+uniform sampler2D Texture;
+
+void main() {
+    vec3 scene = texture2D(Texture, uv).rgb;
+    float edge = smoothstep(0.35, 0.8, distance(uv, vec2(0.5)));
+    gl_FragColor = vec4(scene * (1.0 - edge * 0.7), 1.0);
+}
+"#;
+
+// A curved screen with scanlines and the colors slightly apart
+const CRT: &str = r#"#version 100
+precision lowp float;
+varying vec2 uv;
+varying vec4 color;
+uniform sampler2D Texture;
+uniform vec2 Texel;
+
+void main() {
+    vec2 centered = uv - 0.5;
+    vec2 curved = uv + centered * dot(centered, centered) * 0.15;
+    if (curved.x < 0.0 || curved.x > 1.0 || curved.y < 0.0 || curved.y > 1.0) {
+        gl_FragColor = vec4(0.0, 0.0, 0.0, 1.0);
+        return;
+    }
+    vec2 apart = vec2(Texel.x * 1.5, 0.0);
+    vec3 scene = vec3(
+        texture2D(Texture, curved + apart).r,
+        texture2D(Texture, curved).g,
+        texture2D(Texture, curved - apart).b
+    );
+    float scanline = 0.85 + 0.15 * sin(gl_FragCoord.y * 3.14159);
+    gl_FragColor = vec4(scene * scanline, 1.0);
+}
+"#;
+
+// Bright parts bleed light onto their neighbours
+const BLOOM: &str = r#"#version 100
+precision lowp float;
+varying vec2 uv;
+varying vec4 color;
+uniform sampler2D Texture;
+uniform vec2 Texel;
+
+vec3 bright(vec2 at) {
+    vec3 sample = texture2D(Texture, at).rgb;
+    return sample * smoothstep(0.6, 1.0, max(sample.r, max(sample.g, sample.b)));
+}
+
+void main() {
+    vec3 scene = texture2D(Texture, uv).rgb;
+    vec3 glow = vec3(0.0);
+    for (int x = -2; x <= 2; x++) {
+        for (int y = -2; y <= 2; y++) {
+            glow += bright(uv + vec2(float(x), float(y)) * Texel * 3.0);
+        }
+    }
+    gl_FragColor = vec4(scene + glow / 25.0 * 0.8, 1.0);
+}
+"#;
+
+// Washed out and moonlit blue
+const NIGHT: &str = r#"#version 100
+precision lowp float;
+varying vec2 uv;
+varying vec4 color;
+uniform sampler2D Texture;
+
+void main() {
+    vec3 scene = texture2D(Texture, uv).rgb;
+    float gray = dot(scene, vec3(0.299, 0.587, 0.114));
+    vec3 moonlit = mix(scene, vec3(gray), 0.7) * vec3(0.6, 0.75, 1.1);
+    gl_FragColor = vec4(moonlit * 0.8, 1.0);
+}
+"#;
+
+// Draws the world into a texture instead of the window, then the texture to
+// the window through the `PostEffect` shader. Shaders compile the first time
+// they're picked; one that won't is logged and drawn without.
+pub struct PostProcess {
+    target: Option<RenderTarget>,
+    materials: HashMap<PostEffect, Option<Material>>,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PostProcess {
+    pub fn new() -> Self {
+        Self {
+            target: None,
+            materials: HashMap::new(),
+        }
+    }
+
+    // Points `camera` at the texture, sized to the window, and returns true
+    // if `effect` needs one. Call `finish` once the world is drawn.
+    pub fn begin(&mut self, effect: PostEffect, camera: &mut Camera2D) -> bool {
+        if effect == PostEffect::Off {
+            self.target = None;
+            return false;
+        }
+        let size = vec2(screen_width(), screen_height()).floor();
+        let target = match &self.target {
+            Some(target) if target.texture.size() == size => target.clone(),
+            _ => {
+                let target = render_target(size.x as u32, size.y as u32);
+                target.texture.set_filter(FilterMode::Nearest);
+                self.target = Some(target.clone());
+                target
+            }
+        };
+        camera.render_target = Some(target);
+        true
+    }
+
+    // Draws the texture over the whole window through `effect`'s shader.
+    // Leaves the default camera set.
+    pub fn finish(&mut self, effect: PostEffect) {
+        let Some(target) = self.target.clone() else {
+            return;
+        };
+        set_default_camera();
+        let material = self.material(effect);
+        if let Some(material) = material {
+            material.set_uniform("Texel", vec2(1.0, 1.0) / target.texture.size());
+            gl_use_material(material);
+        }
+        draw_texture_ex(
+            &target.texture,
+            0.0,
+            0.0,
+            WHITE,
+            DrawTextureParams {
+                dest_size: Some(vec2(screen_width(), screen_height())),
+                ..Default::default()
+            },
+        );
+        gl_use_default_material();
+    }
+
+    fn material(&mut self, effect: PostEffect) -> Option<&Material> {
+        self.materials
+            .entry(effect)
+            .or_insert_with(|| {
+                let fragment = match effect {
+                    PostEffect::Off => return None,
+                    PostEffect::Vignette => VIGNETTE,
+                    PostEffect::Crt => CRT,
+                    PostEffect::Bloom => BLOOM,
+                    PostEffect::Night => NIGHT,
+                };
+                let params = MaterialParams {
+                    uniforms: vec![UniformDesc::new("Texel", UniformType::Float2)],
+                    ..Default::default()
+                };
+                let source = ShaderSource::Glsl {
+                    vertex: VERTEX,
+                    fragment,
+                };
+                load_material(source, params)
+                    .map_err(|e| {
+                        warn!(target: logging::RENDER, "Failed to compile the {} filter: {}", effect.name(), e);
+                    })
+                    .ok()
+            })
+            .as_ref()
+    }
+}
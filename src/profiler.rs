@@ -0,0 +1,170 @@
+use crate::ui;
+use macroquad::prelude::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+// Frames shown in the graph, a bit over two seconds at 60 fps
+const HISTORY: usize = 150;
+const BAR_WIDTH: f32 = 2.0;
+const GRAPH_HEIGHT: f32 = 120.0;
+const GRAPH_MS: f32 = 33.3; // Top of the graph, two frames at 60 fps
+const BUDGET_MS: f32 = 1000.0 / 60.0;
+const PANEL_WIDTH: f32 = HISTORY as f32 * BAR_WIDTH + 180.0;
+
+// The parts of a frame, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStage {
+    Input,
+    Simulation,
+    Network, // Replay events and sending queued messages
+    Render,
+}
+
+// In the enum's order, so `stage as usize` finds a stage's entry
+const STAGES: [(FrameStage, &str, Color); 4] = [
+    (FrameStage::Input, "input", SKYBLUE),
+    (FrameStage::Simulation, "simulation", GREEN),
+    (FrameStage::Network, "network", ORANGE),
+    (FrameStage::Render, "render", RED),
+];
+// Whatever the stages don't cover, mostly presenting and waiting for vsync
+const OTHER_COLOR: Color = LIGHTGRAY;
+
+// Milliseconds per stage for one frame, plus the whole frame
+#[derive(Default, Clone, Copy)]
+struct FrameTimes {
+    stages: [f32; STAGES.len()],
+    total: f32,
+}
+
+impl FrameTimes {
+    fn other(&self) -> f32 {
+        (self.total - self.stages.iter().sum::<f32>()).max(0.0)
+    }
+}
+
+// Where each frame's time went, drawn as a bar per frame so a stutter shows
+// which stage it came from. F4 cycles to it after the latency overlay;
+// nothing is measured while it is off.
+#[derive(Default)]
+pub struct FrameProfiler {
+    pub enabled: bool,
+    current: FrameTimes,
+    history: VecDeque<FrameTimes>,
+}
+
+impl FrameProfiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle(&mut self) {
+        self.enabled = !self.enabled;
+        self.current = FrameTimes::default();
+        self.history.clear();
+    }
+
+    // Adds to the stage's time this frame, stages can be measured in pieces
+    pub fn record(&mut self, stage: FrameStage, duration: Duration) {
+        if self.enabled {
+            self.current.stages[stage as usize] += duration.as_secs_f32() * 1000.0;
+        }
+    }
+
+    // The frame was presented after `total`, closing it
+    pub fn end_frame(&mut self, total: Duration) {
+        if !self.enabled {
+            return;
+        }
+        let mut frame = std::mem::take(&mut self.current);
+        frame.total = total.as_secs_f32() * 1000.0;
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(frame);
+    }
+
+    pub fn draw(&self) {
+        if !self.enabled {
+            return;
+        }
+        let x = 10.0;
+        let y = ui::height() - GRAPH_HEIGHT - 40.0;
+        draw_rectangle(
+            x - 4.0,
+            y - 24.0,
+            PANEL_WIDTH,
+            GRAPH_HEIGHT + 44.0,
+            Color::new(1.0, 1.0, 1.0, 0.85),
+        );
+        ui::draw_text("Frame time (F4)", x, y - 6.0, 18.0, BLACK);
+
+        let scale = GRAPH_HEIGHT / GRAPH_MS;
+        let bottom = y + GRAPH_HEIGHT;
+        for (i, frame) in self.history.iter().enumerate() {
+            let bar_x = x + i as f32 * BAR_WIDTH;
+            let mut top = bottom;
+            let parts = STAGES
+                .iter()
+                .zip(frame.stages)
+                .map(|((_, _, color), ms)| (ms, *color))
+                .chain([(frame.other(), OTHER_COLOR)]);
+            for (ms, color) in parts {
+                // Slow frames are cut off at the top of the graph
+                let height = (ms * scale).min(top - y);
+                top -= height;
+                draw_rectangle(bar_x, top, BAR_WIDTH, height, color);
+            }
+        }
+        let budget_y = bottom - BUDGET_MS * scale;
+        draw_line(
+            x,
+            budget_y,
+            x + HISTORY as f32 * BAR_WIDTH,
+            budget_y,
+            1.0,
+            DARKGRAY,
+        );
+
+        // Averages over the graph next to it, doubling as the legend
+        let count = self.history.len().max(1) as f32;
+        let legend_x = x + HISTORY as f32 * BAR_WIDTH + 12.0;
+        let other = self.history.iter().map(FrameTimes::other).sum::<f32>() / count;
+        let rows = STAGES
+            .iter()
+            .enumerate()
+            .map(|(i, (_, label, color))| {
+                let ms = self
+                    .history
+                    .iter()
+                    .map(|frame| frame.stages[i])
+                    .sum::<f32>()
+                    / count;
+                (*label, ms, *color)
+            })
+            .chain([("other", other, OTHER_COLOR)]);
+        for (i, (label, ms, color)) in rows.enumerate() {
+            let row_y = y + 12.0 + i as f32 * 20.0;
+            draw_rectangle(legend_x, row_y - 10.0, 10.0, 10.0, color);
+            ui::draw_text(
+                &format!("{:<10} {:>5.1} ms", label, ms),
+                legend_x + 16.0,
+                row_y,
+                16.0,
+                BLACK,
+            );
+        }
+        let worst = self
+            .history
+            .iter()
+            .map(|frame| frame.total)
+            .fold(0.0, f32::max);
+        ui::draw_text(
+            &format!("worst {:.1} ms", worst),
+            legend_x,
+            y + 12.0 + 5.0 * 20.0,
+            16.0,
+            DARKGRAY,
+        );
+    }
+}
@@ -0,0 +1,722 @@
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Bump whenever `ClientMessage` changes shape
+pub const PROTOCOL_VERSION: u32 = 33;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum ClientMessage {
+    // A client sends its own `tick` and clock, which the server ignores: it
+    // restamps both from its relay sequence and clock when it relays the
+    // update.
+    // Clients comparing `timestamp_ms` to their clock also measure however
+    // far the two clocks are apart.
+    PlayerPosition {
+        id: usize,
+        x: f32,
+        y: f32,
+        tick: u64,
+        timestamp_ms: u64,
+        stance: Stance,
+        facing: Facing,
+    },
+    AssignPlayerId {
+        id: usize,
+    },
+    // `expression` None leaves it to `Expression::detect` on the message.
+    // The server only relays it to whoever `channel` reaches, stamped with
+    // its own clock, clients send 0.
+    UpdateMessage {
+        id: usize,
+        message: String,
+        expression: Option<Expression>,
+        channel: ChatChannel,
+        sent_at_ms: u64,
+    },
+    // A player started an emote, relayed to everyone else
+    Emote {
+        id: usize,
+        emote: Emote,
+    },
+    OtherPlayerDisconnected {
+        id: usize,
+    },
+    // Sent by a client that is quitting on purpose, before it disconnects
+    Goodbye,
+    // Sent by a client that completed a lap; `splits` are ms from the start
+    // line. The leaderboard takes the server's own timing of the lap, not
+    // `time_ms`.
+    RaceFinished {
+        id: usize,
+        course: String,
+        time_ms: u64,
+        splits: Vec<u64>,
+    },
+    // Best times for a course, fastest first
+    Leaderboard {
+        course: String,
+        entries: Vec<LeaderboardEntry>,
+    },
+    // Server-simulated obstacles, sent at a fixed rate
+    ObstacleSnapshot {
+        tick: u64,
+        obstacles: Vec<ObstacleState>,
+    },
+    // Round trip probe, the client echoes `sent_at_ms` straight back
+    Ping {
+        sent_at_ms: u64,
+    },
+    Pong {
+        sent_at_ms: u64,
+    },
+    // Sent on connect so every client shows the same season
+    SeasonInfo {
+        season: Season,
+        event: Option<SeasonalEvent>,
+    },
+    // Sent by the client once it has an id. Names outlive connections, so
+    // mail is addressed to them. `device` is a random id the client keeps on
+    // disk; `protect` asks for a recovery code when the name is first claimed.
+    Login {
+        name: String,
+        device: String,
+        protect: bool,
+    },
+    // The name is protected and `device` hasn't been seen before, the client
+    // has to answer with `SubmitRecoveryCode`
+    RecoveryCodeRequired {
+        name: String,
+    },
+    SubmitRecoveryCode {
+        code: String,
+    },
+    // Sent once when a protected name is registered, the server never shows it again
+    RecoveryCode {
+        code: String,
+    },
+    LoginRejected {
+        reason: String,
+    },
+    // Sent after every successful login. Any server sharing the signing
+    // secret accepts it in place of `Login` until it expires.
+    SessionTicket {
+        ticket: SessionTicket,
+    },
+    ResumeSession {
+        ticket: SessionTicket,
+    },
+    SendMail {
+        to: String,
+        body: String,
+    },
+    MarkMailRead {
+        mail_id: u64,
+    },
+    DeleteMail {
+        mail_id: u64,
+    },
+    // Full contents of the player's mailbox, sent on login and on new mail
+    Mailbox {
+        mail: Vec<Mail>,
+    },
+    RequestDataExport,
+    // Everything the server stores about the logged in name, as JSON
+    DataExport {
+        json: String,
+    },
+    // Answered with a `DeletionCode` the client has to send back to confirm
+    RequestAccountDeletion,
+    DeletionCode {
+        code: String,
+    },
+    ConfirmAccountDeletion {
+        code: String,
+    },
+    // Feedback the client shows as-is
+    ServerNotice {
+        text: String,
+    },
+    // Changes the logged in name's look
+    SetCosmetics {
+        cosmetics: Cosmetics,
+    },
+    // A player's look, sent to everyone when they log in or change it
+    PlayerCosmetics {
+        id: usize,
+        cosmetics: Cosmetics,
+    },
+    // Changes the name shown above the sender, their login name until then
+    SetName {
+        name: String,
+    },
+    // The name to show above a player, sent to everyone when it changes and
+    // to new players for everyone already there
+    PlayerName {
+        id: usize,
+        name: String,
+    },
+    // Answered with a `CharacterExport` if the server takes part in linking
+    RequestCharacterExport,
+    CharacterExport {
+        bundle: CharacterBundle,
+    },
+    // Takes the look from a bundle exported on a server sharing the identity secret
+    ImportCharacter {
+        bundle: CharacterBundle,
+    },
+    // A mobile client going to or from the background. While it is there the
+    // server stops sending it positions and obstacles, pings still go out.
+    SetBackground {
+        background: bool,
+    },
+    // Answered with everyone's position and the obstacles, to catch up after
+    // missing updates
+    RequestSnapshot,
+    // Sent by a client starting or stopping a replay recording or a stream.
+    // Recording only starts once the server answers with `RecordingStatus`.
+    SetRecording {
+        recording: bool,
+    },
+    // Whether a player is recording, sent to everyone. A `false` for the
+    // client's own id means it has to stop, or may not start.
+    RecordingStatus {
+        id: usize,
+        recording: bool,
+    },
+    // Whether this player agrees to be recorded, asked for in private rooms
+    RecordingConsent {
+        consent: bool,
+    },
+    // How far into the day the server's clock is, sent on connect. Clients
+    // keep counting from there.
+    TimeOfDay {
+        time_ms: u64,
+        day_length_ms: u64,
+    },
+    // The server switched maps. Clients drop what they knew about the old
+    // one, respawn at the spawn point and wait for the new obstacles.
+    MapChange {
+        name: String,
+        spawn_x: f32,
+        spawn_y: f32,
+    },
+    // Sent on connect and to everyone whenever a script changes it
+    SetWeather {
+        weather: Weather,
+    },
+    // Joins the sender to a party by name, leaves it with None. Party chat
+    // only reaches the same party.
+    SetParty {
+        party: Option<String>,
+    },
+    // To the server, invites player `id` into the sender's party, or accepts
+    // if `id` invited the sender first. From the server, player `id` sent an
+    // invitation.
+    PartyInvite {
+        id: usize,
+    },
+    // From the server whenever the receiver's party changes: its name and
+    // everyone in it by network id, the receiver too. None once they leave.
+    PartyUpdate {
+        party: Option<String>,
+        members: Vec<usize>,
+    },
+    // Asks for everyone connected, answered with a `PlayerList`
+    RequestPlayerList,
+    PlayerList {
+        players: Vec<PlayerListEntry>,
+    },
+    // To the server, a message only player `id` sees. From the server,
+    // player `id` whispered it, stamped like `UpdateMessage`.
+    Whisper {
+        id: usize,
+        message: String,
+        sent_at_ms: u64,
+    },
+    // Shown to everyone in a banner across the top, e.g. before maintenance
+    ServerAnnouncement {
+        text: String,
+        severity: Severity,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LeaderboardEntry {
+    pub name: String, // Login name, best times outlive connections
+    pub time_ms: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PlayerListEntry {
+    pub id: usize,
+    pub name: Option<String>, // Display name, if they picked one
+    pub ping_ms: Option<u64>, // Round trip, once the server has measured it
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum ObstacleKind {
+    Platform, // Solid, blocks and pushes players
+    Hazard,   // Knocks players back on contact
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ObstacleState {
+    pub id: u32,
+    pub kind: ObstacleKind,
+    pub x: f32, // Top-left corner
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+// Self-contained proof of a login, signed by the issuing server
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SessionTicket {
+    pub name: String,
+    pub player_id: usize, // Id on the issuing server
+    pub expires_at_ms: u64,
+    pub signature: Vec<u8>,
+}
+
+// Which poses a character moves with, sent along with its position
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Stance {
+    #[default]
+    Standing,
+    Sneaking,  // Slower, crouched walk cycle
+    Sitting,   // Playing `Emote::Sit`
+    Jumping,   // In the air, each new jump starts the arc over
+    Walking,   // Slower than a run, with a lazier walk cycle
+    Sprinting, // Faster than a run, with a quicker, bouncier walk cycle
+}
+
+// Which way a character looks, the way it last moved sideways
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Facing {
+    Left,
+    #[default]
+    Right,
+}
+
+impl Facing {
+    // Multiplies offsets drawn for a character facing right
+    pub fn sign(self) -> f32 {
+        match self {
+            Facing::Left => -1.0,
+            Facing::Right => 1.0,
+        }
+    }
+}
+
+// Animations a player can play on the spot. Moving ends them.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emote {
+    Wave,
+    Dance,
+    Sit,
+}
+
+// Who hears a chat message. Global reaches everyone, local only players
+// within `LOCAL_CHAT_RADIUS` of the speaker, party only their party.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChatChannel {
+    #[default]
+    Global,
+    Local,
+    Party,
+}
+
+pub const LOCAL_CHAT_RADIUS: f32 = 400.0;
+pub const MAX_PARTY_NAME: usize = 24;
+
+impl ChatChannel {
+    pub const ALL: [ChatChannel; 3] = [ChatChannel::Global, ChatChannel::Local, ChatChannel::Party];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ChatChannel::Global => "global",
+            ChatChannel::Local => "local",
+            ChatChannel::Party => "party",
+        }
+    }
+}
+
+// A face pulled while a chat message shows, over the player's usual one
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expression {
+    Happy,
+    Surprised,
+    Angry,
+}
+
+// Words and smileys that give an expression away. Checked in this order, so
+// `>:(` reads as angry before anything else.
+const EXPRESSION_CUES: [(Expression, &[&str]); 3] = [
+    (Expression::Angry, &[">:(", "grr", "argh", "ugh", "angry"]),
+    (
+        Expression::Surprised,
+        &[":o", "o_o", "?!", "!?", "wow", "omg", "whoa"],
+    ),
+    (
+        Expression::Happy,
+        &[":)", ":d", "^^", "haha", "lol", "yay", "nice"],
+    ),
+];
+
+impl Expression {
+    pub const ALL: [Expression; 3] = [Expression::Happy, Expression::Surprised, Expression::Angry];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Expression::Happy => "happy",
+            Expression::Surprised => "surprised",
+            Expression::Angry => "angry",
+        }
+    }
+
+    // Guesses the expression from a chat message. Words have to stand alone,
+    // smileys can be anywhere.
+    pub fn detect(message: &str) -> Option<Expression> {
+        let message = message.to_lowercase();
+        let words: Vec<&str> = message
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let matches = |cue: &&str| {
+            if cue.chars().all(char::is_alphanumeric) {
+                words.contains(cue)
+            } else {
+                message.contains(cue)
+            }
+        };
+        EXPRESSION_CUES
+            .iter()
+            .find(|(_, cues)| cues.iter().any(matches))
+            .map(|&(expression, _)| expression)
+    }
+}
+
+// How a character looks. This is all that moves between servers.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cosmetics {
+    pub body_color: [u8; 3], // RGB
+    pub hair_seed: u64,      // Grows the same hair everywhere
+    // Spritesheet drawn instead of the stick figure, clients without it
+    // installed fall back to the stick figure
+    #[serde(default)]
+    pub sprite: Option<u8>,
+    #[serde(default)]
+    pub hair: Hair,
+    #[serde(default)]
+    pub face: Face,
+    #[serde(default)]
+    pub outfit: Outfit,
+    #[serde(default)]
+    pub equipment: Equipment,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Face {
+    pub eyes: EyeStyle,
+    pub mouth: MouthStyle,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EyeStyle {
+    #[default]
+    Dots,
+    Wide,
+    Sleepy,
+    Happy,
+}
+
+impl EyeStyle {
+    pub const ALL: [EyeStyle; 4] = [
+        EyeStyle::Dots,
+        EyeStyle::Wide,
+        EyeStyle::Sleepy,
+        EyeStyle::Happy,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EyeStyle::Dots => "dots",
+            EyeStyle::Wide => "wide",
+            EyeStyle::Sleepy => "sleepy",
+            EyeStyle::Happy => "happy",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MouthStyle {
+    #[default]
+    Smile,
+    Flat,
+    Open,
+    Frown,
+}
+
+impl MouthStyle {
+    pub const ALL: [MouthStyle; 4] = [
+        MouthStyle::Smile,
+        MouthStyle::Flat,
+        MouthStyle::Open,
+        MouthStyle::Frown,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MouthStyle::Smile => "smile",
+            MouthStyle::Flat => "flat",
+            MouthStyle::Open => "open",
+            MouthStyle::Frown => "frown",
+        }
+    }
+}
+
+// Clothes worn over the stick figure, as indices into `OUTFIT_COLORS`. None
+// leaves that part bare; unknown indices are too.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Outfit {
+    pub shirt: Option<u8>,
+    pub pants: Option<u8>,
+}
+
+// Items worn on the stick figure, attached to its head, hand and shoulders
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct Equipment {
+    pub hat: Option<Hat>,
+    pub weapon: Option<Weapon>, // Held in the right hand
+    pub cape: Option<u8>,       // Index into `OUTFIT_COLORS`
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hat {
+    TopHat,
+    Cap,
+    Crown,
+}
+
+impl Hat {
+    pub const ALL: [Hat; 3] = [Hat::TopHat, Hat::Cap, Hat::Crown];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hat::TopHat => "top hat",
+            Hat::Cap => "cap",
+            Hat::Crown => "crown",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weapon {
+    Sword,
+    Staff,
+}
+
+impl Weapon {
+    pub const ALL: [Weapon; 2] = [Weapon::Sword, Weapon::Staff];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weapon::Sword => "sword",
+            Weapon::Staff => "staff",
+        }
+    }
+}
+
+// Names and RGB of the colors clothes come in
+pub const OUTFIT_COLORS: [(&str, [u8; 3]); 8] = [
+    ("navy", [40, 50, 110]),
+    ("forest", [40, 100, 50]),
+    ("maroon", [120, 30, 40]),
+    ("mustard", [210, 170, 40]),
+    ("sky", [110, 170, 230]),
+    ("charcoal", [60, 60, 65]),
+    ("denim", [70, 100, 150]),
+    ("white", [235, 235, 230]),
+];
+
+// Limits the server holds every look to
+pub const MAX_HAIR_LENGTH: u8 = 40;
+pub const MAX_HAIR_STRANDS: u16 = 400;
+
+// How a character's hair grows. `Cosmetics::hair_seed` scatters the strands.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct Hair {
+    pub color: [u8; 3], // RGB
+    pub length: u8,     // Pixels, give or take
+    pub strands: u16,   // None is bald
+    pub style: HairStyle,
+}
+
+impl Default for Hair {
+    fn default() -> Self {
+        Self {
+            color: [127, 106, 79],
+            length: 20,
+            strands: 250,
+            style: HairStyle::Messy,
+        }
+    }
+}
+
+impl Hair {
+    // Within the limits, so no one can make everyone else draw a million strands
+    pub fn clamped(self) -> Self {
+        Self {
+            length: self.length.min(MAX_HAIR_LENGTH),
+            strands: self.strands.min(MAX_HAIR_STRANDS),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HairStyle {
+    #[default]
+    Messy, // Sticks out every way
+    Spiky,    // Straight up and out from the top of the head
+    Straight, // Hangs down the sides
+    Curly,
+}
+
+impl HairStyle {
+    pub const ALL: [HairStyle; 4] = [
+        HairStyle::Messy,
+        HairStyle::Spiky,
+        HairStyle::Straight,
+        HairStyle::Curly,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HairStyle::Messy => "messy",
+            HairStyle::Spiky => "spiky",
+            HairStyle::Straight => "straight",
+            HairStyle::Curly => "curly",
+        }
+    }
+}
+
+// A character's look, signed by the server it was exported from
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CharacterBundle {
+    pub name: String, // On the exporting server, shown when importing
+    pub cosmetics: Cosmetics,
+    pub exported_at_ms: u64,
+    pub signature: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Mail {
+    pub id: u64,
+    pub from: String,
+    pub body: String,
+    pub sent_at_ms: u64,
+    pub read: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+// Short-lived celebrations layered on top of the season
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeasonalEvent {
+    NewYear,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct Weather {
+    pub precipitation: Precipitation,
+    pub wind: f32, // -1 to 1, negative blows to the left
+}
+
+impl Weather {
+    pub fn clamped(self) -> Self {
+        let wind = if self.wind.is_finite() {
+            self.wind
+        } else {
+            0.0
+        };
+        Self {
+            wind: wind.clamp(-1.0, 1.0),
+            ..self
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Precipitation {
+    #[default]
+    Clear,
+    Rain,
+    Snow,
+}
+
+impl Precipitation {
+    pub const ALL: [Precipitation; 3] = [
+        Precipitation::Clear,
+        Precipitation::Rain,
+        Precipitation::Snow,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Precipitation::Clear => "clear",
+            Precipitation::Rain => "rain",
+            Precipitation::Snow => "snow",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+}
+
+// How much a `ServerAnnouncement` matters, which sets its color and how
+// long it stays up
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 3] = [Severity::Info, Severity::Warning, Severity::Critical];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|severity| severity.name() == name)
+    }
+}
+
+// Milliseconds since the unix epoch, used to timestamp network messages
+pub fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
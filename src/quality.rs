@@ -0,0 +1,26 @@
+// Graphics quality preset, trades visual density for frame time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphicsQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl GraphicsQuality {
+    // Multiplier for decorative detail such as grass blades
+    pub fn density_scale(self) -> f32 {
+        match self {
+            GraphicsQuality::Low => 0.25,
+            GraphicsQuality::Medium => 0.6,
+            GraphicsQuality::High => 1.0,
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            GraphicsQuality::Low => GraphicsQuality::Medium,
+            GraphicsQuality::Medium => GraphicsQuality::High,
+            GraphicsQuality::High => GraphicsQuality::Low,
+        }
+    }
+}
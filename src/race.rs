@@ -0,0 +1,167 @@
+use crate::components::{NetworkId, Position};
+use crate::course::{Course, LapEvent, LapTimer};
+use crate::ghost::Ghost;
+use crate::logging;
+use crate::minigame::Minigame;
+use crate::protocol::{ClientMessage, LeaderboardEntry};
+use crate::ui;
+use hecs::{Entity, World};
+use macroquad::prelude::*;
+use std::time::Instant;
+use tracing::info;
+
+// Rows of the server leaderboard shown in the race UI
+const LEADERBOARD_ROWS: usize = 5;
+
+// Racing laps of a course, optionally against a ghost loaded from a replay
+pub struct CheckpointRace {
+    course: &'static Course,
+    timer: LapTimer,
+    ghost: Option<Ghost>,
+    clock: Instant,
+    leaderboard: Vec<LeaderboardEntry>,
+}
+
+impl CheckpointRace {
+    pub fn new(course: &'static Course, ghost: Option<Ghost>) -> Self {
+        Self {
+            course,
+            timer: LapTimer::new(),
+            ghost,
+            clock: Instant::now(),
+            leaderboard: Vec::new(),
+        }
+    }
+
+    fn now_ms(&self) -> u64 {
+        self.clock.elapsed().as_millis() as u64
+    }
+}
+
+impl Minigame for CheckpointRace {
+    fn update(
+        &mut self,
+        dt: f32,
+        world: &World,
+        local_player: Entity,
+        outbox: &mut Vec<ClientMessage>,
+    ) {
+        let mut query = world.query_one::<(&Position, &NetworkId)>(local_player);
+        let Ok((position, network_id)) = query.get() else {
+            return;
+        };
+
+        let now_ms = self.now_ms();
+        match self
+            .timer
+            .update(self.course.checkpoints, now_ms, position.x, position.y)
+        {
+            // Every lap is raced against the ghost from the start line
+            Some(LapEvent::Started) => {
+                if let Some(ghost) = &mut self.ghost {
+                    ghost.restart();
+                }
+            }
+            Some(LapEvent::Finished { lap_ms, splits }) => {
+                info!(target: logging::GAME, "Lap finished in {}", format_lap(Some(lap_ms)));
+                outbox.push(ClientMessage::RaceFinished {
+                    id: network_id.0,
+                    course: self.course.name.to_string(),
+                    time_ms: lap_ms,
+                    splits,
+                });
+                if let Some(ghost) = &mut self.ghost {
+                    ghost.restart();
+                }
+            }
+            Some(LapEvent::Checkpoint) | None => {}
+        }
+
+        if let Some(ghost) = &mut self.ghost {
+            ghost.update(dt);
+        }
+    }
+
+    fn handle_message(&mut self, message: &ClientMessage) {
+        match message {
+            ClientMessage::Leaderboard { course, entries } if course == self.course.name => {
+                self.leaderboard = entries.clone();
+            }
+            // A lap doesn't carry over to another map
+            ClientMessage::MapChange { .. } => self.timer.abandon(),
+            _ => {}
+        }
+    }
+
+    fn draw_world(&self) {
+        for (i, checkpoint) in self.course.checkpoints.iter().enumerate() {
+            let color = if i == self.timer.next_checkpoint() {
+                GREEN
+            } else {
+                LIGHTGRAY
+            };
+            let label = if i == 0 {
+                "START".to_string()
+            } else {
+                i.to_string()
+            };
+            draw_circle_lines(checkpoint.x, checkpoint.y, checkpoint.radius, 3.0, color);
+            ui::draw_text(&label, checkpoint.x - 5.0, checkpoint.y + 5.0, 20.0, color);
+        }
+
+        if let Some(ghost) = &self.ghost {
+            ghost.draw();
+        }
+    }
+
+    fn draw_ui(&self) {
+        let now_ms = self.now_ms();
+        let mut lines = vec![format!(
+            "Lap: {}",
+            format_lap(self.timer.current_lap_ms(now_ms))
+        )];
+        // Splits of the running lap compared against the best lap
+        for (i, split) in self.timer.splits().iter().enumerate() {
+            let delta = match self.timer.best_splits.get(i) {
+                Some(best) => format!(" ({:+.3})", (*split as f64 - *best as f64) / 1000.0),
+                None => String::new(),
+            };
+            lines.push(format!(
+                "  CP{}: {}{}",
+                i + 1,
+                format_lap(Some(*split)),
+                delta
+            ));
+        }
+        lines.push(format!("Last: {}", format_lap(self.timer.last_lap_ms)));
+        lines.push(format!("Best: {}", format_lap(self.timer.best_lap_ms)));
+        if let Some(ghost) = &self.ghost {
+            lines.push(format!("Ghost: {}", format_lap(ghost.lap_time_ms)));
+        }
+
+        if !self.leaderboard.is_empty() {
+            lines.push(String::new());
+            lines.push(format!("Top times ({})", self.course.name));
+            for (rank, entry) in self.leaderboard.iter().take(LEADERBOARD_ROWS).enumerate() {
+                lines.push(format!(
+                    "{}. {}  {}",
+                    rank + 1,
+                    entry.name,
+                    format_lap(Some(entry.time_ms))
+                ));
+            }
+        }
+
+        let x = ui::width() - 200.0;
+        for (i, line) in lines.iter().enumerate() {
+            ui::draw_text(line, x, 60.0 + i as f32 * 20.0, 20.0, DARKGRAY);
+        }
+    }
+}
+
+fn format_lap(ms: Option<u64>) -> String {
+    match ms {
+        Some(ms) => format!("{}.{:03}s", ms / 1000, ms % 1000),
+        None => "--".to_string(),
+    }
+}
@@ -0,0 +1,542 @@
+use crate::animation::{current_pose, key_pose};
+use crate::assets::Assets;
+use crate::batch::{DrawList, Layer};
+use crate::components::{Animation, Appearance, ChatBubble, Hidden, Position, Velocity};
+use crate::emoji;
+use crate::equipment;
+use crate::palette;
+use crate::particles::{CharacterEffects, Particle, ParticleEmitter, FEET_OFFSET};
+use crate::protocol::{ChatChannel, Expression, EyeStyle, Face, MouthStyle};
+use crate::seasonal::SeasonalEffects;
+use crate::sprite::draw_sprite_character;
+use crate::ui;
+use hecs::World;
+use macroquad::prelude::*;
+
+// Distance outside the view within which things still get a cheap update
+const LOD_MARGIN: f32 = 150.0;
+// Names wider than this at full size shrink, and are cut short if that
+// isn't enough
+const NAME_SIZE: f32 = 16.0;
+const MIN_NAME_SIZE: f32 = 12.0;
+const NAME_WIDTH: f32 = 110.0;
+// Around the head and limbs in any pose, from the position at the head's
+// center to the feet at `FEET_OFFSET`
+const CHARACTER_HALF_WIDTH: f32 = 26.0;
+const CHARACTER_TOP: f32 = 24.0;
+// Shadows are this wide and tall at rest and shrink as the body bobs up
+const SHADOW_RADII: Vec2 = Vec2::new(20.0, 6.0);
+const SHADOW_SHRINK: f32 = 0.03; // Share lost per pixel the body is lifted
+
+// With more characters than this on screen, those farther than
+// `SIMPLE_DISTANCE` from the middle of the view are drawn simplified
+const CROWD_SIZE: usize = 24;
+const SIMPLE_DISTANCE: f32 = 350.0;
+// Pixels the eyes and mouth sit off center, toward where the character faces
+const FACE_SHIFT: f32 = 3.0;
+// Between a chat bubble and the older one stacked above it
+const STACK_SPACING: f32 = 54.0;
+
+// How much work something at a given point deserves this frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Detail {
+    Full,    // On screen
+    Reduced, // Just off screen, may come into view soon
+    Culled,  // Far off screen, skip drawing and most simulation
+}
+
+pub fn detail_level(view: &Rect, point: Vec2) -> Detail {
+    if view.contains(point) {
+        Detail::Full
+    } else {
+        let margin = Rect::new(
+            view.x - LOD_MARGIN,
+            view.y - LOD_MARGIN,
+            view.w + LOD_MARGIN * 2.0,
+            view.h + LOD_MARGIN * 2.0,
+        );
+        if margin.contains(point) {
+            Detail::Reduced
+        } else {
+            Detail::Culled
+        }
+    }
+}
+
+// Something drawn in the depth-sorted pass
+pub enum Drawable<'a> {
+    Character {
+        position: &'a Position,
+        appearance: &'a Appearance,
+        animation: &'a Animation,
+        is_moving: bool,
+        bubble: Option<&'a ChatBubble>,
+        simple: bool, // See `draw_simple_character`
+    },
+    Particle(&'a ParticleEmitter, &'a Particle),
+    Decoration(&'a SeasonalEffects, Vec2),
+}
+
+impl Drawable<'_> {
+    fn submit(&self, list: &mut DrawList, assets: &mut Assets) {
+        match *self {
+            Drawable::Character {
+                position,
+                appearance,
+                animation,
+                is_moving,
+                bubble,
+                simple,
+            } => {
+                draw_shadow(list, position, animation, 1.0);
+                let sheet = appearance
+                    .sprite
+                    .as_deref()
+                    .and_then(|file| assets.texture_file(file));
+                match sheet {
+                    Some(sheet) => draw_sprite_character(
+                        list, sheet, position, animation, is_moving, bubble, 1.0,
+                    ),
+                    None if simple => {
+                        draw_simple_character(list, position, appearance, animation, is_moving);
+                        let origin = vec2(position.x, position.y + animation.bobbing_offset);
+                        draw_bubble(list, origin, bubble, 1.0);
+                    }
+                    None => draw_character(
+                        list, position, appearance, animation, is_moving, bubble, 1.0,
+                    ),
+                }
+            }
+            Drawable::Particle(emitter, particle) => emitter.draw_particle(particle, list),
+            Drawable::Decoration(seasonal, at) => seasonal.draw_decoration(list, at),
+        }
+    }
+}
+
+// Everything standing on the ground this frame, drawn back to front by where
+// it touches the ground so nearer things cover farther ones. Ties keep the
+// order they were pushed in.
+#[derive(Default)]
+pub struct RenderQueue<'a> {
+    items: Vec<(f32, Drawable<'a>)>,
+}
+
+impl<'a> RenderQueue<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // `ground` is the y where it touches the ground
+    pub fn push(&mut self, ground: f32, drawable: Drawable<'a>) {
+        self.items.push((ground, drawable));
+    }
+
+    pub fn flush(mut self, list: &mut DrawList, assets: &mut Assets) {
+        self.items.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, drawable) in &self.items {
+            drawable.submit(list, assets);
+        }
+    }
+}
+
+// Queues every character, the particles around them and the seasonal
+// decorations, then submits them in depth order. Characters with a
+// spritesheet are drawn as stick figures until it loads. In a crowd the
+// ones far from the middle of `view` are simplified.
+pub fn render_system(
+    world: &World,
+    effects: &CharacterEffects,
+    seasonal: &SeasonalEffects,
+    view: &Rect,
+    list: &mut DrawList,
+    assets: &mut Assets,
+) {
+    let mut query = world
+        .query::<(
+            &Position,
+            &Appearance,
+            &Animation,
+            &Velocity,
+            Option<&ChatBubble>,
+        )>()
+        .without::<&Hidden>();
+    let mut queue = RenderQueue::new();
+    for at in seasonal.decorations() {
+        queue.push(at.y, Drawable::Decoration(seasonal, at));
+    }
+    for (emitter, particle) in effects.particles() {
+        queue.push(particle.position.y, Drawable::Particle(emitter, particle));
+    }
+    let characters: Vec<_> = query.iter().collect();
+    let on_screen = characters
+        .iter()
+        .filter(|(position, ..)| view.contains(vec2(position.x, position.y)))
+        .count();
+    let center = view.center();
+    for (position, appearance, animation, velocity, bubble) in characters {
+        let simple = on_screen > CROWD_SIZE
+            && center.distance(vec2(position.x, position.y)) > SIMPLE_DISTANCE;
+        let character = Drawable::Character {
+            position,
+            appearance,
+            animation,
+            is_moving: velocity.is_moving,
+            bubble,
+            simple,
+        };
+        queue.push(position.y + FEET_OFFSET, character);
+    }
+    queue.flush(list, assets);
+}
+
+// The box a character is drawn in, head to feet, for picking with the mouse
+pub fn character_bounds(position: &Position) -> Rect {
+    Rect::new(
+        position.x - CHARACTER_HALF_WIDTH,
+        position.y - CHARACTER_TOP,
+        CHARACTER_HALF_WIDTH * 2.0,
+        CHARACTER_TOP + FEET_OFFSET,
+    )
+}
+
+// Submits an outline around a character, the hovered or selected player
+pub fn draw_outline(list: &mut DrawList, position: &Position, color: Color, thickness: f32) {
+    let bounds = character_bounds(position);
+    let corners = [
+        bounds.point(),
+        vec2(bounds.right(), bounds.top()),
+        vec2(bounds.right(), bounds.bottom()),
+        vec2(bounds.left(), bounds.bottom()),
+    ];
+    for (index, &corner) in corners.iter().enumerate() {
+        let next = corners[(index + 1) % corners.len()];
+        list.line(Layer::Bubbles, corner, next, thickness, color);
+    }
+}
+
+// Submits the shadow under a character's feet, faded by `alpha`. It stays on
+// the ground while the body bobs.
+pub fn draw_shadow(list: &mut DrawList, position: &Position, animation: &Animation, alpha: f32) {
+    let lift = (-animation.bobbing_offset).max(0.0);
+    let radii = SHADOW_RADII * (1.0 - lift * SHADOW_SHRINK);
+    let shadow = palette::current().shadow;
+    let color = Color {
+        a: shadow.a * alpha,
+        ..shadow
+    };
+    let feet = vec2(position.x, position.y + FEET_OFFSET);
+    list.soft_ellipse(Layer::Shadows, feet, radii, color);
+}
+
+// Submits a stick-figure character with every color faded by `alpha`
+pub fn draw_character(
+    list: &mut DrawList,
+    position: &Position,
+    appearance: &Appearance,
+    animation: &Animation,
+    is_moving: bool,
+    bubble: Option<&ChatBubble>,
+    alpha: f32,
+) {
+    let fade = |color: Color| Color::new(color.r, color.g, color.b, color.a * alpha);
+    // Apply bobbing offset
+    let origin = vec2(position.x, position.y + animation.bobbing_offset);
+    let at = |x: f32, y: f32| origin + vec2(x, y);
+    let layer = Layer::Characters;
+
+    equipment::draw_behind(list, origin, &appearance.equipment, alpha);
+
+    // Draw hair, all of it in one piece
+    list.mesh(
+        layer,
+        &appearance.hair,
+        origin,
+        animation.hair_sway,
+        fade(appearance.hair_color),
+    );
+
+    let body_color = fade(appearance.body_color);
+
+    // Draw head
+    list.circle(layer, origin.x, origin.y, 20.0, body_color);
+
+    // Pulled for as long as the chat message shows
+    let expression = bubble
+        .filter(|bubble| bubble.message.is_some())
+        .and_then(|bubble| bubble.expression);
+    // The face turns the way the body does
+    let face_center = origin + vec2(FACE_SHIFT * animation.facing.sign(), 0.0);
+    let face_color = fade(palette::current().face);
+    draw_face(list, face_center, appearance.face, expression, face_color);
+
+    // Draw body, under the shirt if there is one
+    list.line(layer, at(0.0, 10.0), at(0.0, 40.0), 2.0, body_color);
+    if let Some(shirt) = appearance.shirt.map(fade) {
+        list.line(layer, at(0.0, 18.0), at(0.0, 42.0), 10.0, shirt);
+    }
+
+    // Get interpolated pose
+    let pose = current_pose(animation, is_moving).facing(animation.facing);
+
+    // Draw arms, sleeves cover the upper half
+    let shoulder = at(0.0, 20.0);
+    for hand in [pose.left_arm, pose.right_arm] {
+        let hand = at(hand.0, hand.1);
+        list.line(layer, shoulder, hand, 2.0, body_color);
+        if let Some(shirt) = appearance.shirt.map(fade) {
+            list.line(layer, shoulder, shoulder.lerp(hand, 0.5), 4.0, shirt);
+        }
+    }
+
+    // Draw legs, in pants if there are any
+    let hip = at(0.0, 40.0);
+    let (leg_thickness, leg_color) = match appearance.pants.map(fade) {
+        Some(pants) => (4.0, pants),
+        None => (2.0, body_color),
+    };
+    for foot in [pose.left_leg, pose.right_leg] {
+        list.line(layer, hip, at(foot.0, foot.1), leg_thickness, leg_color);
+    }
+
+    equipment::draw_in_front(
+        list,
+        origin,
+        &pose,
+        animation.facing,
+        &appearance.equipment,
+        alpha,
+    );
+
+    draw_bubble(list, origin, bubble, alpha);
+}
+
+// A character in a crowd, far from the middle of the view: the head, body
+// and limbs in the pose the walk cycle is on, without hair, face, clothes or
+// equipment
+pub fn draw_simple_character(
+    list: &mut DrawList,
+    position: &Position,
+    appearance: &Appearance,
+    animation: &Animation,
+    is_moving: bool,
+) {
+    let origin = vec2(position.x, position.y + animation.bobbing_offset);
+    let at = |x: f32, y: f32| origin + vec2(x, y);
+    let layer = Layer::Characters;
+    let color = appearance.body_color;
+
+    list.circle(layer, origin.x, origin.y, 20.0, color);
+    list.line(layer, at(0.0, 10.0), at(0.0, 40.0), 2.0, color);
+    let pose = key_pose(animation, is_moving).facing(animation.facing);
+    for hand in [pose.left_arm, pose.right_arm] {
+        list.line(layer, at(0.0, 20.0), at(hand.0, hand.1), 2.0, color);
+    }
+    for foot in [pose.left_leg, pose.right_leg] {
+        list.line(layer, at(0.0, 40.0), at(foot.0, foot.1), 2.0, color);
+    }
+}
+
+// Submits eyes and a mouth on a head centered on `origin`, changed and with
+// eyebrows while `expression` shows
+fn draw_face(
+    list: &mut DrawList,
+    origin: Vec2,
+    face: Face,
+    expression: Option<Expression>,
+    color: Color,
+) {
+    let layer = Layer::Characters;
+    let at = |x: f32, y: f32| origin + vec2(x, y);
+
+    let face = match expression {
+        Some(Expression::Happy) => Face {
+            mouth: MouthStyle::Smile,
+            ..face
+        },
+        Some(Expression::Surprised) => Face {
+            eyes: EyeStyle::Wide,
+            mouth: MouthStyle::Open,
+        },
+        Some(Expression::Angry) => Face {
+            mouth: MouthStyle::Frown,
+            ..face
+        },
+        None => face,
+    };
+    if let Some(expression) = expression {
+        // Heights of the inner and outer ends of each eyebrow
+        let (inner, outer) = match expression {
+            Expression::Happy => (-12.0, -13.0),
+            Expression::Surprised => (-16.0, -16.0),
+            Expression::Angry => (-9.0, -14.0),
+        };
+        for side in [-1.0, 1.0] {
+            list.line(
+                layer,
+                at(side * 3.0, inner),
+                at(side * 11.0, outer),
+                2.0,
+                color,
+            );
+        }
+    }
+
+    for side in [-1.0, 1.0] {
+        let eye = at(side * 7.0, -5.0);
+        match face.eyes {
+            EyeStyle::Dots => list.circle(layer, eye.x, eye.y, 3.0, color),
+            EyeStyle::Wide => {
+                list.circle(layer, eye.x, eye.y, 5.0, color);
+                list.circle(
+                    layer,
+                    eye.x,
+                    eye.y,
+                    2.0,
+                    Color {
+                        a: color.a,
+                        ..palette::current().pupil
+                    },
+                );
+            }
+            EyeStyle::Sleepy => list.line(
+                layer,
+                eye - vec2(4.0, 0.0),
+                eye + vec2(4.0, 0.0),
+                2.0,
+                color,
+            ),
+            EyeStyle::Happy => {
+                let top = eye - vec2(0.0, 3.0);
+                list.line(layer, eye + vec2(-4.0, 1.0), top, 2.0, color);
+                list.line(layer, top, eye + vec2(4.0, 1.0), 2.0, color);
+            }
+        }
+    }
+
+    match face.mouth {
+        MouthStyle::Smile => {
+            list.line(layer, at(-7.0, 5.0), at(0.0, 10.0), 2.0, color);
+            list.line(layer, at(0.0, 10.0), at(7.0, 5.0), 2.0, color);
+        }
+        MouthStyle::Flat => list.line(layer, at(-6.0, 8.0), at(6.0, 8.0), 2.0, color),
+        MouthStyle::Open => {
+            let mouth = at(0.0, 8.0);
+            list.circle(layer, mouth.x, mouth.y, 4.0, color);
+        }
+        MouthStyle::Frown => {
+            list.line(layer, at(-7.0, 10.0), at(0.0, 5.0), 2.0, color);
+            list.line(layer, at(0.0, 5.0), at(7.0, 10.0), 2.0, color);
+        }
+    }
+}
+
+// Submits the chat bubbles over a character whose head is at `origin`, the
+// newest lowest and older ones stacked above, each faded as far as it is
+pub fn draw_bubble(list: &mut DrawList, origin: Vec2, bubble: Option<&ChatBubble>, alpha: f32) {
+    let Some(bubble) = bubble else {
+        return;
+    };
+    let newest = bubble
+        .message
+        .as_deref()
+        .map(|message| (message, bubble.channel, bubble.alpha));
+    let older = bubble
+        .stacked
+        .iter()
+        .rev()
+        .map(|older| (older.message.as_str(), older.channel, older.alpha));
+    // Only the ones that can be seen take up a place in the stack
+    let shown = newest
+        .into_iter()
+        .chain(older)
+        .filter(|&(_, _, shown)| shown > 0.0);
+    for (index, (message, channel, shown)) in shown.enumerate() {
+        let at = origin - vec2(0.0, index as f32 * STACK_SPACING);
+        draw_one_bubble(list, at, message, channel, alpha * shown);
+    }
+}
+
+fn draw_one_bubble(
+    list: &mut DrawList,
+    origin: Vec2,
+    message: &str,
+    channel: ChatChannel,
+    alpha: f32,
+) {
+    let fade = |color: Color| Color::new(color.r, color.g, color.b, color.a * alpha);
+    let palette = palette::current();
+    // Draw a rectangle centered above player
+    list.rect(
+        Layer::Bubbles,
+        origin.x - 75.0,
+        origin.y - 70.0,
+        150.0,
+        50.0,
+        fade(palette.bubble),
+    );
+    emoji::draw_message(
+        list,
+        Layer::Bubbles,
+        message,
+        (origin.x - 50.0, origin.y - 35.0),
+        20.0,
+        fade(palette.channel(channel)),
+    );
+}
+
+// Submits `name` centered over a character, or over the top of its stack
+// of `bubbles`. The local player's name stands out.
+pub fn draw_name_tag(
+    list: &mut DrawList,
+    position: &Position,
+    animation: &Animation,
+    name: &str,
+    local: bool,
+    bubbles: usize,
+) {
+    let origin = vec2(position.x, position.y + animation.bobbing_offset);
+    let (text, size) = fit_name(name);
+    let dimensions = ui::measure(&text, size);
+    let x = origin.x - dimensions.width / 2.0;
+    let y = match bubbles {
+        0 => origin.y - 26.0,
+        _ => origin.y - 76.0 - (bubbles - 1) as f32 * STACK_SPACING,
+    };
+    let palette = palette::current();
+    let (color, backing) = if local {
+        (palette.local_name, palette.local_name_backing)
+    } else {
+        (palette.name, palette.name_backing)
+    };
+    list.rect(
+        Layer::Bubbles,
+        x - 3.0,
+        y - dimensions.offset_y - 2.0,
+        dimensions.width + 6.0,
+        dimensions.height + 4.0,
+        backing,
+    );
+    list.text(Layer::Bubbles, &text, x, y, size, color);
+}
+
+// The name and font size that fit in `NAME_WIDTH`
+fn fit_name(name: &str) -> (String, f32) {
+    let width = ui::text_width;
+    let full = width(name, NAME_SIZE);
+    let size = if full > NAME_WIDTH {
+        (NAME_SIZE * NAME_WIDTH / full).max(MIN_NAME_SIZE)
+    } else {
+        NAME_SIZE
+    };
+    if width(name, size) <= NAME_WIDTH {
+        return (name.to_string(), size);
+    }
+    let mut chars: Vec<char> = name.chars().collect();
+    loop {
+        chars.pop();
+        let text = format!("{}...", chars.iter().collect::<String>().trim_end());
+        if chars.is_empty() || width(&text, size) <= NAME_WIDTH {
+            return (text, size);
+        }
+    }
+}
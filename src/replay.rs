@@ -0,0 +1,244 @@
+use crate::logging;
+use crate::protocol::{now_millis, ClientMessage, PROTOCOL_VERSION};
+use crate::world::map_hash;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::warn;
+
+// Replay files start with this magic followed by the format version
+const REPLAY_MAGIC: &[u8; 4] = b"LAMR";
+const REPLAY_FORMAT_VERSION: u16 = 1;
+
+pub const REPLAY_DIR: &str = "replays";
+pub const REPLAY_EXTENSION: &str = "lamr";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayHeader {
+    pub protocol_version: u32,
+    pub map_hash: u64,
+    pub local_player_id: usize,
+    pub participants: Vec<usize>,
+    pub duration_ms: u64,
+    pub recorded_at_ms: u64, // Unix time the recording started
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayEvent {
+    pub time_ms: u64, // Offset from the start of the recording
+    pub message: ClientMessage,
+}
+
+pub struct Replay {
+    pub header: ReplayHeader,
+    pub events: Vec<ReplayEvent>,
+}
+
+fn invalid_data(error: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
+}
+
+impl Replay {
+    // Layout: magic, format version (u16 LE), bincode header, deflate-compressed
+    // bincode event list
+    pub fn write_to(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&REPLAY_FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut writer, &self.header).map_err(invalid_data)?;
+
+        let mut encoder = DeflateEncoder::new(writer, Compression::default());
+        bincode::serialize_into(&mut encoder, &self.events).map_err(invalid_data)?;
+        encoder.finish()?.flush()
+    }
+
+    pub fn read_from(mut reader: impl Read) -> io::Result<Replay> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(invalid_data("not a replay file"));
+        }
+
+        let mut version = [0u8; 2];
+        reader.read_exact(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != REPLAY_FORMAT_VERSION {
+            return Err(invalid_data(format!(
+                "unsupported replay format version {}",
+                version
+            )));
+        }
+
+        let header: ReplayHeader = bincode::deserialize_from(&mut reader).map_err(invalid_data)?;
+        // Messages are only decodable by a build speaking the same protocol
+        if header.protocol_version != PROTOCOL_VERSION {
+            return Err(invalid_data(format!(
+                "replay uses protocol version {}, this build uses {}",
+                header.protocol_version, PROTOCOL_VERSION
+            )));
+        }
+
+        let events =
+            bincode::deserialize_from(DeflateDecoder::new(reader)).map_err(invalid_data)?;
+        Ok(Replay { header, events })
+    }
+
+    pub fn export(&self, path: &Path) -> io::Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn import(path: &Path) -> io::Result<Replay> {
+        let replay = Replay::read_from(BufReader::new(File::open(path)?))?;
+        if replay.header.map_hash != map_hash() {
+            warn!(target: logging::GAME, "Replay was recorded on a different map");
+        }
+        Ok(replay)
+    }
+}
+
+pub struct ReplayRecorder {
+    started: Instant,
+    recorded_at_ms: u64,
+    local_player_id: usize,
+    participants: BTreeSet<usize>,
+    events: Vec<ReplayEvent>,
+}
+
+impl ReplayRecorder {
+    pub fn new(local_player_id: usize) -> Self {
+        Self {
+            started: Instant::now(),
+            recorded_at_ms: now_millis(),
+            local_player_id,
+            participants: BTreeSet::from([local_player_id]),
+            events: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, message: &ClientMessage) {
+        match message {
+            ClientMessage::PlayerPosition { id, .. }
+            | ClientMessage::UpdateMessage { id, .. }
+            | ClientMessage::Emote { id, .. }
+            | ClientMessage::RaceFinished { id, .. } => {
+                self.participants.insert(*id);
+            }
+            // Id assignment and pings belong to the recording session, not the
+            // replay, and logins, mail and account data are private
+            ClientMessage::AssignPlayerId { .. }
+            | ClientMessage::Ping { .. }
+            | ClientMessage::Pong { .. }
+            | ClientMessage::Goodbye
+            | ClientMessage::Login { .. }
+            | ClientMessage::RecoveryCodeRequired { .. }
+            | ClientMessage::SubmitRecoveryCode { .. }
+            | ClientMessage::RecoveryCode { .. }
+            | ClientMessage::LoginRejected { .. }
+            | ClientMessage::SessionTicket { .. }
+            | ClientMessage::ResumeSession { .. }
+            | ClientMessage::RequestDataExport
+            | ClientMessage::DataExport { .. }
+            | ClientMessage::RequestAccountDeletion
+            | ClientMessage::DeletionCode { .. }
+            | ClientMessage::ConfirmAccountDeletion { .. }
+            | ClientMessage::ServerNotice { .. }
+            | ClientMessage::SendMail { .. }
+            | ClientMessage::MarkMailRead { .. }
+            | ClientMessage::DeleteMail { .. }
+            | ClientMessage::Mailbox { .. }
+            | ClientMessage::SetCosmetics { .. }
+            | ClientMessage::SetName { .. }
+            | ClientMessage::SetParty { .. }
+            | ClientMessage::PartyInvite { .. }
+            | ClientMessage::PartyUpdate { .. }
+            | ClientMessage::RequestPlayerList
+            | ClientMessage::PlayerList { .. }
+            | ClientMessage::Whisper { .. }
+            | ClientMessage::ServerAnnouncement { .. }
+            | ClientMessage::RequestCharacterExport
+            | ClientMessage::CharacterExport { .. }
+            | ClientMessage::ImportCharacter { .. }
+            | ClientMessage::SetBackground { .. }
+            | ClientMessage::RequestSnapshot
+            | ClientMessage::SetRecording { .. }
+            | ClientMessage::RecordingConsent { .. }
+            | ClientMessage::RecordingStatus { .. } => return,
+            ClientMessage::OtherPlayerDisconnected { .. }
+            | ClientMessage::Leaderboard { .. }
+            | ClientMessage::ObstacleSnapshot { .. }
+            | ClientMessage::SeasonInfo { .. }
+            | ClientMessage::TimeOfDay { .. }
+            | ClientMessage::SetWeather { .. }
+            | ClientMessage::PlayerCosmetics { .. }
+            | ClientMessage::PlayerName { .. }
+            | ClientMessage::MapChange { .. } => {}
+        }
+
+        self.events.push(ReplayEvent {
+            time_ms: self.started.elapsed().as_millis() as u64,
+            message: message.clone(),
+        });
+    }
+
+    pub fn finish(self) -> Replay {
+        Replay {
+            header: ReplayHeader {
+                protocol_version: PROTOCOL_VERSION,
+                map_hash: map_hash(),
+                local_player_id: self.local_player_id,
+                participants: self.participants.into_iter().collect(),
+                duration_ms: self.started.elapsed().as_millis() as u64,
+                recorded_at_ms: self.recorded_at_ms,
+            },
+            events: self.events,
+        }
+    }
+}
+
+// Feeds a replay's events back out in real time
+pub struct ReplayPlayback {
+    replay: Replay,
+    started: Instant,
+    next_event: usize,
+}
+
+impl ReplayPlayback {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            started: Instant::now(),
+            next_event: 0,
+        }
+    }
+
+    // Returns every event that has become due since the last call
+    pub fn poll(&mut self) -> Vec<ClientMessage> {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        let mut due = Vec::new();
+        while let Some(event) = self.replay.events.get(self.next_event) {
+            if event.time_ms > elapsed {
+                break;
+            }
+            due.push(event.message.clone());
+            self.next_event += 1;
+        }
+        due
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.replay.events.len()
+    }
+}
+
+// Picks a fresh file name in the replay directory
+pub fn new_replay_path() -> PathBuf {
+    Path::new(REPLAY_DIR).join(format!("replay-{}.{}", now_millis(), REPLAY_EXTENSION))
+}
@@ -0,0 +1,397 @@
+use crate::assets::Assets;
+use crate::error;
+use crate::game::Game;
+use crate::input::{InputSource, KeyboardInput};
+use crate::lifecycle::{AppEvent, Lifecycle};
+use crate::logging;
+use crate::metrics;
+use crate::net::{keep_alive, send_message, sync_network, Connection, ConnectionState};
+use crate::profiler::FrameStage;
+use crate::protocol::ClientMessage;
+use crate::replay::ReplayPlayback;
+use crate::text_field::TextField;
+use crate::touch::TouchControls;
+use crate::transition::{Transition, TransitionKind};
+use crate::ui;
+use macroquad::prelude::*;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tracing::{info, warn};
+
+// Give up on a server that hasn't answered by then
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// Shortest time the map change screen shows, so it reads as a transition
+// rather than a flicker
+const MAP_CHANGE_MIN: Duration = Duration::from_millis(800);
+// Longest server address the main menu takes, a host name and port
+pub const MAX_ADDRESS_LENGTH: usize = 260;
+
+// Everything the scenes share, owned by the main loop
+pub struct Session {
+    pub rt: Runtime,
+    pub game: Arc<Mutex<Game>>,
+    pub network: Option<Connection>,
+    pub playback: Option<ReplayPlayback>, // Set when watching a replay
+    pub server_addr: String,
+    pub address: TextField, // Edited on the main menu, `server_addr` once it connects
+    pub input: KeyboardInput,
+    pub assets: Assets,
+    pub lifecycle: Option<Lifecycle>, // Only on mobile, see `lifecycle::MOBILE`
+    pub transition: Transition,       // From the last scene to this one
+}
+
+impl Session {
+    fn connect(&mut self) -> error::Result<()> {
+        let connection = Connection::connect(&self.server_addr, Arc::clone(&self.game), &self.rt)?;
+        self.network = Some(connection);
+        Ok(())
+    }
+
+    // Why the connection was lost, if it was
+    fn lost_reason(&self) -> Option<String> {
+        let connection = self.network.as_ref()?;
+        if connection.state() != ConnectionState::Lost {
+            return None;
+        }
+        Some(
+            connection
+                .failure()
+                .unwrap_or_else(|| "Lost connection to the server".to_string()),
+        )
+    }
+
+    fn disconnect(&mut self) {
+        if let Some(connection) = self.network.take() {
+            connection.close();
+        }
+        error::lock(&self.game).forget_server();
+    }
+
+    fn set_background(&mut self, background: bool) {
+        info!(target: logging::GAME, "Background: {}", background);
+        let mut game = error::lock(&self.game);
+        send_message(
+            &self.network,
+            &mut game,
+            &ClientMessage::SetBackground { background },
+        );
+        // Everything that moved while away was missed
+        if !background {
+            send_message(&self.network, &mut game, &ClientMessage::RequestSnapshot);
+        }
+    }
+}
+
+// What the window is showing. The main loop runs one frame of the current
+// scene and carries on with whichever scene that frame returns.
+pub enum Scene {
+    MainMenu,
+    Connecting { started: Instant },
+    Playing,
+    // A mobile app out of sight: nothing is updated or drawn and the
+    // connection only gets a heartbeat now and then
+    Background,
+    // The server switched maps, shown until the new one has arrived
+    ChangingMap { name: String, started: Instant },
+    Disconnected { reason: String },
+}
+
+impl Scene {
+    // A replay plays straight away, otherwise the menu comes first
+    pub fn first(session: &Session) -> Self {
+        if session.playback.is_some() {
+            Scene::Playing
+        } else {
+            Scene::MainMenu
+        }
+    }
+
+    // Updates and draws one frame, easing into the next scene if it's a
+    // different one. None means the player asked to quit.
+    pub fn frame(self, session: &mut Session, dt: f32) -> Option<Scene> {
+        let from = std::mem::discriminant(&self);
+        let leaving = self.transition();
+        let next = self.run(session, dt)?;
+        session.transition.draw(dt);
+        if from != std::mem::discriminant(&next) {
+            // Either side may ask for something other than a fade
+            let kind = match (leaving, next.transition()) {
+                (None, _) | (_, None) => None,
+                (Some(TransitionKind::Wipe), _) | (_, Some(TransitionKind::Wipe)) => {
+                    Some(TransitionKind::Wipe)
+                }
+                _ => Some(TransitionKind::Fade),
+            };
+            if let Some(kind) = kind {
+                session.transition.start(kind);
+            }
+        }
+        Some(next)
+    }
+
+    // How this scene is come into and left. Nothing is drawn going in and
+    // out of the background, there is no one to see it.
+    fn transition(&self) -> Option<TransitionKind> {
+        match self {
+            Scene::Background => None,
+            Scene::ChangingMap { .. } => Some(TransitionKind::Wipe),
+            _ => Some(TransitionKind::Fade),
+        }
+    }
+
+    fn run(self, session: &mut Session, dt: f32) -> Option<Scene> {
+        let app_event = session.lifecycle.as_mut().and_then(Lifecycle::poll);
+        match self {
+            Scene::MainMenu => main_menu(session),
+            Scene::Connecting { started } => connecting(session, started),
+            Scene::Playing if app_event == Some(AppEvent::Backgrounded) => {
+                session.set_background(true);
+                Some(Scene::Background)
+            }
+            Scene::Playing => playing(session, dt),
+            Scene::Background if app_event == Some(AppEvent::Foregrounded) => {
+                session.set_background(false);
+                Some(Scene::Playing)
+            }
+            Scene::Background => background(session),
+            Scene::ChangingMap { name, started } => changing_map(session, name, started),
+            Scene::Disconnected { reason } => disconnected(session, reason),
+        }
+    }
+}
+
+fn draw_centered(text: &str, y: f32, size: f32, color: Color) {
+    let width = ui::text_width(text, size);
+    ui::draw_text(text, (ui::width() - width) / 2.0, y, size, color);
+}
+
+// Title screen, the season shows behind it
+fn draw_screen(game: &Game, title: &str, lines: &[&str]) {
+    clear_background(game.weather.tint(game.seasonal.theme.background));
+    let y = ui::height() / 2.0 - 40.0;
+    draw_centered(title, y, 40.0, BLACK);
+    for (i, line) in lines.iter().enumerate() {
+        draw_centered(line, y + 40.0 + i as f32 * 26.0, 20.0, DARKGRAY);
+    }
+}
+
+// The server address can be typed or pasted over before playing
+fn main_menu(session: &mut Session) -> Option<Scene> {
+    session.address.update();
+    {
+        let game = error::lock(&session.game);
+        let name = format!("Name: {}", game.player_name);
+        draw_screen(
+            &game,
+            "Smooth Multiplayer Game",
+            &["", &name, "Enter to play, Escape to quit"],
+        );
+        let label = "Server: ";
+        let width = ui::text_width(label, 20.0) + ui::text_width(session.address.text(), 20.0);
+        let x = (ui::width() - width) / 2.0;
+        let y = ui::height() / 2.0;
+        let label_width = ui::draw_text(label, x, y, 20.0, DARKGRAY).width;
+        session.address.draw(x + label_width, y, 20.0, DARKGRAY);
+    }
+
+    if is_key_pressed(KeyCode::Escape) {
+        return None;
+    }
+    if !is_key_pressed(KeyCode::Enter) {
+        return Some(Scene::MainMenu);
+    }
+    let address = session.address.text().trim();
+    if address.is_empty() {
+        return Some(Scene::MainMenu);
+    }
+    if address != session.server_addr {
+        session.server_addr = address.to_string();
+        let mut game = error::lock(&session.game);
+        game.settings.last_server = Some(session.server_addr.clone());
+        game.save_settings();
+    }
+    Some(match session.connect() {
+        Ok(()) => Scene::Connecting {
+            started: Instant::now(),
+        },
+        Err(e) => Scene::Disconnected {
+            reason: e.to_string(),
+        },
+    })
+}
+
+fn connecting(session: &mut Session, started: Instant) -> Option<Scene> {
+    {
+        let game = error::lock(&session.game);
+        let dots = ".".repeat(1 + (started.elapsed().as_millis() / 400 % 3) as usize);
+        draw_screen(
+            &game,
+            &format!("Connecting{}", dots),
+            &[&session.server_addr, "Escape to cancel"],
+        );
+    }
+
+    if is_key_pressed(KeyCode::Escape) {
+        session.disconnect();
+        return Some(Scene::MainMenu);
+    }
+    let state = session.network.as_ref().map(Connection::state);
+    Some(match state {
+        Some(ConnectionState::Connected) => Scene::Playing,
+        Some(ConnectionState::Connecting) if started.elapsed() < CONNECT_TIMEOUT => {
+            Scene::Connecting { started }
+        }
+        _ => {
+            let reason = session
+                .network
+                .as_ref()
+                .and_then(Connection::failure)
+                .unwrap_or_else(|| format!("Couldn't connect to {}", session.server_addr));
+            session.disconnect();
+            Scene::Disconnected { reason }
+        }
+    })
+}
+
+fn playing(session: &mut Session, dt: f32) -> Option<Scene> {
+    let game = &session.game;
+
+    // A replay just carries on, its recorded snapshots follow straight after
+    if session.playback.is_none() {
+        if let Some(name) = metrics::lock(game).loading_map.clone() {
+            return Some(Scene::ChangingMap {
+                name,
+                started: Instant::now(),
+            });
+        }
+    }
+
+    // Feed due replay events as if they came from the server
+    if let Some(playback) = &mut session.playback {
+        let started = Instant::now();
+        let mut game = metrics::lock(game);
+        for message in playback.poll() {
+            game.handle_message(message);
+        }
+        game.profiler.record(FrameStage::Network, started.elapsed());
+        if playback.is_finished() && is_key_pressed(KeyCode::Escape) {
+            return None;
+        }
+    }
+
+    // Update game state
+    {
+        let mut game = metrics::lock(game);
+        let started = Instant::now();
+        // Keys and quick chat can be changed in the settings panel while playing
+        if session.input.keys != game.settings.keys {
+            session.input.keys = game.settings.keys.clone();
+        }
+        if session.input.quick_chat != game.settings.quick_chat {
+            session.input.quick_chat = game.settings.quick_chat.clone();
+        }
+        session.input.hold_to_move = game.settings.hold_to_move;
+        session.input.focus = game.focus();
+        if session.input.touch.is_some() != game.settings.touch_controls {
+            session.input.touch = game.settings.touch_controls.then(TouchControls::new);
+        }
+        let mut frame = session.input.poll(dt);
+        // Clicks are in window coordinates
+        frame.move_target = frame.move_target.map(|p| game.camera.screen_to_world(p));
+        frame.pointer = frame.pointer.map(|p| game.camera.screen_to_world(p));
+        if frame.direction != Vec2::ZERO || frame.move_target.is_some() {
+            game.latency.input_captured();
+        }
+        let polled = Instant::now();
+        game.update(dt, &frame);
+        if let Some(touch) = &mut session.input.touch {
+            touch.show_keyboard(game.is_typing());
+        }
+        game.profiler.record(FrameStage::Input, polled - started);
+        game.profiler
+            .record(FrameStage::Simulation, polled.elapsed());
+    }
+
+    let started = Instant::now();
+    sync_network(game, &session.network);
+    let synced = started.elapsed();
+
+    // Render
+    {
+        let mut game = metrics::lock(game);
+        game.profiler.record(FrameStage::Network, synced);
+        let started = Instant::now();
+        clear_background(game.weather.tint(game.seasonal.theme.background));
+        game.draw(&mut session.assets);
+        if let Some(touch) = &session.input.touch {
+            touch.draw(&session.input.quick_chat);
+        }
+        game.profiler.record(FrameStage::Render, started.elapsed());
+    }
+
+    if let Some(reason) = session.lost_reason() {
+        warn!(target: logging::NET, "Lost connection to {}: {}", session.server_addr, reason);
+        session.disconnect();
+        return Some(Scene::Disconnected { reason });
+    }
+    Some(Scene::Playing)
+}
+
+fn background(session: &mut Session) -> Option<Scene> {
+    keep_alive(&session.game, &session.network);
+    if let Some(reason) = session.lost_reason() {
+        warn!(
+            target: logging::NET,
+            "Lost connection to {} in the background: {}", session.server_addr, reason
+        );
+        session.disconnect();
+        return Some(Scene::Disconnected { reason });
+    }
+    Some(Scene::Background)
+}
+
+fn changing_map(session: &mut Session, name: String, started: Instant) -> Option<Scene> {
+    {
+        let game = error::lock(&session.game);
+        draw_screen(
+            &game,
+            &format!("Loading {}", name),
+            &["The server changed maps"],
+        );
+    }
+
+    // Gets the respawned position out
+    sync_network(&session.game, &session.network);
+    if let Some(reason) = session.lost_reason() {
+        warn!(target: logging::NET, "Lost connection to {}: {}", session.server_addr, reason);
+        session.disconnect();
+        return Some(Scene::Disconnected { reason });
+    }
+
+    let loaded = error::lock(&session.game).loading_map.is_none();
+    Some(if loaded && started.elapsed() >= MAP_CHANGE_MIN {
+        Scene::Playing
+    } else {
+        Scene::ChangingMap { name, started }
+    })
+}
+
+fn disconnected(session: &mut Session, reason: String) -> Option<Scene> {
+    {
+        let game = error::lock(&session.game);
+        draw_screen(
+            &game,
+            "Disconnected",
+            &[&reason, "Enter for the menu, Escape to quit"],
+        );
+    }
+
+    if is_key_pressed(KeyCode::Escape) {
+        return None;
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        return Some(Scene::MainMenu);
+    }
+    Some(Scene::Disconnected { reason })
+}
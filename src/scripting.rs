@@ -0,0 +1,219 @@
+use crate::error;
+use crate::logging;
+use crate::protocol::{Precipitation, Severity, Weather};
+use rhai::{Dynamic, Engine, EvalAltResult, Scope, AST};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+pub const SCRIPT_EXTENSION: &str = "rhai";
+
+// Something a script asked the server to do. Hooks only queue these, the
+// server applies them once the hook returns.
+#[derive(Debug, Clone)]
+pub enum ScriptAction {
+    Say { id: usize, text: String }, // Chat bubble above a player, e.g. an emote
+    Notice { id: usize, text: String },
+    Broadcast { text: String }, // Notice for everyone
+    Announce { text: String, severity: Severity },
+    SetWeather { weather: Weather },
+}
+
+// What `on_chat` decided about a message
+pub enum ChatVerdict {
+    Keep,
+    Replace(String),
+    Drop,
+}
+
+struct Script {
+    name: String,
+    ast: AST,
+    scope: Scope<'static>,
+}
+
+// Server-side rhai scripts. Each `.rhai` file in the scripts directory may
+// define any of these hooks:
+//
+//   fn on_player_join(id)      a player connected
+//   fn on_chat(id, message)    return a string to replace the message, false to drop it
+//   fn on_tick(tick)           20 times a second, `tick` counts from server start
+//
+// and call `say(id, text)`, `notice(id, text)`, `broadcast(text)`,
+// `announce(text, severity)` and `set_weather(kind, wind)`, with severity
+// "info", "warning" or "critical", kind "clear", "rain" or "snow" and wind
+// from -1 to 1.
+pub struct Scripts {
+    engine: Engine,
+    scripts: Vec<Script>,
+    actions: Arc<Mutex<Vec<ScriptAction>>>,
+}
+
+impl Scripts {
+    pub fn empty() -> Self {
+        let actions = Arc::new(Mutex::new(Vec::new()));
+        Self {
+            engine: new_engine(&actions),
+            scripts: Vec::new(),
+            actions,
+        }
+    }
+
+    // Compiles every script in `dir` and runs its top level once. A script
+    // that fails is reported and skipped.
+    pub fn load_dir(dir: &Path) -> io::Result<Self> {
+        let mut scripts = Self::empty();
+        let mut paths: Vec<_> = fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == SCRIPT_EXTENSION))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let name = path.display().to_string();
+            let ast = match scripts.engine.compile_file(path) {
+                Ok(ast) => ast,
+                Err(e) => {
+                    error!(target: logging::SCRIPTS, "Failed to compile script {}: {}", name, e);
+                    continue;
+                }
+            };
+            let mut scope = Scope::new();
+            if let Err(e) = scripts.engine.run_ast_with_scope(&mut scope, &ast) {
+                error!(target: logging::SCRIPTS, "Failed to run script {}: {}", name, e);
+                continue;
+            }
+            info!(target: logging::SCRIPTS, "Loaded script {}", name);
+            scripts.scripts.push(Script { name, ast, scope });
+        }
+        Ok(scripts)
+    }
+
+    pub fn on_player_join(&mut self, id: usize) {
+        self.call("on_player_join", (id as i64,));
+    }
+
+    pub fn on_tick(&mut self, tick: u64) {
+        self.call("on_tick", (tick as i64,));
+    }
+
+    // Scripts run in load order, each seeing the message as the previous one left it
+    pub fn on_chat(&mut self, id: usize, message: &str) -> ChatVerdict {
+        let mut verdict = ChatVerdict::Keep;
+        let mut message = message.to_string();
+        for script in &mut self.scripts {
+            let result = call_hook(
+                &self.engine,
+                script,
+                "on_chat",
+                (id as i64, message.clone()),
+            );
+            let Some(result) = result else {
+                continue;
+            };
+            if result.as_bool() == Ok(false) {
+                return ChatVerdict::Drop;
+            }
+            if let Ok(replacement) = result.into_string() {
+                message = replacement.clone();
+                verdict = ChatVerdict::Replace(replacement);
+            }
+        }
+        verdict
+    }
+
+    // Actions queued by hooks since the last call
+    pub fn take_actions(&self) -> Vec<ScriptAction> {
+        std::mem::take(&mut error::lock(&self.actions))
+    }
+
+    fn call(&mut self, hook: &str, args: impl rhai::FuncArgs + Clone) {
+        for script in &mut self.scripts {
+            call_hook(&self.engine, script, hook, args.clone());
+        }
+    }
+}
+
+fn new_engine(actions: &Arc<Mutex<Vec<ScriptAction>>>) -> Engine {
+    let mut engine = Engine::new();
+    // Keep a runaway hook from stalling the server
+    engine.set_max_operations(100_000);
+
+    let queue = Arc::clone(actions);
+    engine.register_fn("say", move |id: i64, text: &str| {
+        error::lock(&queue).push(ScriptAction::Say {
+            id: id as usize,
+            text: text.to_string(),
+        });
+    });
+    let queue = Arc::clone(actions);
+    engine.register_fn("notice", move |id: i64, text: &str| {
+        error::lock(&queue).push(ScriptAction::Notice {
+            id: id as usize,
+            text: text.to_string(),
+        });
+    });
+    let queue = Arc::clone(actions);
+    engine.register_fn("broadcast", move |text: &str| {
+        error::lock(&queue).push(ScriptAction::Broadcast {
+            text: text.to_string(),
+        });
+    });
+    let queue = Arc::clone(actions);
+    engine.register_fn(
+        "announce",
+        move |text: &str, severity: &str| -> Result<(), Box<EvalAltResult>> {
+            let severity = Severity::from_name(severity)
+                .ok_or_else(|| format!("Unknown severity {:?}", severity))?;
+            error::lock(&queue).push(ScriptAction::Announce {
+                text: text.to_string(),
+                severity,
+            });
+            Ok(())
+        },
+    );
+    let queue = Arc::clone(actions);
+    engine.register_fn(
+        "set_weather",
+        move |kind: &str, wind: f64| -> Result<(), Box<EvalAltResult>> {
+            let precipitation = Precipitation::from_name(kind)
+                .ok_or_else(|| format!("Unknown weather {:?}", kind))?;
+            error::lock(&queue).push(ScriptAction::SetWeather {
+                weather: Weather {
+                    precipitation,
+                    wind: wind as f32,
+                },
+            });
+            Ok(())
+        },
+    );
+    engine
+}
+
+// Calls `hook` if the script defines it with a matching arity. Errors are
+// reported and treated as the hook not being there.
+fn call_hook(
+    engine: &Engine,
+    script: &mut Script,
+    hook: &str,
+    args: impl rhai::FuncArgs,
+) -> Option<Dynamic> {
+    let mut values = Vec::new();
+    args.parse(&mut values);
+    let defined = script
+        .ast
+        .iter_functions()
+        .any(|f| f.name == hook && f.params.len() == values.len());
+    if !defined {
+        return None;
+    }
+    match engine.call_fn::<Dynamic>(&mut script.scope, &script.ast, hook, values) {
+        Ok(result) => Some(result),
+        Err(e) => {
+            warn!(target: logging::SCRIPTS, "Script {} failed in {}: {}", script.name, hook, e);
+            None
+        }
+    }
+}
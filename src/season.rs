@@ -0,0 +1,49 @@
+use crate::protocol::{now_millis, Season, SeasonalEvent};
+
+impl Season {
+    pub fn parse(name: &str) -> Option<Season> {
+        match name.to_ascii_lowercase().as_str() {
+            "spring" => Some(Season::Spring),
+            "summer" => Some(Season::Summer),
+            "autumn" | "fall" => Some(Season::Autumn),
+            "winter" => Some(Season::Winter),
+            _ => None,
+        }
+    }
+
+    // Northern hemisphere meteorological seasons
+    pub fn for_month(month: u32) -> Season {
+        match month {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+}
+
+// Civil (year, month, day) in UTC for a unix time in ms
+pub fn civil_date(unix_ms: u64) -> (i64, u32, u32) {
+    // Howard Hinnant's days-to-civil algorithm
+    let days = (unix_ms / 86_400_000) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+// Season and event for today's date
+pub fn current_season() -> (Season, Option<SeasonalEvent>) {
+    let (_, month, day) = civil_date(now_millis());
+    let event = match (month, day) {
+        (12, 31) | (1, 1) => Some(SeasonalEvent::NewYear),
+        _ => None,
+    };
+    (Season::for_month(month), event)
+}
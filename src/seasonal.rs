@@ -0,0 +1,247 @@
+use crate::batch::{DrawList, Layer};
+use crate::protocol::{Season, SeasonalEvent};
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use ::rand::{thread_rng, Rng};
+use macroquad::prelude::*;
+
+const SNOWFLAKE_COUNT: usize = 150;
+const FIREWORK_INTERVAL: f32 = 0.8; // Seconds between launches
+const FIREWORK_SPARKS: usize = 40;
+const SPARK_LIFETIME: f32 = 1.2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decoration {
+    Flowers,
+    Parasols,
+    Pumpkins,
+    Snowmen,
+}
+
+// Everything that changes with the season
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub background: Color,
+    pub grass: Color,
+    pub decoration: Decoration,
+    pub butterflies: bool,
+    pub snowfall: bool, // Default weather
+}
+
+impl Theme {
+    pub fn for_season(season: Season) -> Theme {
+        match season {
+            Season::Spring => Theme {
+                background: Color::new(0.96, 1.0, 0.94, 1.0),
+                grass: Color::new(0.3, 0.65, 0.25, 1.0),
+                decoration: Decoration::Flowers,
+                butterflies: true,
+                snowfall: false,
+            },
+            Season::Summer => Theme {
+                background: WHITE,
+                grass: Color::new(0.25, 0.55, 0.2, 1.0),
+                decoration: Decoration::Parasols,
+                butterflies: true,
+                snowfall: false,
+            },
+            Season::Autumn => Theme {
+                background: Color::new(1.0, 0.96, 0.9, 1.0),
+                grass: Color::new(0.6, 0.5, 0.2, 1.0),
+                decoration: Decoration::Pumpkins,
+                butterflies: false,
+                snowfall: false,
+            },
+            Season::Winter => Theme {
+                background: Color::new(0.93, 0.95, 1.0, 1.0),
+                grass: Color::new(0.55, 0.6, 0.55, 1.0),
+                decoration: Decoration::Snowmen,
+                butterflies: false,
+                snowfall: true,
+            },
+        }
+    }
+}
+
+// Fixed spots for seasonal decorations, clear of the race checkpoints
+const DECORATION_SPOTS: [(f32, f32); 4] = [
+    (120.0, 160.0),
+    (680.0, 140.0),
+    (90.0, 540.0),
+    (720.0, 520.0),
+];
+
+struct Snowflake {
+    position: Vec2,
+    speed: f32,
+    drift_phase: f32,
+}
+
+struct Spark {
+    position: Vec2,
+    velocity: Vec2,
+    age: f32,
+    color: Color,
+}
+
+// Snowfall, fireworks and decorations for the current season
+pub struct SeasonalEffects {
+    pub season: Season,
+    pub event: Option<SeasonalEvent>,
+    pub theme: Theme,
+    snowflakes: Vec<Snowflake>,
+    sparks: Vec<Spark>,
+    next_firework: f32,
+    time: f32,
+}
+
+impl SeasonalEffects {
+    pub fn new(season: Season, event: Option<SeasonalEvent>) -> Self {
+        let theme = Theme::for_season(season);
+        let mut rng = thread_rng();
+        let snowflakes = if theme.snowfall {
+            (0..SNOWFLAKE_COUNT)
+                .map(|_| Snowflake {
+                    position: Vec2::new(
+                        rng.gen_range(0.0..WORLD_WIDTH),
+                        rng.gen_range(0.0..WORLD_HEIGHT),
+                    ),
+                    speed: rng.gen_range(20.0..60.0),
+                    drift_phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        Self {
+            season,
+            event,
+            theme,
+            snowflakes,
+            sparks: Vec::new(),
+            next_firework: 0.0,
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        let mut rng = thread_rng();
+
+        for flake in &mut self.snowflakes {
+            flake.position.y += flake.speed * dt;
+            flake.position.x += (self.time + flake.drift_phase).sin() * 10.0 * dt;
+            if flake.position.y > WORLD_HEIGHT {
+                flake.position.y = 0.0;
+                flake.position.x = rng.gen_range(0.0..WORLD_WIDTH);
+            }
+        }
+
+        if self.event == Some(SeasonalEvent::NewYear) {
+            self.next_firework -= dt;
+            if self.next_firework <= 0.0 {
+                self.next_firework = FIREWORK_INTERVAL;
+                let center = Vec2::new(
+                    rng.gen_range(100.0..WORLD_WIDTH - 100.0),
+                    rng.gen_range(60.0..WORLD_HEIGHT / 2.0),
+                );
+                let color = Color::new(
+                    rng.gen_range(0.5..1.0),
+                    rng.gen_range(0.2..1.0),
+                    rng.gen_range(0.2..1.0),
+                    1.0,
+                );
+                for i in 0..FIREWORK_SPARKS {
+                    let angle = i as f32 / FIREWORK_SPARKS as f32 * std::f32::consts::TAU;
+                    self.sparks.push(Spark {
+                        position: center,
+                        velocity: Vec2::from_angle(angle) * rng.gen_range(60.0..120.0),
+                        age: 0.0,
+                        color,
+                    });
+                }
+            }
+        }
+
+        for spark in &mut self.sparks {
+            spark.age += dt;
+            spark.velocity.y += 60.0 * dt; // Gravity
+            spark.position += spark.velocity * dt;
+        }
+        self.sparks.retain(|spark| spark.age < SPARK_LIFETIME);
+    }
+
+    // Where the decorations stand, at the bottom middle of each
+    pub fn decorations(&self) -> impl Iterator<Item = Vec2> {
+        DECORATION_SPOTS.iter().map(|&(x, y)| Vec2::new(x, y))
+    }
+
+    // Submits the decoration standing at `at`, with characters so whoever
+    // is in front of it covers it
+    pub fn draw_decoration(&self, list: &mut DrawList, at: Vec2) {
+        let layer = Layer::Characters;
+        let Vec2 { x, y } = at;
+        match self.theme.decoration {
+            Decoration::Flowers => {
+                for (dx, color) in [(-8.0, PINK), (0.0, YELLOW), (8.0, VIOLET)] {
+                    list.line(
+                        layer,
+                        vec2(x + dx, y),
+                        vec2(x + dx, y - 12.0),
+                        1.5,
+                        DARKGREEN,
+                    );
+                    list.circle(layer, x + dx, y - 14.0, 3.5, color);
+                }
+            }
+            Decoration::Parasols => {
+                list.line(layer, vec2(x, y), vec2(x, y - 40.0), 2.0, DARKGRAY);
+                list.triangle(
+                    layer,
+                    Vec2::new(x - 25.0, y - 35.0),
+                    Vec2::new(x + 25.0, y - 35.0),
+                    Vec2::new(x, y - 50.0),
+                    RED,
+                );
+            }
+            Decoration::Pumpkins => {
+                list.circle(layer, x, y - 8.0, 10.0, ORANGE);
+                list.line(
+                    layer,
+                    vec2(x, y - 18.0),
+                    vec2(x + 2.0, y - 23.0),
+                    2.0,
+                    DARKGREEN,
+                );
+            }
+            Decoration::Snowmen => {
+                list.circle(layer, x, y - 12.0, 12.0, WHITE);
+                list.circle_lines(layer, x, y - 12.0, 12.0, 1.0, GRAY);
+                list.circle(layer, x, y - 30.0, 8.0, WHITE);
+                list.circle_lines(layer, x, y - 30.0, 8.0, 1.0, GRAY);
+                list.triangle(
+                    layer,
+                    Vec2::new(x, y - 31.0),
+                    Vec2::new(x, y - 29.0),
+                    Vec2::new(x + 7.0, y - 30.0),
+                    ORANGE,
+                );
+            }
+        }
+    }
+
+    // Snow and fireworks fall over everything
+    pub fn draw_overlay(&self, list: &mut DrawList) {
+        let layer = Layer::Particles;
+        for flake in &self.snowflakes {
+            let Vec2 { x, y } = flake.position;
+            list.circle(layer, x, y, 1.5, WHITE);
+            list.circle_lines(layer, x, y, 1.5, 0.5, LIGHTGRAY);
+        }
+        for spark in &self.sparks {
+            let mut color = spark.color;
+            color.a = 1.0 - spark.age / SPARK_LIFETIME;
+            list.circle(layer, spark.position.x, spark.position.y, 2.0, color);
+        }
+    }
+}
@@ -0,0 +1,1633 @@
+use crate::course::{self, LapEvent, LapTimer, MAP_COURSES};
+use crate::daytime::{DAY_LENGTH_MS, START_TIME_MS};
+use crate::link::{LinkQuality, SnapshotPolicy};
+use crate::logging;
+use crate::map::{GameMap, DEFAULT_MAP};
+use crate::protocol::{
+    now_millis, ChatChannel, ClientMessage, Cosmetics, Facing, LeaderboardEntry, Mail,
+    PlayerListEntry, Season, SessionTicket, Severity, Stance, Weather, LOCAL_CHAT_RADIUS,
+    MAX_PARTY_NAME,
+};
+use crate::scripting::{ChatVerdict, ScriptAction, Scripts};
+use crate::season::current_season;
+use crate::storage::{self, Storage, StorageKind};
+use crate::ticket::{TicketSigner, TICKET_LIFETIME};
+use message_io::network::{Endpoint, NetEvent, Transport};
+use message_io::node::{self, NodeEvent, NodeHandler};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::PathBuf;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{debug, debug_span, error, info, trace_span, warn};
+
+// Race best times survive server restarts under this storage key. The old
+// "leaderboard" key held times by connection id.
+const LEADERBOARD_KEY: &str = "race_times";
+const LEADERBOARD_SIZE: usize = 10;
+
+// Offline messages survive restarts under this key
+const MAIL_KEY: &str = "mail";
+const MAILBOX_SIZE: usize = 50;
+const MAIL_BODY_LIMIT: usize = 500; // Characters
+const NAME_LIMIT: usize = 24;
+
+// Names and the devices allowed to use them
+const ACCOUNTS_KEY: &str = "accounts";
+// Each name's look
+const COSMETICS_KEY: &str = "cosmetics";
+// The name each login name picked to show above them
+const DISPLAY_NAMES_KEY: &str = "display_names";
+// Deleted names, whose session tickets stop working
+const REVOKED_KEY: &str = "revoked_sessions";
+// Wrong recovery codes allowed per connection. After that it can't log in
+// with a name at all, so starting over with `Login` doesn't buy more guesses.
+const RECOVERY_ATTEMPTS: u32 = 3;
+// No 0/O or 1/I, codes are meant to be written down or typed back
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const DELETION_CODE_LEN: usize = 6;
+
+// Obstacle simulation and snapshot rate
+const TICK_INTERVAL: Duration = Duration::from_millis(50);
+// How often every client is pinged to measure its link
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+enum ServerSignal {
+    Tick,
+    LoadMap(GameMap), // From `ServerHandle::load_map`
+    Announce(String, Severity),
+}
+
+struct ServerPlayer {
+    id: usize,
+    name: Option<String>,                    // Set once the client logs in
+    display_name: Option<String>,            // Shown above them, see `SetName`
+    pending_login: Option<(String, String)>, // Name and device waiting on a recovery code
+    pending_deletion: Option<String>,        // Code that confirms deleting the account
+    failed_codes: u32,
+    link: LinkQuality,
+    background: bool, // A mobile client in the background only wants pings
+    recording: bool,  // Recording a replay or streaming
+    recording_consent: bool,
+    x: f32,
+    y: f32,
+    heading: (f32, f32), // The way they last moved
+    stance: Stance,
+    facing: Facing,
+    party: Option<String>,      // Lowercased, see `SetParty`
+    invited_by: HashSet<usize>, // Players whose party invitations are waiting
+    laps: Vec<LapTimer>,        // One per course in `MAP_COURSES`
+}
+
+impl ServerPlayer {
+    // For notices about them
+    fn shown_name(&self) -> String {
+        self.display_name
+            .clone()
+            .unwrap_or_else(|| format!("Player {}", self.id))
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Leaderboards {
+    courses: HashMap<String, Vec<LeaderboardEntry>>,
+}
+
+impl Leaderboards {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, LEADERBOARD_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, LEADERBOARD_KEY, self)
+    }
+
+    // Records a finished lap, keeping only each name's best. Returns whether
+    // the visible board changed.
+    fn submit(&mut self, course: &str, entry: LeaderboardEntry) -> bool {
+        let entries = self.courses.entry(course.to_string()).or_default();
+        if let Some(existing) = entries.iter_mut().find(|e| e.name == entry.name) {
+            if existing.time_ms <= entry.time_ms {
+                return false;
+            }
+            existing.time_ms = entry.time_ms;
+        } else {
+            entries.push(entry);
+        }
+        entries.sort_by_key(|e| e.time_ms);
+        entries.truncate(LEADERBOARD_SIZE);
+        true
+    }
+
+    // Drops every best time set by `name`. Returns the courses that changed.
+    fn remove(&mut self, name: &str) -> Vec<String> {
+        let mut changed = Vec::new();
+        for (course, entries) in &mut self.courses {
+            let before = entries.len();
+            entries.retain(|entry| entry.name != name);
+            if entries.len() != before {
+                changed.push(course.clone());
+            }
+        }
+        changed
+    }
+
+    fn message(&self, course: &str) -> ClientMessage {
+        ClientMessage::Leaderboard {
+            course: course.to_string(),
+            entries: self.courses.get(course).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+// Mail waiting for players, keyed by name
+#[derive(Serialize, Deserialize, Default)]
+struct MailStore {
+    next_id: u64,
+    mailboxes: HashMap<String, Vec<Mail>>,
+}
+
+impl MailStore {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, MAIL_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, MAIL_KEY, self)
+    }
+
+    // Returns false when the recipient's mailbox is full
+    fn deliver(&mut self, from: &str, to: &str, body: String) -> bool {
+        let mailbox = self.mailboxes.entry(to.to_string()).or_default();
+        if mailbox.len() >= MAILBOX_SIZE {
+            return false;
+        }
+        self.next_id += 1;
+        mailbox.push(Mail {
+            id: self.next_id,
+            from: from.to_string(),
+            body,
+            sent_at_ms: now_millis(),
+            read: false,
+        });
+        true
+    }
+
+    fn message(&self, name: &str) -> ClientMessage {
+        ClientMessage::Mailbox {
+            mail: self.mailboxes.get(name).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+// What `/export-my-data` hands back
+#[derive(Serialize)]
+struct DataExport<'a> {
+    name: &'a str,
+    exported_at_ms: u64,
+    protected: bool,
+    devices: &'a [String],
+    mail: &'a [Mail],
+    cosmetics: Option<&'a Cosmetics>,
+    display_name: Option<&'a String>,
+    lap_times: Vec<(&'a str, u64)>, // Course and time
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Account {
+    recovery_code: Option<String>, // Unprotected names can be used from anywhere
+    devices: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Accounts {
+    names: HashMap<String, Account>,
+}
+
+impl Accounts {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, ACCOUNTS_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, ACCOUNTS_KEY, self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct CosmeticsStore {
+    names: HashMap<String, Cosmetics>,
+}
+
+impl CosmeticsStore {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, COSMETICS_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, COSMETICS_KEY, self)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct DisplayNames {
+    names: HashMap<String, String>, // Login name -> display name
+}
+
+impl DisplayNames {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, DISPLAY_NAMES_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, DISPLAY_NAMES_KEY, self)
+    }
+}
+
+// Names deleted here and when. Tickets for them issued before that can't
+// resume a session, so a deleted account stays gone.
+#[derive(Serialize, Deserialize, Default)]
+struct RevokedSessions {
+    names: HashMap<String, u64>, // Login name -> deleted at, ms since the epoch
+}
+
+impl RevokedSessions {
+    fn load(storage: &dyn Storage) -> Self {
+        storage::load_or_default(storage, REVOKED_KEY)
+    }
+
+    fn save(&self, storage: &mut dyn Storage) -> io::Result<()> {
+        storage::save(storage, REVOKED_KEY, self)
+    }
+
+    // Deletions older than the oldest ticket that could still be valid are
+    // forgotten on the way
+    fn revoke(&mut self, name: &str) {
+        let now_ms = now_millis();
+        let lifetime_ms = TICKET_LIFETIME.as_millis() as u64;
+        self.names
+            .retain(|_, deleted_at_ms| now_ms.saturating_sub(*deleted_at_ms) < lifetime_ms);
+        self.names.insert(name.to_string(), now_ms);
+    }
+
+    fn is_revoked(&self, ticket: &SessionTicket) -> bool {
+        let issued_at_ms = ticket
+            .expires_at_ms
+            .saturating_sub(TICKET_LIFETIME.as_millis() as u64);
+        self.names
+            .get(&ticket.name)
+            .is_some_and(|&deleted_at_ms| issued_at_ms <= deleted_at_ms)
+    }
+}
+
+// Trimmed and without control characters, None unless 1 to `NAME_LIMIT`
+// characters are left
+fn clean_display_name(name: &str) -> Option<String> {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    let name = name.trim();
+    (!name.is_empty() && name.chars().count() <= NAME_LIMIT).then(|| name.to_string())
+}
+
+// Trimmed and lowercased, so "Raiders" and "raiders " are the same party
+fn clean_party_name(party: &str) -> Option<String> {
+    let party: String = party.chars().filter(|c| !c.is_control()).collect();
+    let party = party.trim().to_lowercase();
+    (!party.is_empty() && party.chars().count() <= MAX_PARTY_NAME).then_some(party)
+}
+
+// Four groups of four, e.g. `K7QD-M2XP-9RTA-WF3H`
+fn generate_recovery_code() -> String {
+    (0..4).map(|_| random_code(4)).collect::<Vec<_>>().join("-")
+}
+
+fn random_code(len: usize) -> String {
+    let mut rng = rand::thread_rng();
+    (0..len)
+        .map(|_| CODE_ALPHABET[rng.gen_range(0..CODE_ALPHABET.len())] as char)
+        .collect()
+}
+
+// Codes are accepted in any case, with or without the dashes
+fn normalize_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+struct Server {
+    handler: NodeHandler<ServerSignal>,
+    players: HashMap<Endpoint, ServerPlayer>,
+    next_id: usize,
+    tick: u64,      // Simulation ticks, advanced only by `on_tick`
+    relay_seq: u64, // Stamps relayed positions so clients can order them
+    leaderboards: Leaderboards,
+    mail: MailStore,
+    accounts: Accounts,
+    cosmetics: CosmeticsStore,
+    display_names: DisplayNames,
+    revoked: RevokedSessions,
+    storage: Box<dyn Storage>,
+    map: GameMap,
+    started: Instant,
+    season_override: Option<Season>, // Otherwise follows the calendar
+    weather: Weather,
+    scripts: Scripts,
+    tickets: TicketSigner,
+    identity: Option<TicketSigner>, // Signs and checks exported characters
+    snapshot_policy: SnapshotPolicy,
+    last_ping: Instant,
+    private_room: bool, // Recording needs everyone's consent
+}
+
+// Everything about a server that can be set from the command line
+#[derive(Default)]
+pub struct ServerOptions {
+    pub season_override: Option<Season>,
+    pub scripts_dir: Option<PathBuf>, // Gameplay scripts, see `Scripts`
+    // Servers sharing this secret accept each other's session tickets.
+    // Without it tickets only work on this process.
+    pub ticket_secret_file: Option<PathBuf>,
+    // Servers sharing this secret import characters exported from each
+    // other. Without it character export and import are turned off.
+    pub identity_secret_file: Option<PathBuf>,
+    pub snapshot_policy: SnapshotPolicy,
+    pub storage: StorageKind, // Where leaderboards, mail and accounts are kept
+    // Nobody may record unless every player present has agreed with
+    // `/consent on`. Otherwise recording is allowed and shown to everyone.
+    pub private_room: bool,
+    pub map_file: Option<PathBuf>, // Map to start on, see `GameMap`
+}
+
+impl Server {
+    fn new(
+        handler: NodeHandler<ServerSignal>,
+        season_override: Option<Season>,
+        scripts: Scripts,
+        tickets: TicketSigner,
+        identity: Option<TicketSigner>,
+        snapshot_policy: SnapshotPolicy,
+        storage: Box<dyn Storage>,
+    ) -> Self {
+        Self {
+            handler,
+            players: HashMap::new(),
+            next_id: 1, // Clients treat id 0 as "not assigned yet"
+            tick: 0,
+            relay_seq: 0,
+            leaderboards: Leaderboards::load(&*storage),
+            mail: MailStore::load(&*storage),
+            accounts: Accounts::load(&*storage),
+            cosmetics: CosmeticsStore::load(&*storage),
+            display_names: DisplayNames::load(&*storage),
+            revoked: RevokedSessions::load(&*storage),
+            storage,
+            map: GameMap::default(),
+            started: Instant::now(),
+            season_override,
+            weather: Weather::default(),
+            scripts,
+            tickets,
+            identity,
+            snapshot_policy,
+            last_ping: Instant::now(),
+            private_room: false,
+        }
+    }
+
+    fn on_tick(&mut self) {
+        self.tick += 1;
+        let _span = trace_span!(target: logging::SERVER, "tick", tick = self.tick).entered();
+        if !self.players.is_empty() {
+            let message = self.obstacle_snapshot();
+            // Clients that are falling behind get snapshots less often
+            let due: Vec<Endpoint> = self
+                .players
+                .iter_mut()
+                .filter(|(_, player)| !player.background)
+                .filter_map(|(endpoint, player)| player.link.snapshot_due().then_some(*endpoint))
+                .collect();
+            for endpoint in due {
+                self.send(endpoint, &message);
+            }
+        }
+        if self.last_ping.elapsed() >= PING_INTERVAL {
+            self.last_ping = Instant::now();
+            self.ping_players();
+        }
+        self.scripts.on_tick(self.tick);
+        self.apply_script_actions();
+        self.handler
+            .signals()
+            .send_with_timer(ServerSignal::Tick, TICK_INTERVAL);
+    }
+
+    fn obstacle_snapshot(&self) -> ClientMessage {
+        let t_secs = self.started.elapsed().as_secs_f32();
+        ClientMessage::ObstacleSnapshot {
+            tick: self.tick,
+            obstacles: self
+                .map
+                .obstacles
+                .iter()
+                .map(|obstacle| obstacle.state_at(t_secs))
+                .collect(),
+        }
+    }
+
+    fn map_change(&self) -> ClientMessage {
+        ClientMessage::MapChange {
+            name: self.map.name.clone(),
+            spawn_x: self.map.spawn.0,
+            spawn_y: self.map.spawn.1,
+        }
+    }
+
+    // Switches everyone to `map` where they are. Clients respawn at its spawn
+    // point and get the new obstacles straight away rather than on the next
+    // tick, so the loading screen is short.
+    fn load_map(&mut self, map: GameMap) {
+        info!(target: logging::SERVER, "Loading map {}", map.name);
+        self.map = map;
+        let (x, y) = self.map.spawn;
+        for player in self.players.values_mut() {
+            player.x = x;
+            player.y = y;
+            player.laps.iter_mut().for_each(LapTimer::abandon);
+        }
+        self.broadcast(&self.map_change(), None);
+        self.broadcast(&self.obstacle_snapshot(), None);
+    }
+
+    // Tells `endpoint` where every other player is
+    fn send_positions(&self, endpoint: Endpoint) {
+        for (other, player) in &self.players {
+            if *other == endpoint {
+                continue;
+            }
+            let message = ClientMessage::PlayerPosition {
+                id: player.id,
+                x: player.x,
+                y: player.y,
+                tick: self.relay_seq,
+                timestamp_ms: now_millis(),
+                stance: player.stance,
+                facing: player.facing,
+            };
+            self.send(endpoint, &message);
+        }
+    }
+
+    fn send(&self, endpoint: Endpoint, message: &ClientMessage) {
+        match bincode::serialize(message) {
+            Ok(data) => {
+                self.handler.network().send(endpoint, &data);
+            }
+            Err(e) => error!(target: logging::SERVER, "Failed to serialize message: {:?}", e),
+        }
+    }
+
+    // Sends a message to every connected player except `skip`
+    fn broadcast(&self, message: &ClientMessage, skip: Option<Endpoint>) {
+        for endpoint in self.players.keys() {
+            if Some(*endpoint) != skip {
+                self.send(*endpoint, message);
+            }
+        }
+    }
+
+    fn on_accepted(&mut self, endpoint: Endpoint) {
+        let id = self.next_id;
+        self.next_id += 1;
+        info!(target: logging::SERVER, "Player {} connected from {}", id, endpoint.addr());
+
+        self.send(endpoint, &ClientMessage::AssignPlayerId { id });
+
+        let (season, event) = current_season();
+        let message = ClientMessage::SeasonInfo {
+            season: self.season_override.unwrap_or(season),
+            event,
+        };
+        self.send(endpoint, &message);
+
+        let message = ClientMessage::TimeOfDay {
+            time_ms: (START_TIME_MS + self.started.elapsed().as_millis() as u64) % DAY_LENGTH_MS,
+            day_length_ms: DAY_LENGTH_MS,
+        };
+        self.send(endpoint, &message);
+
+        if self.weather != Weather::default() {
+            let message = ClientMessage::SetWeather {
+                weather: self.weather,
+            };
+            self.send(endpoint, &message);
+        }
+
+        // Clients start out on the built-in map
+        if self.map.name != DEFAULT_MAP {
+            self.send(endpoint, &self.map_change());
+        }
+
+        // Tell the new player where everyone already is
+        self.send_positions(endpoint);
+
+        // And who is recording. The new player hasn't agreed to it yet.
+        if self.private_room {
+            self.stop_recordings("someone joined who hasn't agreed to be recorded");
+        }
+        for player in self.players.values().filter(|player| player.recording) {
+            let message = ClientMessage::RecordingStatus {
+                id: player.id,
+                recording: true,
+            };
+            self.send(endpoint, &message);
+        }
+
+        for course in self.leaderboards.courses.keys() {
+            self.send(endpoint, &self.leaderboards.message(course));
+        }
+
+        for player in self.players.values() {
+            if let Some(name) = &player.display_name {
+                let message = ClientMessage::PlayerName {
+                    id: player.id,
+                    name: name.clone(),
+                };
+                self.send(endpoint, &message);
+            }
+        }
+
+        self.players.insert(
+            endpoint,
+            ServerPlayer {
+                id,
+                name: None,
+                display_name: None,
+                pending_login: None,
+                pending_deletion: None,
+                failed_codes: 0,
+                link: LinkQuality::new(),
+                background: false,
+                recording: false,
+                recording_consent: false,
+                x: self.map.spawn.0,
+                y: self.map.spawn.1,
+                heading: (0.0, 0.0),
+                stance: Stance::default(),
+                facing: Facing::default(),
+                party: None,
+                invited_by: HashSet::new(),
+                laps: MAP_COURSES.iter().map(|_| LapTimer::new()).collect(),
+            },
+        );
+        self.scripts.on_player_join(id);
+        self.apply_script_actions();
+    }
+
+    fn on_message(&mut self, endpoint: Endpoint, data: &[u8]) {
+        let message = match bincode::deserialize::<ClientMessage>(data) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(target: logging::SERVER, "Failed to deserialize message: {:?}", e);
+                return;
+            }
+        };
+
+        // Never trust the id a client claims, use the one we assigned
+        let Some(id) = self.players.get(&endpoint).map(|p| p.id) else {
+            return;
+        };
+        // Everything logged while handling the message names the sender
+        let _span = debug_span!(target: logging::SERVER, "message", player = id).entered();
+
+        match message {
+            ClientMessage::PlayerPosition {
+                x,
+                y,
+                stance,
+                facing,
+                ..
+            } => {
+                // Laps are timed here rather than trusting the client's clock
+                let mut finished = Vec::new();
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    if (x, y) != (player.x, player.y) {
+                        player.heading = (x - player.x, y - player.y);
+                    }
+                    player.x = x;
+                    player.y = y;
+                    player.stance = stance;
+                    player.facing = facing;
+                    let now_ms = now_millis();
+                    for (timer, course) in player.laps.iter_mut().zip(&MAP_COURSES) {
+                        let event = timer.update(course.checkpoints, now_ms, x, y);
+                        if let (Some(LapEvent::Finished { lap_ms, .. }), Some(name)) =
+                            (event, &player.name)
+                        {
+                            finished.push((course.name, name.clone(), lap_ms));
+                        }
+                    }
+                }
+                for (course, name, time_ms) in finished {
+                    self.submit_lap(course, name, time_ms);
+                }
+                self.relay_seq += 1;
+                let message = ClientMessage::PlayerPosition {
+                    id,
+                    x,
+                    y,
+                    tick: self.relay_seq,
+                    timestamp_ms: now_millis(),
+                    stance,
+                    facing,
+                };
+                // Backed off clients only hear about players near them, or
+                // about to be
+                let heading = self
+                    .players
+                    .get(&endpoint)
+                    .map_or((0.0, 0.0), |p| p.heading);
+                for (other, player) in &self.players {
+                    let offset = (x - player.x, y - player.y);
+                    let closing = (player.heading.0 - heading.0, player.heading.1 - heading.1);
+                    let in_range = player
+                        .link
+                        .is_relayed(&self.snapshot_policy, offset, closing);
+                    if *other != endpoint && in_range && !player.background {
+                        self.send(*other, &message);
+                    }
+                }
+            }
+            ClientMessage::Goodbye => {
+                info!(target: logging::SERVER, "Player {} is leaving", id);
+                self.on_disconnected(endpoint);
+            }
+            ClientMessage::Pong { sent_at_ms } => {
+                let rtt_ms = now_millis().saturating_sub(sent_at_ms);
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    player.link.pong_received(rtt_ms);
+                }
+            }
+            ClientMessage::UpdateMessage {
+                message,
+                expression,
+                channel,
+                ..
+            } => {
+                // A replaced message gets its expression from the new text
+                let (message, expression) = match self.scripts.on_chat(id, &message) {
+                    ChatVerdict::Keep => (message, expression),
+                    ChatVerdict::Replace(replacement) => (replacement, None),
+                    ChatVerdict::Drop => {
+                        self.apply_script_actions();
+                        return;
+                    }
+                };
+                let Some(listeners) = self.chat_listeners(endpoint, channel) else {
+                    self.notice(endpoint, "Join a party with /party <name> first");
+                    self.apply_script_actions();
+                    return;
+                };
+                let message = ClientMessage::UpdateMessage {
+                    id,
+                    message,
+                    expression,
+                    channel,
+                    sent_at_ms: now_millis(),
+                };
+                for listener in listeners {
+                    self.send(listener, &message);
+                }
+                self.apply_script_actions();
+            }
+            ClientMessage::Whisper {
+                id: to, message, ..
+            } => {
+                let found = self
+                    .players
+                    .iter()
+                    .find(|(_, player)| player.id == to && to != id)
+                    .map(|(&endpoint, _)| endpoint);
+                let Some(listener) = found else {
+                    self.notice(endpoint, "That player isn't here");
+                    return;
+                };
+                // Scripts moderate whispers like any other chat
+                let message = match self.scripts.on_chat(id, &message) {
+                    ChatVerdict::Keep => message,
+                    ChatVerdict::Replace(replacement) => replacement,
+                    ChatVerdict::Drop => {
+                        self.apply_script_actions();
+                        return;
+                    }
+                };
+                let message = ClientMessage::Whisper {
+                    id,
+                    message,
+                    sent_at_ms: now_millis(),
+                };
+                self.send(listener, &message);
+                self.apply_script_actions();
+            }
+            ClientMessage::RequestPlayerList => {
+                let mut players: Vec<PlayerListEntry> = self
+                    .players
+                    .values()
+                    .map(|player| PlayerListEntry {
+                        id: player.id,
+                        name: player.display_name.clone(),
+                        ping_ms: player.link.rtt_ms,
+                    })
+                    .collect();
+                players.sort_by_key(|player| player.id);
+                self.send(endpoint, &ClientMessage::PlayerList { players });
+            }
+            ClientMessage::Emote { emote, .. } => {
+                self.broadcast(&ClientMessage::Emote { id, emote }, Some(endpoint));
+            }
+            // Laps go on the leaderboard as they're timed from positions, see
+            // `PlayerPosition`. The client's time is only what it saw.
+            ClientMessage::RaceFinished {
+                course, time_ms, ..
+            } => {
+                if course::find(&course).is_none() {
+                    warn!(target: logging::SERVER, "Lap of unknown course {:?}", course);
+                    return;
+                }
+                debug!(target: logging::SERVER, "Client timed a lap of {} at {} ms", course, time_ms);
+            }
+            ClientMessage::Login {
+                name,
+                device,
+                protect,
+            } => {
+                if self.out_of_recovery_attempts(endpoint) {
+                    return;
+                }
+                let name = name.trim().to_string();
+                if name.is_empty() || name.chars().count() > NAME_LIMIT {
+                    self.reject_login(
+                        endpoint,
+                        &format!("Names are 1 to {} characters", NAME_LIMIT),
+                    );
+                    return;
+                }
+
+                match self.accounts.names.get(&name) {
+                    None => {
+                        let recovery_code = protect.then(generate_recovery_code);
+                        if let Some(code) = &recovery_code {
+                            self.send(
+                                endpoint,
+                                &ClientMessage::RecoveryCode { code: code.clone() },
+                            );
+                        }
+                        self.accounts.names.insert(
+                            name.clone(),
+                            Account {
+                                recovery_code,
+                                devices: Vec::new(),
+                            },
+                        );
+                    }
+                    Some(account)
+                        if account.recovery_code.is_some()
+                            && !account.devices.contains(&device) =>
+                    {
+                        info!(target: logging::AUTH, "Player {} needs a recovery code for {}", id, name);
+                        self.send(
+                            endpoint,
+                            &ClientMessage::RecoveryCodeRequired { name: name.clone() },
+                        );
+                        if let Some(player) = self.players.get_mut(&endpoint) {
+                            player.pending_login = Some((name, device));
+                        }
+                        return;
+                    }
+                    Some(_) => {}
+                }
+                self.complete_login(endpoint, id, name, Some(device));
+            }
+            ClientMessage::ResumeSession { ticket } => match self.tickets.verify(&ticket) {
+                Ok(()) if self.revoked.is_revoked(&ticket) => {
+                    warn!(
+                        target: logging::AUTH,
+                        "Player {} sent a ticket for deleted account {}", id, ticket.name
+                    );
+                    self.reject_login(endpoint, "That account has been deleted");
+                }
+                Ok(()) => {
+                    info!(
+                        target: logging::AUTH,
+                        "Player {} resumed {}'s session from player {}",
+                        id, ticket.name, ticket.player_id
+                    );
+                    self.complete_login(endpoint, id, ticket.name, None);
+                }
+                Err(e) => {
+                    warn!(target: logging::AUTH, "Player {} sent a bad session ticket: {}", id, e);
+                    self.reject_login(endpoint, &format!("Session {}", e));
+                }
+            },
+            ClientMessage::SubmitRecoveryCode { code } => {
+                if self.out_of_recovery_attempts(endpoint) {
+                    return;
+                }
+                let Some(player) = self.players.get_mut(&endpoint) else {
+                    return;
+                };
+                let Some((name, device)) = player.pending_login.take() else {
+                    return;
+                };
+                let expected = self
+                    .accounts
+                    .names
+                    .get(&name)
+                    .and_then(|account| account.recovery_code.as_deref());
+                if expected.map(normalize_code) == Some(normalize_code(&code)) {
+                    self.complete_login(endpoint, id, name, Some(device));
+                    return;
+                }
+
+                player.failed_codes += 1;
+                warn!(target: logging::AUTH, "Player {} gave a wrong recovery code for {}", id, name);
+                if player.failed_codes >= RECOVERY_ATTEMPTS {
+                    self.reject_login(endpoint, "Too many wrong recovery codes");
+                } else {
+                    player.pending_login = Some((name.clone(), device));
+                    self.send(endpoint, &ClientMessage::RecoveryCodeRequired { name });
+                }
+            }
+            ClientMessage::SendMail { to, body } => {
+                let Some(from) = self.name_of(endpoint) else {
+                    return;
+                };
+                // Every mailbox is kept, so only names someone logged in with get one
+                let to = to.trim();
+                if to.is_empty() {
+                    self.notice(endpoint, "Mail needs a name to go to");
+                    return;
+                }
+                if !self.accounts.names.contains_key(to) {
+                    self.notice(endpoint, &format!("No one called {} to mail", to));
+                    return;
+                }
+                let body: String = body.chars().take(MAIL_BODY_LIMIT).collect();
+                if !self.mail.deliver(&from, to, body) {
+                    self.notice(endpoint, &format!("{}'s mailbox is full", to));
+                    return;
+                }
+                self.save_mail();
+                // Let the recipient know right away if they happen to be online
+                self.send_mailbox(to);
+            }
+            ClientMessage::MarkMailRead { mail_id } => {
+                let Some(name) = self.name_of(endpoint) else {
+                    return;
+                };
+                if let Some(mail) = self
+                    .mail
+                    .mailboxes
+                    .get_mut(&name)
+                    .and_then(|mailbox| mailbox.iter_mut().find(|m| m.id == mail_id))
+                {
+                    mail.read = true;
+                    self.save_mail();
+                }
+            }
+            ClientMessage::DeleteMail { mail_id } => {
+                let Some(name) = self.name_of(endpoint) else {
+                    return;
+                };
+                if let Some(mailbox) = self.mail.mailboxes.get_mut(&name) {
+                    mailbox.retain(|m| m.id != mail_id);
+                    if mailbox.is_empty() {
+                        self.mail.mailboxes.remove(&name);
+                    }
+                    self.save_mail();
+                }
+            }
+            ClientMessage::RequestDataExport => {
+                let Some(name) = self.name_of(endpoint) else {
+                    self.notice(endpoint, "Log in to export your data");
+                    return;
+                };
+                match self.export_data(&name) {
+                    Ok(json) => self.send(endpoint, &ClientMessage::DataExport { json }),
+                    Err(e) => {
+                        error!(target: logging::SERVER, "Failed to export data for {}: {}", name, e);
+                        self.notice(endpoint, "Export failed");
+                    }
+                }
+            }
+            ClientMessage::RequestAccountDeletion => {
+                if self.name_of(endpoint).is_none() {
+                    self.notice(endpoint, "Log in to delete your account");
+                    return;
+                }
+                let code = random_code(DELETION_CODE_LEN);
+                self.send(
+                    endpoint,
+                    &ClientMessage::DeletionCode { code: code.clone() },
+                );
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    player.pending_deletion = Some(code);
+                }
+            }
+            ClientMessage::ConfirmAccountDeletion { code } => {
+                let Some(player) = self.players.get_mut(&endpoint) else {
+                    return;
+                };
+                let (Some(name), Some(expected)) =
+                    (player.name.clone(), player.pending_deletion.take())
+                else {
+                    self.notice(endpoint, "Request a deletion code first");
+                    return;
+                };
+                if normalize_code(&code) != normalize_code(&expected) {
+                    self.notice(endpoint, "Wrong code, request a new one");
+                    return;
+                }
+                player.name = None;
+                self.delete_account(&name);
+                info!(target: logging::AUTH, "Player {} deleted account {}", id, name);
+                self.notice(endpoint, "Your account and mail were deleted");
+            }
+            ClientMessage::SetBackground { background } => {
+                debug!(target: logging::SERVER, "Player {} background: {}", id, background);
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    player.background = background;
+                }
+            }
+            ClientMessage::RequestSnapshot => {
+                self.send_positions(endpoint);
+                self.send(endpoint, &self.obstacle_snapshot());
+            }
+            ClientMessage::SetRecording { recording } => {
+                let everyone_agreed = self
+                    .players
+                    .iter()
+                    .all(|(other, player)| *other == endpoint || player.recording_consent);
+                if recording && self.private_room && !everyone_agreed {
+                    self.notice(
+                        endpoint,
+                        "This room is private, everyone here has to agree to recording with /consent on",
+                    );
+                    self.send(
+                        endpoint,
+                        &ClientMessage::RecordingStatus {
+                            id,
+                            recording: false,
+                        },
+                    );
+                    return;
+                }
+                info!(target: logging::SERVER, "Player {} recording: {}", id, recording);
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    player.recording = recording;
+                }
+                self.broadcast(&ClientMessage::RecordingStatus { id, recording }, None);
+            }
+            ClientMessage::RecordingConsent { consent } => {
+                if let Some(player) = self.players.get_mut(&endpoint) {
+                    player.recording_consent = consent;
+                }
+                if !consent && self.private_room {
+                    self.stop_recordings("someone withdrew their consent");
+                }
+            }
+            ClientMessage::SetParty { party } => {
+                let party = match party.as_deref().map(clean_party_name) {
+                    Some(None) => {
+                        let notice = format!("Party names are 1 to {} characters", MAX_PARTY_NAME);
+                        self.notice(endpoint, &notice);
+                        return;
+                    }
+                    Some(Some(party)) => Some(party),
+                    None => None,
+                };
+                let notice = match &party {
+                    Some(party) => format!("Joined party {}", party),
+                    None => "Left your party".to_string(),
+                };
+                self.set_party(endpoint, party);
+                self.notice(endpoint, &notice);
+            }
+            ClientMessage::PartyInvite { id: other } => {
+                let found = self
+                    .players
+                    .iter()
+                    .find(|(_, player)| player.id == other && other != id)
+                    .map(|(&endpoint, player)| {
+                        (endpoint, player.party.clone(), player.shown_name())
+                    });
+                let Some((other_endpoint, other_party, other_name)) = found else {
+                    self.notice(endpoint, "That player isn't here");
+                    return;
+                };
+                let Some(player) = self.players.get_mut(&endpoint) else {
+                    return;
+                };
+                let (party, name) = (player.party.clone(), player.shown_name());
+                // Accepting joins their party, made for the two of them if
+                // they weren't in one
+                if player.invited_by.remove(&other) {
+                    let party = other_party
+                        .or_else(|| clean_party_name(&format!("{}'s party", other_name)))
+                        .unwrap_or_else(|| format!("party {}", other));
+                    self.set_party(other_endpoint, Some(party.clone()));
+                    self.set_party(endpoint, Some(party.clone()));
+                    self.notice(endpoint, &format!("Joined party {}", party));
+                    self.notice(other_endpoint, &format!("{} joined your party", name));
+                    return;
+                }
+                if party.is_some() && party == other_party {
+                    self.notice(
+                        endpoint,
+                        &format!("{} is already in your party", other_name),
+                    );
+                    return;
+                }
+                if let Some(invited) = self.players.get_mut(&other_endpoint) {
+                    invited.invited_by.insert(id);
+                }
+                self.send(other_endpoint, &ClientMessage::PartyInvite { id });
+                self.notice(endpoint, &format!("Invited {} to your party", other_name));
+            }
+            ClientMessage::SetName { name } => {
+                let Some(name) = clean_display_name(&name) else {
+                    let notice = format!("Names are 1 to {} characters", NAME_LIMIT);
+                    self.notice(endpoint, &notice);
+                    return;
+                };
+                let current = self.players.get(&endpoint).map(|p| &p.display_name);
+                if current.is_some_and(|current| current.as_ref() == Some(&name)) {
+                    return;
+                }
+                // Kept for the next login once logged in
+                if let Some(login) = self.name_of(endpoint) {
+                    self.display_names.names.insert(login, name.clone());
+                    self.save_display_names();
+                }
+                self.notice(endpoint, &format!("You're now known as {}", name));
+                self.set_display_name(endpoint, id, name);
+            }
+            ClientMessage::SetCosmetics { cosmetics } => {
+                let Some(name) = self.name_of(endpoint) else {
+                    self.notice(endpoint, "Log in to change your look");
+                    return;
+                };
+                self.set_cosmetics(id, &name, cosmetics);
+            }
+            ClientMessage::RequestCharacterExport => {
+                let Some(identity) = &self.identity else {
+                    self.notice(endpoint, "This server doesn't export characters");
+                    return;
+                };
+                let Some(name) = self.name_of(endpoint) else {
+                    self.notice(endpoint, "Log in to export your character");
+                    return;
+                };
+                let Some(&cosmetics) = self.cosmetics.names.get(&name) else {
+                    self.notice(endpoint, "Pick a look with /look first");
+                    return;
+                };
+                let bundle = identity.sign_bundle(&name, cosmetics);
+                self.send(endpoint, &ClientMessage::CharacterExport { bundle });
+            }
+            // Only the look is taken, the bundle's name and history stay behind
+            ClientMessage::ImportCharacter { bundle } => {
+                let verified = match &self.identity {
+                    Some(identity) => identity.verify_bundle(&bundle),
+                    None => {
+                        self.notice(endpoint, "This server doesn't import characters");
+                        return;
+                    }
+                };
+                let Some(name) = self.name_of(endpoint) else {
+                    self.notice(endpoint, "Log in to import a character");
+                    return;
+                };
+                if let Err(e) = verified {
+                    warn!(target: logging::AUTH, "Player {} sent a bad character bundle: {}", id, e);
+                    self.notice(endpoint, &format!("Character {}", e));
+                    return;
+                }
+                info!(target: logging::AUTH, "Player {} imported the look of {}", id, bundle.name);
+                self.set_cosmetics(id, &name, bundle.cosmetics);
+                self.notice(endpoint, &format!("Imported the look of {}", bundle.name));
+            }
+            // Server-to-client only
+            ClientMessage::AssignPlayerId { .. }
+            | ClientMessage::OtherPlayerDisconnected { .. }
+            | ClientMessage::Leaderboard { .. }
+            | ClientMessage::ObstacleSnapshot { .. }
+            | ClientMessage::SeasonInfo { .. }
+            | ClientMessage::TimeOfDay { .. }
+            | ClientMessage::SetWeather { .. }
+            | ClientMessage::Mailbox { .. }
+            | ClientMessage::RecoveryCodeRequired { .. }
+            | ClientMessage::RecoveryCode { .. }
+            | ClientMessage::LoginRejected { .. }
+            | ClientMessage::SessionTicket { .. }
+            | ClientMessage::Ping { .. }
+            | ClientMessage::DataExport { .. }
+            | ClientMessage::DeletionCode { .. }
+            | ClientMessage::ServerNotice { .. }
+            | ClientMessage::PlayerCosmetics { .. }
+            | ClientMessage::PlayerName { .. }
+            | ClientMessage::PartyUpdate { .. }
+            | ClientMessage::PlayerList { .. }
+            | ClientMessage::ServerAnnouncement { .. }
+            | ClientMessage::CharacterExport { .. }
+            | ClientMessage::RecordingStatus { .. }
+            | ClientMessage::MapChange { .. } => {}
+        }
+    }
+
+    fn export_data(&self, name: &str) -> serde_json::Result<String> {
+        let account = self.accounts.names.get(name);
+        let export = DataExport {
+            name,
+            exported_at_ms: now_millis(),
+            protected: account.is_some_and(|account| account.recovery_code.is_some()),
+            devices: account.map_or(&[][..], |account| account.devices.as_slice()),
+            mail: self.mail.mailboxes.get(name).map_or(&[][..], Vec::as_slice),
+            cosmetics: self.cosmetics.names.get(name),
+            display_name: self.display_names.names.get(name),
+            lap_times: self
+                .leaderboards
+                .courses
+                .iter()
+                .flat_map(|(course, entries)| {
+                    entries
+                        .iter()
+                        .filter(|entry| entry.name == name)
+                        .map(|entry| (course.as_str(), entry.time_ms))
+                })
+                .collect(),
+        };
+        serde_json::to_string_pretty(&export)
+    }
+
+    // Forgets the name, its mailbox and its race times, and ends its session
+    // tickets. Mail it sent stays with the recipients.
+    fn delete_account(&mut self, name: &str) {
+        if self.accounts.names.remove(name).is_some() {
+            if let Err(e) = self.accounts.save(&mut *self.storage) {
+                error!(target: logging::SERVER, "Failed to save accounts: {}", e);
+            }
+        }
+        if self.mail.mailboxes.remove(name).is_some() {
+            self.save_mail();
+        }
+        if self.cosmetics.names.remove(name).is_some() {
+            self.save_cosmetics();
+        }
+        if self.display_names.names.remove(name).is_some() {
+            self.save_display_names();
+        }
+        let courses = self.leaderboards.remove(name);
+        if !courses.is_empty() {
+            if let Err(e) = self.leaderboards.save(&mut *self.storage) {
+                error!(target: logging::SERVER, "Failed to save leaderboard: {}", e);
+            }
+            for course in courses {
+                self.broadcast(&self.leaderboards.message(&course), None);
+            }
+        }
+        self.revoked.revoke(name);
+        if let Err(e) = self.revoked.save(&mut *self.storage) {
+            error!(target: logging::SERVER, "Failed to save revoked sessions: {}", e);
+        }
+    }
+
+    // Re-evaluates every client's snapshot rate from the last round of pings,
+    // then starts the next round
+    fn ping_players(&mut self) {
+        let message = ClientMessage::Ping {
+            sent_at_ms: now_millis(),
+        };
+        let policy = &self.snapshot_policy;
+        for player in self.players.values_mut() {
+            if player.link.adjust(policy) {
+                debug!(
+                    target: logging::NET,
+                    "Player {} snapshots every {} ticks (rtt {:?} ms)",
+                    player.id, player.link.snapshot_interval, player.link.rtt_ms
+                );
+            }
+            player.link.ping_sent();
+        }
+        self.broadcast(&message, None);
+    }
+
+    fn apply_script_actions(&mut self) {
+        for action in self.scripts.take_actions() {
+            match action {
+                ScriptAction::Say { id, text } => {
+                    let message = ClientMessage::UpdateMessage {
+                        id,
+                        message: text,
+                        expression: None,
+                        channel: ChatChannel::Global,
+                        sent_at_ms: now_millis(),
+                    };
+                    self.broadcast(&message, None);
+                }
+                ScriptAction::Notice { id, text } => {
+                    if let Some((endpoint, _)) = self.players.iter().find(|(_, p)| p.id == id) {
+                        self.notice(*endpoint, &text);
+                    }
+                }
+                ScriptAction::Broadcast { text } => {
+                    self.broadcast(&ClientMessage::ServerNotice { text }, None);
+                }
+                ScriptAction::Announce { text, severity } => self.announce(text, severity),
+                ScriptAction::SetWeather { weather } => {
+                    self.weather = weather.clamped();
+                    info!(target: logging::SERVER, "Weather is now {:?}", self.weather);
+                    let message = ClientMessage::SetWeather {
+                        weather: self.weather,
+                    };
+                    self.broadcast(&message, None);
+                }
+            }
+        }
+    }
+
+    fn announce(&mut self, text: String, severity: Severity) {
+        info!(target: logging::SERVER, "Announcing ({}): {}", severity.name(), text);
+        self.broadcast(&ClientMessage::ServerAnnouncement { text, severity }, None);
+    }
+
+    // Makes everyone who is recording stop, telling them why
+    fn stop_recordings(&mut self, reason: &str) {
+        let recording: Vec<(Endpoint, usize)> = self
+            .players
+            .iter_mut()
+            .filter(|(_, player)| player.recording)
+            .map(|(endpoint, player)| {
+                player.recording = false;
+                (*endpoint, player.id)
+            })
+            .collect();
+        for (endpoint, id) in recording {
+            info!(target: logging::SERVER, "Stopped player {} recording: {}", id, reason);
+            self.notice(endpoint, &format!("Recording stopped, {}", reason));
+            self.broadcast(
+                &ClientMessage::RecordingStatus {
+                    id,
+                    recording: false,
+                },
+                None,
+            );
+        }
+    }
+
+    // Everyone but the speaker that a message on `channel` reaches, None for
+    // party chat from someone in no party
+    fn chat_listeners(&self, speaker: Endpoint, channel: ChatChannel) -> Option<Vec<Endpoint>> {
+        let from = self.players.get(&speaker)?;
+        let hears = |player: &ServerPlayer| match channel {
+            ChatChannel::Global => true,
+            ChatChannel::Local => (player.x - from.x).hypot(player.y - from.y) <= LOCAL_CHAT_RADIUS,
+            ChatChannel::Party => player.party == from.party,
+        };
+        if channel == ChatChannel::Party && from.party.is_none() {
+            return None;
+        }
+        Some(
+            self.players
+                .iter()
+                .filter(|&(&endpoint, player)| endpoint != speaker && hears(player))
+                .map(|(&endpoint, _)| endpoint)
+                .collect(),
+        )
+    }
+
+    // Moves a player into `party`, or out of theirs with None, and tells
+    // the parties on both sides who's in them now
+    fn set_party(&mut self, endpoint: Endpoint, party: Option<String>) {
+        let Some(player) = self.players.get_mut(&endpoint) else {
+            return;
+        };
+        let left = std::mem::replace(&mut player.party, party.clone());
+        if left == party {
+            return;
+        }
+        if let Some(left) = left {
+            self.send_party_update(&left);
+        }
+        match party {
+            Some(party) => self.send_party_update(&party),
+            None => {
+                let message = ClientMessage::PartyUpdate {
+                    party: None,
+                    members: Vec::new(),
+                };
+                self.send(endpoint, &message);
+            }
+        }
+    }
+
+    // Tells everyone in `party` who else is
+    fn send_party_update(&self, party: &str) {
+        let members: Vec<(Endpoint, usize)> = self
+            .players
+            .iter()
+            .filter(|(_, player)| player.party.as_deref() == Some(party))
+            .map(|(&endpoint, player)| (endpoint, player.id))
+            .collect();
+        let message = ClientMessage::PartyUpdate {
+            party: Some(party.to_string()),
+            members: members.iter().map(|&(_, id)| id).collect(),
+        };
+        for (endpoint, _) in members {
+            self.send(endpoint, &message);
+        }
+    }
+
+    fn notice(&self, endpoint: Endpoint, text: &str) {
+        self.send(
+            endpoint,
+            &ClientMessage::ServerNotice {
+                text: text.to_string(),
+            },
+        );
+    }
+
+    // Binds `name` to the connection, remembers `device` as trusted for it and
+    // hands out a ticket for the next connection
+    fn complete_login(
+        &mut self,
+        endpoint: Endpoint,
+        id: usize,
+        name: String,
+        device: Option<String>,
+    ) {
+        if let (Some(account), Some(device)) = (self.accounts.names.get_mut(&name), device) {
+            if !account.devices.contains(&device) {
+                account.devices.push(device);
+                if let Err(e) = self.accounts.save(&mut *self.storage) {
+                    error!(target: logging::SERVER, "Failed to save accounts: {}", e);
+                }
+            }
+        }
+        info!(target: logging::AUTH, "Player {} logged in as {}", id, name);
+        self.send(endpoint, &self.mail.message(&name));
+        let ticket = self.tickets.issue(&name, id);
+        self.send(endpoint, &ClientMessage::SessionTicket { ticket });
+        let mut named = false;
+        if let Some(player) = self.players.get_mut(&endpoint) {
+            player.name = Some(name.clone());
+            player.pending_login = None;
+            named = player.display_name.is_some();
+        }
+        if !named {
+            let display_name = self.display_names.names.get(&name).cloned();
+            self.set_display_name(endpoint, id, display_name.unwrap_or_else(|| name.clone()));
+        }
+        self.share_cosmetics(endpoint, id, &name);
+    }
+
+    fn set_display_name(&mut self, endpoint: Endpoint, id: usize, name: String) {
+        if let Some(player) = self.players.get_mut(&endpoint) {
+            player.display_name = Some(name.clone());
+        }
+        self.broadcast(&ClientMessage::PlayerName { id, name }, None);
+    }
+
+    // Shows a player who just logged in everyone's look, and everyone theirs
+    fn share_cosmetics(&self, endpoint: Endpoint, id: usize, name: &str) {
+        for player in self.players.values() {
+            let Some(other) = &player.name else {
+                continue;
+            };
+            if let Some(&cosmetics) = self.cosmetics.names.get(other) {
+                let message = ClientMessage::PlayerCosmetics {
+                    id: player.id,
+                    cosmetics,
+                };
+                self.send(endpoint, &message);
+            }
+        }
+        if let Some(&cosmetics) = self.cosmetics.names.get(name) {
+            self.broadcast(
+                &ClientMessage::PlayerCosmetics { id, cosmetics },
+                Some(endpoint),
+            );
+        }
+    }
+
+    fn set_cosmetics(&mut self, id: usize, name: &str, cosmetics: Cosmetics) {
+        let cosmetics = Cosmetics {
+            hair: cosmetics.hair.clamped(),
+            ..cosmetics
+        };
+        self.cosmetics.names.insert(name.to_string(), cosmetics);
+        self.save_cosmetics();
+        self.broadcast(&ClientMessage::PlayerCosmetics { id, cosmetics }, None);
+    }
+
+    fn save_cosmetics(&mut self) {
+        if let Err(e) = self.cosmetics.save(&mut *self.storage) {
+            error!(target: logging::SERVER, "Failed to save cosmetics: {}", e);
+        }
+    }
+
+    fn save_display_names(&mut self) {
+        if let Err(e) = self.display_names.save(&mut *self.storage) {
+            error!(target: logging::SERVER, "Failed to save display names: {}", e);
+        }
+    }
+
+    // Turns `endpoint` away once it has used up its recovery code guesses
+    fn out_of_recovery_attempts(&self, endpoint: Endpoint) -> bool {
+        let out = self
+            .players
+            .get(&endpoint)
+            .is_some_and(|player| player.failed_codes >= RECOVERY_ATTEMPTS);
+        if out {
+            self.reject_login(endpoint, "Too many wrong recovery codes");
+        }
+        out
+    }
+
+    fn reject_login(&self, endpoint: Endpoint, reason: &str) {
+        self.send(
+            endpoint,
+            &ClientMessage::LoginRejected {
+                reason: reason.to_string(),
+            },
+        );
+    }
+
+    fn name_of(&self, endpoint: Endpoint) -> Option<String> {
+        self.players.get(&endpoint)?.name.clone()
+    }
+
+    // Puts a lap timed from `name`'s positions on the leaderboard
+    fn submit_lap(&mut self, course: &str, name: String, time_ms: u64) {
+        let entry = LeaderboardEntry { name, time_ms };
+        if time_ms > 0 && self.leaderboards.submit(course, entry) {
+            if let Err(e) = self.leaderboards.save(&mut *self.storage) {
+                error!(target: logging::SERVER, "Failed to save leaderboard: {}", e);
+            }
+            self.broadcast(&self.leaderboards.message(course), None);
+        }
+    }
+
+    fn save_mail(&mut self) {
+        if let Err(e) = self.mail.save(&mut *self.storage) {
+            error!(target: logging::SERVER, "Failed to save mail: {}", e);
+        }
+    }
+
+    // Sends `name` their mailbox on every connection they are logged in on
+    fn send_mailbox(&self, name: &str) {
+        let message = self.mail.message(name);
+        for (endpoint, player) in &self.players {
+            if player.name.as_deref() == Some(name) {
+                self.send(*endpoint, &message);
+            }
+        }
+    }
+
+    fn on_disconnected(&mut self, endpoint: Endpoint) {
+        if let Some(player) = self.players.remove(&endpoint) {
+            info!(target: logging::SERVER, "Player {} disconnected", player.id);
+            self.broadcast(
+                &ClientMessage::OtherPlayerDisconnected { id: player.id },
+                None,
+            );
+            for other in self.players.values_mut() {
+                other.invited_by.remove(&player.id);
+            }
+            if let Some(party) = &player.party {
+                self.send_party_update(party);
+            }
+        }
+    }
+}
+
+// A server running on its own thread, see `spawn`
+pub struct ServerHandle {
+    thread: JoinHandle<()>,
+    handler: NodeHandler<ServerSignal>,
+}
+
+impl ServerHandle {
+    // Moves everyone to `map` without them reconnecting
+    pub fn load_map(&self, map: GameMap) {
+        self.handler.signals().send(ServerSignal::LoadMap(map));
+    }
+
+    // A banner for everyone connected
+    pub fn announce(&self, text: String, severity: Severity) {
+        self.handler
+            .signals()
+            .send(ServerSignal::Announce(text, severity));
+    }
+
+    pub fn join(self) -> thread::Result<()> {
+        self.thread.join()
+    }
+}
+
+// Starts listening on `addr` and runs the server on a background thread.
+// Listening happens before returning so a local client can connect right away.
+pub fn spawn(addr: &str, options: ServerOptions) -> io::Result<ServerHandle> {
+    let scripts = match &options.scripts_dir {
+        Some(dir) => Scripts::load_dir(dir)?,
+        None => Scripts::empty(),
+    };
+    let tickets = match &options.ticket_secret_file {
+        Some(path) => TicketSigner::from_file(path)?,
+        None => TicketSigner::random(),
+    };
+    let identity = match &options.identity_secret_file {
+        Some(path) => Some(TicketSigner::from_file(path)?),
+        None => None,
+    };
+    let season_override = options.season_override;
+    let snapshot_policy = options.snapshot_policy;
+    let private_room = options.private_room;
+    let map = match &options.map_file {
+        Some(path) => GameMap::from_file(path)?,
+        None => GameMap::default(),
+    };
+    let storage = options.storage.open()?;
+    let (handler, listener) = node::split::<ServerSignal>();
+    let (_, local_addr) = handler.network().listen(Transport::FramedTcp, addr)?;
+    info!(target: logging::SERVER, "Server listening on {}", local_addr);
+
+    let server_handler = handler.clone();
+    let thread = thread::spawn(move || {
+        let mut server = Server::new(
+            handler,
+            season_override,
+            scripts,
+            tickets,
+            identity,
+            snapshot_policy,
+            storage,
+        );
+        server.private_room = private_room;
+        server.map = map;
+        server.handler.signals().send(ServerSignal::Tick);
+        listener.for_each(move |event| match event {
+            NodeEvent::Network(net_event) => match net_event {
+                NetEvent::Connected(_, _) => unreachable!(),
+                NetEvent::Accepted(endpoint, _) => server.on_accepted(endpoint),
+                NetEvent::Message(endpoint, data) => server.on_message(endpoint, data),
+                NetEvent::Disconnected(endpoint) => server.on_disconnected(endpoint),
+            },
+            NodeEvent::Signal(ServerSignal::Tick) => server.on_tick(),
+            NodeEvent::Signal(ServerSignal::LoadMap(map)) => server.load_map(map),
+            NodeEvent::Signal(ServerSignal::Announce(text, severity)) => {
+                server.announce(text, severity)
+            }
+        });
+    });
+
+    Ok(ServerHandle {
+        thread,
+        handler: server_handler,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELETED_AT_MS: u64 = 1_000_000_000;
+
+    // Signatures aren't looked at, only when the ticket was issued
+    fn ticket(name: &str, issued_at_ms: u64) -> SessionTicket {
+        SessionTicket {
+            name: name.to_string(),
+            player_id: 1,
+            expires_at_ms: issued_at_ms + TICKET_LIFETIME.as_millis() as u64,
+            signature: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn deleted_name_revokes_tickets_issued_before() {
+        let mut revoked = RevokedSessions::default();
+        revoked.names.insert("alice".to_string(), DELETED_AT_MS);
+        assert!(revoked.is_revoked(&ticket("alice", DELETED_AT_MS - 1)));
+        assert!(revoked.is_revoked(&ticket("alice", DELETED_AT_MS)));
+    }
+
+    #[test]
+    fn deleted_name_accepts_tickets_issued_after() {
+        let mut revoked = RevokedSessions::default();
+        revoked.names.insert("alice".to_string(), DELETED_AT_MS);
+        assert!(!revoked.is_revoked(&ticket("alice", DELETED_AT_MS + 1)));
+        assert!(!revoked.is_revoked(&ticket("bob", DELETED_AT_MS - 1)));
+    }
+}
@@ -0,0 +1,430 @@
+use crate::lifecycle;
+use crate::logging;
+use directories::ProjectDirs;
+use macroquad::input::{is_key_down, is_key_pressed, KeyCode};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+const SETTINGS_FILE: &str = "settings.toml";
+
+// A key stored by name, e.g. "W" or "Space"
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Key(pub KeyCode);
+
+macro_rules! key_names {
+    ($($name:ident),* $(,)?) => {
+        fn key_code(name: &str) -> Option<KeyCode> {
+            match name {
+                $(stringify!($name) => Some(KeyCode::$name),)*
+                _ => None,
+            }
+        }
+
+        fn key_name(code: KeyCode) -> &'static str {
+            match code {
+                $(KeyCode::$name => stringify!($name),)*
+                _ => "Unknown",
+            }
+        }
+    };
+}
+
+// Keys that can be bound. Digits are `Key0` to `Key9`, as in macroquad.
+#[rustfmt::skip]
+key_names!(
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9,
+    Up, Down, Left, Right, Space, Enter, Tab, Backspace, Escape,
+    Insert, Delete, Home, End, PageUp, PageDown,
+    Apostrophe, Comma, Minus, Period, Slash, Semicolon, Equal,
+    LeftBracket, Backslash, RightBracket, GraveAccent,
+    LeftShift, RightShift, LeftControl, RightControl, LeftAlt, RightAlt,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+);
+
+impl TryFrom<String> for Key {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, String> {
+        key_code(&name)
+            .map(Key)
+            .ok_or_else(|| format!("unknown key {:?}", name))
+    }
+}
+
+impl From<Key> for String {
+    fn from(key: Key) -> Self {
+        key_name(key.0).to_string()
+    }
+}
+
+impl Key {
+    // None for keys that can't be bound, which couldn't be saved
+    pub fn from_code(code: KeyCode) -> Option<Self> {
+        key_code(key_name(code)).map(Key)
+    }
+
+    pub fn name(self) -> &'static str {
+        key_name(self.0)
+    }
+}
+
+// Something a key does. Each has a field in `KeyBindings`, which is how
+// it's saved, and a label in the settings panel.
+macro_rules! actions {
+    ($($action:ident, $field:ident, $key:ident, $label:literal;)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Action {
+            $($action,)*
+        }
+
+        impl Action {
+            pub const ALL: &'static [Action] = &[$(Action::$action,)*];
+
+            pub fn label(self) -> &'static str {
+                match self {
+                    $(Action::$action => $label,)*
+                }
+            }
+        }
+
+        // Every action's key, rebound from the settings panel. Quick chat
+        // keys are in `Settings::quick_chat`.
+        #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+        #[serde(default)]
+        pub struct KeyBindings {
+            $(pub $field: Key,)*
+        }
+
+        impl Default for KeyBindings {
+            fn default() -> Self {
+                Self {
+                    $($field: Key(KeyCode::$key),)*
+                }
+            }
+        }
+
+        impl KeyBindings {
+            pub fn key(&self, action: Action) -> Key {
+                match action {
+                    $(Action::$action => self.$field,)*
+                }
+            }
+
+            fn key_mut(&mut self, action: Action) -> &mut Key {
+                match action {
+                    $(Action::$action => &mut self.$field,)*
+                }
+            }
+        }
+    };
+}
+
+#[rustfmt::skip]
+actions!(
+    Up, up, W, "Move up";
+    Down, down, S, "Move down";
+    Left, left, A, "Move left";
+    Right, right, D, "Move right";
+    Sprint, sprint, LeftShift, "Sprint (held)";
+    Walk, walk, LeftControl, "Walk (held)";
+    Sneak, sneak, C, "Sneak (held)";
+    Jump, jump, J, "Jump";
+    EmoteWave, emote_wave, Key1, "Wave";
+    EmoteDance, emote_dance, Key2, "Dance";
+    EmoteSit, emote_sit, Key3, "Sit";
+    ResetAnimation, reset_animation, R, "Reset animation";
+    OpenChat, open_chat, Enter, "Chat";
+    OpenCommands, open_commands, Slash, "Command line";
+    OpenMailbox, open_mailbox, M, "Mailbox";
+    PlayerList, player_list, Tab, "Player list (held)";
+    ToggleMinimap, toggle_minimap, V, "Minimap";
+    OpenCharacter, open_character, F9, "Character";
+    OpenSettings, open_settings, F10, "Settings";
+    ToggleFullscreen, toggle_fullscreen, F11, "Fullscreen";
+    Help, help, F1, "Controls help";
+    DebugOverlay, debug_overlay, F3, "Debug overlay";
+    TimingOverlay, timing_overlay, F4, "Timing overlay";
+    ToggleRecording, toggle_recording, F5, "Record replay";
+    GraphicsQuality, graphics_quality, F6, "Graphics quality";
+    SnapshotDiff, snapshot_diff, F7, "Snapshot diff";
+);
+
+impl KeyBindings {
+    pub fn pressed(&self, action: Action) -> bool {
+        is_key_pressed(self.key(action).0)
+    }
+
+    // Held down this frame
+    pub fn down(&self, action: Action) -> bool {
+        is_key_down(self.key(action).0)
+    }
+
+    // An action already on `key` swaps to the key `action` had, so no two
+    // share one
+    pub fn bind(&mut self, action: Action, key: Key) {
+        let old = self.key(action);
+        for &other in Action::ALL {
+            if self.key(other) == key {
+                *self.key_mut(other) = old;
+            }
+        }
+        *self.key_mut(action) = key;
+    }
+
+    // These keys bound over the defaults one at a time, leaving out the ones
+    // that are still the default. An action added since they were saved gets
+    // its default key even if something saved has it, this moves it off.
+    pub fn without_conflicts(&self) -> Self {
+        let defaults = Self::default();
+        let mut keys = defaults.clone();
+        for &action in Action::ALL {
+            if self.key(action) != defaults.key(action) {
+                keys.bind(action, self.key(action));
+            }
+        }
+        keys
+    }
+}
+
+// Longer tables don't fit in the settings panel
+pub const MAX_QUICK_CHAT: usize = 4;
+
+// A phrase said with a single key press, edited in the settings panel
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct QuickChat {
+    pub phrase: String,
+    pub key: Option<Key>, // None until one is picked
+}
+
+impl QuickChat {
+    fn new(phrase: &str, key: KeyCode) -> Self {
+        Self {
+            phrase: phrase.to_string(),
+            key: Some(Key(key)),
+        }
+    }
+}
+
+fn default_quick_chat() -> Vec<QuickChat> {
+    vec![
+        QuickChat::new("Hello, world!", KeyCode::Space),
+        QuickChat::new("Come over here.", KeyCode::G),
+        QuickChat::new("Okay.", KeyCode::H),
+    ]
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    Fullscreen,
+}
+
+impl WindowMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            WindowMode::Windowed => WindowMode::Fullscreen,
+            WindowMode::Fullscreen => WindowMode::Windowed,
+        }
+    }
+}
+
+// A filter over the finished world, see `PostProcess`
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PostEffect {
+    #[default]
+    Off,
+    Vignette,
+    Crt,
+    Bloom,
+    Night,
+}
+
+impl PostEffect {
+    pub const ALL: [PostEffect; 5] = [
+        PostEffect::Off,
+        PostEffect::Vignette,
+        PostEffect::Crt,
+        PostEffect::Bloom,
+        PostEffect::Night,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PostEffect::Off => "Off",
+            PostEffect::Vignette => "Vignette",
+            PostEffect::Crt => "CRT",
+            PostEffect::Bloom => "Bloom",
+            PostEffect::Night => "Night",
+        }
+    }
+}
+
+// Which `Palette` the game draws with
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorTheme {
+    #[default]
+    Standard,
+    Deuteranopia, // Nothing told apart by red against green alone
+    HighContrast,
+}
+
+impl ColorTheme {
+    pub const ALL: [ColorTheme; 3] = [
+        ColorTheme::Standard,
+        ColorTheme::Deuteranopia,
+        ColorTheme::HighContrast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorTheme::Standard => "Standard",
+            ColorTheme::Deuteranopia => "Deuteranopia",
+            ColorTheme::HighContrast => "High contrast",
+        }
+    }
+}
+
+// What the player picked, kept in the platform config directory between
+// runs. Unlike `lam.toml` the game writes this itself.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    pub name: Option<String>, // Login name, a random one until set
+    // Shown above the player instead of the login name, sent once logged in
+    // and updated whenever the server confirms a new one
+    pub display_name: Option<String>,
+    pub last_server: Option<String>,
+    pub volume: f32, // 0 to 1, nothing plays sound yet
+    pub window_mode: WindowMode,
+    // Size of the window when not fullscreen, otherwise `lam.toml` decides
+    pub resolution: Option<(u32, u32)>,
+    pub night_glow: bool, // Light around characters at night
+    // Size of the HUD, text and world, None follows the display's DPI
+    pub ui_scale: Option<f32>,
+    pub show_minimap: bool,
+    pub minimap_size: f32, // Width in UI pixels, one of `MINIMAP_SIZES`
+    pub post_effect: PostEffect,
+    pub color_theme: ColorTheme,
+    pub chat_emotes: bool, // Saying `:wave:` and the like plays the emote
+    pub word_filter: bool, // Masks rude words in other players' chat
+    pub filtered_words: Vec<String>, // Masked on top of the built in list
+    pub save_chat: bool,   // Chat is written to a file a session, see `ChatHistory`
+    pub muted: Vec<String>, // Display names whose chat is hidden, see `/mute`
+    pub friends: Vec<String>, // Display names, see `/friends`
+    pub touch_controls: bool, // A joystick and buttons on screen, see `TouchControls`
+    pub show_path: bool,   // Draws where a click to move is walking
+    pub hold_to_move: bool, // Holding right click walks toward the mouse, not just pressing it
+    pub keys: KeyBindings,
+    pub quick_chat: Vec<QuickChat>, // At most `MAX_QUICK_CHAT`
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            name: None,
+            display_name: None,
+            last_server: None,
+            volume: 1.0,
+            window_mode: WindowMode::Windowed,
+            resolution: None,
+            night_glow: true,
+            ui_scale: None,
+            show_minimap: true,
+            minimap_size: 200.0,
+            post_effect: PostEffect::Off,
+            color_theme: ColorTheme::Standard,
+            chat_emotes: true,
+            word_filter: true,
+            filtered_words: Vec::new(),
+            save_chat: true,
+            muted: Vec::new(),
+            friends: Vec::new(),
+            touch_controls: lifecycle::MOBILE,
+            show_path: true,
+            hold_to_move: false,
+            keys: KeyBindings::default(),
+            quick_chat: default_quick_chat(),
+        }
+    }
+}
+
+// e.g. `~/.config/lam/settings.toml` on Linux, None if there is no home directory
+pub fn settings_path() -> Option<PathBuf> {
+    ProjectDirs::from("", "", "lam").map(|dirs| dirs.config_dir().join(SETTINGS_FILE))
+}
+
+impl Settings {
+    // The saved settings, or the defaults on the first run or if they can't be read
+    pub fn load() -> Self {
+        let Some(path) = settings_path() else {
+            return Self::default();
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                warn!(target: logging::CONFIG, "Failed to read {}, using defaults: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        match toml::from_str::<Settings>(&text) {
+            Ok(mut settings) => {
+                settings.keys = settings.keys.without_conflicts();
+                settings
+            }
+            Err(e) => {
+                warn!(target: logging::CONFIG, "Failed to load {}, using defaults: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = settings_path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let text = toml::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(&path, text)?;
+        info!(target: logging::CONFIG, "Saved settings to {}", path.display());
+        Ok(())
+    }
+
+    // Whether chat from `name` is hidden, ignoring case
+    pub fn is_muted(&self, name: &str) -> bool {
+        self.muted
+            .iter()
+            .any(|muted| muted.eq_ignore_ascii_case(name))
+    }
+
+    pub fn set_muted(&mut self, name: &str, muted: bool) {
+        self.muted.retain(|other| !other.eq_ignore_ascii_case(name));
+        if muted {
+            self.muted.push(name.to_string());
+        }
+    }
+
+    pub fn is_friend(&self, name: &str) -> bool {
+        self.friends
+            .iter()
+            .any(|friend| friend.eq_ignore_ascii_case(name))
+    }
+
+    pub fn set_friend(&mut self, name: &str, friend: bool) {
+        self.friends
+            .retain(|other| !other.eq_ignore_ascii_case(name));
+        if friend {
+            self.friends.push(name.to_string());
+        }
+    }
+}
@@ -0,0 +1,547 @@
+use crate::chat;
+use crate::minimap::MINIMAP_SIZES;
+use crate::settings::{
+    Action, ColorTheme, Key, KeyBindings, PostEffect, QuickChat, Settings, WindowMode,
+    MAX_QUICK_CHAT,
+};
+use crate::ui;
+use macroquad::prelude::*;
+
+const PANEL_WIDTH: f32 = 380.0;
+const ROW_HEIGHT: f32 = 26.0;
+// Characters of a quick chat phrase that fit in its row
+const PHRASE_SHOWN: usize = 22;
+// Actions listed at once on the controls page, it scrolls past that
+const CONTROLS_SHOWN: usize = 14;
+// Window sizes to pick from. Fullscreen always uses the desktop's.
+pub const RESOLUTIONS: [(u32, u32); 6] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1366, 768),
+    (1600, 900),
+    (1920, 1080),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Row {
+    Resolution,
+    WindowMode,
+    UiScale,
+    NightGlow,
+    Minimap,
+    PostEffect,
+    ColorTheme,
+    WordFilter,
+    SaveChat,
+    Volume,
+    TouchControls,
+    ShowPath,
+    HoldToMove,
+    Controls,         // Opens the list of key bindings
+    QuickChat(usize), // Index into `Settings::quick_chat`
+    AddQuickChat,
+}
+
+const ROWS: [Row; 14] = [
+    Row::Resolution,
+    Row::WindowMode,
+    Row::UiScale,
+    Row::NightGlow,
+    Row::Minimap,
+    Row::PostEffect,
+    Row::ColorTheme,
+    Row::WordFilter,
+    Row::SaveChat,
+    Row::Volume,
+    Row::TouchControls,
+    Row::ShowPath,
+    Row::HoldToMove,
+    Row::Controls,
+];
+
+// `ROWS`, then a row per quick chat phrase and one to add another
+fn rows(settings: &Settings) -> Vec<Row> {
+    let mut rows = ROWS.to_vec();
+    rows.extend((0..settings.quick_chat.len()).map(Row::QuickChat));
+    if settings.quick_chat.len() < MAX_QUICK_CHAT {
+        rows.push(Row::AddQuickChat);
+    }
+    rows
+}
+
+// A quick chat phrase being typed, then the key for it being waited on, or
+// the key for an action
+enum Editing {
+    Phrase(usize, String), // Past the end of the table for a new one
+    Key(usize),
+    Binding(Action),
+}
+
+// Graphics and sound options, opened with the `open_settings` key, the key
+// bindings and the quick chat table. Changes apply and save straight away.
+pub struct SettingsPanel {
+    open: bool,
+    selected: usize,
+    controls: Option<usize>, // The action selected while the controls page is up
+    editing: Option<Editing>,
+    fresh: bool, // Keys queued before editing started are still pending
+}
+
+impl Default for SettingsPanel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SettingsPanel {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            selected: 0,
+            controls: None,
+            editing: None,
+            fresh: false,
+        }
+    }
+
+    // While open the arrow keys belong to the panel
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // While editing quick chat or waiting on a key every key does
+    pub fn is_typing(&self) -> bool {
+        self.editing.is_some()
+    }
+
+    // Returns true if `settings` changed and has to be applied
+    pub fn update(&mut self, settings: &mut Settings) -> bool {
+        if self.open && self.editing.is_some() {
+            return self.update_editing(settings);
+        }
+        if settings.keys.pressed(Action::OpenSettings) {
+            self.open = !self.open;
+            self.controls = None;
+            return false;
+        }
+        if !self.open {
+            return false;
+        }
+        if let Some(selected) = self.controls {
+            return self.update_controls(settings, selected);
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            self.open = false;
+            return false;
+        }
+
+        let rows = rows(settings);
+        self.selected = self.selected.min(rows.len() - 1);
+        if is_key_pressed(KeyCode::Up) {
+            self.selected = self.selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) && self.selected + 1 < rows.len() {
+            self.selected += 1;
+        }
+        if is_key_pressed(KeyCode::Enter) {
+            let editing = match rows[self.selected] {
+                Row::QuickChat(index) => Some(Editing::Phrase(
+                    index,
+                    settings.quick_chat[index].phrase.clone(),
+                )),
+                Row::AddQuickChat => {
+                    Some(Editing::Phrase(settings.quick_chat.len(), String::new()))
+                }
+                Row::Controls => {
+                    self.controls = Some(0);
+                    return false;
+                }
+                _ => None,
+            };
+            if editing.is_some() {
+                self.editing = editing;
+                self.fresh = true;
+                return false;
+            }
+        }
+        let step = match (
+            is_key_pressed(KeyCode::Left),
+            is_key_pressed(KeyCode::Right),
+        ) {
+            (true, false) => -1,
+            (false, true) => 1,
+            _ => return false,
+        };
+
+        match rows[self.selected] {
+            Row::Resolution => {
+                let current = settings
+                    .resolution
+                    .unwrap_or((screen_width() as u32, screen_height() as u32));
+                // The nearest listed size to start from, the window may be any size
+                let index = RESOLUTIONS
+                    .iter()
+                    .position(|&size| size >= current)
+                    .unwrap_or(RESOLUTIONS.len() - 1) as i32;
+                let index = (index + step).clamp(0, RESOLUTIONS.len() as i32 - 1);
+                settings.resolution = Some(RESOLUTIONS[index as usize]);
+            }
+            Row::WindowMode => settings.window_mode = settings.window_mode.toggled(),
+            Row::UiScale => {
+                // Following the display comes first, then `ui::SCALES`
+                let index = settings
+                    .ui_scale
+                    .and_then(|scale| ui::SCALES.iter().position(|&s| s == scale))
+                    .map_or(0, |index| index + 1) as i32;
+                let index = (index + step).clamp(0, ui::SCALES.len() as i32);
+                settings.ui_scale = (index > 0).then(|| ui::SCALES[index as usize - 1]);
+            }
+            Row::NightGlow => settings.night_glow = !settings.night_glow,
+            Row::Minimap => {
+                // Hidden comes first, then `MINIMAP_SIZES`
+                let index = MINIMAP_SIZES
+                    .iter()
+                    .position(|&size| size >= settings.minimap_size)
+                    .filter(|_| settings.show_minimap)
+                    .map_or(0, |index| index + 1) as i32;
+                let index = (index + step).clamp(0, MINIMAP_SIZES.len() as i32);
+                settings.show_minimap = index > 0;
+                if index > 0 {
+                    settings.minimap_size = MINIMAP_SIZES[index as usize - 1];
+                }
+            }
+            Row::PostEffect => {
+                let index = PostEffect::ALL
+                    .iter()
+                    .position(|&effect| effect == settings.post_effect)
+                    .unwrap_or(0) as i32;
+                let index = (index + step).rem_euclid(PostEffect::ALL.len() as i32);
+                settings.post_effect = PostEffect::ALL[index as usize];
+            }
+            Row::ColorTheme => {
+                let index = ColorTheme::ALL
+                    .iter()
+                    .position(|&theme| theme == settings.color_theme)
+                    .unwrap_or(0) as i32;
+                let index = (index + step).rem_euclid(ColorTheme::ALL.len() as i32);
+                settings.color_theme = ColorTheme::ALL[index as usize];
+            }
+            Row::WordFilter => settings.word_filter = !settings.word_filter,
+            Row::SaveChat => settings.save_chat = !settings.save_chat,
+            Row::TouchControls => settings.touch_controls = !settings.touch_controls,
+            Row::ShowPath => settings.show_path = !settings.show_path,
+            Row::HoldToMove => settings.hold_to_move = !settings.hold_to_move,
+            Row::Volume => {
+                settings.volume = (settings.volume + step as f32 * 0.1).clamp(0.0, 1.0);
+            }
+            Row::Controls | Row::QuickChat(_) | Row::AddQuickChat => return false,
+        }
+        true
+    }
+
+    // Enter waits for a new key for the selected action, Backspace puts
+    // back its default and Esc goes back to the rest of the settings
+    fn update_controls(&mut self, settings: &mut Settings, selected: usize) -> bool {
+        if is_key_pressed(KeyCode::Escape) {
+            self.controls = None;
+            return false;
+        }
+        let mut selected = selected;
+        if is_key_pressed(KeyCode::Up) {
+            selected = selected.saturating_sub(1);
+        }
+        if is_key_pressed(KeyCode::Down) && selected + 1 < Action::ALL.len() {
+            selected += 1;
+        }
+        self.controls = Some(selected);
+        let action = Action::ALL[selected];
+        if is_key_pressed(KeyCode::Enter) {
+            self.editing = Some(Editing::Binding(action));
+            return false;
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            let key = KeyBindings::default().key(action);
+            if settings.keys.key(action) != key {
+                settings.keys.bind(action, key);
+                return true;
+            }
+        }
+        false
+    }
+
+    // Enter takes the phrase and waits for a key, an empty phrase removes
+    // it. Esc leaves the phrase as it was, or the key once waiting on one,
+    // and Backspace then unbinds it.
+    fn update_editing(&mut self, settings: &mut Settings) -> bool {
+        match &mut self.editing {
+            Some(Editing::Phrase(index, text)) => {
+                while let Some(c) = get_char_pressed() {
+                    if !self.fresh && !c.is_control() && text.chars().count() < chat::MAX_LENGTH {
+                        text.push(c);
+                    }
+                }
+                self.fresh = false;
+                if is_key_pressed(KeyCode::Backspace) {
+                    text.pop();
+                }
+                if is_key_pressed(KeyCode::Escape) {
+                    self.editing = None;
+                    return false;
+                }
+                if !is_key_pressed(KeyCode::Enter) {
+                    return false;
+                }
+                let (index, phrase) = (*index, text.trim().to_string());
+                if phrase.is_empty() {
+                    if index < settings.quick_chat.len() {
+                        settings.quick_chat.remove(index);
+                    }
+                    self.editing = None;
+                } else {
+                    match settings.quick_chat.get_mut(index) {
+                        Some(quick) => quick.phrase = phrase,
+                        None => settings.quick_chat.push(QuickChat { phrase, key: None }),
+                    }
+                    self.editing = Some(Editing::Key(index));
+                }
+                true
+            }
+            Some(Editing::Key(index)) => {
+                let index = *index;
+                let key = match get_last_key_pressed() {
+                    Some(KeyCode::Escape) => {
+                        self.editing = None;
+                        return false;
+                    }
+                    Some(KeyCode::Backspace) => None,
+                    // Enter opens the chat line, it can't say something too
+                    Some(KeyCode::Enter) | None => return false,
+                    Some(code) => match Key::from_code(code) {
+                        Some(key) => Some(key),
+                        None => return false,
+                    },
+                };
+                settings.quick_chat[index].key = key;
+                self.editing = None;
+                true
+            }
+            Some(Editing::Binding(action)) => {
+                let action = *action;
+                let Some(code) = get_last_key_pressed() else {
+                    return false;
+                };
+                if code == KeyCode::Escape {
+                    self.editing = None;
+                    return false;
+                }
+                // Keys that couldn't be saved are waited past
+                let Some(key) = Key::from_code(code) else {
+                    return false;
+                };
+                settings.keys.bind(action, key);
+                self.editing = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn draw(&self, settings: &Settings) {
+        if !self.open {
+            return;
+        }
+        if let Some(selected) = self.controls {
+            self.draw_controls(&settings.keys, selected);
+            return;
+        }
+
+        let x = (ui::width() - PANEL_WIDTH) / 2.0;
+        let y = 80.0;
+        let rows = rows(settings);
+        let height = 60.0 + rows.len() as f32 * ROW_HEIGHT + 30.0;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, PANEL_WIDTH, height, 2.0, DARKGRAY);
+        ui::draw_text("Settings", x + 12.0, y + 26.0, 24.0, BLACK);
+
+        for (index, row) in rows.iter().enumerate() {
+            let row_y = y + 56.0 + index as f32 * ROW_HEIGHT;
+            if index == self.selected {
+                draw_rectangle(
+                    x + 4.0,
+                    row_y - 18.0,
+                    PANEL_WIDTH - 8.0,
+                    ROW_HEIGHT,
+                    LIGHTGRAY,
+                );
+            }
+            let (label, value) = match row {
+                Row::Resolution => (
+                    "Window size",
+                    match settings.resolution {
+                        Some((width, height)) => format!("{} x {}", width, height),
+                        None => format!("{} x {}", screen_width(), screen_height()),
+                    },
+                ),
+                Row::WindowMode => (
+                    "Window mode",
+                    match settings.window_mode {
+                        WindowMode::Windowed => "Windowed".to_string(),
+                        WindowMode::Fullscreen => "Fullscreen".to_string(),
+                    },
+                ),
+                Row::UiScale => (
+                    "UI scale",
+                    match settings.ui_scale {
+                        Some(scale) => format!("{:.0}%", scale * 100.0),
+                        None => format!("Auto ({:.0}%)", ui::scale() * 100.0),
+                    },
+                ),
+                Row::NightGlow => (
+                    "Light at night",
+                    if settings.night_glow { "On" } else { "Off" }.to_string(),
+                ),
+                Row::Minimap => (
+                    "Minimap",
+                    if settings.show_minimap {
+                        format!("{:.0}", settings.minimap_size)
+                    } else {
+                        "Off".to_string()
+                    },
+                ),
+                Row::PostEffect => ("Filter", settings.post_effect.name().to_string()),
+                Row::ColorTheme => ("Colors", settings.color_theme.name().to_string()),
+                Row::WordFilter => (
+                    "Word filter",
+                    if settings.word_filter { "On" } else { "Off" }.to_string(),
+                ),
+                Row::SaveChat => (
+                    "Save chat",
+                    if settings.save_chat { "On" } else { "Off" }.to_string(),
+                ),
+                Row::Volume => ("Volume", format!("{:.0}", settings.volume * 100.0)),
+                Row::TouchControls => (
+                    "Touch controls",
+                    if settings.touch_controls { "On" } else { "Off" }.to_string(),
+                ),
+                Row::ShowPath => (
+                    "Show walking path",
+                    if settings.show_path { "On" } else { "Off" }.to_string(),
+                ),
+                Row::HoldToMove => (
+                    "Right click moves",
+                    if settings.hold_to_move {
+                        "While held"
+                    } else {
+                        "On press"
+                    }
+                    .to_string(),
+                ),
+                Row::Controls => {
+                    ui::draw_text("Controls", x + 12.0, row_y, 20.0, BLACK);
+                    ui::draw_text("Enter", x + PANEL_WIDTH / 2.0, row_y, 20.0, BLACK);
+                    continue;
+                }
+                Row::QuickChat(_) | Row::AddQuickChat => {
+                    self.draw_quick_chat(*row, settings, x, row_y);
+                    continue;
+                }
+            };
+            ui::draw_text(label, x + 12.0, row_y, 20.0, BLACK);
+            ui::draw_text(
+                &format!("< {} >", value),
+                x + PANEL_WIDTH / 2.0,
+                row_y,
+                20.0,
+                BLACK,
+            );
+        }
+
+        let help = match self.editing {
+            Some(Editing::Phrase(..)) => "Enter to keep, empty to remove, Esc to cancel",
+            Some(Editing::Key(_)) => "Press a key, Backspace for none, Esc to keep",
+            Some(Editing::Binding(_)) => "Press a key, Esc to keep",
+            None => "Up/Down select, Left/Right change, Enter edit, Esc close",
+        };
+        ui::draw_text(help, x + 12.0, y + height - 12.0, 16.0, GRAY);
+    }
+    // Every action and its key, scrolled to keep `selected` in sight. A key
+    // taken from another action swaps with it.
+    fn draw_controls(&self, keys: &KeyBindings, selected: usize) {
+        let x = (ui::width() - PANEL_WIDTH) / 2.0;
+        let y = 80.0;
+        let shown = CONTROLS_SHOWN.min(Action::ALL.len());
+        let height = 60.0 + shown as f32 * ROW_HEIGHT + 30.0;
+        draw_rectangle(x, y, PANEL_WIDTH, height, Color::new(1.0, 1.0, 1.0, 0.95));
+        draw_rectangle_lines(x, y, PANEL_WIDTH, height, 2.0, DARKGRAY);
+        ui::draw_text("Controls", x + 12.0, y + 26.0, 24.0, BLACK);
+
+        let first = selected
+            .saturating_sub(shown / 2)
+            .min(Action::ALL.len() - shown);
+        for (index, &action) in Action::ALL.iter().enumerate().skip(first).take(shown) {
+            let row_y = y + 56.0 + (index - first) as f32 * ROW_HEIGHT;
+            if index == selected {
+                draw_rectangle(
+                    x + 4.0,
+                    row_y - 18.0,
+                    PANEL_WIDTH - 8.0,
+                    ROW_HEIGHT,
+                    LIGHTGRAY,
+                );
+            }
+            let key = match self.editing {
+                Some(Editing::Binding(binding)) if binding == action => "Press a key",
+                _ => keys.key(action).name(),
+            };
+            ui::draw_text(action.label(), x + 12.0, row_y, 20.0, BLACK);
+            ui::draw_text(key, x + PANEL_WIDTH / 2.0 + 40.0, row_y, 20.0, BLACK);
+        }
+
+        let help = match self.editing {
+            Some(_) => "Press a key, Esc to keep",
+            None => "Enter rebind, Backspace default, Esc back",
+        };
+        ui::draw_text(help, x + 12.0, y + height - 12.0, 16.0, GRAY);
+    }
+
+    // The phrase on the left and its key on the right, or what's being typed
+    fn draw_quick_chat(&self, row: Row, settings: &Settings, x: f32, y: f32) {
+        let index = match row {
+            Row::QuickChat(index) => index,
+            _ => settings.quick_chat.len(),
+        };
+        let quick = settings.quick_chat.get(index);
+        let (label, value) = match &self.editing {
+            Some(Editing::Phrase(editing, text)) if *editing == index => {
+                // The end being typed stays in sight
+                let shown: String = text.chars().rev().take(PHRASE_SHOWN).collect();
+                let shown: String = shown.chars().rev().collect();
+                (format!("{}_", shown), String::new())
+            }
+            Some(Editing::Key(editing)) if *editing == index => (
+                quoted(quick.map_or("", |quick| &quick.phrase)),
+                "Press a key".to_string(),
+            ),
+            _ => match quick {
+                Some(quick) => (
+                    quoted(&quick.phrase),
+                    quick.key.map_or("None", Key::name).to_string(),
+                ),
+                None => ("Add quick chat".to_string(), String::new()),
+            },
+        };
+        ui::draw_text(&label, x + 12.0, y, 20.0, BLACK);
+        ui::draw_text(&value, x + PANEL_WIDTH - 110.0, y, 20.0, BLACK);
+    }
+}
+
+// Cut short to fit beside the key
+fn quoted(phrase: &str) -> String {
+    if phrase.chars().count() > PHRASE_SHOWN {
+        let start: String = phrase.chars().take(PHRASE_SHOWN - 3).collect();
+        format!("\"{}...\"", start)
+    } else {
+        format!("\"{}\"", phrase)
+    }
+}
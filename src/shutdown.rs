@@ -0,0 +1,84 @@
+use crate::error;
+use crate::game::Game;
+use crate::logging;
+use crate::metrics;
+use crate::net::{send_message, Connection};
+use crate::protocol::ClientMessage;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::info;
+
+// How long the network task gets to notice it was stopped
+const NETWORK_JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+// Exit code of a process stopped by a second Ctrl+C, as shells report SIGINT
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static QUIT_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+// Turns Ctrl+C into a request for the main loop to end, so `shutdown` runs
+// instead of the process dying mid-frame. A second Ctrl+C exits right away
+// in case the loop is stuck.
+pub fn handle_ctrl_c(rt: &Runtime) {
+    rt.spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if QUIT_REQUESTED.swap(true, Ordering::Relaxed) {
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+            info!(target: logging::GAME, "Ctrl+C, quitting");
+        }
+    });
+}
+
+// Whether Ctrl+C was pressed. Main loops check it alongside their own reasons to stop.
+pub fn quit_requested() -> bool {
+    QUIT_REQUESTED.load(Ordering::Relaxed)
+}
+
+// The steps of a clean exit, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownStep {
+    Goodbye,     // The server hears we left instead of timing us out
+    SaveState,   // Settings, metrics and a replay being recorded are written out
+    StopAudio,   // Nothing plays sound yet, kept so the order is fixed when it does
+    FlushLogs,   // Stdout, so the last lines aren't lost with the process
+    JoinNetwork, // The listener task has finished
+}
+
+// Runs every shutdown step in order, returning the steps as they happened.
+// Called once the main loop has ended, whether the window was closed, Ctrl+C
+// was pressed or a headless run timed out.
+pub fn shutdown(game: &Mutex<Game>, network: Option<Connection>, rt: Runtime) -> Vec<ShutdownStep> {
+    let mut steps = Vec::new();
+
+    {
+        let mut game = error::lock(game);
+        if game.local_id() != 0 {
+            send_message(&network, &mut game, &ClientMessage::Goodbye);
+        }
+        steps.push(ShutdownStep::Goodbye);
+
+        game.save_settings();
+        metrics::save();
+        if game.recorder.is_some() {
+            game.toggle_recording();
+        }
+        steps.push(ShutdownStep::SaveState);
+    }
+
+    steps.push(ShutdownStep::StopAudio);
+
+    info!(target: logging::GAME, "Shutting down");
+    let _ = io::stdout().flush();
+    steps.push(ShutdownStep::FlushLogs);
+
+    if let Some(connection) = network {
+        connection.close();
+    }
+    rt.shutdown_timeout(NETWORK_JOIN_TIMEOUT);
+    steps.push(ShutdownStep::JoinNetwork);
+
+    steps
+}
@@ -0,0 +1,61 @@
+use hecs::{Entity, World};
+use macroquad::prelude::*;
+use std::collections::HashMap;
+
+use crate::components::Position;
+
+// Uniform grid of entity positions, rebuilt every frame, for cheap "what is
+// near this point" queries
+pub struct SpatialHash {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<(Entity, Vec2)>>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    pub fn rebuild(&mut self, world: &World) {
+        for entities in self.cells.values_mut() {
+            entities.clear();
+        }
+        for (entity, position) in world.query::<(Entity, &Position)>().iter() {
+            let cell = self.cell(position.x, position.y);
+            self.cells
+                .entry(cell)
+                .or_default()
+                .push((entity, Vec2::new(position.x, position.y)));
+        }
+    }
+
+    // Entities within `radius` of `center`
+    pub fn query_radius(&self, center: Vec2, radius: f32) -> Vec<(Entity, Vec2)> {
+        let (min_x, min_y) = self.cell(center.x - radius, center.y - radius);
+        let (max_x, max_y) = self.cell(center.x + radius, center.y + radius);
+
+        let mut found = Vec::new();
+        for cx in min_x..=max_x {
+            for cy in min_y..=max_y {
+                if let Some(entities) = self.cells.get(&(cx, cy)) {
+                    found.extend(
+                        entities
+                            .iter()
+                            .filter(|(_, position)| position.distance(center) <= radius),
+                    );
+                }
+            }
+        }
+        found
+    }
+}
@@ -0,0 +1,69 @@
+use crate::animation::RUN_POSES;
+use crate::batch::{DrawList, Layer};
+use crate::components::{Animation, ChatBubble, Position};
+use crate::render::draw_bubble;
+use macroquad::prelude::*;
+
+// Character spritesheets, under the assets directory
+pub const CHARACTERS_DIR: &str = "characters";
+
+// A sheet is one row of equally wide frames: the run cycle in `RUN_POSES`
+// order, then `IDLE_POSE`. Sheets keep this layout whatever the run clip in
+// `ANIMATIONS_FILE` looks like.
+const FRAME_COUNT: usize = RUN_POSES.len() + 1;
+const IDLE_FRAME: usize = RUN_POSES.len();
+// Frames are scaled to the stick figure's height, head top to feet, and
+// placed where it would be
+const SPRITE_HEIGHT: f32 = 80.0;
+const SPRITE_TOP: f32 = -20.0;
+
+// Where the sheet picked in `Cosmetics::sprite` is, for `Assets::texture_file`
+pub fn sheet_file(sprite: u8) -> String {
+    format!("{}/{}.png", CHARACTERS_DIR, sprite)
+}
+
+// Sheets can't blend between poses, so this is whichever is closer
+fn frame(animation: &Animation, is_moving: bool) -> usize {
+    if !is_moving {
+        IDLE_FRAME
+    } else if animation.pose_interp_factor < 0.5 {
+        animation.current_pose_index % RUN_POSES.len()
+    } else {
+        animation.next_pose_index % RUN_POSES.len()
+    }
+}
+
+// Submits a character drawn from `sheet`, faded by `alpha`
+pub fn draw_sprite_character(
+    list: &mut DrawList,
+    sheet: &Texture2D,
+    position: &Position,
+    animation: &Animation,
+    is_moving: bool,
+    bubble: Option<&ChatBubble>,
+    alpha: f32,
+) {
+    let origin = vec2(position.x, position.y + animation.bobbing_offset);
+    let frame_width = sheet.width() / FRAME_COUNT as f32;
+    let source = Rect::new(
+        frame(animation, is_moving) as f32 * frame_width,
+        0.0,
+        frame_width,
+        sheet.height(),
+    );
+    let width = SPRITE_HEIGHT * frame_width / sheet.height();
+    let dest = Rect::new(
+        origin.x - width / 2.0,
+        origin.y + SPRITE_TOP,
+        width,
+        SPRITE_HEIGHT,
+    );
+    list.sprite(
+        Layer::Characters,
+        sheet,
+        source,
+        dest,
+        Color::new(1.0, 1.0, 1.0, alpha),
+    );
+    draw_bubble(list, origin, bubble, alpha);
+}
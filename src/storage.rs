@@ -0,0 +1,172 @@
+use crate::logging;
+use rusqlite::{Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+// Where the server keeps what outlives a restart. Each record is a blob
+// under a short key like "mail"; callers decide what goes in it.
+pub trait Storage: Send {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    fn save(&mut self, key: &str, data: &[u8]) -> io::Result<()>;
+}
+
+// Which `Storage` a server uses, from `--storage`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StorageKind {
+    Memory,          // Nothing survives a restart, for tests and throwaway servers
+    Files(PathBuf),  // One file per record in a directory
+    Sqlite(PathBuf), // One database file
+}
+
+impl Default for StorageKind {
+    // The files the server has always written, next to the executable
+    fn default() -> Self {
+        StorageKind::Files(PathBuf::from("."))
+    }
+}
+
+impl StorageKind {
+    // `memory`, `files:<dir>` or `sqlite:<file>`
+    pub fn parse(text: &str) -> Option<Self> {
+        match text.split_once(':') {
+            None if text == "memory" => Some(StorageKind::Memory),
+            Some(("files", dir)) => Some(StorageKind::Files(PathBuf::from(dir))),
+            Some(("sqlite", path)) => Some(StorageKind::Sqlite(PathBuf::from(path))),
+            _ => None,
+        }
+    }
+
+    pub fn open(&self) -> io::Result<Box<dyn Storage>> {
+        Ok(match self {
+            StorageKind::Memory => Box::new(MemoryStorage::default()),
+            StorageKind::Files(dir) => Box::new(FileStorage::new(dir)?),
+            StorageKind::Sqlite(path) => Box::new(SqliteStorage::open(path)?),
+        })
+    }
+}
+
+// Reads `key` from `storage`, starting fresh if it is missing or unreadable
+pub fn load_or_default<T: DeserializeOwned + Default>(storage: &dyn Storage, key: &str) -> T {
+    let data = match storage.load(key) {
+        Ok(Some(data)) => data,
+        Ok(None) => return T::default(),
+        Err(e) => {
+            warn!(target: logging::SERVER, "Failed to load {}, starting fresh: {}", key, e);
+            return T::default();
+        }
+    };
+    match bincode::deserialize(&data) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!(target: logging::SERVER, "Failed to load {}, starting fresh: {:?}", key, e);
+            T::default()
+        }
+    }
+}
+
+pub fn save<T: Serialize>(storage: &mut dyn Storage, key: &str, value: &T) -> io::Result<()> {
+    let data = bincode::serialize(value).map_err(io::Error::other)?;
+    storage.save(key, &data)
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    records: HashMap<String, Vec<u8>>,
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.records.get(key).cloned())
+    }
+
+    fn save(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.records.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+// Each record is `<key>.bin` in `dir`
+pub struct FileStorage {
+    dir: PathBuf,
+}
+
+impl FileStorage {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", key))
+    }
+}
+
+impl Storage for FileStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        // Write to a temporary file first so a crash can't leave a half-written file
+        let path = self.path(key);
+        let tmp_path = path.with_extension("bin.tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(tmp_path, path)
+    }
+}
+
+// Every record is a row in one table, for hosts that would rather back up a
+// single database than a directory
+pub struct SqliteStorage {
+    connection: Connection,
+}
+
+fn sqlite_error(e: rusqlite::Error) -> io::Error {
+    io::Error::other(e)
+}
+
+impl SqliteStorage {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let connection = Connection::open(path).map_err(sqlite_error)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS records (key TEXT PRIMARY KEY, data BLOB NOT NULL)",
+                (),
+            )
+            .map_err(sqlite_error)?;
+        Ok(Self { connection })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn load(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        self.connection
+            .query_row("SELECT data FROM records WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()
+            .map_err(sqlite_error)
+    }
+
+    fn save(&mut self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO records (key, data) VALUES (?1, ?2)
+                 ON CONFLICT(key) DO UPDATE SET data = excluded.data",
+                (key, data),
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+}
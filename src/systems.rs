@@ -0,0 +1,167 @@
+use crate::animation::{
+    clips, emote_clip, gait, walk_clip, walk_interval, BOBBING, JUMP_DURATION, JUMP_HEIGHT,
+    SNEAK_CROUCH,
+};
+use crate::components::{Animation, ChatBubble, LocalPlayer, Position, Velocity};
+use crate::protocol::Stance;
+use hecs::World;
+use macroquad::prelude::*;
+use std::time::{Duration, Instant};
+
+// Chat bubbles disappear after this long, unless `lam.toml` says otherwise
+pub const MESSAGE_LIFETIME: Duration = Duration::from_secs(15);
+// Bubbles fade out over the end of their lifetime
+const BUBBLE_FADE: Duration = Duration::from_secs(3);
+// Other players' bubbles further away than this are hidden, unless
+// `lam.toml` says otherwise
+pub const BUBBLE_RADIUS: f32 = 600.0;
+// From a character's position to the middle of its chat bubble
+const BUBBLE_OFFSET: f32 = -45.0;
+
+// Moves entities towards their target position at a constant speed
+pub fn movement_system(world: &mut World, dt: f32) {
+    for (position, velocity, local) in
+        world.query_mut::<(&mut Position, &mut Velocity, Option<&mut LocalPlayer>)>()
+    {
+        let Some((target_x, target_y)) = velocity.target else {
+            continue;
+        };
+
+        let direction = Vec2::new(target_x - position.x, target_y - position.y);
+        let distance = direction.length();
+        let speed = match &local {
+            Some(local) => velocity.speed * local.speed_scale(),
+            None => velocity.speed,
+        };
+
+        if distance < speed * dt {
+            // Close enough to the target
+            position.x = target_x;
+            position.y = target_y;
+            velocity.target = None;
+            velocity.is_moving = false;
+        } else {
+            let direction = direction.normalize();
+            position.x += direction.x * speed * dt;
+            position.y += direction.y * speed * dt;
+            velocity.is_moving = true;
+        }
+        if let Some(local) = local {
+            local.position_changed = true;
+        }
+    }
+}
+
+// Advances the walk cycle and bobbing of one character. Only `dt` moves it
+// along, so the same steps always give the same pose.
+pub fn animate(animation: &mut Animation, is_moving: bool, dt: f32) {
+    let sneaking = animation.stance == Stance::Sneaking;
+    let clips = clips();
+    animation.step = None;
+    if is_moving {
+        let clip = clips.clip(walk_clip(animation.stance));
+        let interval = walk_interval(animation, clip, animation.current_pose_index);
+        animation.pose_elapsed += dt;
+        if animation.pose_elapsed >= interval {
+            animation.current_pose_index = animation.next_pose_index % clip.frames.len();
+            animation.next_pose_index = (animation.current_pose_index + 1) % clip.frames.len();
+            animation.step = clip.frames[animation.current_pose_index].step;
+            animation.pose_interp_factor = 0.0;
+            animation.pose_elapsed = 0.0;
+        } else {
+            animation.pose_interp_factor = (animation.pose_elapsed / interval).min(1.0);
+        }
+
+        // Update bobbing when moving, quicker and higher the faster the gait
+        let (rate, height) = gait(animation.stance);
+        animation.bobbing_time += dt * rate;
+        animation.bobbing_offset = (animation.bobbing_time * 5.0).sin() * BOBBING * height;
+        if sneaking {
+            animation.bobbing_offset = animation.bobbing_offset * 0.4 + SNEAK_CROUCH;
+        }
+        animation.emote = None;
+        animation.idle_elapsed = 0.0;
+    } else {
+        // Reset bobbing when not moving
+        animation.bobbing_time = 0.0;
+        animation.bobbing_offset = 0.0;
+        animation.idle_elapsed += dt;
+        if let Some(emote) = animation.emote {
+            let clip = clips.clip(emote_clip(emote));
+            animation.emote_elapsed += dt;
+            if clip.is_finished(animation.emote_elapsed) {
+                animation.emote = None;
+            } else {
+                animation.bobbing_offset = clip.drop;
+            }
+        }
+    }
+
+    // The jump arc rides on top of everything else
+    if let Some(elapsed) = animation.jump_elapsed {
+        let elapsed = elapsed + dt;
+        if elapsed >= JUMP_DURATION {
+            animation.jump_elapsed = None;
+        } else {
+            let t = elapsed / JUMP_DURATION;
+            animation.bobbing_offset -= JUMP_HEIGHT * 4.0 * t * (1.0 - t);
+            animation.jump_elapsed = Some(elapsed);
+        }
+    }
+}
+
+pub fn animation_system(world: &mut World, dt: f32) {
+    for (animation, velocity) in world.query_mut::<(&mut Animation, &Velocity)>() {
+        animate(animation, velocity.is_moving, dt);
+    }
+}
+
+// Sets how much of each chat bubble shows: none further than `radius` from
+// `listener`, and less and less over the last seconds of `lifetime`. A
+// message waiting to be sent shows in full.
+pub fn chat_fade_system(world: &mut World, listener: Vec2, radius: f32, lifetime: Duration) {
+    let fade = BUBBLE_FADE.min(lifetime).as_secs_f32();
+    let fading = |shown_at: Instant| {
+        let left = lifetime.saturating_sub(shown_at.elapsed()).as_secs_f32();
+        if fade <= 0.0 {
+            1.0
+        } else {
+            (left / fade).min(1.0)
+        }
+    };
+    for (position, bubble) in world.query_mut::<(&Position, &mut ChatBubble)>() {
+        let in_range = listener.distance(vec2(position.x, position.y)) <= radius;
+        bubble.alpha = match (in_range, bubble.sent) {
+            (false, _) => 0.0,
+            (true, false) => 1.0,
+            (true, true) => fading(bubble.shown_at),
+        };
+        for older in &mut bubble.stacked {
+            older.alpha = if in_range {
+                fading(older.shown_at)
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+// Clears chat bubbles once they have been shown for `lifetime`, never a
+// message still waiting to be sent. Returns where the bubbles that were
+// showing a message were, centered.
+pub fn chat_expiry_system(world: &mut World, lifetime: Duration) -> Vec<Vec2> {
+    let mut expired = Vec::new();
+    for (position, bubble) in world.query_mut::<(&Position, &mut ChatBubble)>() {
+        bubble
+            .stacked
+            .retain(|older| older.shown_at.elapsed() < lifetime);
+        if bubble.sent && bubble.shown_at.elapsed() >= lifetime {
+            bubble.expression = None;
+            if bubble.message.take().is_some() {
+                expired.push(Vec2::new(position.x, position.y + BUBBLE_OFFSET));
+            }
+            bubble.shown_at = Instant::now();
+        }
+    }
+    expired
+}
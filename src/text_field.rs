@@ -0,0 +1,305 @@
+use crate::ui;
+use macroquad::miniquad::window::{clipboard_get, clipboard_set};
+use macroquad::prelude::*;
+
+// A line of typed text with a cursor. Shift with the arrows, Home or End
+// selects, Ctrl+A selects everything, and Ctrl+C, Ctrl+X and Ctrl+V copy,
+// cut and paste through the system clipboard.
+#[derive(Clone, Debug)]
+pub struct TextField {
+    text: String,
+    cursor: usize,         // In chars, not bytes
+    anchor: Option<usize>, // The other end of the selection, in chars
+    max_chars: usize,
+}
+
+impl TextField {
+    pub fn new(max_chars: usize) -> Self {
+        Self {
+            text: String::new(),
+            cursor: 0,
+            anchor: None,
+            max_chars,
+        }
+    }
+
+    pub fn with_text(max_chars: usize, text: &str) -> Self {
+        let mut field = Self::new(max_chars);
+        field.set_text(text);
+        field
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+
+    // Replaces everything, with the cursor at the end
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.chars().take(self.max_chars).collect();
+        self.cursor = self.text.chars().count();
+        self.anchor = None;
+    }
+
+    // Start and end of what's selected, None if nothing is
+    fn selection(&self) -> Option<(usize, usize)> {
+        let anchor = self.anchor.filter(|&anchor| anchor != self.cursor)?;
+        Some((anchor.min(self.cursor), anchor.max(self.cursor)))
+    }
+
+    fn byte(&self, index: usize) -> usize {
+        self.text
+            .char_indices()
+            .nth(index)
+            .map_or(self.text.len(), |(byte, _)| byte)
+    }
+
+    pub(crate) fn selected_text(&self) -> Option<&str> {
+        let (start, end) = self.selection()?;
+        Some(&self.text[self.byte(start)..self.byte(end)])
+    }
+
+    // False if there was nothing selected
+    fn delete_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection() else {
+            return false;
+        };
+        let range = self.byte(start)..self.byte(end);
+        self.text.replace_range(range, "");
+        self.cursor = start;
+        self.anchor = None;
+        true
+    }
+
+    // Over the selection if there is one. Line breaks and the like are left
+    // out, and whatever doesn't fit.
+    pub(crate) fn insert(&mut self, text: &str) {
+        self.delete_selection();
+        let room = self.max_chars.saturating_sub(self.text.chars().count());
+        let text: String = text
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(room)
+            .collect();
+        let at = self.byte(self.cursor);
+        self.text.insert_str(at, &text);
+        self.cursor += text.chars().count();
+    }
+
+    pub(crate) fn select_all(&mut self) {
+        self.anchor = Some(0);
+        self.cursor = self.text.chars().count();
+    }
+
+    // Takes out the selection, None if nothing is selected
+    pub(crate) fn cut(&mut self) -> Option<String> {
+        let text = self.selected_text()?.to_string();
+        self.delete_selection();
+        Some(text)
+    }
+
+    // The selection, or the char before the cursor
+    pub(crate) fn backspace(&mut self) {
+        if !self.delete_selection() && self.cursor > 0 {
+            self.cursor -= 1;
+            let at = self.byte(self.cursor);
+            self.text.remove(at);
+        }
+    }
+
+    // The selection, or the char after the cursor
+    pub(crate) fn delete(&mut self) {
+        if !self.delete_selection() && self.cursor < self.text.chars().count() {
+            let at = self.byte(self.cursor);
+            self.text.remove(at);
+        }
+    }
+
+    // Without `select`, a selection collapses to its start
+    pub(crate) fn move_left(&mut self, select: bool) {
+        match self.selection() {
+            Some((start, _)) if !select => self.move_to(start, false),
+            _ => self.move_to(self.cursor.saturating_sub(1), select),
+        }
+    }
+
+    // Without `select`, a selection collapses to its end
+    pub(crate) fn move_right(&mut self, select: bool) {
+        let length = self.text.chars().count();
+        match self.selection() {
+            Some((_, end)) if !select => self.move_to(end, false),
+            _ => self.move_to((self.cursor + 1).min(length), select),
+        }
+    }
+
+    pub(crate) fn move_home(&mut self, select: bool) {
+        self.move_to(0, select);
+    }
+
+    pub(crate) fn move_end(&mut self, select: bool) {
+        self.move_to(self.text.chars().count(), select);
+    }
+
+    fn move_to(&mut self, index: usize, select: bool) {
+        if select {
+            self.anchor.get_or_insert(self.cursor);
+        } else {
+            self.anchor = None;
+        }
+        self.cursor = index;
+    }
+
+    // Takes this frame's typing and editing keys
+    pub fn update(&mut self) {
+        let ctrl = is_key_down(KeyCode::LeftControl)
+            || is_key_down(KeyCode::RightControl)
+            || is_key_down(KeyCode::LeftSuper)
+            || is_key_down(KeyCode::RightSuper);
+        // AltGr comes through as Ctrl+Alt, and types
+        let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+        let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+        while let Some(c) = get_char_pressed() {
+            if !ctrl || alt {
+                self.insert(&c.to_string());
+            }
+        }
+
+        if ctrl {
+            if is_key_pressed(KeyCode::A) {
+                self.select_all();
+            }
+            if is_key_pressed(KeyCode::C) {
+                if let Some(text) = self.selected_text() {
+                    clipboard_set(text);
+                }
+            }
+            if is_key_pressed(KeyCode::X) {
+                if let Some(text) = self.cut() {
+                    clipboard_set(&text);
+                }
+            }
+            if is_key_pressed(KeyCode::V) {
+                if let Some(text) = clipboard_get() {
+                    self.insert(&text);
+                }
+            }
+        }
+
+        if is_key_pressed(KeyCode::Left) {
+            self.move_left(shift);
+        }
+        if is_key_pressed(KeyCode::Right) {
+            self.move_right(shift);
+        }
+        if is_key_pressed(KeyCode::Home) {
+            self.move_home(shift);
+        }
+        if is_key_pressed(KeyCode::End) {
+            self.move_end(shift);
+        }
+        if is_key_pressed(KeyCode::Backspace) {
+            self.backspace();
+        }
+        if is_key_pressed(KeyCode::Delete) {
+            self.delete();
+        }
+    }
+
+    // With the selection shaded and a bar for the cursor. `y` is the
+    // baseline, like `ui::draw_text`.
+    pub fn draw(&self, x: f32, y: f32, font_size: f32, color: Color) {
+        let offset = |index: usize| x + ui::text_width(&self.text[..self.byte(index)], font_size);
+        if let Some((start, end)) = self.selection() {
+            let (left, right) = (offset(start), offset(end));
+            draw_rectangle(
+                left,
+                y - font_size * 0.8,
+                right - left,
+                font_size,
+                Color::new(0.3, 0.5, 1.0, 0.5),
+            );
+        }
+        ui::draw_text(&self.text, x, y, font_size, color);
+        draw_rectangle(
+            offset(self.cursor),
+            y - font_size * 0.8,
+            2.0,
+            font_size,
+            color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_at_the_cursor_in_multi_byte_text() {
+        let mut field = TextField::with_text(20, "héllo");
+        field.move_left(false);
+        field.move_left(false);
+        field.insert("ü");
+        assert_eq!(field.text(), "hélülo");
+        field.insert("日本");
+        assert_eq!(field.text(), "hélü日本lo");
+    }
+
+    #[test]
+    fn selects_and_cuts_multi_byte_text() {
+        let mut field = TextField::with_text(20, "añb日c");
+        field.move_home(false);
+        field.move_right(false);
+        field.move_right(true);
+        field.move_right(true);
+        field.move_right(true);
+        assert_eq!(field.selected_text(), Some("ñb日"));
+        assert_eq!(field.cut(), Some("ñb日".to_string()));
+        assert_eq!(field.text(), "ac");
+        assert_eq!(field.cut(), None);
+
+        field.select_all();
+        assert_eq!(field.selected_text(), Some("ac"));
+        field.insert("é");
+        assert_eq!(field.text(), "é");
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_whole_chars() {
+        let mut field = TextField::with_text(20, "日本語");
+        field.backspace();
+        assert_eq!(field.text(), "日本");
+        field.move_home(false);
+        field.delete();
+        assert_eq!(field.text(), "本");
+        field.backspace();
+        assert_eq!(field.text(), "本");
+        field.move_end(true);
+        field.backspace();
+        assert_eq!(field.text(), "");
+    }
+
+    #[test]
+    fn insert_stops_at_max_chars() {
+        let mut field = TextField::with_text(5, "日本語です、どうぞ");
+        assert_eq!(field.text(), "日本語です");
+        field.backspace();
+        field.insert("ねこ");
+        assert_eq!(field.text(), "日本語でね");
+        field.move_home(false);
+        field.move_right(true);
+        field.insert("ありがとう");
+        assert_eq!(field.text(), "あ本語でね");
+    }
+
+    #[test]
+    fn insert_drops_control_chars() {
+        let mut field = TextField::new(20);
+        field.insert("a\nb\tc\u{7}d\r");
+        assert_eq!(field.text(), "abcd");
+    }
+}
@@ -0,0 +1,211 @@
+use crate::protocol::{now_millis, CharacterBundle, Cosmetics, SessionTicket};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// How long a ticket lets a player skip the recovery code
+pub const TICKET_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
+// How long an exported character can still be imported
+pub const BUNDLE_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TicketError {
+    BadSignature,
+    Expired,
+}
+
+impl std::fmt::Display for TicketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TicketError::BadSignature => write!(f, "signature doesn't match"),
+            TicketError::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+// Issues and checks session tickets, and signs exported characters. Servers
+// that share a secret accept each other's tickets and characters without
+// talking to each other.
+pub struct TicketSigner {
+    secret: Vec<u8>,
+}
+
+impl TicketSigner {
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret }
+    }
+
+    // Tickets from a random secret only work on this process
+    pub fn random() -> Self {
+        Self::new((0..32).map(|_| ::rand::random::<u8>()).collect())
+    }
+
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let secret = fs::read(path)?;
+        if secret.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "ticket secret file is empty",
+            ));
+        }
+        Ok(Self::new(secret))
+    }
+
+    pub fn issue(&self, name: &str, player_id: usize) -> SessionTicket {
+        let expires_at_ms = now_millis() + TICKET_LIFETIME.as_millis() as u64;
+        let signature = self
+            .mac(name, player_id, expires_at_ms)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        SessionTicket {
+            name: name.to_string(),
+            player_id,
+            expires_at_ms,
+            signature,
+        }
+    }
+
+    pub fn verify(&self, ticket: &SessionTicket) -> Result<(), TicketError> {
+        self.mac(&ticket.name, ticket.player_id, ticket.expires_at_ms)
+            .verify_slice(&ticket.signature)
+            .map_err(|_| TicketError::BadSignature)?;
+        if ticket.expires_at_ms <= now_millis() {
+            return Err(TicketError::Expired);
+        }
+        Ok(())
+    }
+
+    pub fn sign_bundle(&self, name: &str, cosmetics: Cosmetics) -> CharacterBundle {
+        let exported_at_ms = now_millis();
+        let signature = self
+            .bundle_mac(name, cosmetics, exported_at_ms)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        CharacterBundle {
+            name: name.to_string(),
+            cosmetics,
+            exported_at_ms,
+            signature,
+        }
+    }
+
+    pub fn verify_bundle(&self, bundle: &CharacterBundle) -> Result<(), TicketError> {
+        self.bundle_mac(&bundle.name, bundle.cosmetics, bundle.exported_at_ms)
+            .verify_slice(&bundle.signature)
+            .map_err(|_| TicketError::BadSignature)?;
+        if bundle.exported_at_ms + BUNDLE_LIFETIME.as_millis() as u64 <= now_millis() {
+            return Err(TicketError::Expired);
+        }
+        Ok(())
+    }
+
+    fn keyed(&self) -> HmacSha256 {
+        HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length")
+    }
+
+    fn mac(&self, name: &str, player_id: usize, expires_at_ms: u64) -> HmacSha256 {
+        let mut mac = self.keyed();
+        // Length-prefix the name so fields can't run into each other
+        mac.update(&(name.len() as u64).to_le_bytes());
+        mac.update(name.as_bytes());
+        mac.update(&(player_id as u64).to_le_bytes());
+        mac.update(&expires_at_ms.to_le_bytes());
+        mac
+    }
+
+    fn bundle_mac(&self, name: &str, cosmetics: Cosmetics, exported_at_ms: u64) -> HmacSha256 {
+        let mut mac = self.keyed();
+        // Tagged so a ticket signature can never pass as a bundle's
+        mac.update(b"character");
+        mac.update(&(name.len() as u64).to_le_bytes());
+        mac.update(name.as_bytes());
+        mac.update(&cosmetics.body_color);
+        mac.update(&cosmetics.hair_seed.to_le_bytes());
+        mac.update(&cosmetics.sprite.map_or([0, 0], |sprite| [1, sprite]));
+        let hair = cosmetics.hair;
+        mac.update(&hair.color);
+        mac.update(&[hair.length, hair.style as u8]);
+        mac.update(&hair.strands.to_le_bytes());
+        let (face, outfit) = (cosmetics.face, cosmetics.outfit);
+        mac.update(&[face.eyes as u8, face.mouth as u8]);
+        mac.update(&outfit.shirt.map_or([0, 0], |shirt| [1, shirt]));
+        mac.update(&outfit.pants.map_or([0, 0], |pants| [1, pants]));
+        let equipment = cosmetics.equipment;
+        mac.update(&equipment.hat.map_or([0, 0], |hat| [1, hat as u8]));
+        mac.update(&equipment.weapon.map_or([0, 0], |weapon| [1, weapon as u8]));
+        mac.update(&equipment.cape.map_or([0, 0], |cape| [1, cape]));
+        mac.update(&exported_at_ms.to_le_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signer() -> TicketSigner {
+        TicketSigner::new(b"test secret".to_vec())
+    }
+
+    #[test]
+    fn issued_ticket_verifies() {
+        let ticket = signer().issue("alice", 7);
+        assert_eq!(ticket.name, "alice");
+        assert_eq!(ticket.player_id, 7);
+        assert_eq!(signer().verify(&ticket), Ok(()));
+    }
+
+    #[test]
+    fn other_secret_rejects_ticket() {
+        let ticket = TicketSigner::new(b"other secret".to_vec()).issue("alice", 7);
+        assert_eq!(signer().verify(&ticket), Err(TicketError::BadSignature));
+    }
+
+    #[test]
+    fn tampered_ticket_is_rejected() {
+        let ticket = signer().issue("alice", 7);
+        let renamed = SessionTicket {
+            name: "mallory".to_string(),
+            ..ticket.clone()
+        };
+        assert_eq!(signer().verify(&renamed), Err(TicketError::BadSignature));
+        let other_player = SessionTicket {
+            player_id: 8,
+            ..ticket.clone()
+        };
+        assert_eq!(
+            signer().verify(&other_player),
+            Err(TicketError::BadSignature)
+        );
+        let extended = SessionTicket {
+            expires_at_ms: ticket.expires_at_ms + 1,
+            ..ticket
+        };
+        assert_eq!(signer().verify(&extended), Err(TicketError::BadSignature));
+    }
+
+    #[test]
+    fn expired_ticket_is_rejected() {
+        let signer = signer();
+        let expires_at_ms = now_millis() - 1;
+        let signature = signer
+            .mac("alice", 7, expires_at_ms)
+            .finalize()
+            .into_bytes()
+            .to_vec();
+        let ticket = SessionTicket {
+            name: "alice".to_string(),
+            player_id: 7,
+            expires_at_ms,
+            signature,
+        };
+        assert_eq!(signer.verify(&ticket), Err(TicketError::Expired));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::assets::{Assets, ASSETS_DIR};
+use crate::logging;
+use macroquad::prelude::*;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+use std::path::Path;
+use tracing::{info, warn};
+
+// Where tilemaps live, under the assets directory. The map called `name`
+// is drawn from `maps/<name>.json` when there is one.
+pub const TILEMAPS_DIR: &str = "maps";
+
+// Tiled keeps flip and rotation flags in the top bits of a tile id
+const FLIP_FLAGS: u32 = 0xf000_0000;
+
+// The parts of Tiled's JSON export we draw. Tilesets have to be embedded in
+// the map and layers stored as plain arrays, not base64.
+#[derive(Deserialize)]
+struct TiledMap {
+    width: u32, // In tiles
+    height: u32,
+    tilewidth: f32,
+    tileheight: f32,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+}
+
+#[derive(Deserialize)]
+struct TiledLayer {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default = "visible")]
+    visible: bool,
+    #[serde(default = "opaque")]
+    opacity: f32,
+}
+
+fn visible() -> bool {
+    true
+}
+
+fn opaque() -> f32 {
+    1.0
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    image: Option<String>, // Missing for external tilesets
+    columns: u32,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    margin: f32,
+    #[serde(default)]
+    spacing: f32,
+}
+
+struct TileLayer {
+    tiles: Vec<u32>, // Row by row, 0 is empty
+    opacity: f32,
+}
+
+struct Tileset {
+    first_id: u32,
+    image: String, // Relative to the assets directory
+    columns: u32,
+    tile_size: Vec2,
+    margin: f32,
+    spacing: f32,
+}
+
+impl Tileset {
+    // Where tile `id` is in the image
+    fn source(&self, id: u32) -> Rect {
+        let index = id - self.first_id;
+        let (column, row) = (index % self.columns, index / self.columns);
+        Rect::new(
+            self.margin + column as f32 * (self.tile_size.x + self.spacing),
+            self.margin + row as f32 * (self.tile_size.y + self.spacing),
+            self.tile_size.x,
+            self.tile_size.y,
+        )
+    }
+}
+
+// The ground and decorations of a map, drawn underneath everything else.
+// Only the client has it; the size also bounds where players can walk.
+pub struct Tilemap {
+    pub columns: u32,
+    pub rows: u32,
+    pub tile_size: Vec2,
+    layers: Vec<TileLayer>, // Bottom first
+    tilesets: Vec<Tileset>,
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl Tilemap {
+    // Reads a map exported from Tiled as JSON
+    pub fn from_file(path: &Path) -> io::Result<Self> {
+        let json = fs::read_to_string(path)?;
+        let map: TiledMap = serde_json::from_str(&json).map_err(|e| invalid(e.to_string()))?;
+
+        // Tileset images are relative to the map file
+        let dir = path
+            .parent()
+            .and_then(|dir| dir.strip_prefix(ASSETS_DIR).ok())
+            .unwrap_or(Path::new(TILEMAPS_DIR));
+        let mut tilesets = Vec::new();
+        for tileset in map.tilesets {
+            let Some(image) = tileset.image else {
+                return Err(invalid("external tilesets aren't supported".to_string()));
+            };
+            if tileset.columns == 0 {
+                return Err(invalid(format!("tileset {} has no columns", image)));
+            }
+            tilesets.push(Tileset {
+                first_id: tileset.firstgid,
+                image: dir.join(image).to_string_lossy().into_owned(),
+                columns: tileset.columns,
+                tile_size: Vec2::new(tileset.tilewidth, tileset.tileheight),
+                margin: tileset.margin,
+                spacing: tileset.spacing,
+            });
+        }
+        tilesets.sort_by_key(|tileset| tileset.first_id);
+
+        let tile_count = (map.width * map.height) as usize;
+        let mut layers = Vec::new();
+        for layer in map.layers {
+            // Object and image layers have nothing to draw for us
+            if layer.kind != "tilelayer" || !layer.visible {
+                continue;
+            }
+            if layer.data.len() != tile_count {
+                return Err(invalid(format!(
+                    "layer {} has {} tiles, expected {}",
+                    layer.name,
+                    layer.data.len(),
+                    tile_count
+                )));
+            }
+            layers.push(TileLayer {
+                tiles: layer.data,
+                opacity: layer.opacity,
+            });
+        }
+
+        Ok(Self {
+            columns: map.width,
+            rows: map.height,
+            tile_size: Vec2::new(map.tilewidth, map.tileheight),
+            layers,
+            tilesets,
+        })
+    }
+
+    // The tilemap for the map called `name`, if it has one
+    pub fn for_map(name: &str) -> Option<Self> {
+        let path = Path::new(ASSETS_DIR)
+            .join(TILEMAPS_DIR)
+            .join(format!("{}.json", name));
+        match Self::from_file(&path) {
+            Ok(tilemap) => {
+                info!(
+                    target: logging::RENDER,
+                    "Tilemap {}: {}x{} tiles",
+                    path.display(),
+                    tilemap.columns,
+                    tilemap.rows
+                );
+                Some(tilemap)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                warn!(target: logging::RENDER, "Failed to load {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    // The whole map in world coordinates
+    pub fn bounds(&self) -> Rect {
+        Rect::new(
+            0.0,
+            0.0,
+            self.columns as f32 * self.tile_size.x,
+            self.rows as f32 * self.tile_size.y,
+        )
+    }
+
+    // Draws the tiles inside `view`. Tileset images load in the background
+    // the first time they are needed, the map shows up once they are in.
+    pub fn draw(&self, assets: &mut Assets, view: &Rect) {
+        let textures: Vec<Option<Texture2D>> = self
+            .tilesets
+            .iter()
+            .map(|tileset| assets.texture_file(&tileset.image).cloned())
+            .collect();
+
+        // Tiles taller than the grid stick out upwards, so look a row further down
+        let overhang = self
+            .tilesets
+            .iter()
+            .map(|tileset| (tileset.tile_size.y / self.tile_size.y).ceil() as i64)
+            .max()
+            .unwrap_or(1);
+        let first_column = (view.x / self.tile_size.x).floor().max(0.0) as u32;
+        let first_row = (view.y / self.tile_size.y).floor().max(0.0) as u32;
+        let last_column = (((view.x + view.w) / self.tile_size.x).ceil() as i64)
+            .clamp(0, self.columns as i64) as u32;
+        let last_row = (((view.y + view.h) / self.tile_size.y).ceil() as i64 + overhang - 1)
+            .clamp(0, self.rows as i64) as u32;
+
+        for layer in &self.layers {
+            let tint = Color::new(1.0, 1.0, 1.0, layer.opacity);
+            for row in first_row..last_row {
+                for column in first_column..last_column {
+                    let id = layer.tiles[(row * self.columns + column) as usize] & !FLIP_FLAGS;
+                    if id == 0 {
+                        continue;
+                    }
+                    let Some(index) = self
+                        .tilesets
+                        .iter()
+                        .rposition(|tileset| tileset.first_id <= id)
+                    else {
+                        continue;
+                    };
+                    let (tileset, Some(texture)) = (&self.tilesets[index], &textures[index]) else {
+                        continue;
+                    };
+                    // Anchored at the bottom left of their cell, like Tiled does
+                    let x = column as f32 * self.tile_size.x;
+                    let y = (row + 1) as f32 * self.tile_size.y - tileset.tile_size.y;
+                    draw_texture_ex(
+                        texture,
+                        x,
+                        y,
+                        tint,
+                        DrawTextureParams {
+                            source: Some(tileset.source(id)),
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::input::InputFrame;
+use crate::settings::QuickChat;
+use crate::ui;
+use macroquad::miniquad::window::show_keyboard;
+use macroquad::prelude::*;
+
+// The joystick, in UI pixels. Touches a little outside it still grab it.
+const STICK_RADIUS: f32 = 70.0;
+const STICK_GRAB: f32 = 1.3;
+const KNOB_RADIUS: f32 = 28.0;
+// Under this much of the way out the joystick doesn't move
+const DEAD_ZONE: f32 = 0.15;
+const BUTTON_WIDTH: f32 = 150.0;
+const BUTTON_HEIGHT: f32 = 56.0;
+const BUTTON_GAP: f32 = 10.0;
+
+#[derive(Clone, Copy)]
+enum Button {
+    Chat,
+    Say(usize), // Index into `Settings::quick_chat`
+}
+
+// Where the joystick sits, above the chat log
+fn stick_center() -> Vec2 {
+    vec2(STICK_RADIUS + 30.0, ui::height() - 260.0)
+}
+
+// Two columns up the right side, above the minimap: the chat button, then
+// a button per quick chat phrase
+fn buttons(quick_chat: &[QuickChat]) -> Vec<(Rect, Button)> {
+    let kinds = std::iter::once(Button::Chat).chain((0..quick_chat.len()).map(Button::Say));
+    kinds
+        .enumerate()
+        .map(|(index, button)| {
+            let column = (index % 2) as f32;
+            let row = (index / 2) as f32;
+            let x = ui::width() - (column + 1.0) * (BUTTON_WIDTH + BUTTON_GAP);
+            let y = ui::height() - 240.0 - (row + 1.0) * (BUTTON_HEIGHT + BUTTON_GAP);
+            (Rect::new(x, y, BUTTON_WIDTH, BUTTON_HEIGHT), button)
+        })
+        .collect()
+}
+
+// On-screen controls for phones and tablets: a joystick on the left to
+// walk, large chat buttons on the right, and a tap anywhere else walks to
+// that spot like a right click. Touches still click as the mouse too, so
+// menus and chat names work as they do on a desktop.
+#[derive(Default)]
+pub struct TouchControls {
+    stick: Option<(u64, Vec2)>, // The touch holding the joystick and where it is, in UI pixels
+    keyboard_shown: bool,
+}
+
+impl TouchControls {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Fills in what the mouse would otherwise, and the movement keys
+    pub fn poll(&mut self, frame: &mut InputFrame, quick_chat: &[QuickChat]) {
+        let scale = ui::scale();
+        let buttons = buttons(quick_chat);
+        for touch in touches() {
+            let at = touch.position / scale;
+            match touch.phase {
+                TouchPhase::Started => {
+                    if at.distance(stick_center()) <= STICK_RADIUS * STICK_GRAB {
+                        self.stick = Some((touch.id, at));
+                    } else if let Some(&(_, button)) =
+                        buttons.iter().find(|(rect, _)| rect.contains(at))
+                    {
+                        match button {
+                            Button::Chat => frame.open_chat = true,
+                            Button::Say(index) => {
+                                frame.say = Some(quick_chat[index].phrase.clone());
+                            }
+                        }
+                    } else {
+                        // Window coordinates, like the mouse
+                        let position = (touch.position.x, touch.position.y);
+                        frame.move_target = Some(position);
+                        frame.pointer = Some(position);
+                        frame.select = true;
+                    }
+                }
+                TouchPhase::Moved | TouchPhase::Stationary => {
+                    if let Some((id, stick)) = &mut self.stick {
+                        if *id == touch.id {
+                            *stick = at;
+                        }
+                    }
+                }
+                TouchPhase::Ended | TouchPhase::Cancelled => {
+                    if self.stick.is_some_and(|(id, _)| id == touch.id) {
+                        self.stick = None;
+                    }
+                }
+            }
+        }
+        if let Some((_, at)) = self.stick {
+            let offset = ((at - stick_center()) / STICK_RADIUS).clamp_length_max(1.0);
+            if offset.length() > DEAD_ZONE {
+                frame.direction += offset;
+            }
+        }
+    }
+
+    // The on-screen keyboard shows while something takes text
+    pub fn show_keyboard(&mut self, typing: bool) {
+        if typing != self.keyboard_shown {
+            show_keyboard(typing);
+            self.keyboard_shown = typing;
+        }
+    }
+
+    // In UI pixels, over the rest of the HUD
+    pub fn draw(&self, quick_chat: &[QuickChat]) {
+        let center = stick_center();
+        draw_circle(
+            center.x,
+            center.y,
+            STICK_RADIUS,
+            Color::new(0.0, 0.0, 0.0, 0.25),
+        );
+        draw_circle_lines(
+            center.x,
+            center.y,
+            STICK_RADIUS,
+            2.0,
+            Color::new(1.0, 1.0, 1.0, 0.5),
+        );
+        let knob = match self.stick {
+            Some((_, at)) => center + (at - center).clamp_length_max(STICK_RADIUS),
+            None => center,
+        };
+        draw_circle(knob.x, knob.y, KNOB_RADIUS, Color::new(1.0, 1.0, 1.0, 0.6));
+
+        for (rect, button) in buttons(quick_chat) {
+            draw_rectangle(
+                rect.x,
+                rect.y,
+                rect.w,
+                rect.h,
+                Color::new(0.0, 0.0, 0.0, 0.45),
+            );
+            draw_rectangle_lines(
+                rect.x,
+                rect.y,
+                rect.w,
+                rect.h,
+                2.0,
+                Color::new(1.0, 1.0, 1.0, 0.6),
+            );
+            let label = match button {
+                Button::Chat => "Chat".to_string(),
+                Button::Say(index) => shortened(&quick_chat[index].phrase),
+            };
+            let width = ui::text_width(&label, 20.0);
+            let x = rect.x + (rect.w - width) / 2.0;
+            ui::draw_text(&label, x, rect.y + rect.h / 2.0 + 7.0, 20.0, WHITE);
+        }
+    }
+}
+
+// Cut short to fit on a button
+fn shortened(phrase: &str) -> String {
+    const SHOWN: usize = 14;
+    if phrase.chars().count() > SHOWN {
+        let start: String = phrase.chars().take(SHOWN - 3).collect();
+        format!("{}...", start)
+    } else {
+        phrase.to_string()
+    }
+}
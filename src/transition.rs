@@ -0,0 +1,90 @@
+use macroquad::prelude::*;
+
+const FADE_DURATION: f32 = 0.35; // Seconds
+const WIPE_DURATION: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TransitionKind {
+    Fade, // The old screen fades out over the new one
+    Wipe, // The new screen sweeps in from the left over the old one
+}
+
+// Eases from one scene to the next instead of cutting. The last frame of
+// the old scene is kept as a texture and drawn over the new one, less of it
+// each frame, so neither scene has to know about the other.
+pub struct Transition {
+    from: Option<Texture2D>,
+    kind: TransitionKind,
+    elapsed: f32,
+}
+
+impl Default for Transition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transition {
+    pub fn new() -> Self {
+        Self {
+            from: None,
+            kind: TransitionKind::Fade,
+            elapsed: 0.0,
+        }
+    }
+
+    // Keeps what's on screen to transition away from. Call after the old
+    // scene's last frame is drawn.
+    pub fn start(&mut self, kind: TransitionKind) {
+        let texture = Texture2D::from_image(&get_screen_data());
+        texture.set_filter(FilterMode::Nearest);
+        self.from = Some(texture);
+        self.kind = kind;
+        self.elapsed = 0.0;
+    }
+
+    // Draws what's left of the old scene over the new one. Leaves the
+    // default camera set.
+    pub fn draw(&mut self, dt: f32) {
+        let Some(from) = &self.from else {
+            return;
+        };
+        let duration = match self.kind {
+            TransitionKind::Fade => FADE_DURATION,
+            TransitionKind::Wipe => WIPE_DURATION,
+        };
+        self.elapsed += dt;
+        let progress = (self.elapsed / duration).min(1.0);
+        // Eased so it starts and settles gently
+        let eased = progress * progress * (3.0 - 2.0 * progress);
+
+        set_default_camera();
+        let (width, height) = (screen_width(), screen_height());
+        let size = from.size();
+        let (color, covered) = match self.kind {
+            TransitionKind::Fade => (Color::new(1.0, 1.0, 1.0, 1.0 - eased), 0.0),
+            TransitionKind::Wipe => (WHITE, eased),
+        };
+        // Screen data is read bottom row first
+        draw_texture_ex(
+            from,
+            width * covered,
+            0.0,
+            color,
+            DrawTextureParams {
+                dest_size: Some(vec2(width * (1.0 - covered), height)),
+                source: Some(Rect::new(
+                    size.x * covered,
+                    0.0,
+                    size.x * (1.0 - covered),
+                    size.y,
+                )),
+                flip_y: true,
+                ..Default::default()
+            },
+        );
+        if progress >= 1.0 {
+            self.from = None;
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use crate::logging;
+use macroquad::prelude::*;
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use tracing::{info, warn};
+
+// Scales the settings panel offers besides following the display
+pub const SCALES: [f32; 5] = [1.0, 1.25, 1.5, 1.75, 2.0];
+const MIN_SCALE: f32 = 0.5;
+const MAX_SCALE: f32 = 4.0;
+
+// Bits of the current scale, an f32. Everything drawn reads it, so it lives
+// here rather than being passed down to every draw call.
+static SCALE: AtomicU32 = AtomicU32::new(0x3f80_0000); // 1.0
+
+// How many window pixels one UI pixel covers. The HUD, chat bubbles and the
+// world are laid out in UI pixels so they keep their size on high-DPI
+// displays.
+pub fn scale() -> f32 {
+    f32::from_bits(SCALE.load(Ordering::Relaxed))
+}
+
+// `scale` None follows the display's DPI. Call once a frame, the window may
+// have moved to another display.
+pub fn set_scale(scale: Option<f32>) {
+    let scale = scale
+        .unwrap_or_else(screen_dpi_scale)
+        .clamp(MIN_SCALE, MAX_SCALE);
+    SCALE.store(scale.to_bits(), Ordering::Relaxed);
+}
+
+// Size of the window in UI pixels, use these instead of `screen_width` and
+// `screen_height` when laying out
+pub fn width() -> f32 {
+    screen_width() / scale()
+}
+
+pub fn height() -> f32 {
+    screen_height() / scale()
+}
+
+// Where the mouse is, in UI pixels
+pub fn mouse() -> Vec2 {
+    Vec2::from(mouse_position()) / scale()
+}
+
+// For `set_camera`, the window in UI pixels with the origin top left
+pub fn camera() -> Camera2D {
+    let (width, height) = (width(), height());
+    Camera2D {
+        target: Vec2::new(width / 2.0, height / 2.0),
+        zoom: Vec2::new(2.0 / width, 2.0 / height),
+        ..Default::default()
+    }
+}
+
+pub fn set_ui_camera() {
+    set_camera(&camera());
+}
+
+// Where fonts for what the built-in font can't draw live, under the assets
+// directory
+pub const FONTS_DIR: &str = "fonts";
+
+// A font for characters the built-in one lacks, like accented letters, CJK
+// or Cyrillic
+struct FallbackFont {
+    font: Font,
+    glyphs: fontdue::Font, // To ask which characters it has
+}
+
+thread_local! {
+    // Tried in order for every character outside ASCII, see `load_fonts`.
+    // Fonts live on the thread that draws, so they're kept there.
+    static FALLBACK_FONTS: RefCell<Vec<FallbackFont>> = const { RefCell::new(Vec::new()) };
+}
+
+// Loads every `.ttf` and `.otf` in `dir` as a fallback, in file name order,
+// so `10-latin.ttf` is tried before `20-cjk.otf`. ASCII always uses the
+// built-in font, so the game looks the same with none.
+pub fn load_fonts(dir: &Path) {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| {
+                        extension.eq_ignore_ascii_case("ttf")
+                            || extension.eq_ignore_ascii_case("otf")
+                    })
+            })
+            .collect(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+        Err(e) => {
+            warn!(target: logging::ASSETS, "Failed to read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+    paths.sort();
+    let fonts = paths
+        .iter()
+        .filter_map(|path| match load_fallback_font(path) {
+            Ok(font) => {
+                info!(target: logging::ASSETS, "Fallback font {}", path.display());
+                Some(font)
+            }
+            Err(e) => {
+                warn!(target: logging::ASSETS, "Failed to load {}: {}", path.display(), e);
+                None
+            }
+        })
+        .collect();
+    FALLBACK_FONTS.with_borrow_mut(|fallbacks| *fallbacks = fonts);
+}
+
+fn load_fallback_font(path: &Path) -> Result<FallbackFont, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let glyphs = fontdue::Font::from_bytes(bytes.as_slice(), fontdue::FontSettings::default())
+        .map_err(str::to_string)?;
+    let font = load_ttf_font_from_bytes(&bytes).map_err(|e| e.to_string())?;
+    Ok(FallbackFont { font, glyphs })
+}
+
+// `text` cut where the font it's drawn with changes, with the index of that
+// fallback font, None for the built-in one. A character no font has is left
+// to the built-in one.
+fn runs<'a>(text: &'a str, fonts: &[FallbackFont]) -> Vec<(&'a str, Option<usize>)> {
+    if fonts.is_empty() || text.is_ascii() {
+        return vec![(text, None)];
+    }
+    let font_for = |c: char| {
+        if c.is_ascii() {
+            return None;
+        }
+        fonts
+            .iter()
+            .position(|font| font.glyphs.lookup_glyph_index(c) != 0)
+    };
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    for (index, c) in text.char_indices() {
+        let font = font_for(c);
+        if font != current && index > start {
+            runs.push((&text[start..index], current));
+            start = index;
+        }
+        current = font;
+    }
+    runs.push((&text[start..], current));
+    runs
+}
+
+// Text drawn in pieces, side by side, as one
+fn join(total: TextDimensions, piece: TextDimensions) -> TextDimensions {
+    TextDimensions {
+        width: total.width + piece.width,
+        height: total.height.max(piece.height),
+        offset_y: total.offset_y.max(piece.offset_y),
+    }
+}
+
+const NO_TEXT: TextDimensions = TextDimensions {
+    width: 0.0,
+    height: 0.0,
+    offset_y: 0.0,
+};
+
+// Like macroquad's `draw_text`, but the glyphs are rasterized at the scaled
+// size so text stays sharp instead of being stretched, and characters the
+// built-in font lacks come from the fallback fonts
+pub fn draw_text(text: &str, x: f32, y: f32, font_size: f32, color: Color) -> TextDimensions {
+    let scale = scale();
+    FALLBACK_FONTS.with_borrow(|fonts| {
+        runs(text, fonts)
+            .into_iter()
+            .fold(NO_TEXT, |total, (run, font)| {
+                let piece = draw_text_ex(
+                    run,
+                    x + total.width,
+                    y,
+                    TextParams {
+                        font: font.map(|index| &fonts[index].font),
+                        font_size: (font_size * scale).round() as u16,
+                        font_scale: 1.0 / scale,
+                        color,
+                        ..Default::default()
+                    },
+                );
+                join(total, piece)
+            })
+    })
+}
+
+// What `draw_text` would give `text`, without drawing it
+pub fn measure(text: &str, font_size: f32) -> TextDimensions {
+    let scale = scale();
+    FALLBACK_FONTS.with_borrow(|fonts| {
+        runs(text, fonts)
+            .into_iter()
+            .fold(NO_TEXT, |total, (run, font)| {
+                let font = font.map(|index| &fonts[index].font);
+                let piece =
+                    measure_text(run, font, (font_size * scale).round() as u16, 1.0 / scale);
+                join(total, piece)
+            })
+    })
+}
+
+pub fn text_width(text: &str, font_size: f32) -> f32 {
+    measure(text, font_size).width
+}
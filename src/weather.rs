@@ -0,0 +1,142 @@
+use crate::components::Animation;
+use crate::protocol::{Precipitation, Weather};
+use crate::ui;
+use ::rand::{thread_rng, Rng};
+use hecs::{Entity, World};
+use macroquad::prelude::*;
+
+const RAINDROP_COUNT: usize = 250;
+const SNOWFLAKE_COUNT: usize = 180;
+const RAINDROP_LENGTH: f32 = 12.0;
+// Sideways pixels a second per unit of wind
+const RAIN_DRIFT: f32 = 250.0;
+const SNOW_DRIFT: f32 = 80.0;
+// Pixels the hair tips are blown aside in the strongest wind
+const MAX_HAIR_SWAY: f32 = 8.0;
+// How far the background leans towards the weather's color
+const RAIN_TINT: Color = Color::new(0.45, 0.5, 0.6, 0.2);
+const SNOW_TINT: Color = Color::new(0.85, 0.9, 1.0, 0.25);
+
+struct Droplet {
+    position: Vec2, // In UI pixels
+    speed: f32,     // Pixels a second, straight down
+    phase: f32,     // Where in its flutter a snowflake starts
+}
+
+// Rain or snow over the whole screen and wind in everyone's hair, as the
+// server last said. Drops live in UI pixels and wrap around the window
+// rather than falling through the world.
+pub struct WeatherEffects {
+    pub weather: Weather,
+    drops: Vec<Droplet>,
+    time: f32,
+}
+
+impl Default for WeatherEffects {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WeatherEffects {
+    pub fn new() -> Self {
+        Self {
+            weather: Weather::default(),
+            drops: Vec::new(),
+            time: 0.0,
+        }
+    }
+
+    // The drops are spawned on the next `update`, which needs a window
+    pub fn set(&mut self, weather: Weather) {
+        let weather = weather.clamped();
+        if weather.precipitation != self.weather.precipitation {
+            self.drops.clear();
+        }
+        self.weather = weather;
+    }
+
+    // Moves the drops along and blows every character's hair
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        self.time += dt;
+        if self.drops.is_empty() {
+            self.drops = spawn_drops(self.weather.precipitation);
+        }
+        let (width, height) = (ui::width().max(1.0), ui::height().max(1.0));
+        let wind = self.weather.wind;
+        let snowing = self.weather.precipitation == Precipitation::Snow;
+        for drop in &mut self.drops {
+            let drift = if snowing {
+                wind * SNOW_DRIFT + (self.time * 2.0 + drop.phase).sin() * 15.0
+            } else {
+                wind * RAIN_DRIFT
+            };
+            drop.position += Vec2::new(drift, drop.speed) * dt;
+            drop.position.x = drop.position.x.rem_euclid(width);
+            drop.position.y = drop.position.y.rem_euclid(height);
+        }
+
+        // Gusts come and go, out of step from one character to the next
+        for (entity, animation) in world.query_mut::<(Entity, &mut Animation)>() {
+            let phase = entity.id() as f32;
+            let gust = 0.75 + 0.25 * (self.time * 3.0 + phase).sin();
+            animation.hair_sway = wind * MAX_HAIR_SWAY * gust;
+        }
+    }
+
+    // The window's clear color with the weather's tint over it
+    pub fn tint(&self, background: Color) -> Color {
+        let tint = match self.weather.precipitation {
+            Precipitation::Clear => return background,
+            Precipitation::Rain => RAIN_TINT,
+            Precipitation::Snow => SNOW_TINT,
+        };
+        let mix = |from: f32, to: f32| from + (to - from) * tint.a;
+        Color::new(
+            mix(background.r, tint.r),
+            mix(background.g, tint.g),
+            mix(background.b, tint.b),
+            background.a,
+        )
+    }
+
+    // Over the world and under the UI, with the UI camera set
+    pub fn draw(&self) {
+        match self.weather.precipitation {
+            Precipitation::Clear => {}
+            Precipitation::Rain => {
+                let color = Color::new(0.55, 0.6, 0.75, 0.6);
+                let slant = Vec2::new(self.weather.wind * RAIN_DRIFT, 0.0);
+                for drop in &self.drops {
+                    let direction = (Vec2::new(0.0, drop.speed) + slant).normalize();
+                    let tail = drop.position - direction * RAINDROP_LENGTH;
+                    draw_line(tail.x, tail.y, drop.position.x, drop.position.y, 1.0, color);
+                }
+            }
+            Precipitation::Snow => {
+                for drop in &self.drops {
+                    draw_circle(drop.position.x, drop.position.y, 2.0, WHITE);
+                }
+            }
+        }
+    }
+}
+
+fn spawn_drops(precipitation: Precipitation) -> Vec<Droplet> {
+    let (count, speed) = match precipitation {
+        Precipitation::Clear => return Vec::new(),
+        Precipitation::Rain => (RAINDROP_COUNT, 500.0..800.0),
+        Precipitation::Snow => (SNOWFLAKE_COUNT, 30.0..70.0),
+    };
+    let mut rng = thread_rng();
+    (0..count)
+        .map(|_| Droplet {
+            position: Vec2::new(
+                rng.gen_range(0.0..ui::width().max(1.0)),
+                rng.gen_range(0.0..ui::height().max(1.0)),
+            ),
+            speed: rng.gen_range(speed.clone()),
+            phase: rng.gen_range(0.0..std::f32::consts::TAU),
+        })
+        .collect()
+}
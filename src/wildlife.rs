@@ -0,0 +1,269 @@
+use crate::batch::{DrawList, Layer};
+use crate::render::{detail_level, Detail};
+use crate::spatial::SpatialHash;
+use crate::world::{WORLD_HEIGHT, WORLD_WIDTH};
+use ::rand::{thread_rng, Rng};
+use macroquad::prelude::*;
+
+// Critter counts at density 1.0
+const BIRD_COUNT: usize = 24;
+const BUTTERFLY_COUNT: usize = 12;
+
+// Critters roam a bit past the world edges so they can fly in and out of view
+const ROAM_MARGIN: f32 = 200.0;
+
+const BIRD_SPEED: f32 = 70.0;
+const BIRD_SCATTER_SPEED: f32 = 220.0;
+const FLOCK_RADIUS: f32 = 80.0;
+const SEPARATION_RADIUS: f32 = 20.0;
+
+const BUTTERFLY_SPEED: f32 = 30.0;
+const BUTTERFLY_SCATTER_SPEED: f32 = 90.0;
+const BUTTERFLY_LEASH: f32 = 60.0; // How far a butterfly strays from its flower
+
+// Players closer than this spook critters
+const SCARE_RADIUS: f32 = 90.0;
+const SCARE_DURATION: f32 = 1.5;
+
+// Off-screen critters only get a full update every this many frames
+const REDUCED_UPDATE_INTERVAL: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CritterKind {
+    Bird,
+    Butterfly,
+}
+
+struct Critter {
+    kind: CritterKind,
+    position: Vec2,
+    velocity: Vec2,
+    home: Vec2, // Butterflies wander around this point
+    wander_angle: f32,
+    flap_phase: f32,
+    scared: f32, // Seconds left fleeing
+    color: Color,
+}
+
+// Non-networked ambient birds and butterflies. Every client has its own.
+pub struct Wildlife {
+    critters: Vec<Critter>,
+    frame: u32,
+}
+
+fn random_point(rng: &mut impl Rng, margin: f32) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(-margin..WORLD_WIDTH + margin),
+        rng.gen_range(-margin..WORLD_HEIGHT + margin),
+    )
+}
+
+impl Wildlife {
+    // Butterflies stay away in the colder seasons
+    pub fn new(density: f32, butterflies: bool) -> Self {
+        let mut rng = thread_rng();
+        let mut critters = Vec::new();
+
+        let bird_count = (BIRD_COUNT as f32 * density) as usize;
+        for _ in 0..bird_count {
+            let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+            critters.push(Critter {
+                kind: CritterKind::Bird,
+                position: random_point(&mut rng, ROAM_MARGIN),
+                velocity: Vec2::from_angle(angle) * BIRD_SPEED,
+                home: Vec2::ZERO,
+                wander_angle: angle,
+                flap_phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                scared: 0.0,
+                color: DARKGRAY,
+            });
+        }
+
+        let butterfly_colors = [ORANGE, YELLOW, PINK, SKYBLUE];
+        let butterfly_count = if butterflies {
+            (BUTTERFLY_COUNT as f32 * density) as usize
+        } else {
+            0
+        };
+        for i in 0..butterfly_count {
+            let home = random_point(&mut rng, -40.0);
+            critters.push(Critter {
+                kind: CritterKind::Butterfly,
+                position: home,
+                velocity: Vec2::ZERO,
+                home,
+                wander_angle: rng.gen_range(0.0..std::f32::consts::TAU),
+                flap_phase: rng.gen_range(0.0..std::f32::consts::TAU),
+                scared: 0.0,
+                color: butterfly_colors[i % butterfly_colors.len()],
+            });
+        }
+
+        Self { critters, frame: 0 }
+    }
+
+    // `view` is the visible part of the world, see `Camera::view`
+    pub fn update(&mut self, dt: f32, spatial: &SpatialHash, view: &Rect) {
+        self.frame = self.frame.wrapping_add(1);
+        let mut rng = thread_rng();
+
+        // Flocking reads the previous frame's bird positions
+        let birds: Vec<(Vec2, Vec2)> = self
+            .critters
+            .iter()
+            .filter(|critter| critter.kind == CritterKind::Bird)
+            .map(|critter| (critter.position, critter.velocity))
+            .collect();
+
+        for (index, critter) in self.critters.iter_mut().enumerate() {
+            let detail = detail_level(view, critter.position);
+            let full_update = match detail {
+                Detail::Full => true,
+                Detail::Reduced => {
+                    (self.frame + index as u32).is_multiple_of(REDUCED_UPDATE_INTERVAL)
+                }
+                Detail::Culled => false,
+            };
+
+            if full_update {
+                critter.steer(dt, spatial, &birds, &mut rng);
+            }
+            critter.position += critter.velocity * dt;
+            critter.flap_phase += dt * if critter.scared > 0.0 { 30.0 } else { 14.0 };
+            critter.scared = (critter.scared - dt).max(0.0);
+
+            // Keep everything inside the roaming area
+            let min = Vec2::splat(-ROAM_MARGIN);
+            let max = Vec2::new(WORLD_WIDTH, WORLD_HEIGHT) + ROAM_MARGIN;
+            if critter.position.x < min.x || critter.position.x > max.x {
+                critter.velocity.x = -critter.velocity.x;
+            }
+            if critter.position.y < min.y || critter.position.y > max.y {
+                critter.velocity.y = -critter.velocity.y;
+            }
+            critter.position = critter.position.clamp(min, max);
+        }
+    }
+
+    pub fn draw(&self, list: &mut DrawList, view: &Rect) {
+        for critter in &self.critters {
+            if detail_level(view, critter.position) == Detail::Full {
+                critter.draw(list);
+            }
+        }
+    }
+}
+
+impl Critter {
+    fn steer(
+        &mut self,
+        dt: f32,
+        spatial: &SpatialHash,
+        birds: &[(Vec2, Vec2)],
+        rng: &mut impl Rng,
+    ) {
+        // Run directly away from the nearest player
+        let nearest_player = spatial
+            .query_radius(self.position, SCARE_RADIUS)
+            .into_iter()
+            .map(|(_, position)| position)
+            .min_by(|a, b| {
+                a.distance_squared(self.position)
+                    .total_cmp(&b.distance_squared(self.position))
+            });
+        if let Some(player) = nearest_player {
+            let away = (self.position - player).normalize_or(Vec2::X);
+            let speed = match self.kind {
+                CritterKind::Bird => BIRD_SCATTER_SPEED,
+                CritterKind::Butterfly => BUTTERFLY_SCATTER_SPEED,
+            };
+            self.velocity = away * speed;
+            self.scared = SCARE_DURATION;
+            return;
+        }
+        if self.scared > 0.0 {
+            return;
+        }
+
+        match self.kind {
+            CritterKind::Bird => self.flock(dt, birds),
+            CritterKind::Butterfly => self.wander(dt, rng),
+        }
+    }
+
+    // Classic boids: stay apart, match heading, drift toward the group
+    fn flock(&mut self, dt: f32, birds: &[(Vec2, Vec2)]) {
+        let mut separation = Vec2::ZERO;
+        let mut heading = Vec2::ZERO;
+        let mut center = Vec2::ZERO;
+        let mut neighbours = 0;
+
+        for &(position, velocity) in birds {
+            let offset = self.position - position;
+            let distance = offset.length();
+            if distance == 0.0 || distance > FLOCK_RADIUS {
+                continue;
+            }
+            if distance < SEPARATION_RADIUS {
+                separation += offset / distance;
+            }
+            heading += velocity;
+            center += position;
+            neighbours += 1;
+        }
+
+        if neighbours > 0 {
+            let count = neighbours as f32;
+            let cohesion = center / count - self.position;
+            let alignment = heading / count - self.velocity;
+            let steering = separation * 60.0 + alignment * 0.5 + cohesion * 0.3;
+            self.velocity += steering * dt;
+        }
+        self.velocity = self.velocity.normalize_or(Vec2::X) * BIRD_SPEED;
+    }
+
+    // Random flutter that stays leashed to the home flower
+    fn wander(&mut self, dt: f32, rng: &mut impl Rng) {
+        self.wander_angle += rng.gen_range(-4.0..4.0) * dt;
+        let mut direction = Vec2::from_angle(self.wander_angle);
+
+        let to_home = self.home - self.position;
+        if to_home.length() > BUTTERFLY_LEASH {
+            direction = (direction + to_home.normalize()).normalize_or(direction);
+            self.wander_angle = direction.to_angle();
+        }
+        self.velocity = direction * BUTTERFLY_SPEED;
+    }
+
+    fn draw(&self, list: &mut DrawList) {
+        let flap = self.flap_phase.sin();
+        let Vec2 { x, y } = self.position;
+        let layer = Layer::Critters;
+        match self.kind {
+            CritterKind::Bird => {
+                // A simple "v" whose wings beat up and down
+                let wing = 4.0 + flap * 3.0;
+                list.line(
+                    layer,
+                    vec2(x - 6.0, y - wing),
+                    self.position,
+                    1.5,
+                    self.color,
+                );
+                list.line(
+                    layer,
+                    self.position,
+                    vec2(x + 6.0, y - wing),
+                    1.5,
+                    self.color,
+                );
+            }
+            CritterKind::Butterfly => {
+                let wing = 3.0 * flap.abs() + 1.0;
+                list.circle(layer, x - wing, y, 2.5, self.color);
+                list.circle(layer, x + wing, y, 2.5, self.color);
+                list.line(layer, vec2(x, y - 2.0), vec2(x, y + 2.0), 1.0, BLACK);
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+// Masks rude words in chat from other players, see `Settings::word_filter`.
+// Only whole words match, with a few common endings, so "class" and
+// "Scunthorpe" come through untouched.
+const WORDS: [&str; 9] = [
+    "fuck", "shit", "bitch", "bastard", "asshole", "cunt", "dick", "piss", "wanker",
+];
+const ENDINGS: [&str; 6] = ["s", "es", "ed", "er", "ing", "y"];
+
+// `message` with every filtered word, built in or in `extra`, turned into
+// asterisks
+pub fn mask(message: &str, extra: &[String]) -> String {
+    let mut masked = String::with_capacity(message.len());
+    let mut word = String::new();
+    for c in message.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        if is_filtered(&word, extra) {
+            masked.extend(word.chars().map(|_| '*'));
+        } else {
+            masked.push_str(&word);
+        }
+        word.clear();
+        masked.push(c);
+    }
+    // Drop the space added to finish the last word
+    masked.pop();
+    masked
+}
+
+fn is_filtered(word: &str, extra: &[String]) -> bool {
+    if word.is_empty() {
+        return false;
+    }
+    let word = word.to_lowercase();
+    let listed = |stem: &str| {
+        WORDS.contains(&stem) || extra.iter().any(|extra| extra.to_lowercase() == stem)
+    };
+    listed(&word)
+        || ENDINGS.iter().any(|ending| {
+            word.strip_suffix(ending)
+                .is_some_and(|stem| !stem.is_empty() && listed(stem))
+        })
+}
@@ -0,0 +1,18 @@
+// Size of the playable world in pixels
+pub const WORLD_WIDTH: f32 = 800.0;
+pub const WORLD_HEIGHT: f32 = 600.0;
+
+// Stable identifier of the world layout, stored in replays. Uses FNV-1a because
+// std's hasher is not guaranteed to match across builds.
+pub fn map_hash() -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in WORLD_WIDTH
+        .to_le_bytes()
+        .iter()
+        .chain(WORLD_HEIGHT.to_le_bytes().iter())
+    {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
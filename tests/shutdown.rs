@@ -0,0 +1,52 @@
+// Plays a short headless session against a local server and checks that
+// quitting runs every shutdown step, in order, without hanging.
+
+use lam_test::game::Game;
+use lam_test::headless;
+use lam_test::input::ScriptedInput;
+use lam_test::net::Connection;
+use lam_test::server::{self, ServerOptions};
+use lam_test::shutdown::{shutdown, ShutdownStep};
+use lam_test::storage::StorageKind;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+
+const ADDR: &str = "127.0.0.1:3142";
+
+#[test]
+fn shutdown_runs_every_step_in_order() {
+    let options = ServerOptions {
+        storage: StorageKind::Memory,
+        ..Default::default()
+    };
+    server::spawn(ADDR, options).expect("Failed to start server");
+
+    let rt = Runtime::new().unwrap();
+    let game = Arc::new(Mutex::new(Game::with_seed(7)));
+    game.lock().unwrap().headless = true;
+    let network =
+        Some(Connection::connect(ADDR, Arc::clone(&game), &rt).expect("Failed to connect"));
+
+    let mut input = ScriptedInput::wander_with_seed(7);
+    headless::run(&game, &network, &mut input, Some(Duration::from_secs(1)));
+    assert_ne!(
+        game.lock().unwrap().local_id(),
+        0,
+        "never joined the server"
+    );
+
+    let started = Instant::now();
+    let steps = shutdown(&game, network, rt);
+    assert_eq!(
+        steps,
+        [
+            ShutdownStep::Goodbye,
+            ShutdownStep::SaveState,
+            ShutdownStep::StopAudio,
+            ShutdownStep::FlushLogs,
+            ShutdownStep::JoinNetwork,
+        ]
+    );
+    assert!(started.elapsed() < Duration::from_secs(5), "shutdown hung");
+}